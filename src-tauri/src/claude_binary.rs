@@ -1040,7 +1040,9 @@ fn query_registry_paths(_tool: &str) -> Vec<String> {
 /// Main function to find the Claude binary - Cross-platform version
 /// Supports Windows and macOS, only uses system-installed Claude CLI
 /// 🔥 增强：添加详细日志，支持多 Node 版本场景
-pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, String> {
+pub fn find_claude_binary<R: tauri::Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+) -> Result<String, String> {
     info!("========================================");
     info!("Starting Claude CLI binary search...");
     info!("========================================");
@@ -1177,7 +1179,10 @@ pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, Strin
 }
 
 /// Store Claude CLI path in database for future use
-fn store_claude_path(app_handle: &tauri::AppHandle, path: &str) -> Result<(), String> {
+fn store_claude_path<R: tauri::Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    path: &str,
+) -> Result<(), String> {
     if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
         if let Err(e) = std::fs::create_dir_all(&app_data_dir) {
             return Err(format!("Failed to create app data directory: {}", e));
@@ -0,0 +1,598 @@
+//! Any Code headless CLI (`any-code-cli`)
+//!
+//! Runs the same backend functions as the desktop app without opening a
+//! window, so Any Code can be scripted from terminals and CI:
+//!
+//! ```text
+//! any-code-cli run --engine codex --project . "fix the failing test"
+//! any-code-cli usage --days 7
+//! any-code-cli mcp sync
+//! any-code-cli provider switch <ID> --engine codex
+//! any-code-cli revert <SESSION_ID> 2 --mode code_only
+//! ```
+//!
+//! Runs go through the engine runners on a windowless Tauri app, so budgets,
+//! timeouts, usage recording and prompt git records apply as in the GUI.
+//! Provider and MCP configuration are read from the same files the GUI manages.
+
+// The CLI compiles the whole backend but drives only part of it; the rest is
+// dead code in this binary
+#[allow(dead_code)]
+mod claude_binary;
+// Engine modules also re-export their Tauri commands for main.rs's invoke handler
+#[allow(dead_code, unused_imports)]
+mod commands;
+#[allow(dead_code)]
+mod process;
+#[allow(dead_code)]
+mod utils;
+
+#[allow(dead_code)]
+mod claude_mcp;
+mod codex_mcp;
+mod gemini_mcp;
+#[allow(dead_code)]
+mod mcp;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::Value;
+use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+use tauri::{App, Manager};
+
+use commands::claude::{encode_project_path, ClaudeProcessState};
+use commands::codex::CodexProcessState;
+use commands::engine::{runner_for, spawn_watched, unified_message, EngineKind, EngineRequest};
+use commands::gemini::GeminiProcessState;
+use commands::prompt_queue::RunRecords;
+use commands::prompt_tracker::RewindMode;
+use commands::storage::AgentDb;
+use process::ProcessRegistryState;
+
+/// The desktop app's bundle identifier (tauri.conf.json), so the CLI shares its
+/// app data directory: agents.db and the stored Claude binary path
+const APP_IDENTIFIER: &str = "claude.workbench.app";
+
+/// How often streamed output is flushed to the terminal while a run is going
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const USAGE: &str = "\
+Any Code headless CLI
+
+USAGE:
+    any-code-cli <COMMAND> [OPTIONS]
+
+COMMANDS:
+    run       Run a prompt on an engine and stream the output
+    revert    Revert a session to a prompt (revert <SESSION_ID> <PROMPT_INDEX>)
+    usage     Print usage statistics as JSON
+    mcp       Manage MCP servers (mcp sync [--engine <ENGINE>])
+    provider  Manage providers (provider list | current | switch <ID>)
+    help      Print this message
+
+RUN OPTIONS:
+    --engine <claude|codex|gemini>   Engine to use (default: claude)
+    --project <PATH>                 Project directory (default: .)
+    --model <MODEL>                  Model override
+    --resume <SESSION_ID>            Resume an existing session
+    --plan                           Claude plan mode
+    --json                           Print unified JSONL messages instead of text
+    <PROMPT>                         Prompt text, or '-' to read from stdin
+
+REVERT OPTIONS:
+    --engine <claude|codex|gemini>   Engine of the session (default: claude)
+    --project <PATH>                 Project directory (default: .)
+    --mode <both|code_only|conversation_only>
+                                     What to revert (default: both)
+
+USAGE OPTIONS:
+    --days <N>                       Only include the last N days
+    --engine <claude|codex|gemini>   Only one engine (default: all)
+
+PROVIDER OPTIONS:
+    --engine <claude|codex|gemini>   Engine whose providers to use (default: claude)
+";
+
+/// Minimal argv parser: `--flag value`, boolean `--flag`, and positionals
+struct CliArgs {
+    positionals: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl CliArgs {
+    const BOOLEAN_FLAGS: [&'static str; 3] = ["--json", "--plan", "--help"];
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut positionals = Vec::new();
+        let mut options = Vec::new();
+
+        while let Some(arg) = args.next() {
+            if arg.starts_with("--") {
+                if let Some((key, value)) = arg.split_once('=') {
+                    options.push((key.to_string(), Some(value.to_string())));
+                } else if Self::BOOLEAN_FLAGS.contains(&arg.as_str()) {
+                    options.push((arg, None));
+                } else {
+                    let value = args.next();
+                    options.push((arg, value));
+                }
+            } else {
+                positionals.push(arg);
+            }
+        }
+
+        Self {
+            positionals,
+            options,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.options.iter().any(|(k, _)| k == key)
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    // CLI tools (claude, codex, gemini) must be found the same way the GUI finds them
+    claude_binary::init_shell_environment();
+
+    let args = CliArgs::parse(std::env::args().skip(1));
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("Failed to start async runtime: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Engine runners spawn their readers on Tauri's runtime; share this one
+    tauri::async_runtime::set(runtime.handle().clone());
+
+    let code = match runtime.block_on(dispatch(args)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+async fn dispatch(args: CliArgs) -> Result<i32, String> {
+    let command = args.positionals.first().map(String::as_str);
+    if args.has("--help") {
+        print!("{}", USAGE);
+        return Ok(0);
+    }
+
+    match command {
+        Some("run") => run_prompt(&args).await,
+        Some("revert") => revert_command(&args).await,
+        Some("usage") => print_usage(&args).await,
+        Some("mcp") => mcp_command(&args),
+        Some("provider") => provider_command(&args).await,
+        Some("help") | None => {
+            print!("{}", USAGE);
+            Ok(0)
+        }
+        Some(other) => Err(format!(
+            "Unknown command: {} (see 'any-code-cli help')",
+            other
+        )),
+    }
+}
+
+/// Windowless app for the engine runners, managing the state the GUI sets up
+///
+/// Runs go through the same functions as the desktop app (budget checks,
+/// timeout supervisor, usage recording, process registry), which all take an
+/// `AppHandle`; the mock runtime provides one without a display.
+fn headless_app() -> Result<App<MockRuntime>, String> {
+    let mut context = mock_context(noop_assets());
+    context.config_mut().identifier = APP_IDENTIFIER.to_string();
+    let app = mock_builder()
+        .build(context)
+        .map_err(|e| format!("Failed to start headless app: {}", e))?;
+
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+    std::fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
+    let conn = commands::storage::open_database(&app_dir.join("agents.db"))
+        .map_err(|e| format!("Failed to open agents.db: {}", e))?;
+    app.manage(AgentDb(Mutex::new(conn)));
+
+    // Not attached to the persisted store: its run IDs belong to the desktop app
+    app.manage(ProcessRegistryState::default());
+    app.manage(ClaudeProcessState::default());
+    app.manage(CodexProcessState::default());
+    app.manage(GeminiProcessState::default());
+    Ok(app)
+}
+
+fn project_path_arg(args: &CliArgs) -> Result<String, String> {
+    Ok(std::fs::canonicalize(args.get("--project").unwrap_or("."))
+        .map_err(|e| format!("Invalid project path: {}", e))?
+        .to_string_lossy()
+        .to_string())
+}
+
+// ============================================================================
+// run
+// ============================================================================
+
+async fn run_prompt(args: &CliArgs) -> Result<i32, String> {
+    let kind: EngineKind = args.get("--engine").unwrap_or("claude").parse()?;
+    let project_path = project_path_arg(args)?;
+
+    let prompt = match args.positionals.get(1).map(String::as_str) {
+        Some("-") | None => {
+            let mut buf = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)
+                .map_err(|e| format!("Failed to read prompt from stdin: {}", e))?;
+            buf
+        }
+        Some(_) => args.positionals[1..].join(" "),
+    };
+    if prompt.trim().is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+
+    let request = EngineRequest {
+        project_path,
+        prompt,
+        model: args.get("--model").map(String::from),
+        plan_mode: args.has("--plan"),
+        ..Default::default()
+    };
+    let resume = args.get("--resume");
+    let json_output = args.has("--json");
+
+    let app = headless_app()?;
+    let app = app.handle();
+
+    // Same bracketing as a queued GUI prompt, so the run can be reverted
+    let records = RunRecords::begin(kind, resume, &request).await;
+    let mut watch = spawn_watched(app, kind, request.clone(), resume, true).await?;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let finished = loop {
+        let done = tokio::select! {
+            done = tokio::time::timeout(OUTPUT_POLL_INTERVAL, watch.wait()) => done.ok(),
+            _ = &mut ctrl_c => break None,
+        };
+        print_output(kind, watch.take_output(), json_output);
+        if done.is_some() {
+            break done;
+        }
+    };
+
+    let session_id = resume.map(String::from).or_else(|| watch.session_id());
+    if finished.is_none() {
+        eprintln!("\n[{}] interrupted, stopping...", kind);
+        // Same path as the GUI's stop button: kills the process group and its MCP servers
+        if let Err(e) = runner_for(kind).cancel(app, watch.session_id()).await {
+            eprintln!("[{}] failed to stop: {}", kind, e);
+        }
+    }
+    drop(watch);
+
+    records.finish(session_id.as_deref(), &request).await;
+    if let Some(sid) = &session_id {
+        eprintln!("[{}] session: {}", kind, sid);
+    }
+
+    Ok(match finished {
+        Some(true) => 0,
+        Some(false) => 1,
+        None => 130,
+    })
+}
+
+fn print_output(kind: EngineKind, lines: Vec<String>, json_output: bool) {
+    for line in lines {
+        let Some(message) = unified_message(kind, &line) else {
+            continue;
+        };
+        if json_output {
+            println!("{}", message);
+        } else {
+            print_message(&message);
+        }
+    }
+}
+
+/// Render a unified message as plain terminal text
+fn print_message(message: &Value) {
+    match message.get("type").and_then(|t| t.as_str()) {
+        Some("assistant") => {
+            let blocks = message
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array());
+            for block in blocks.into_iter().flatten() {
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => {
+                        if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                            println!("{}", text);
+                        }
+                    }
+                    Some("tool_use") => {
+                        let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("tool");
+                        eprintln!("→ {} {}", name, block.get("input").unwrap_or(&Value::Null));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some("result") => {
+            if let Some(cost) = message.get("total_cost_usd").and_then(|c| c.as_f64()) {
+                eprintln!("cost: ${:.4}", cost);
+            }
+        }
+        Some("system") => {
+            if let Some(error) = message
+                .get("error")
+                .and_then(|e| e.get("message"))
+                .and_then(|m| m.as_str())
+            {
+                eprintln!("error: {}", error);
+            }
+        }
+        _ => {}
+    }
+}
+
+// ============================================================================
+// revert
+// ============================================================================
+
+async fn revert_command(args: &CliArgs) -> Result<i32, String> {
+    let (Some(session_id), Some(prompt_index)) = (args.positionals.get(1), args.positionals.get(2))
+    else {
+        return Err(
+            "Usage: any-code-cli revert <SESSION_ID> <PROMPT_INDEX> [--engine <ENGINE>] [--mode <MODE>]"
+                .to_string(),
+        );
+    };
+    let prompt_index: usize = prompt_index
+        .parse()
+        .map_err(|e| format!("Invalid prompt index: {}", e))?;
+    let kind: EngineKind = args.get("--engine").unwrap_or("claude").parse()?;
+    let project_path = project_path_arg(args)?;
+    let mode_name = args.get("--mode").unwrap_or("both");
+    let mode: RewindMode = serde_json::from_value(Value::String(mode_name.to_string()))
+        .map_err(|_| format!("Invalid --mode: {}", mode_name))?;
+
+    let session_id = session_id.clone();
+    let message = match kind {
+        EngineKind::Claude => {
+            let project_id = encode_project_path(&project_path);
+            commands::prompt_tracker::revert_to_prompt(
+                session_id,
+                project_id,
+                project_path,
+                prompt_index,
+                mode,
+            )
+            .await?
+        }
+        EngineKind::Codex => {
+            commands::codex::git_ops::revert_codex_to_prompt(
+                session_id,
+                project_path,
+                prompt_index,
+                mode,
+            )
+            .await?
+        }
+        EngineKind::Gemini => {
+            commands::gemini::git_ops::revert_gemini_to_prompt(
+                session_id,
+                project_path,
+                prompt_index,
+                mode,
+            )
+            .await?
+        }
+    };
+
+    eprintln!("{}", message);
+    Ok(0)
+}
+
+// ============================================================================
+// usage
+// ============================================================================
+
+async fn print_usage(args: &CliArgs) -> Result<i32, String> {
+    let days = args
+        .get("--days")
+        .map(|d| {
+            d.parse::<u32>()
+                .map_err(|e| format!("Invalid --days: {}", e))
+        })
+        .transpose()?;
+    let engines: Vec<EngineKind> = match args.get("--engine") {
+        Some(engine) => vec![engine.parse()?],
+        None => vec![EngineKind::Claude, EngineKind::Codex, EngineKind::Gemini],
+    };
+
+    // Codex/Gemini commands take an inclusive date range instead of a day count
+    let (start_date, end_date) = match days {
+        Some(days) => {
+            let today = chrono::Local::now().date_naive();
+            let start = today - chrono::Duration::days(days.saturating_sub(1) as i64);
+            (
+                Some(start.format("%Y-%m-%d").to_string()),
+                Some(today.format("%Y-%m-%d").to_string()),
+            )
+        }
+        None => (None, None),
+    };
+
+    // Reports are read-only; index into a throwaway database instead of agents.db
+    let db = std::sync::Mutex::new(
+        commands::storage::open_database(std::path::Path::new(":memory:"))
+            .map_err(|e| format!("Failed to open usage database: {}", e))?,
+    );
+
+    let mut report = serde_json::Map::new();
+    for engine in engines {
+        let stats = match engine {
+            EngineKind::Claude => serde_json::to_value(commands::usage::usage_stats(&db, days)?),
+            EngineKind::Codex => serde_json::to_value(commands::codex::usage::codex_usage_stats(
+                &db,
+                start_date.clone(),
+                end_date.clone(),
+            )?),
+            EngineKind::Gemini => {
+                serde_json::to_value(commands::gemini::usage::gemini_usage_stats(
+                    &db,
+                    start_date.clone(),
+                    end_date.clone(),
+                )?)
+            }
+        }
+        .map_err(|e| format!("Failed to serialize usage: {}", e))?;
+        report.insert(engine.to_string(), stats);
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&Value::Object(report)).unwrap_or_default()
+    );
+    Ok(0)
+}
+
+// ============================================================================
+// mcp / provider
+// ============================================================================
+
+fn mcp_command(args: &CliArgs) -> Result<i32, String> {
+    match args.positionals.get(1).map(String::as_str) {
+        Some("sync") => {
+            let engines: Vec<EngineKind> = match args.get("--engine") {
+                Some(engine) => vec![engine.parse()?],
+                None => vec![EngineKind::Claude, EngineKind::Codex, EngineKind::Gemini],
+            };
+            for engine in engines {
+                mcp::registry::sync_registry_to_engine(engine.as_str())?;
+                eprintln!("Synced MCP registry to {}", engine);
+            }
+            Ok(0)
+        }
+        Some("list") => {
+            let servers = mcp::get_unified_servers()?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&servers).unwrap_or_default()
+            );
+            Ok(0)
+        }
+        _ => Err("Usage: any-code-cli mcp <sync|list> [--engine <ENGINE>]".to_string()),
+    }
+}
+
+async fn provider_command(args: &CliArgs) -> Result<i32, String> {
+    const PROVIDER_USAGE: &str =
+        "Usage: any-code-cli provider <list|current|switch <ID>> [--engine <ENGINE>]";
+    let engine: EngineKind = args.get("--engine").unwrap_or("claude").parse()?;
+
+    let output = match args.positionals.get(1).map(String::as_str) {
+        Some("list") => match engine {
+            EngineKind::Claude => serde_json::to_value(commands::provider::get_provider_presets()?),
+            EngineKind::Codex => {
+                serde_json::to_value(commands::codex::config::get_codex_provider_presets().await?)
+            }
+            EngineKind::Gemini => serde_json::to_value(
+                commands::gemini::provider::get_gemini_provider_presets().await?,
+            ),
+        },
+        Some("current") => match engine {
+            EngineKind::Claude => {
+                serde_json::to_value(commands::provider::get_current_provider_config()?)
+            }
+            EngineKind::Codex => {
+                serde_json::to_value(commands::codex::config::get_current_codex_config().await?)
+            }
+            EngineKind::Gemini => serde_json::to_value(
+                commands::gemini::provider::get_current_gemini_provider_config().await?,
+            ),
+        },
+        Some("switch") => {
+            let id = args.positionals.get(2).ok_or(PROVIDER_USAGE)?;
+            eprintln!("{}", switch_provider(engine, id).await?);
+            return Ok(0);
+        }
+        _ => return Err(PROVIDER_USAGE.to_string()),
+    }
+    .map_err(|e| format!("Failed to serialize provider config: {}", e))?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&mask_secrets(output)).unwrap_or_default()
+    );
+    Ok(0)
+}
+
+/// Switch to a saved provider through the same command as the GUI's provider picker
+async fn switch_provider(engine: EngineKind, id: &str) -> Result<String, String> {
+    match engine {
+        EngineKind::Claude => {
+            let config = commands::provider::get_provider_config(id.to_string())?;
+            let app = headless_app()?;
+            commands::provider::switch_provider_config(app.handle().clone(), config).await
+        }
+        EngineKind::Codex => {
+            let config = commands::codex::config::get_codex_provider_presets()
+                .await?
+                .into_iter()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("No Codex provider with ID '{}'", id))?;
+            commands::codex::config::switch_codex_provider(config).await
+        }
+        EngineKind::Gemini => {
+            let config = commands::gemini::provider::get_gemini_provider_presets()
+                .await?
+                .into_iter()
+                .find(|p| p.id == id)
+                .ok_or_else(|| format!("No Gemini provider with ID '{}'", id))?;
+            commands::gemini::provider::switch_gemini_provider(config).await
+        }
+    }
+}
+
+/// Hide tokens and API keys so CI logs never capture credentials
+fn mask_secrets(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, v)| {
+                    let lower = key.to_lowercase();
+                    let is_secret = (lower.contains("token") || lower.contains("api_key"))
+                        && !lower.contains("helper");
+                    if is_secret && v.is_string() {
+                        (key, Value::String("***".to_string()))
+                    } else {
+                        (key, mask_secrets(v))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(mask_secrets).collect()),
+        other => other,
+    }
+}
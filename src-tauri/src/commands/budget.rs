@@ -0,0 +1,548 @@
+//! Cost budget guardrails
+//!
+//! Usage printed on the CLI streams is priced as it arrives, using the same
+//! tables as the usage statistics, and charged against three budgets:
+//!
+//! - run: spend of the current CLI run
+//! - project: today's spend in the run's project (runs in session / fan-out
+//!   worktrees count towards the checkout they were created from)
+//! - daily: today's spend across all projects
+//!
+//! Crossing the warning threshold emits `budget-warning` (once per run and
+//! scope). Reaching a limit emits `budget-exceeded` and cancels the run
+//! through the engine's cancel command, and new runs are refused while the
+//! project or daily limit stays reached. Claude reports usage per assistant
+//! message, Codex per turn and Gemini only on its result line, so the latter
+//! two mostly feed the project/daily totals.
+//!
+//! - Config: ~/.anycode/budgets.json (disabled by default)
+//! - Today's spend: ~/.anycode/budget_ledger.json (always counted, so enabling
+//!   a limit mid-day starts from the real total)
+
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use super::engine::{runner_for, EngineKind};
+use super::session_worktree;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// Today's spend, loaded from disk on first use
+static LEDGER: Lazy<Mutex<Option<SpendLedger>>> = Lazy::new(|| Mutex::new(None));
+
+/// Budget config, loaded from disk on first use and replaced on update
+static CONFIG: Lazy<Mutex<Option<BudgetConfig>>> = Lazy::new(|| Mutex::new(None));
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Limit for a single CLI run (USD)
+    #[serde(default)]
+    pub run_limit_usd: Option<f64>,
+    /// Limit for today's spend across all projects (USD)
+    #[serde(default)]
+    pub daily_limit_usd: Option<f64>,
+    /// Daily limit per project, keyed by project path (USD)
+    #[serde(default)]
+    pub project_limits_usd: HashMap<String, f64>,
+    /// Warn once spend reaches this percentage of a limit
+    #[serde(default = "default_warn_at_percent")]
+    pub warn_at_percent: f64,
+}
+
+fn default_warn_at_percent() -> f64 {
+    80.0
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            run_limit_usd: None,
+            daily_limit_usd: None,
+            project_limits_usd: HashMap::new(),
+            warn_at_percent: default_warn_at_percent(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetScope {
+    Run,
+    Project,
+    Daily,
+}
+
+/// Spend of the current day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendLedger {
+    /// Local date, YYYY-MM-DD
+    pub date: String,
+    pub total_usd: f64,
+    pub by_project: HashMap<String, f64>,
+}
+
+/// Payload of `budget-warning` and `budget-exceeded`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetEvent {
+    pub engine: EngineKind,
+    /// Session ID the run can be cancelled with
+    pub session_id: String,
+    pub project_path: String,
+    pub scope: BudgetScope,
+    pub limit_usd: f64,
+    pub spent_usd: f64,
+    pub run_spent_usd: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub config: BudgetConfig,
+    pub today: SpendLedger,
+}
+
+fn anycode_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode"))
+}
+
+fn load_config() -> BudgetConfig {
+    let mut slot = CONFIG.lock().unwrap();
+    slot.get_or_insert_with(|| {
+        anycode_dir()
+            .and_then(|dir| load_json_config(dir.join("budgets.json")))
+            .unwrap_or_else(|e| {
+                log::warn!("[Budget] Failed to load config, using defaults: {}", e);
+                BudgetConfig::default()
+            })
+    })
+    .clone()
+}
+
+/// Project paths are compared without trailing separators
+fn project_key(project_path: &str) -> String {
+    project_path.trim_end_matches(['/', '\\']).to_string()
+}
+
+fn project_limit(config: &BudgetConfig, project: &str) -> Option<f64> {
+    config
+        .project_limits_usd
+        .iter()
+        .find(|(path, _)| project_key(path) == project)
+        .map(|(_, limit)| *limit)
+}
+
+// ============================================================================
+// Limit Checks
+// ============================================================================
+
+/// Spend counted against each budget
+#[derive(Debug, Clone, Copy, Default)]
+struct Spend {
+    run: f64,
+    project: f64,
+    daily: f64,
+}
+
+/// A budget whose limit (or warning threshold) was reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LimitHit {
+    scope: BudgetScope,
+    limit: f64,
+    spent: f64,
+}
+
+#[derive(Debug, PartialEq)]
+enum BudgetCheck {
+    /// First budget, in run/project/daily order, at or over its limit
+    Exceeded(LimitHit),
+    /// Budgets past the warning threshold that were not warned about yet
+    Warn(Vec<LimitHit>),
+}
+
+/// Compare spend with the configured limits (limits of 0 or less are ignored)
+fn check_limits(
+    config: &BudgetConfig,
+    project: &str,
+    spend: Spend,
+    warned: &HashSet<BudgetScope>,
+) -> BudgetCheck {
+    let project_limit = project_limit(config, project);
+    let checks = [
+        (BudgetScope::Run, spend.run, config.run_limit_usd),
+        (BudgetScope::Project, spend.project, project_limit),
+        (BudgetScope::Daily, spend.daily, config.daily_limit_usd),
+    ];
+
+    let mut warnings = Vec::new();
+    for (scope, spent, limit) in checks {
+        let Some(limit) = limit.filter(|l| *l > 0.0) else {
+            continue;
+        };
+        let hit = LimitHit {
+            scope,
+            limit,
+            spent,
+        };
+        if spent >= limit {
+            return BudgetCheck::Exceeded(hit);
+        }
+        if spent >= limit * config.warn_at_percent / 100.0 && !warned.contains(&scope) {
+            warnings.push(hit);
+        }
+    }
+    BudgetCheck::Warn(warnings)
+}
+
+/// Refuse to start a run while its project or daily budget is used up
+pub fn check_before_run(engine: EngineKind, project_path: &str) -> Result<(), String> {
+    let config = load_config();
+    if !config.enabled {
+        return Ok(());
+    }
+    let project = project_key(&session_worktree::source_project_path(project_path));
+    let spend = {
+        let mut slot = LEDGER.lock().map_err(|e| e.to_string())?;
+        let ledger = current_ledger(&mut slot);
+        Spend {
+            run: 0.0,
+            project: ledger.by_project.get(&project).copied().unwrap_or(0.0),
+            daily: ledger.total_usd,
+        }
+    };
+
+    match check_limits(&config, &project, spend, &HashSet::new()) {
+        BudgetCheck::Exceeded(hit) => {
+            let message = format!(
+                "{} budget of ${:.2} reached (${:.2} spent today), not starting {} run",
+                scope_name(hit.scope),
+                hit.limit,
+                hit.spent,
+                engine
+            );
+            log::warn!("[Budget] {}: {}", project, message);
+            Err(message)
+        }
+        BudgetCheck::Warn(_) => Ok(()),
+    }
+}
+
+fn scope_name(scope: BudgetScope) -> &'static str {
+    match scope {
+        BudgetScope::Run => "run",
+        BudgetScope::Project => "project daily",
+        BudgetScope::Daily => "daily",
+    }
+}
+
+// ============================================================================
+// Ledger
+// ============================================================================
+
+fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// Today's ledger; a ledger from an earlier day starts over
+fn current_ledger(slot: &mut Option<SpendLedger>) -> &mut SpendLedger {
+    let ledger = slot.get_or_insert_with(|| {
+        anycode_dir()
+            .and_then(|dir| load_json_config(dir.join("budget_ledger.json")))
+            .unwrap_or_default()
+    });
+    let today = today();
+    if ledger.date != today {
+        *ledger = SpendLedger {
+            date: today,
+            ..Default::default()
+        };
+    }
+    ledger
+}
+
+/// Add `cost` to today's spend; returns (daily total, project total)
+fn record_spend(project: &str, cost: f64) -> Result<(f64, f64), String> {
+    let mut slot = LEDGER.lock().map_err(|e| e.to_string())?;
+    let ledger = current_ledger(&mut slot);
+    ledger.total_usd += cost;
+    let project_total = {
+        let spent = ledger.by_project.entry(project.to_string()).or_default();
+        *spent += cost;
+        *spent
+    };
+    save_json_config(&*ledger, anycode_dir()?.join("budget_ledger.json"))?;
+    Ok((ledger.total_usd, project_total))
+}
+
+// ============================================================================
+// Per-run tracking
+// ============================================================================
+
+/// Budget state of one CLI run, owned by its stdout reader
+pub struct RunBudget {
+    engine: EngineKind,
+    project_path: String,
+    spent: f64,
+    /// Claude repeats a message's usage on every content block
+    message_costs: HashMap<String, f64>,
+    warned: HashSet<BudgetScope>,
+    stopped: bool,
+}
+
+impl RunBudget {
+    pub fn new(engine: EngineKind, project_path: &str) -> Self {
+        Self {
+            engine,
+            project_path: project_key(&session_worktree::source_project_path(project_path)),
+            spent: 0.0,
+            message_costs: HashMap::new(),
+            warned: HashSet::new(),
+            stopped: false,
+        }
+    }
+
+    /// Charge the cost of one message; repeated reports only charge the difference
+    pub fn charge_message<R: Runtime>(
+        &mut self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        message_id: &str,
+        cost: f64,
+    ) {
+        let previous = self.message_costs.insert(message_id.to_string(), cost);
+        self.charge(app, session_id, cost - previous.unwrap_or(0.0));
+    }
+
+    /// Reconcile with the run total reported by the CLI at the end of a run
+    pub fn charge_total<R: Runtime>(&mut self, app: &AppHandle<R>, session_id: &str, total: f64) {
+        self.charge(app, session_id, total - self.spent);
+    }
+
+    /// Charge `cost` and enforce the limits
+    ///
+    /// Spend is always recorded; limits only apply while budgets are enabled.
+    pub fn charge<R: Runtime>(&mut self, app: &AppHandle<R>, session_id: &str, cost: f64) {
+        if cost <= 0.0 || self.stopped {
+            return;
+        }
+
+        self.spent += cost;
+        let (daily, project) = match record_spend(&self.project_path, cost) {
+            Ok(totals) => totals,
+            Err(e) => {
+                log::warn!("[Budget] Failed to record spend: {}", e);
+                return;
+            }
+        };
+
+        let config = load_config();
+        if !config.enabled {
+            return;
+        }
+        let spend = Spend {
+            run: self.spent,
+            project,
+            daily,
+        };
+        match check_limits(&config, &self.project_path, spend, &self.warned) {
+            BudgetCheck::Exceeded(hit) => {
+                self.stopped = true;
+                self.emit(app, "budget-exceeded", session_id, hit);
+                self.cancel(app, session_id);
+            }
+            BudgetCheck::Warn(hits) => {
+                for hit in hits {
+                    self.warned.insert(hit.scope);
+                    self.emit(app, "budget-warning", session_id, hit);
+                }
+            }
+        }
+    }
+
+    fn emit<R: Runtime>(&self, app: &AppHandle<R>, event: &str, session_id: &str, hit: LimitHit) {
+        let LimitHit {
+            scope,
+            limit,
+            spent,
+        } = hit;
+        let message = if event == "budget-exceeded" {
+            format!(
+                "{} budget of ${:.2} reached (${:.2} spent), cancelling {} run",
+                scope_name(scope),
+                limit,
+                spent,
+                self.engine
+            )
+        } else {
+            format!(
+                "{} budget at {:.0}% (${:.2} of ${:.2})",
+                scope_name(scope),
+                spent / limit * 100.0,
+                spent,
+                limit
+            )
+        };
+        log::warn!("[Budget] {}: {}", session_id, message);
+
+        let payload = BudgetEvent {
+            engine: self.engine,
+            session_id: session_id.to_string(),
+            project_path: self.project_path.clone(),
+            scope,
+            limit_usd: limit,
+            spent_usd: spent,
+            run_spent_usd: self.spent,
+            message,
+        };
+        if let Err(e) = app.emit(event, &payload) {
+            log::warn!("[Budget] Failed to emit {}: {}", event, e);
+        }
+    }
+
+    fn cancel<R: Runtime>(&self, app: &AppHandle<R>, session_id: &str) {
+        let app = app.clone();
+        let engine = self.engine;
+        let session_id = session_id.to_string();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = runner_for::<R>(engine).cancel(&app, Some(session_id)).await {
+                log::error!("[Budget] Failed to cancel {} run: {}", engine, e);
+            }
+        });
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_budget_config() -> Result<BudgetConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_budget_config(config: BudgetConfig) -> Result<BudgetConfig, String> {
+    save_json_config(&config, anycode_dir()?.join("budgets.json"))?;
+    *CONFIG.lock().map_err(|e| e.to_string())? = Some(config.clone());
+    Ok(config)
+}
+
+/// Limits together with today's spend
+#[tauri::command]
+pub async fn get_budget_status() -> Result<BudgetStatus, String> {
+    let today = {
+        let mut slot = LEDGER.lock().map_err(|e| e.to_string())?;
+        current_ledger(&mut slot).clone()
+    };
+    Ok(BudgetStatus {
+        config: load_config(),
+        today,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ledger_starts_over_on_a_new_day() {
+        let mut slot = Some(SpendLedger {
+            date: "2000-01-01".to_string(),
+            total_usd: 12.5,
+            by_project: HashMap::from([("/tmp/app".to_string(), 12.5)]),
+        });
+        let ledger = current_ledger(&mut slot);
+        assert_eq!(ledger.date, today());
+        assert_eq!(ledger.total_usd, 0.0);
+        assert!(ledger.by_project.is_empty());
+
+        assert_eq!(project_key("/tmp/app/"), project_key("/tmp/app"));
+    }
+
+    fn limits() -> BudgetConfig {
+        BudgetConfig {
+            enabled: true,
+            run_limit_usd: Some(1.0),
+            daily_limit_usd: Some(10.0),
+            project_limits_usd: HashMap::from([("/tmp/app/".to_string(), 5.0)]),
+            warn_at_percent: 80.0,
+        }
+    }
+
+    #[test]
+    fn check_limits_warns_once_per_scope() {
+        let config = limits();
+        let spend = Spend {
+            run: 0.85,
+            project: 4.5,
+            daily: 4.5,
+        };
+
+        let BudgetCheck::Warn(hits) = check_limits(&config, "/tmp/app", spend, &HashSet::new())
+        else {
+            panic!("expected warnings");
+        };
+        let scopes: Vec<BudgetScope> = hits.iter().map(|h| h.scope).collect();
+        assert_eq!(scopes, vec![BudgetScope::Run, BudgetScope::Project]);
+        assert_eq!(hits[1].limit, 5.0);
+
+        let warned = HashSet::from([BudgetScope::Run, BudgetScope::Project]);
+        assert_eq!(
+            check_limits(&config, "/tmp/app", spend, &warned),
+            BudgetCheck::Warn(Vec::new())
+        );
+
+        // Other projects have no project limit
+        let BudgetCheck::Warn(hits) = check_limits(&config, "/tmp/other", spend, &warned) else {
+            panic!("expected warnings");
+        };
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn check_limits_reports_first_exceeded_scope() {
+        let config = limits();
+        let spend = Spend {
+            run: 0.5,
+            project: 5.0,
+            daily: 12.0,
+        };
+        assert_eq!(
+            check_limits(&config, "/tmp/app", spend, &HashSet::new()),
+            BudgetCheck::Exceeded(LimitHit {
+                scope: BudgetScope::Project,
+                limit: 5.0,
+                spent: 5.0,
+            })
+        );
+
+        // Zero limits are treated as unset
+        let config = BudgetConfig {
+            run_limit_usd: Some(0.0),
+            ..limits()
+        };
+        let spend = Spend {
+            run: 3.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_limits(&config, "/tmp/app", spend, &HashSet::new()),
+            BudgetCheck::Warn(Vec::new())
+        );
+    }
+}
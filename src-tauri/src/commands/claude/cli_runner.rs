@@ -2,80 +2,37 @@ use std::fs;
 use std::process::Stdio;
 use std::sync::Arc;
 
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::process::Command;
 
+use crate::commands::budget::{self, RunBudget};
+use crate::commands::engine::timeout::{self, RunActivity};
+use crate::commands::engine::process::attach_job_object;
+use crate::commands::engine::{EngineKind, EngineProcessHandle, EngineProcessState};
 use crate::commands::permission_config::{
     build_execution_args, ClaudeExecutionConfig, ClaudePermissionConfig,
 };
-#[cfg(windows)]
+use crate::commands::session_archive;
+use crate::commands::usage_recorder::{self, UsageRecord};
 use crate::process::JobObject;
 
 use super::config::get_claude_execution_config;
 use super::paths::{encode_project_path, get_claude_dir};
 use super::platform;
+use super::{parse_claude_line, ClaudeStreamEvent};
 
-/// Global state to track current Claude process
-pub struct ClaudeProcessState {
-    pub current_process: Arc<Mutex<Option<Child>>>,
-    pub last_spawned_pid: Arc<Mutex<Option<u32>>>,
-}
+/// Global state to track Claude processes
+///
+/// Keyed by a backend run ID: the CLI session ID only arrives with the init
+/// message, and cancel by session goes through the ProcessRegistry anyway.
+#[derive(Default)]
+pub struct ClaudeProcessState(pub EngineProcessState);
 
-impl Default for ClaudeProcessState {
-    fn default() -> Self {
-        Self {
-            current_process: Arc::new(Mutex::new(None)),
-            last_spawned_pid: Arc::new(Mutex::new(None)),
-        }
-    }
-}
+impl std::ops::Deref for ClaudeProcessState {
+    type Target = EngineProcessState;
 
-impl Drop for ClaudeProcessState {
-    fn drop(&mut self) {
-        // When the application exits, clean up the current process
-        log::info!("ClaudeProcessState dropping, cleaning up current process...");
-
-        // Use a runtime to execute the async cleanup
-        let process = self.current_process.clone();
-        if let Ok(handle) = tokio::runtime::Handle::try_current() {
-            // We're in a tokio runtime context
-            handle.block_on(async move {
-                let mut current_process = process.lock().await;
-                if let Some(mut child) = current_process.take() {
-                    log::info!("Cleanup on drop: Killing Claude process");
-                    match child.kill().await {
-                        Ok(_) => {
-                            log::info!("Cleanup on drop: Successfully killed Claude process");
-                        }
-                        Err(e) => {
-                            log::error!("Cleanup on drop: Failed to kill Claude process: {}", e);
-                        }
-                    }
-                }
-            });
-        } else {
-            // Create a temporary runtime for cleanup
-            if let Ok(rt) = tokio::runtime::Runtime::new() {
-                rt.block_on(async move {
-                    let mut current_process = process.lock().await;
-                    if let Some(mut child) = current_process.take() {
-                        log::info!("Cleanup on drop: Killing Claude process");
-                        match child.kill().await {
-                            Ok(_) => {
-                                log::info!("Cleanup on drop: Successfully killed Claude process");
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "Cleanup on drop: Failed to kill Claude process: {}",
-                                    e
-                                );
-                            }
-                        }
-                    }
-                });
-            }
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -261,11 +218,7 @@ fn create_windows_command(
 
     // On Unix-like systems, create a new process group
     // This allows us to kill the entire process tree with a single signal
-    #[cfg(unix)]
-    {
-        use std::os::unix::process::CommandExt;
-        cmd.process_group(0); // Create new process group, process becomes group leader
-    }
+    platform::apply_process_group_async(&mut cmd);
 
     Ok(cmd)
 }
@@ -274,8 +227,8 @@ fn create_windows_command(
 /// Always tries to resume project context first for better continuity
 /// Enhanced for Windows with better error handling
 #[tauri::command]
-pub async fn execute_claude_code(
-    app: AppHandle,
+pub async fn execute_claude_code<R: Runtime>(
+    app: AppHandle<R>,
     project_path: String,
     prompt: String,
     model: String,
@@ -338,8 +291,8 @@ pub async fn execute_claude_code(
 /// Continue an existing Claude Code conversation with streaming output
 /// Enhanced for Windows with better error handling
 #[tauri::command]
-pub async fn continue_claude_code(
-    app: AppHandle,
+pub async fn continue_claude_code<R: Runtime>(
+    app: AppHandle<R>,
     project_path: String,
     prompt: String,
     model: String,
@@ -405,8 +358,8 @@ pub async fn continue_claude_code(
 /// Resume an existing Claude Code session by ID with streaming output
 /// Enhanced for Windows with better error handling
 #[tauri::command]
-pub async fn resume_claude_code(
-    app: AppHandle,
+pub async fn resume_claude_code<R: Runtime>(
+    app: AppHandle<R>,
     project_path: String,
     session_id: String,
     prompt: String,
@@ -416,6 +369,16 @@ pub async fn resume_claude_code(
     tab_id: Option<String>,
 ) -> Result<(), String> {
     let plan_mode = plan_mode.unwrap_or(false);
+    // Sessions started in isolation mode must resume inside their worktree
+    let project_path =
+        crate::commands::session_worktree::resolve_project_path(&session_id, project_path);
+    // The CLI cannot resume an archived (compressed) session file
+    session_archive::rehydrate(
+        EngineKind::Claude,
+        &session_id,
+        Some(&encode_project_path(&project_path)),
+        None,
+    )?;
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}, plan_mode: {}",
         session_id,
@@ -517,8 +480,8 @@ pub async fn resume_claude_code(
 
 /// Cancel the currently running Claude Code execution
 #[tauri::command]
-pub async fn cancel_claude_execution(
-    app: AppHandle,
+pub async fn cancel_claude_execution<R: Runtime>(
+    app: AppHandle<R>,
     session_id: Option<String>,
 ) -> Result<(), String> {
     log::info!(
@@ -564,72 +527,21 @@ pub async fn cancel_claude_execution(
         }
     }
 
-    // Method 2: Try the legacy approach via ClaudeProcessState
+    // Method 2: The latest spawned process (not registered yet, or no session ID given)
     if !killed {
         let claude_state = app.state::<ClaudeProcessState>();
-        let mut current_process = claude_state.current_process.lock().await;
-
-        if let Some(mut child) = current_process.take() {
-            // Try to get the PID before killing
-            let pid = child.id();
-            log::info!(
-                "Attempting to kill Claude process via ClaudeProcessState with PID: {:?}",
-                pid
-            );
-
-            // Kill the process
-            match child.kill().await {
-                Ok(_) => {
-                    log::info!("Successfully killed Claude process via ClaudeProcessState");
-                    killed = true;
-                }
-                Err(e) => {
-                    log::error!(
-                        "Failed to kill Claude process via ClaudeProcessState: {}",
-                        e
-                    );
-
-                    // Fallback: If we have a PID, try system kill as last resort
-                    if let Some(pid) = pid {
-                        log::info!("Attempting system kill as last resort for PID: {}", pid);
-                        match platform::kill_process_tree(pid) {
-                            Ok(_) => {
-                                log::info!("Successfully killed process tree via platform module");
-                                killed = true;
-                            }
-                            Err(e) => {
-                                log::error!("Failed to kill process tree: {}", e);
-                            }
-                        }
-                    }
-                }
-            }
-            attempted_methods.push("claude_state");
-        } else {
+        let last_run = claude_state.last_session_id.lock().await.clone();
+        let handles = match last_run {
+            Some(run) => claude_state.take(Some(&run)).await,
+            None => Vec::new(),
+        };
+        if handles.is_empty() {
             log::warn!("No active Claude process in ClaudeProcessState");
         }
-    }
-
-    // Method 3: Try killing the last spawned PID when session_id is not available
-    if !killed {
-        let claude_state = app.state::<ClaudeProcessState>();
-        let last_pid = { *claude_state.last_spawned_pid.lock().await };
-        if let Some(pid) = last_pid {
-            log::info!("Attempting to kill Claude process via last spawned PID: {}", pid);
-            match platform::kill_process_tree(pid) {
-                Ok(_) => {
-                    log::info!("Successfully killed process tree via last spawned PID");
-                    let mut last_pid_guard = claude_state.last_spawned_pid.lock().await;
-                    if last_pid_guard.as_ref() == Some(&pid) {
-                        *last_pid_guard = None;
-                    }
-                    killed = true;
-                }
-                Err(e) => {
-                    log::error!("Failed to kill process tree via last spawned PID: {}", e);
-                }
-            }
-            attempted_methods.push("last_spawned_pid");
+        for (run, handle) in handles {
+            handle.terminate("Claude", &run).await;
+            killed = true;
+            attempted_methods.push("claude_state");
         }
     }
 
@@ -691,8 +603,8 @@ fn is_slash_command(prompt: &str) -> bool {
 /// 🔥 修复：斜杠命令通过 -p 参数传递（触发命令解析），普通 prompt 通过 stdin 管道传递
 /// 这样既支持斜杠命令，又避免操作系统命令行长度限制（Windows ~8KB, Linux/macOS ~128KB-2MB）
 /// 🔒 CRITICAL FIX: 添加 tab_id 参数，用于全局事件中标识消息来源，解决新建会话并发时的消息串扰
-async fn spawn_claude_process(
-    app: AppHandle,
+async fn spawn_claude_process<R: Runtime>(
+    app: AppHandle<R>,
     mut cmd: Command,
     prompt: String,
     model: String,
@@ -702,6 +614,8 @@ async fn spawn_claude_process(
     use std::sync::Mutex;
     use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
+    budget::check_before_run(EngineKind::Claude, &project_path)?;
+
     // 🔥 关键修复：检测斜杠命令，通过 -p 参数传递以触发命令解析
     // Claude CLI 只在 -p 参数中解析斜杠命令，stdin 管道不会触发
     let use_p_flag = is_slash_command(&prompt);
@@ -763,29 +677,9 @@ async fn spawn_claude_process(
     // This ensures all child processes (including MCP node processes) are automatically
     // added to the Job Object and will be terminated when the job is closed.
     // Previously, Job Object was created when receiving init message, which was too late.
-    #[cfg(windows)]
-    let job_object: Option<Arc<JobObject>> = if pid != 0 {
-        match JobObject::create() {
-            Ok(job) => {
-                match job.assign_process_by_pid(pid) {
-                    Ok(_) => {
-                        log::info!(
-                            "🔧 FIX: Assigned process {} to Job Object immediately after spawn",
-                            pid
-                        );
-                        Some(Arc::new(job))
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to assign process {} to Job Object: {}", pid, e);
-                        None
-                    }
-                }
-            }
-            Err(e) => {
-                log::warn!("Failed to create Job Object: {}", e);
-                None
-            }
-        }
+    // On Unix the JobObject tracks the process group Claude was spawned into.
+    let job_object = if pid != 0 {
+        attach_job_object("Claude", pid)
     } else {
         None
     };
@@ -797,29 +691,21 @@ async fn spawn_claude_process(
     // We'll extract the session ID from Claude's init message
     let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
     let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
-    #[cfg(windows)]
     let job_object_holder: Arc<std::sync::Mutex<Option<Arc<JobObject>>>> =
         Arc::new(std::sync::Mutex::new(job_object));
 
-    // 🔒 CRITICAL FIX: 不再使用全局 ClaudeProcessState 管理进程生命周期
-    // 原因：全局单例只能存储一个 child，多会话并发时会互相覆盖
-    // 解决：每个进程独立管理自己的 child，取消功能通过 ProcessRegistry 实现
-    //
-    // ClaudeProcessState 仅作为向后兼容的备选取消机制（存储最新进程的 PID）
-    let claude_state = app.state::<ClaudeProcessState>();
-    {
-        let mut current_process = claude_state.current_process.lock().await;
-        // 仅用于记录，不影响进程管理
-        if current_process.is_some() {
-            log::info!("Another Claude process is running, allowing concurrent sessions");
-        }
-        // 不再存储 child，改为独立管理
-        *current_process = None;
-    }
-    if pid != 0 {
-        let mut last_pid = claude_state.last_spawned_pid.lock().await;
-        *last_pid = Some(pid);
-    }
+    // Track the child per run so concurrent sessions never overwrite each other.
+    // The handle shares the Job Object, so dropping it on exit cleans up MCP servers.
+    let run_key = format!("claude-{}", uuid::Uuid::new_v4());
+    let handle = EngineProcessHandle {
+        child,
+        pid,
+        job_object: job_object_holder.lock().unwrap().clone(),
+        registration: None,
+    };
+    app.state::<ClaudeProcessState>()
+        .insert(run_key.clone(), handle)
+        .await;
 
     // Check if auto-compact state is available
     let auto_compact_available = app
@@ -838,22 +724,27 @@ async fn spawn_claude_process(
     // 🔒 CRITICAL FIX: 克隆 tab_id 用于事件发送
     let tab_id_for_stdout = tab_id.clone();
     // 🔧 FIX: Clone job_object_holder for passing to register_claude_session
-    #[cfg(windows)]
     let job_object_holder_clone = job_object_holder.clone();
+    // Wall-clock / inactivity supervision (engine::timeout)
+    let activity = RunActivity::new();
+    let activity_stdout = activity.clone();
+    let mut run_budget = RunBudget::new(EngineKind::Claude, &project_path);
     let stdout_task = tokio::spawn(async move {
         let mut lines = stdout_reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
+            activity_stdout.touch();
             // Use trace level to avoid flooding logs in debug mode
             log::trace!("Claude stdout: {}", line);
 
             // Parse the line to check for init message with session ID
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
+            if let Ok(event) = parse_claude_line(&line) {
+                if let ClaudeStreamEvent::Init { .. } = &event {
+                    if let Some(claude_session_id) = event.session_id() {
                         let mut session_id_guard = session_id_holder_clone.lock().unwrap();
                         if session_id_guard.is_none() {
                             *session_id_guard = Some(claude_session_id.to_string());
                             log::info!("Extracted Claude session ID: {}", claude_session_id);
+                            activity_stdout.set_session_id(claude_session_id);
 
                             // Register with auto-compact manager
                             if auto_compact_available {
@@ -870,13 +761,10 @@ async fn spawn_claude_process(
 
                             // Now register with ProcessRegistry using Claude's session ID
                             // 🔧 FIX: Pass the pre-created Job Object to avoid orphan processes
-                            #[cfg(windows)]
                             let job_object_for_register = job_object_holder_clone
                                 .lock()
                                 .unwrap()
                                 .take();
-                            #[cfg(not(windows))]
-                            let job_object_for_register: Option<()> = None;
 
                             match registry_clone.register_claude_session_with_job(
                                 claude_session_id.to_string(),
@@ -927,50 +815,78 @@ async fn spawn_claude_process(
                     }
                 }
 
-                // Check for usage information and update context tracking
-                if let Some(usage) = msg.get("usage") {
-                    if let (Some(input_tokens), Some(output_tokens)) = (
-                        usage.get("input_tokens").and_then(|t| t.as_u64()),
-                        usage.get("output_tokens").and_then(|t| t.as_u64()),
-                    ) {
-                        let total_tokens = (input_tokens + output_tokens) as usize;
-
-                        // Extract cache tokens if available
-                        let _cache_creation_tokens = usage
-                            .get("cache_creation_input_tokens")
-                            .and_then(|t| t.as_u64());
-                        let _cache_read_tokens = usage
-                            .get("cache_read_input_tokens")
-                            .and_then(|t| t.as_u64());
-
-                        // Store usage data in database for real-time token statistics
-                        let session_id_for_update =
-                            { session_id_holder_clone.lock().unwrap().as_ref().cloned() };
-
-                        if let Some(session_id_str) = &session_id_for_update {
-                            // Agent database functionality removed - usage tracking disabled
-
-                            // Update auto-compact manager with token count
-                            if auto_compact_available {
-                                if let Some(auto_compact_state) = app_handle.try_state::<crate::commands::context_manager::AutoCompactState>() {
-                                    let auto_compact_state_clone = auto_compact_state.inner().clone();
-                                    let session_id_for_compact = session_id_str.clone();
-
-                                    // Spawn async task to avoid blocking main output loop
-                                    tokio::spawn(async move {
-                                        match auto_compact_state_clone.0.update_session_tokens(&session_id_for_compact, total_tokens).await {
-                                            Ok(compaction_triggered) => {
-                                                if compaction_triggered {
-                                                    log::info!("Auto-compaction triggered for session {}", session_id_for_compact);
-                                                    // The actual compaction will be handled by the background monitoring thread
-                                                }
-                                            }
-                                            Err(e) => {
-                                                log::warn!("Failed to update session tokens for auto-compact: {}", e);
+                if let Some(error) = event.error_message() {
+                    log::warn!("Claude run reported an error: {}", error);
+                }
+
+                // Record per-message usage and charge it against the cost budgets
+                let usage_session_id =
+                    { session_id_holder_clone.lock().unwrap().as_ref().cloned() };
+                if let Some(session_id_str) = &usage_session_id {
+                    match &event {
+                        ClaudeStreamEvent::Assistant {
+                            message_id: Some(message_id),
+                            ..
+                        } => {
+                            if let Some(record) = UsageRecord::from_claude_event(
+                                &event,
+                                &model_clone,
+                                session_id_str,
+                                &project_path_clone,
+                            ) {
+                                let cost = record.cost;
+                                usage_recorder::record_usage(&app_handle, record);
+                                run_budget.charge_message(
+                                    &app_handle,
+                                    session_id_str,
+                                    message_id,
+                                    cost,
+                                );
+                            }
+                        }
+                        ClaudeStreamEvent::Result {
+                            total_cost_usd: Some(total),
+                            ..
+                        } => {
+                            run_budget.charge_total(&app_handle, session_id_str, *total);
+                        }
+                        _ => {}
+                    }
+                }
+
+                // Each assistant message reports the prompt it was given, i.e. the
+                // current context size; feed that to the auto-compact manager
+                let assistant_usage = match &event {
+                    ClaudeStreamEvent::Assistant { .. } => event.usage(),
+                    _ => None,
+                };
+                if let Some(usage) = assistant_usage {
+                    let context_tokens = usage.context_tokens() as usize;
+
+                    let session_id_for_update =
+                        { session_id_holder_clone.lock().unwrap().as_ref().cloned() };
+
+                    if let Some(session_id_str) = &session_id_for_update {
+                        // Update auto-compact manager with token count
+                        if auto_compact_available {
+                            if let Some(auto_compact_state) = app_handle.try_state::<crate::commands::context_manager::AutoCompactState>() {
+                                let auto_compact_state_clone = auto_compact_state.inner().clone();
+                                let session_id_for_compact = session_id_str.clone();
+
+                                // Spawn async task to avoid blocking main output loop
+                                tokio::spawn(async move {
+                                    match auto_compact_state_clone.0.update_session_tokens(&session_id_for_compact, context_tokens).await {
+                                        Ok(compaction_triggered) => {
+                                            if compaction_triggered {
+                                                log::info!("Auto-compaction triggered for session {}", session_id_for_compact);
+                                                // The actual compaction will be handled by the background monitoring thread
                                             }
                                         }
-                                    });
-                                }
+                                        Err(e) => {
+                                            log::warn!("Failed to update session tokens for auto-compact: {}", e);
+                                        }
+                                    }
+                                });
                             }
                         }
                     }
@@ -1016,6 +932,37 @@ async fn spawn_claude_process(
         }
     });
 
+    // Kill the run once it exceeds its time limits. Registered runs go through the
+    // registry; before the init message only the pre-created Job Object is known.
+    let registry_for_timeout = registry.0.clone();
+    let run_id_for_timeout = run_id_holder.clone();
+    let job_object_for_timeout = job_object_holder.clone();
+    timeout::supervise(
+        &app,
+        EngineKind::Claude,
+        pid,
+        project_path.clone(),
+        activity.clone(),
+        move || async move {
+            let run_id = *run_id_for_timeout.lock().unwrap();
+            if let Some(run_id) = run_id {
+                if let Ok(true) = registry_for_timeout.kill_process(run_id).await {
+                    return;
+                }
+            }
+            let job = job_object_for_timeout.lock().unwrap().take();
+            let terminated = match job {
+                Some(job) => job.terminate_all_async(1).await.is_ok(),
+                None => false,
+            };
+            if !terminated && pid != 0 {
+                if let Err(e) = platform::kill_process_tree(pid) {
+                    log::error!("Failed to kill timed out Claude process {}: {}", pid, e);
+                }
+            }
+        },
+    );
+
     // Wait for the process to complete
     // 🔒 CRITICAL FIX: 直接将 child 移动到 wait task 中，而不是从全局 state 取出
     // 这样每个进程独立管理自己的生命周期，支持真正的多会话并发
@@ -1023,16 +970,25 @@ async fn spawn_claude_process(
     let session_id_holder_clone3 = session_id_holder.clone();
     let run_id_holder_clone2 = run_id_holder.clone();
     let registry_clone2 = registry.0.clone();
-    let last_spawned_pid = claude_state.last_spawned_pid.clone();
     // 🔒 CRITICAL FIX: 克隆 tab_id 用于 complete 事件
     let tab_id_for_complete = tab_id;
     tokio::spawn(async move {
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
-        // 🔒 CRITICAL FIX: 直接等待 child，不再从全局 state 取出
-        // child 已经被移动到这个 async block 中
-        match child.wait().await {
+        // Cancelled runs have their handle taken; report them as failed
+        let wait_result = app_handle_wait
+            .state::<ClaudeProcessState>()
+            .wait(&run_key)
+            .await
+            .unwrap_or_else(|| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    "process was cancelled",
+                ))
+            });
+        activity.finish();
+        match wait_result {
             Ok(status) => {
                 log::info!("Claude process exited with status: {}", status);
                 // Add a small delay to ensure all messages are processed
@@ -1087,12 +1043,6 @@ async fn spawn_claude_process(
             let _ = registry_clone2.unregister_process(run_id);
         }
 
-        if pid != 0 {
-            let mut last_pid = last_spawned_pid.lock().await;
-            if last_pid.as_ref() == Some(&pid) {
-                *last_pid = None;
-            }
-        }
     });
 
     Ok(())
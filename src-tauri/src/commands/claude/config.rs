@@ -5,7 +5,7 @@ use std::time::SystemTime;
 use dirs;
 use regex::Regex;
 use rusqlite;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, Runtime};
 use tauri_plugin_shell::ShellExt;
 
 use serde::Serialize;
@@ -816,7 +816,9 @@ fn clear_binary_override(tool: &str) -> Result<(), String> {
 }
 /// 获取当前Claude执行配置
 #[tauri::command]
-pub async fn get_claude_execution_config(_app: AppHandle) -> Result<ClaudeExecutionConfig, String> {
+pub async fn get_claude_execution_config<R: Runtime>(
+    _app: AppHandle<R>,
+) -> Result<ClaudeExecutionConfig, String> {
     let claude_dir =
         get_claude_dir().map_err(|e| format!("Failed to get Claude directory: {}", e))?;
     let config_file = claude_dir.join("execution_config.json");
@@ -7,6 +7,7 @@ mod paths;
 mod platform;
 mod project_store;
 mod session_history;
+mod stream;
 
 pub use models::*;
 pub use paths::*;
@@ -15,6 +16,10 @@ pub use self::cli_runner::{
     cancel_claude_execution, continue_claude_code, execute_claude_code, get_claude_session_output,
     list_running_claude_sessions, resume_claude_code, ClaudeProcessState,
 };
+// Typed stream-json events (session ID / usage / error extraction)
+pub(crate) use self::stream::{
+    extract_init_session_id, parse_claude_line, ClaudeStreamEvent, ClaudeUsage,
+};
 pub use self::config::{
     check_claude_version, clear_custom_claude_path, find_claude_md_files, get_available_tools,
     get_claude_execution_config, get_claude_path, get_claude_permission_config,
@@ -29,7 +34,7 @@ pub use self::config::{
 pub use self::hooks::{get_hooks_config, update_hooks_config, validate_hook_command};
 use self::project_store::ProjectStore;
 pub use file_ops::{list_directory_contents, search_files};
-pub use platform::{apply_no_window_async, kill_process_tree};
+pub use platform::{apply_no_window_async, apply_process_group_async, kill_process_tree};
 // Agent functionality removed
 
 #[tauri::command]
@@ -118,7 +123,7 @@ pub async fn restore_project(project_id: String) -> Result<String, String> {
     Ok(result_msg)
 }
 
-/// Permanently delete a project from the file system with intelligent directory detection
+/// Delete a project directory (moved to the recycle bin) with intelligent directory detection
 #[tauri::command]
 pub async fn delete_project_permanently(project_id: String) -> Result<String, String> {
     let store = ProjectStore::new()?;
@@ -126,11 +131,11 @@ pub async fn delete_project_permanently(project_id: String) -> Result<String, St
 
     let result_msg = if actual_project_id != project_id {
         format!(
-            "项目 '{}' (实际目录: '{}') 已永久删除",
+            "项目 '{}' (实际目录: '{}') 已移至回收站",
             project_id, actual_project_id
         )
     } else {
-        format!("项目 '{}' 已永久删除", project_id)
+        format!("项目 '{}' 已移至回收站", project_id)
     };
 
     log::info!("{}", result_msg);
@@ -51,6 +51,20 @@ pub fn apply_no_window_async(_cmd: &mut tokio::process::Command) {
     // No-op on non-Windows platforms
 }
 
+/// Spawn the command as the leader of a new process group (Unix)
+///
+/// Lets `JobObject` signal the CLI together with every MCP server it starts.
+/// On Windows the Job Object is assigned by PID after spawning, so this is a no-op.
+#[cfg(unix)]
+pub fn apply_process_group_async(cmd: &mut tokio::process::Command) {
+    cmd.process_group(0);
+}
+
+#[cfg(not(unix))]
+pub fn apply_process_group_async(_cmd: &mut tokio::process::Command) {
+    // No-op on non-Unix platforms
+}
+
 /// Kill a process tree (parent and all children)
 ///
 /// On Windows, uses taskkill with /T flag.
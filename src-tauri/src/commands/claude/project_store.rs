@@ -1,11 +1,16 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use serde_json::Value;
 
+use super::super::engine::EngineKind;
+use super::super::session_archive::{
+    archived_path, is_session_file, locate_session_file, open_session_file, session_file_stem,
+};
+use super::super::trash::{move_to_trash, TrashItem, TrashItemKind};
 use super::models::{Project, Session};
 use super::paths::{decode_project_path, get_claude_dir, normalize_path_for_comparison};
 use super::session_history::{
@@ -103,13 +108,8 @@ impl ProjectStore {
                     if let Ok(session_entries) = fs::read_dir(&path) {
                         for session_entry in session_entries.flatten() {
                             let session_path = session_entry.path();
-                            if session_path.is_file()
-                                && session_path.extension().and_then(|s| s.to_str())
-                                    == Some("jsonl")
-                            {
-                                if let Some(session_id) =
-                                    session_path.file_stem().and_then(|s| s.to_str())
-                                {
+                            if session_path.is_file() && is_session_file(&session_path, "jsonl") {
+                                if let Some(session_id) = session_file_stem(&session_path) {
                                     let (first_message, _) =
                                         extract_first_user_message(&session_path);
                                     if first_message.is_some() {
@@ -178,8 +178,8 @@ impl ProjectStore {
             let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
             let path = entry.path();
 
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                if let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) {
+            if path.is_file() && is_session_file(&path, "jsonl") {
+                if let Some(session_id) = session_file_stem(&path).as_deref() {
                     // 🔧 Skip agent-*.jsonl files (subagent sessions)
                     if session_id.starts_with("agent-") {
                         continue;
@@ -253,6 +253,7 @@ impl ProjectStore {
         Ok(sessions)
     }
 
+    /// Moves a session and its TODO / git record files to the recycle bin
     pub fn delete_session(&self, project_id: &str, session_id: &str) -> Result<bool, String> {
         log::info!(
             "Deleting session {} from project {}",
@@ -260,50 +261,43 @@ impl ProjectStore {
             project_id
         );
 
-        let mut session_deleted = false;
-
-        let session_file = self
-            .projects_dir()
-            .join(project_id)
-            .join(format!("{}.jsonl", session_id));
+        let project_dir = self.projects_dir().join(project_id);
+        let session_file = project_dir.join(format!("{}.jsonl", session_id));
 
-        if session_file.exists() {
-            fs::remove_file(&session_file)
-                .map_err(|e| format!("Failed to delete session file: {}", e))?;
-            log::info!("Deleted session file: {:?}", session_file);
-            session_deleted = true;
-        } else {
+        let session_deleted = locate_session_file(&session_file).is_some();
+        if !session_deleted {
             log::warn!("Session file not found: {:?}", session_file);
         }
 
-        let todo_file = self
-            .claude_dir
-            .join("todos")
-            .join(format!("{}.json", session_id));
-
-        if todo_file.exists() {
-            fs::remove_file(&todo_file)
-                .map_err(|e| format!("Failed to delete TODO file: {}", e))?;
-            log::info!("Deleted TODO file: {:?}", todo_file);
+        let (title, _) = extract_first_user_message(&session_file);
+
+        let paths = vec![
+            archived_path(&session_file),
+            session_file,
+            self.todos_dir().join(format!("{}.json", session_id)),
+            // prompt_tracker 的 git 记录，以及旧版本使用的位置
+            project_dir
+                .join("sessions")
+                .join(format!("{}.git-records.json", session_id)),
+            self.claude_dir
+                .join("sessions")
+                .join(project_id)
+                .join(format!("{}.git-records.json", session_id)),
+        ];
+
+        if !paths.iter().any(|p| p.exists()) {
+            return Ok(false);
         }
 
-        let git_records_file = self
-            .claude_dir
-            .join("sessions")
-            .join(project_id)
-            .join(format!("{}.git-records.json", session_id));
-
-        if git_records_file.exists() {
-            if let Err(e) = fs::remove_file(&git_records_file) {
-                log::warn!(
-                    "Failed to delete git records file for {}: {}",
-                    session_id,
-                    e
-                );
-            } else {
-                log::info!("Deleted git records file: {:?}", git_records_file);
-            }
-        }
+        let item = TrashItem {
+            kind: TrashItemKind::Session,
+            engine: EngineKind::Claude,
+            project_id: Some(project_id.to_string()),
+            project_path: Some(decode_project_path(project_id)),
+            session_id: Some(session_id.to_string()),
+            title,
+        };
+        move_to_trash(item, &paths)?;
 
         Ok(session_deleted)
     }
@@ -417,8 +411,17 @@ impl ProjectStore {
             }
         })?;
 
-        fs::remove_dir_all(&dir_to_delete)
-            .map_err(|e| format!("Failed to delete project directory: {}", e))?;
+        let project_path = get_project_path_from_sessions(&dir_to_delete)
+            .unwrap_or_else(|_| decode_project_path(&actual_project_id));
+        let item = TrashItem {
+            kind: TrashItemKind::Project,
+            engine: EngineKind::Claude,
+            project_id: Some(actual_project_id.clone()),
+            project_path: Some(project_path),
+            session_id: None,
+            title: None,
+        };
+        move_to_trash(item, &[dir_to_delete])?;
 
         self.remove_from_hidden_projects(&[project_id, &actual_project_id])?;
 
@@ -628,9 +631,8 @@ fn get_project_path_from_sessions(project_dir: &Path) -> Result<String, String>
     for entry in entries {
         if let Ok(entry) = entry {
             let path = entry.path();
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                if let Ok(file) = fs::File::open(&path) {
-                    let reader = BufReader::new(file);
+            if path.is_file() && is_session_file(&path, "jsonl") {
+                if let Ok(reader) = open_session_file(&path) {
                     // Read up to 10 lines to find cwd field
                     for line_result in reader.lines().take(10) {
                         if let Ok(line) = line_result {
@@ -1,11 +1,14 @@
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 use std::path::Path;
 use std::time::SystemTime;
 
 use chrono::{DateTime, Utc};
 use serde_json::Value;
 
+use super::super::session_archive::{
+    is_session_file, locate_session_file, open_session_file, session_file_stem,
+};
 use super::models::JsonlEntry;
 use super::paths::get_claude_dir;
 
@@ -13,13 +16,11 @@ use super::paths::get_claude_dir;
 pub fn extract_first_user_message<P: AsRef<Path>>(
     jsonl_path: P,
 ) -> (Option<String>, Option<String>) {
-    let file = match fs::File::open(jsonl_path) {
-        Ok(file) => file,
+    let reader = match open_session_file(jsonl_path.as_ref()) {
+        Ok(reader) => reader,
         Err(_) => return (None, None),
     };
 
-    let reader = BufReader::new(file);
-
     for line in reader.lines() {
         if let Ok(line) = line {
             if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
@@ -88,12 +89,10 @@ pub fn extract_first_user_message<P: AsRef<Path>>(
 
 /// Extracts the timestamp of the last message (user or assistant) from a JSONL file
 pub fn extract_last_message_timestamp<P: AsRef<Path>>(jsonl_path: P) -> Option<String> {
-    let file = match fs::File::open(jsonl_path) {
-        Ok(file) => file,
+    let reader = match open_session_file(jsonl_path.as_ref()) {
+        Ok(reader) => reader,
         Err(_) => return None,
     };
-
-    let reader = BufReader::new(file);
     let mut last_timestamp: Option<String> = None;
 
     for line in reader.lines() {
@@ -116,12 +115,10 @@ pub fn extract_last_message_timestamp<P: AsRef<Path>>(jsonl_path: P) -> Option<S
 /// Extracts the model used in the session from a JSONL file
 /// Looks for model information in system init messages or assistant messages
 pub fn extract_session_model<P: AsRef<Path>>(jsonl_path: P) -> Option<String> {
-    let file = match fs::File::open(jsonl_path) {
-        Ok(file) => file,
+    let reader = match open_session_file(jsonl_path.as_ref()) {
+        Ok(reader) => reader,
         Err(_) => return None,
     };
-
-    let reader = BufReader::new(file);
     let mut last_model: Option<String> = None;
 
     for line in reader.lines() {
@@ -157,11 +154,9 @@ pub fn load_session_history(session_id: &str, project_id: &str) -> Result<Vec<Va
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
     let project_dir = claude_dir.join("projects").join(project_id);
-    let session_path = project_dir.join(format!("{}.jsonl", session_id));
-
-    if !session_path.exists() {
-        return Err(format!("Session file not found: {}", session_id));
-    }
+    // 已归档的会话 (.jsonl.zst) 同样可读
+    let session_path = locate_session_file(&project_dir.join(format!("{}.jsonl", session_id)))
+        .ok_or_else(|| format!("Session file not found: {}", session_id))?;
 
     // Get file modification time as base timestamp
     let file_metadata =
@@ -170,10 +165,9 @@ pub fn load_session_history(session_id: &str, project_id: &str) -> Result<Vec<Va
         .modified()
         .unwrap_or_else(|_| SystemTime::now());
 
-    let file =
-        fs::File::open(&session_path).map_err(|e| format!("Failed to open session file: {}", e))?;
+    let reader = open_session_file(&session_path)
+        .map_err(|e| format!("Failed to open session file: {}", e))?;
 
-    let reader = BufReader::new(file);
     let mut messages = Vec::new();
 
     // Step 1: Load main session messages and build agentId -> tool_use_id mapping
@@ -224,14 +218,11 @@ pub fn load_session_history(session_id: &str, project_id: &str) -> Result<Vec<Va
         if let Ok(entries) = fs::read_dir(&project_dir) {
             for entry in entries.flatten() {
                 let path = entry.path();
-                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    // Match agent-*.jsonl files
-                    if file_name.starts_with("agent-") && file_name.ends_with(".jsonl") {
+                if let Some(file_name) = session_file_stem(&path) {
+                    // Match agent-*.jsonl files (plain or archived)
+                    if file_name.starts_with("agent-") && is_session_file(&path, "jsonl") {
                         // Extract agentId from filename (e.g., "agent-aa740fde.jsonl" -> "aa740fde")
-                        let agent_id = file_name
-                            .strip_prefix("agent-")
-                            .and_then(|s| s.strip_suffix(".jsonl"))
-                            .unwrap_or("");
+                        let agent_id = file_name.strip_prefix("agent-").unwrap_or("");
 
                         // Check if this agent belongs to our session
                         if let Some(tool_use_id) = agent_to_tool_use_id.get(agent_id) {
@@ -242,8 +233,7 @@ pub fn load_session_history(session_id: &str, project_id: &str) -> Result<Vec<Va
                             );
 
                             // Load subagent messages
-                            if let Ok(file) = fs::File::open(&path) {
-                                let reader = BufReader::new(file);
+                            if let Ok(reader) = open_session_file(&path) {
                                 for line in reader.lines() {
                                     if let Ok(line) = line {
                                         if let Ok(mut json) = serde_json::from_str::<Value>(&line) {
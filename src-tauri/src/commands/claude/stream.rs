@@ -0,0 +1,390 @@
+//! Claude CLI stream-json event model
+//!
+//! Typed view over the JSONL lines printed by `claude --output-format stream-json`.
+//! The frontend still receives the raw lines; this model is for the backend
+//! (session-ID capture, usage tracking, error reporting) so the probing logic
+//! lives in one place and can be tested against recorded output.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// ============================================================================
+// Stream Event Types
+// ============================================================================
+
+/// Claude CLI stream event - one line of stream-json output
+#[derive(Debug, Clone, Serialize)]
+pub enum ClaudeStreamEvent {
+    /// `system` / `init`: first line of every run, carries the CLI session ID
+    Init {
+        session_id: String,
+        model: Option<String>,
+        cwd: Option<String>,
+        permission_mode: Option<String>,
+        tools: Vec<String>,
+    },
+
+    /// Any other `system` subtype (compact_boundary, hook output, ...)
+    System {
+        subtype: Option<String>,
+        session_id: Option<String>,
+        data: Value,
+    },
+
+    /// Assistant turn (text, thinking and tool_use blocks)
+    Assistant {
+        session_id: Option<String>,
+        message_id: Option<String>,
+        model: Option<String>,
+        content: Vec<ClaudeContentBlock>,
+        usage: Option<ClaudeUsage>,
+        parent_tool_use_id: Option<String>,
+    },
+
+    /// User turn; in stream-json these carry tool_result blocks
+    User {
+        session_id: Option<String>,
+        content: Vec<ClaudeContentBlock>,
+        parent_tool_use_id: Option<String>,
+    },
+
+    /// Final line of a run with totals
+    Result {
+        subtype: String,
+        is_error: bool,
+        session_id: Option<String>,
+        result: Option<String>,
+        total_cost_usd: Option<f64>,
+        duration_ms: Option<u64>,
+        num_turns: Option<u32>,
+        usage: Option<ClaudeUsage>,
+    },
+
+    /// Valid JSON with a type we don't model; kept as-is
+    Unknown(Value),
+}
+
+/// Content block inside an assistant / user message
+#[derive(Debug, Clone, Serialize)]
+pub enum ClaudeContentBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        /// String or array of content blocks, depending on the tool
+        content: Value,
+        is_error: bool,
+    },
+    Other(Value),
+}
+
+/// Token usage reported on assistant messages and on the final result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct ClaudeUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl ClaudeUsage {
+    /// Prompt size as seen by the model (fresh input + cache writes + cache reads)
+    pub fn context_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(String::from)
+}
+
+fn usage_field(value: &Value) -> Option<ClaudeUsage> {
+    value
+        .get("usage")
+        .and_then(|u| serde_json::from_value(u.clone()).ok())
+}
+
+impl ClaudeContentBlock {
+    fn from_json(value: &Value) -> Self {
+        match value.get("type").and_then(|t| t.as_str()) {
+            Some("text") => Self::Text {
+                text: str_field(value, "text").unwrap_or_default(),
+            },
+            Some("thinking") => Self::Thinking {
+                thinking: str_field(value, "thinking").unwrap_or_default(),
+            },
+            Some("tool_use") => Self::ToolUse {
+                id: str_field(value, "id").unwrap_or_default(),
+                name: str_field(value, "name").unwrap_or_default(),
+                input: value.get("input").cloned().unwrap_or(Value::Null),
+            },
+            Some("tool_result") => Self::ToolResult {
+                tool_use_id: str_field(value, "tool_use_id").unwrap_or_default(),
+                content: value.get("content").cloned().unwrap_or(Value::Null),
+                is_error: value
+                    .get("is_error")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            },
+            _ => Self::Other(value.clone()),
+        }
+    }
+
+    /// Blocks of `message.content`; a plain string becomes a single text block
+    fn list_from_message(message: Option<&Value>) -> Vec<Self> {
+        match message.and_then(|m| m.get("content")) {
+            Some(Value::Array(blocks)) => blocks.iter().map(Self::from_json).collect(),
+            Some(Value::String(text)) => vec![Self::Text { text: text.clone() }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ClaudeStreamEvent {
+    /// Convert a parsed JSON line; types we don't know become `Unknown`
+    pub fn from_json(value: &Value) -> Self {
+        let session_id = str_field(value, "session_id");
+
+        match value.get("type").and_then(|t| t.as_str()) {
+            Some("system") => {
+                let subtype = str_field(value, "subtype");
+                let is_init = subtype.as_deref() == Some("init");
+                match (is_init, session_id) {
+                    (true, Some(session_id)) => Self::Init {
+                        session_id,
+                        model: str_field(value, "model"),
+                        cwd: str_field(value, "cwd"),
+                        permission_mode: str_field(value, "permissionMode"),
+                        tools: value
+                            .get("tools")
+                            .and_then(|t| t.as_array())
+                            .map(|tools| {
+                                tools
+                                    .iter()
+                                    .filter_map(|t| t.as_str().map(String::from))
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    },
+                    (_, session_id) => Self::System {
+                        subtype,
+                        session_id,
+                        data: value.clone(),
+                    },
+                }
+            }
+            Some("assistant") => {
+                let message = value.get("message");
+                Self::Assistant {
+                    session_id,
+                    message_id: message.and_then(|m| str_field(m, "id")),
+                    model: message.and_then(|m| str_field(m, "model")),
+                    content: ClaudeContentBlock::list_from_message(message),
+                    usage: message.and_then(usage_field),
+                    parent_tool_use_id: str_field(value, "parent_tool_use_id"),
+                }
+            }
+            Some("user") => Self::User {
+                session_id,
+                content: ClaudeContentBlock::list_from_message(value.get("message")),
+                parent_tool_use_id: str_field(value, "parent_tool_use_id"),
+            },
+            Some("result") => Self::Result {
+                subtype: str_field(value, "subtype").unwrap_or_default(),
+                is_error: value
+                    .get("is_error")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                session_id,
+                result: str_field(value, "result"),
+                total_cost_usd: value.get("total_cost_usd").and_then(|v| v.as_f64()),
+                duration_ms: value.get("duration_ms").and_then(|v| v.as_u64()),
+                num_turns: value
+                    .get("num_turns")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as u32),
+                usage: usage_field(value),
+            },
+            _ => Self::Unknown(value.clone()),
+        }
+    }
+
+    /// Session ID carried by the event, if any
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Self::Init { session_id, .. } => Some(session_id.as_str()),
+            Self::System { session_id, .. }
+            | Self::Assistant { session_id, .. }
+            | Self::User { session_id, .. }
+            | Self::Result { session_id, .. } => session_id.as_deref(),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    /// Usage of an assistant turn, or the run totals on the result line
+    pub fn usage(&self) -> Option<&ClaudeUsage> {
+        match self {
+            Self::Assistant { usage, .. } | Self::Result { usage, .. } => usage.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Error reported by the CLI (failed result line), if any
+    pub fn error_message(&self) -> Option<String> {
+        match self {
+            Self::Result {
+                subtype,
+                is_error,
+                result,
+                ..
+            } if *is_error || subtype.starts_with("error") => Some(
+                result
+                    .clone()
+                    .filter(|r| !r.trim().is_empty())
+                    .unwrap_or_else(|| subtype.clone()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// Parse a single line of stream-json output
+pub fn parse_claude_line(line: &str) -> Result<ClaudeStreamEvent, String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Err("Empty line".to_string());
+    }
+
+    let value: Value = serde_json::from_str(trimmed)
+        .map_err(|e| format!("Failed to parse JSON: {} - line: {}", e, trimmed))?;
+    Ok(ClaudeStreamEvent::from_json(&value))
+}
+
+/// Session ID from a `system/init` line (the only line the CLI uses to announce it)
+pub fn extract_init_session_id(line: &str) -> Option<String> {
+    match parse_claude_line(line).ok()? {
+        ClaudeStreamEvent::Init { session_id, .. } if !session_id.is_empty() => Some(session_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMPLE_SESSION: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/claude/simple_session.jsonl"
+    ));
+    const ERROR_SESSION: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/claude/error_session.jsonl"
+    ));
+
+    fn parse_fixture(fixture: &str) -> Vec<ClaudeStreamEvent> {
+        fixture
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| parse_claude_line(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_simple_session() {
+        let events = parse_fixture(SIMPLE_SESSION);
+        assert_eq!(events.len(), 6);
+
+        match &events[0] {
+            ClaudeStreamEvent::Init {
+                session_id,
+                model,
+                tools,
+                ..
+            } => {
+                assert_eq!(session_id, "7f1c2a9e-3b4d-4e8a-9c1f-0a2b3c4d5e6f");
+                assert_eq!(model.as_deref(), Some("claude-sonnet-4-5-20250929"));
+                assert!(tools.contains(&"Read".to_string()));
+            }
+            other => panic!("Expected Init event, got {:?}", other),
+        }
+
+        match &events[1] {
+            ClaudeStreamEvent::Assistant { content, usage, .. } => {
+                assert!(
+                    matches!(&content[0], ClaudeContentBlock::ToolUse { name, .. } if name == "Read")
+                );
+                let usage = usage.unwrap();
+                assert_eq!(usage.cache_read_input_tokens, 12000);
+                assert_eq!(usage.context_tokens(), 3 + 1500 + 12000);
+            }
+            other => panic!("Expected Assistant event, got {:?}", other),
+        }
+
+        match &events[2] {
+            ClaudeStreamEvent::User { content, .. } => {
+                assert!(matches!(
+                    &content[0],
+                    ClaudeContentBlock::ToolResult { tool_use_id, is_error: false, .. } if tool_use_id == "toolu_01"
+                ));
+            }
+            other => panic!("Expected User event, got {:?}", other),
+        }
+
+        assert!(matches!(events[4], ClaudeStreamEvent::Unknown(_)));
+
+        let result = events.last().unwrap();
+        assert_eq!(
+            result.session_id(),
+            Some("7f1c2a9e-3b4d-4e8a-9c1f-0a2b3c4d5e6f")
+        );
+        let usage = result.usage().unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (9, 240));
+        assert!(result.error_message().is_none());
+        assert!(
+            matches!(result, ClaudeStreamEvent::Result { total_cost_usd: Some(c), .. } if (*c - 0.0123).abs() < 1e-9)
+        );
+    }
+
+    #[test]
+    fn test_parse_error_session() {
+        let events = parse_fixture(ERROR_SESSION);
+
+        assert!(matches!(
+            &events[1],
+            ClaudeStreamEvent::System { subtype: Some(s), .. } if s == "compact_boundary"
+        ));
+        assert_eq!(
+            events.last().unwrap().error_message().as_deref(),
+            Some("error_max_turns")
+        );
+    }
+
+    #[test]
+    fn test_extract_init_session_id() {
+        let first_line = SIMPLE_SESSION.lines().next().unwrap();
+        assert_eq!(
+            extract_init_session_id(first_line).as_deref(),
+            Some("7f1c2a9e-3b4d-4e8a-9c1f-0a2b3c4d5e6f")
+        );
+        assert!(extract_init_session_id(r#"{"type":"assistant","session_id":"x"}"#).is_none());
+        assert!(extract_init_session_id("not json").is_none());
+        assert!(parse_claude_line("   ").is_err());
+    }
+}
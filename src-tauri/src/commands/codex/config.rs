@@ -21,6 +21,8 @@ use crate::claude_binary::detect_binary_for_tool;
 use crate::commands::claude::apply_no_window_async;
 // Import WSL utilities
 use super::super::wsl_utils;
+use crate::commands::engine::EngineKind;
+use crate::commands::provider_history;
 
 // ============================================================================
 // Type Definitions
@@ -1119,6 +1121,7 @@ pub async fn get_current_codex_config() -> Result<CurrentCodexConfig, String> {
 #[tauri::command]
 pub async fn switch_codex_provider(config: CodexProviderConfig) -> Result<String, String> {
     log::info!("[Codex Provider] Switching to provider: {}", config.name);
+    let previous = provider_history::current_base_url(EngineKind::Codex).await;
 
     let is_wsl_mode = should_use_wsl_config();
     log::info!("[Codex Provider] WSL mode: {}", is_wsl_mode);
@@ -1245,6 +1248,7 @@ pub async fn switch_codex_provider(config: CodexProviderConfig) -> Result<String
     // Write merged config.toml
     fs::write(&config_path, &final_config)
         .map_err(|e| format!("Failed to write config.toml: {}", e))?;
+    provider_history::record_switch(EngineKind::Codex, previous).await;
 
     log::info!("[Codex Provider] Successfully switched to: {}", config.name);
 
@@ -1422,6 +1426,7 @@ pub async fn reorder_codex_provider_configs(ids: Vec<String>) -> Result<String,
 #[tauri::command]
 pub async fn clear_codex_provider_config() -> Result<String, String> {
     log::info!("[Codex Provider] Clearing config");
+    let previous = provider_history::current_base_url(EngineKind::Codex).await;
 
     let auth_path = get_codex_auth_path()?;
     let config_path = get_codex_config_path()?;
@@ -1436,6 +1441,7 @@ pub async fn clear_codex_provider_config() -> Result<String, String> {
         fs::remove_file(&config_path)
             .map_err(|e| format!("Failed to remove config.toml: {}", e))?;
     }
+    provider_history::record_switch(EngineKind::Codex, previous).await;
 
     log::info!("[Codex Provider] Successfully cleared config");
     Ok("Successfully cleared Codex configuration. Now using official OpenAI.".to_string())
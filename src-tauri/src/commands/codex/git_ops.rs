@@ -13,6 +13,7 @@ use std::fs;
 use std::path::PathBuf;
 
 // Import simple_git for rewind operations
+use super::super::session_worktree::resolve_project_path;
 use super::super::simple_git;
 // Import rewind helpers/types shared with Claude
 use super::super::prompt_tracker::{
@@ -21,6 +22,8 @@ use super::super::prompt_tracker::{
 // Import WSL utilities
 use super::super::wsl_utils;
 // Import session helpers
+use super::super::engine::EngineKind;
+use super::super::session_archive::{is_archived, open_session_file, read_session_file, rehydrate};
 use super::session::find_session_file;
 
 // Align Codex prompt record type with Claude prompt tracker representation
@@ -49,6 +52,9 @@ pub struct CodexPromptGitRecord {
     pub commit_before: String,
     pub commit_after: Option<String>,
     pub timestamp: String,
+    /// Why the run was stopped early (e.g. "inactivity_timeout")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
 }
 
 /// Collection of Git records for a Codex session
@@ -155,6 +161,18 @@ pub fn truncate_codex_git_records(session_id: &str, prompt_index: usize) -> Resu
     Ok(())
 }
 
+/// Note why the latest prompt's run was stopped; false when there is no record
+pub fn record_codex_stop_reason(session_id: &str, reason: &str) -> Result<bool, String> {
+    let mut git_records = load_codex_git_records(session_id)?;
+    // Records are appended per prompt, so the last one belongs to the current run
+    match git_records.records.last_mut() {
+        Some(record) => record.stop_reason = Some(reason.to_string()),
+        None => return Ok(false),
+    }
+    save_codex_git_records(session_id, &git_records)?;
+    Ok(true)
+}
+
 // ============================================================================
 // Prompt Extraction
 // ============================================================================
@@ -166,7 +184,7 @@ pub fn extract_codex_prompts(session_id: &str) -> Result<Vec<PromptRecord>, Stri
     let session_file = find_session_file(&sessions_dir, session_id)
         .ok_or_else(|| format!("Session file not found for: {}", session_id))?;
 
-    let content = fs::read_to_string(&session_file)
+    let content = read_session_file(&session_file)
         .map_err(|e| format!("Failed to read session file: {}", e))?;
 
     let mut prompts: Vec<PromptRecord> = Vec::new();
@@ -350,11 +368,10 @@ pub fn get_codex_prompt_text(session_id: &str, prompt_index: usize) -> Result<St
     let session_file = find_session_file(&sessions_dir, session_id)
         .ok_or_else(|| format!("Session file not found for: {}", session_id))?;
 
-    use std::io::{BufRead, BufReader};
-    let file =
-        fs::File::open(&session_file).map_err(|e| format!("Failed to open session file: {}", e))?;
+    use std::io::BufRead;
+    let reader = open_session_file(&session_file)
+        .map_err(|e| format!("Failed to open session file: {}", e))?;
 
-    let reader = BufReader::new(file);
     let mut user_message_count = 0;
 
     for line in reader.lines() {
@@ -401,6 +418,12 @@ pub fn truncate_codex_session_to_prompt(
     let sessions_dir = get_codex_sessions_dir()?;
     let session_file = find_session_file(&sessions_dir, session_id)
         .ok_or_else(|| format!("Session file not found for: {}", session_id))?;
+    if is_archived(&session_file) {
+        return Err(format!(
+            "Session {} is archived, rehydrate it before rewinding",
+            session_id
+        ));
+    }
 
     let content = fs::read_to_string(&session_file)
         .map_err(|e| format!("Failed to read session file: {}", e))?;
@@ -487,6 +510,78 @@ pub fn truncate_codex_session_to_prompt(
     Ok(())
 }
 
+/// Copy a Codex session up to (not including) a prompt into a new rollout file
+///
+/// The source rollout and its git records are not modified. Returns the new
+/// session ID (the `session_meta` id, not the file name).
+pub fn fork_codex_session_at_prompt(
+    session_id: &str,
+    prompt_index: usize,
+) -> Result<String, String> {
+    let prompts = extract_codex_prompts(session_id)?;
+    let prompt = prompts
+        .get(prompt_index)
+        .ok_or_else(|| format!("Prompt #{} not found in session", prompt_index))?;
+
+    let sessions_dir = get_codex_sessions_dir()?;
+    let session_file = find_session_file(&sessions_dir, session_id)
+        .ok_or_else(|| format!("Session file not found for: {}", session_id))?;
+    let content = read_session_file(&session_file)
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+
+    let new_session_id = uuid::Uuid::new_v4().to_string();
+
+    // 只有 session_meta 携带会话 ID
+    let mut new_content = String::new();
+    for line in content.lines().take(prompt.line_number) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(mut event) if event["type"].as_str() == Some("session_meta") => {
+                event["payload"]["id"] = serde_json::Value::String(new_session_id.clone());
+                new_content.push_str(&event.to_string());
+            }
+            _ => new_content.push_str(line),
+        }
+        new_content.push('\n');
+    }
+
+    // 与 Codex CLI 一致：sessions/YYYY/MM/DD/rollout-{timestamp}-{uuid}.jsonl
+    let now = Utc::now();
+    let date_dir = sessions_dir
+        .join(now.format("%Y").to_string())
+        .join(now.format("%m").to_string())
+        .join(now.format("%d").to_string());
+    fs::create_dir_all(&date_dir).map_err(|e| format!("Failed to create date directory: {}", e))?;
+    let file_path = date_dir.join(format!(
+        "rollout-{}-{}.jsonl",
+        now.format("%Y-%m-%dT%H-%M-%S"),
+        new_session_id
+    ));
+    fs::write(&file_path, new_content)
+        .map_err(|e| format!("Failed to write forked session: {}", e))?;
+
+    // Carry over git records for the prompts that were copied
+    let mut git_records = load_codex_git_records(session_id)?;
+    git_records
+        .records
+        .retain(|r| r.prompt_index < prompt_index);
+    if !git_records.records.is_empty() {
+        git_records.session_id = new_session_id.clone();
+        save_codex_git_records(&new_session_id, &git_records)?;
+    }
+
+    log::info!(
+        "[Codex Fork] Forked session {} at prompt #{} into {:?}",
+        session_id,
+        prompt_index,
+        file_path
+    );
+
+    Ok(new_session_id)
+}
+
 // ============================================================================
 // Prompt Recording (for rewind tracking)
 // ============================================================================
@@ -498,6 +593,7 @@ pub async fn record_codex_prompt_sent(
     project_path: String,
     _prompt_text: String,
 ) -> Result<usize, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Codex Record] Recording prompt sent for session: {}",
         session_id
@@ -527,35 +623,45 @@ pub async fn record_codex_prompt_sent(
     let commit_before = simple_git::git_current_commit(&project_path)
         .map_err(|e| format!("Failed to get current commit: {}", e))?;
 
-    // Load existing records
-    let mut git_records = load_codex_git_records(&session_id)?;
+    let prompt_index = load_codex_git_records(&session_id)?.records.len();
+    save_codex_prompt_sent_record(&session_id, &project_path, prompt_index, commit_before)?;
+
+    Ok(prompt_index)
+}
+
+/// Save the git record of a prompt about to run from `commit_before`
+///
+/// The prompt queue calls this directly for new sessions, with the HEAD it
+/// captured before the run, since the session ID only exists afterwards.
+pub(crate) fn save_codex_prompt_sent_record(
+    session_id: &str,
+    project_path: &str,
+    prompt_index: usize,
+    commit_before: String,
+) -> Result<(), String> {
+    let mut git_records = load_codex_git_records(session_id)?;
 
     // Update project path if needed
     if git_records.project_path.is_empty() {
-        git_records.project_path = project_path.clone();
+        git_records.project_path = project_path.to_string();
     }
 
-    // Calculate prompt index
-    let prompt_index = git_records.records.len();
-
-    // Create new record
-    let record = CodexPromptGitRecord {
-        prompt_index,
-        commit_before: commit_before.clone(),
-        commit_after: None,
-        timestamp: Utc::now().to_rfc3339(),
-    };
-
-    git_records.records.push(record);
-    save_codex_git_records(&session_id, &git_records)?;
-
     log::info!(
         "[Codex Record] Recorded prompt #{} with commit_before: {}",
         prompt_index,
         &commit_before[..8.min(commit_before.len())]
     );
-
-    Ok(prompt_index)
+    git_records
+        .records
+        .retain(|r| r.prompt_index != prompt_index);
+    git_records.records.push(CodexPromptGitRecord {
+        prompt_index,
+        commit_before,
+        commit_after: None,
+        timestamp: Utc::now().to_rfc3339(),
+        stop_reason: None,
+    });
+    save_codex_git_records(session_id, &git_records)
 }
 
 /// Record a Codex prompt completion (called after AI response)
@@ -566,6 +672,7 @@ pub async fn record_codex_prompt_completed(
     prompt_index: usize,
     prompt_text: Option<String>,
 ) -> Result<(), String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Codex Record] Recording prompt #{} completed for session: {}",
         prompt_index,
@@ -644,6 +751,8 @@ pub async fn revert_codex_to_prompt(
     prompt_index: usize,
     mode: RewindMode,
 ) -> Result<String, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
+    rehydrate(EngineKind::Codex, &session_id, None, None)?;
     log::info!(
         "[Codex Rewind] Reverting session {} to prompt #{} with mode: {:?}",
         session_id,
@@ -43,6 +43,7 @@ pub use session::{
     cancel_codex, delete_codex_session, execute_codex, list_codex_sessions,
     load_codex_session_history, resume_codex, resume_last_codex,
 };
+pub(crate) use session::build_codex_command;
 
 // ============================================================================
 // Re-export Tauri Commands - Git Operations / Rewind
@@ -7,19 +7,16 @@
  * - Session deletion
  */
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::process::Stdio;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tauri::AppHandle;
+use tokio::process::Command;
 
 // Import platform-specific utilities for window hiding
 use crate::claude_binary::detect_binary_for_tool;
-use crate::commands::claude::apply_no_window_async;
-use crate::process::JobObject;
+use crate::commands::engine::{
+    CodexEngine, EngineKind, EngineProcessHandle, EngineProcessState, EngineRunner,
+};
+use crate::commands::session_archive::{is_session_file, open_session_file};
+use crate::commands::trash::{move_to_trash, TrashItem, TrashItemKind};
 // Import WSL utilities for Windows + WSL Codex support
 use super::super::wsl_utils;
 // Import config module for sessions directory
@@ -126,25 +123,17 @@ pub struct CodexSession {
 }
 
 /// Codex process handle with PID for proper cleanup
-pub struct CodexProcessHandle {
-    pub child: Child,
-    pub pid: u32,
-    /// Windows Job Object (kills all child processes when dropped); no-op on non-Windows.
-    pub job_object: Option<JobObject>,
-}
+pub type CodexProcessHandle = EngineProcessHandle;
 
 /// Global state to track Codex processes
-pub struct CodexProcessState {
-    pub processes: Arc<Mutex<HashMap<String, CodexProcessHandle>>>,
-    pub last_session_id: Arc<Mutex<Option<String>>>,
-}
+#[derive(Default)]
+pub struct CodexProcessState(pub EngineProcessState);
 
-impl Default for CodexProcessState {
-    fn default() -> Self {
-        Self {
-            processes: Arc::new(Mutex::new(HashMap::new())),
-            last_session_id: Arc::new(Mutex::new(None)),
-        }
+impl std::ops::Deref for CodexProcessState {
+    type Target = EngineProcessState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -174,12 +163,7 @@ pub async fn execute_codex(
         options.prompt.len()
     );
 
-    // Build codex exec command
-    let (cmd, prompt) = build_codex_command(&options, false, None)?;
-
-    // Execute and stream output
-    let session_id = format!("codex-{}", uuid::Uuid::new_v4());
-    execute_codex_process(session_id, cmd, prompt, options.project_path.clone(), app_handle).await
+    CodexEngine.execute(&app_handle, options, None).await
 }
 
 /// Resumes a previous Codex session
@@ -190,20 +174,9 @@ pub async fn resume_codex(
     app_handle: AppHandle,
 ) -> Result<(), String> {
     log::info!("resume_codex called for session: {}", session_id);
-
-    // Build codex exec resume command (session_id added inside build function)
-    let (cmd, prompt) = build_codex_command(&options, true, Some(&session_id))?;
-
-    // Execute and stream output
-    let channel_session_id = format!("codex-{}", uuid::Uuid::new_v4());
-    execute_codex_process(
-        channel_session_id,
-        cmd,
-        prompt,
-        options.project_path.clone(),
-        app_handle,
-    )
-    .await
+    CodexEngine
+        .resume_session(&app_handle, &session_id, options)
+        .await
 }
 
 /// Resumes the last Codex session
@@ -215,62 +188,14 @@ pub async fn resume_last_codex(
     log::info!("resume_last_codex called");
 
     // Build codex exec resume --last command
-    let (cmd, prompt) = build_codex_command(&options, true, Some("--last"))?;
-
-    // Execute and stream output
-    let session_id = format!("codex-{}", uuid::Uuid::new_v4());
-    execute_codex_process(session_id, cmd, prompt, options.project_path.clone(), app_handle).await
+    CodexEngine.execute(&app_handle, options, Some("--last")).await
 }
 
 /// Cancels a running Codex execution
 #[tauri::command]
 pub async fn cancel_codex(session_id: Option<String>, app_handle: AppHandle) -> Result<(), String> {
-    use crate::commands::claude::kill_process_tree;
-
     log::info!("cancel_codex called for session: {:?}", session_id);
-
-    let state: tauri::State<'_, CodexProcessState> = app_handle.state();
-    let mut processes = state.processes.lock().await;
-
-    if let Some(sid) = session_id {
-        // Cancel specific session
-        if let Some(handle) = processes.remove(&sid) {
-            let pid = handle.pid;
-            log::info!("Killing Codex process tree for session: {} (PID: {})", sid, pid);
-
-            // Kill the entire process tree (parent + all children)
-            if let Err(e) = kill_process_tree(pid) {
-                log::error!("Failed to kill process tree for session {}: {}", sid, e);
-                // Fallback: try to kill main process directly
-                let mut child = handle.child;
-                if let Err(e2) = child.kill().await {
-                    log::error!("Fallback kill also failed: {}", e2);
-                }
-            } else {
-                log::info!("Successfully killed Codex process tree for session: {}", sid);
-            }
-        } else {
-            log::warn!("No running process found for session: {}", sid);
-        }
-    } else {
-        // Cancel all processes
-        for (sid, handle) in processes.drain() {
-            let pid = handle.pid;
-            log::info!("Killing Codex process tree for session: {} (PID: {})", sid, pid);
-
-            if let Err(e) = kill_process_tree(pid) {
-                log::error!("Failed to kill process tree for session {}: {}", sid, e);
-                let mut child = handle.child;
-                if let Err(e2) = child.kill().await {
-                    log::error!("Fallback kill also failed: {}", e2);
-                }
-            } else {
-                log::info!("Successfully killed Codex process tree for session: {}", sid);
-            }
-        }
-    }
-
-    Ok(())
+    CodexEngine.cancel(&app_handle, session_id).await
 }
 
 // ============================================================================
@@ -309,9 +234,7 @@ pub async fn list_codex_sessions() -> Result<Vec<CodexSession>, String> {
                                 if let Ok(file_entries) = std::fs::read_dir(day_entry.path()) {
                                     for file_entry in file_entries.flatten() {
                                         let path = file_entry.path();
-                                        if path.extension().and_then(|s| s.to_str())
-                                            == Some("jsonl")
-                                        {
+                                        if is_session_file(&path, "jsonl") {
                                             match parse_codex_session_file(&path) {
                                                 Some(session) => {
                                                     log::debug!(
@@ -345,10 +268,9 @@ pub async fn list_codex_sessions() -> Result<Vec<CodexSession>, String> {
 
 /// Parses a Codex session JSONL file to extract metadata
 pub fn parse_codex_session_file(path: &std::path::Path) -> Option<CodexSession> {
-    use std::io::{BufRead, BufReader};
+    use std::io::BufRead;
 
-    let file = std::fs::File::open(path).ok()?;
-    let reader = BufReader::new(file);
+    let reader = open_session_file(path).ok()?;
     let mut lines = reader.lines();
 
     // Read first line (session_meta)
@@ -474,11 +396,10 @@ pub async fn load_codex_session_history(
         .ok_or_else(|| format!("Session file not found for ID: {}", session_id))?;
 
     // Read and parse JSONL file
-    use std::io::{BufRead, BufReader};
-    let file = std::fs::File::open(&session_file)
+    use std::io::BufRead;
+    let reader = open_session_file(&session_file)
         .map_err(|e| format!("Failed to open session file: {}", e))?;
 
-    let reader = BufReader::new(file);
     let mut events = Vec::new();
     let mut line_count = 0;
     let mut parse_errors = 0;
@@ -532,14 +453,14 @@ pub fn find_session_file(
     sessions_dir: &std::path::Path,
     session_id: &str,
 ) -> Option<std::path::PathBuf> {
-    use std::io::{BufRead, BufReader};
+    use std::io::BufRead;
     use walkdir::WalkDir;
 
     for entry in WalkDir::new(sessions_dir).into_iter().flatten() {
-        if entry.path().extension().and_then(|s| s.to_str()) == Some("jsonl") {
+        // Archived rollouts (.jsonl.zst) are matched too
+        if is_session_file(entry.path(), "jsonl") {
             // Read the first line to check session_id
-            if let Ok(file) = std::fs::File::open(entry.path()) {
-                let reader = BufReader::new(file);
+            if let Ok(reader) = open_session_file(entry.path()) {
                 if let Some(Ok(first_line)) = reader.lines().next() {
                     if let Ok(meta) = serde_json::from_str::<serde_json::Value>(&first_line) {
                         // Check if this is a session_meta event with matching ID
@@ -578,12 +499,28 @@ pub async fn delete_codex_session(session_id: String) -> Result<String, String>
     let session_file = find_session_file(&sessions_dir, &session_id)
         .ok_or_else(|| format!("Session file not found for ID: {}", session_id))?;
 
-    // Delete the file
-    std::fs::remove_file(&session_file)
-        .map_err(|e| format!("Failed to delete session file: {}", e))?;
+    // Move the rollout and its git records to the recycle bin
+    let project_path = open_session_file(&session_file).ok().and_then(|reader| {
+        use std::io::BufRead;
+        let first_line = reader.lines().next()?.ok()?;
+        let meta = serde_json::from_str::<serde_json::Value>(&first_line).ok()?;
+        meta["payload"]["cwd"].as_str().map(str::to_string)
+    });
+    let git_records_file =
+        super::git_ops::get_codex_git_records_dir()?.join(format!("{}.json", session_id));
+
+    let item = TrashItem {
+        kind: TrashItemKind::Session,
+        engine: EngineKind::Codex,
+        project_id: None,
+        project_path,
+        session_id: Some(session_id.clone()),
+        title: None,
+    };
+    move_to_trash(item, &[session_file.clone(), git_records_file])?;
 
     log::info!(
-        "Successfully deleted Codex session file: {:?}",
+        "Successfully moved Codex session file to trash: {:?}",
         session_file
     );
     Ok(format!("Session {} deleted", session_id))
@@ -596,7 +533,7 @@ pub async fn delete_codex_session(session_id: String) -> Result<String, String>
 /// Builds a Codex command with the given options
 /// Returns (Command, Option<String>) where the String is the prompt to be passed via stdin
 /// Supports both native execution and WSL mode on Windows
-fn build_codex_command(
+pub(crate) fn build_codex_command(
     options: &CodexExecutionOptions,
     is_resume: bool,
     session_id: Option<&str>,
@@ -826,384 +763,3 @@ fn build_wsl_codex_command(
 
     Ok((cmd, Some(options.prompt.clone())))
 }
-
-/// Executes a Codex process and streams output to frontend
-async fn execute_codex_process(
-    session_id: String,
-    mut cmd: Command,
-    prompt: Option<String>,
-    _project_path: String,
-    app_handle: AppHandle,
-) -> Result<(), String> {
-    // 启动流程一开始就发送 session_init，确保即使启动失败也能让前端拿到 session_id 做隔离与错误反馈
-    let init_payload = serde_json::json!({
-        "type": "session_init",
-        "session_id": session_id
-    });
-    if let Err(e) = app_handle.emit("codex-session-init", init_payload) {
-        log::error!("Failed to emit codex-session-init: {}", e);
-    }
-    log::info!("Codex session initialized with ID: {}", session_id);
-
-    // Setup stdio
-    cmd.stdin(Stdio::piped()); // Enable stdin to pass prompt
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
-
-    // Fix: Apply platform-specific no-window configuration to hide console
-    // This prevents the terminal window from flashing when starting Codex sessions
-    apply_no_window_async(&mut cmd);
-
-    // Spawn process
-    let mut child = match cmd.spawn() {
-        Ok(child) => child,
-        Err(e) => {
-            emit_codex_error(&app_handle, &session_id, "启动 Codex 失败", Some(&e.to_string()));
-            // 这里不返回错误给前端（避免覆盖错误事件的可诊断信息），统一走事件通道
-            return Ok(());
-        }
-    };
-
-    // Get process PID for proper cleanup (needed to kill child processes)
-    let pid = match child.id() {
-        Some(pid) => pid,
-        None => {
-            emit_codex_error(
-                &app_handle,
-                &session_id,
-                "启动 Codex 失败：无法获取进程 PID",
-                None,
-            );
-            let _ = child.kill().await;
-            return Ok(());
-        }
-    };
-    log::info!("[Codex] Spawned process with PID: {}", pid);
-
-    // Windows robustness: assign the process to a Job Object so *all* descendants are cleaned up
-    // even if Codex/MCP spawns detached node.exe processes.
-    #[cfg(windows)]
-    let job_object = match JobObject::create() {
-        Ok(job) => match job.assign_process_by_pid(pid) {
-            Ok(_) => {
-                log::info!("[Codex] Assigned PID {} to Job Object for cleanup", pid);
-                Some(job)
-            }
-            Err(e) => {
-                log::warn!("[Codex] Failed to assign PID {} to Job Object: {}", pid, e);
-                None
-            }
-        },
-        Err(e) => {
-            log::warn!("[Codex] Failed to create Job Object: {}", e);
-            None
-        }
-    };
-
-    #[cfg(not(windows))]
-    let job_object: Option<JobObject> = None;
-
-    // FIX: Write prompt to stdin if provided
-    // This avoids command line length limits and special character issues
-    if let Some(prompt_text) = prompt {
-        if let Some(mut stdin) = child.stdin.take() {
-            use tokio::io::AsyncWriteExt;
-
-            log::debug!("Writing prompt to stdin ({} bytes)", prompt_text.len());
-
-            if let Err(e) = stdin.write_all(prompt_text.as_bytes()).await {
-                log::error!("Failed to write prompt to stdin: {}", e);
-                let _ = child.kill().await;
-                emit_codex_error(
-                    &app_handle,
-                    &session_id,
-                    "Codex 写入 stdin 失败",
-                    Some(&e.to_string()),
-                );
-                return Ok(());
-            }
-
-            // Close stdin to signal end of input
-            drop(stdin);
-            log::debug!("Stdin closed successfully");
-        } else {
-            log::error!("Failed to get stdin handle");
-            let _ = child.kill().await;
-            emit_codex_error(
-                &app_handle,
-                &session_id,
-                "Codex 启动失败：无法获取 stdin 句柄",
-                None,
-            );
-            return Ok(());
-        }
-    }
-
-    // Extract stdout and stderr
-    let stdout = match child.stdout.take() {
-        Some(stdout) => stdout,
-        None => {
-            emit_codex_error(&app_handle, &session_id, "启动 Codex 失败：无法捕获 stdout", None);
-            let _ = child.kill().await;
-            return Ok(());
-        }
-    };
-    let stderr = match child.stderr.take() {
-        Some(stderr) => stderr,
-        None => {
-            emit_codex_error(&app_handle, &session_id, "启动 Codex 失败：无法捕获 stderr", None);
-            let _ = child.kill().await;
-            return Ok(());
-        }
-    };
-
-    // Store process in state with PID for proper cleanup
-    let state: tauri::State<'_, CodexProcessState> = app_handle.state();
-    {
-        let mut processes = state.processes.lock().await;
-        let handle = CodexProcessHandle {
-            child,
-            pid,
-            job_object,
-        };
-        processes.insert(session_id.clone(), handle);
-
-        let mut last_session = state.last_session_id.lock().await;
-        *last_session = Some(session_id.clone());
-    }
-
-    // Clone handles for async tasks
-    let app_handle_stdout = app_handle.clone();
-    let app_handle_complete = app_handle.clone();
-    let session_id_stdout = session_id.clone(); // Clone for stdout task
-    let session_id_stderr = session_id.clone(); // Clone for stderr task
-    let session_id_complete = session_id.clone();
-
-    // 用于判断是否收到了任何 stdout 事件；仅当 stdout 完全无输出且存在 stderr 时，才触发 codex-error
-    let saw_stdout = Arc::new(AtomicBool::new(false));
-    let saw_stdout_for_complete = saw_stdout.clone();
-    let stderr_buffer: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
-    let stderr_buffer_for_stderr = stderr_buffer.clone();
-    let stderr_buffer_for_complete = stderr_buffer.clone();
-
-    // 🔧 FIX: Use channels to track stdout/stderr closure for timeout detection
-    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
-    let (stderr_done_tx, _stderr_done_rx) = tokio::sync::oneshot::channel();
-
-    // Spawn task to read stdout (JSONL events)
-    // FIX: Emit to both session-specific and global channels for proper multi-tab isolation
-    tokio::spawn(async move {
-        let mut reader = BufReader::new(stdout).lines();
-        let mut done_tx = Some(done_tx);
-        while let Ok(Some(line)) = reader.next_line().await {
-            if !line.trim().is_empty() {
-                saw_stdout.store(true, Ordering::Relaxed);
-                // Use trace level to avoid flooding logs in debug mode
-                log::trace!("Codex output: {}", line);
-                // Emit to session-specific channel first (for multi-tab isolation)
-                if let Err(e) =
-                    app_handle_stdout.emit(&format!("codex-output:{}", session_id_stdout), &line)
-                {
-                    log::error!("Failed to emit codex-output (session-specific): {}", e);
-                }
-                // Also emit to global channel for backward compatibility
-                if let Err(e) = app_handle_stdout.emit("codex-output", &line) {
-                    log::error!("Failed to emit codex-output (global): {}", e);
-                }
-
-                // Detect turn completion to trigger backend cleanup even if stdout never closes.
-                if done_tx.is_some() {
-                    let is_done_event = serde_json::from_str::<serde_json::Value>(&line)
-                        .ok()
-                        .and_then(|v| {
-                            v.get("type")
-                                .and_then(|t| t.as_str())
-                                .map(|s| s.to_string())
-                        })
-                        .map(|t| matches!(t.as_str(), "turn.completed" | "turn.failed" | "error"))
-                        .unwrap_or(false);
-
-                    if is_done_event {
-                        log::info!(
-                            "[Codex] Detected completion event on stdout for session: {}",
-                            session_id_stdout
-                        );
-                        if let Some(tx) = done_tx.take() {
-                            let _ = tx.send(());
-                        }
-                    }
-                }
-            }
-        }
-        log::info!("[Codex] Stdout closed for session: {}", session_id_stdout);
-        // Fallback: stdout closed, treat as completion if not already signaled.
-        if let Some(tx) = done_tx.take() {
-            let _ = tx.send(());
-        }
-    });
-
-    // Spawn task to read stderr (log errors, suppress debug output)
-    tokio::spawn(async move {
-        let mut reader = BufReader::new(stderr).lines();
-        while let Ok(Some(line)) = reader.next_line().await {
-            // Log error messages for debugging
-            if !line.trim().is_empty() {
-                log::warn!("Codex stderr: {}", line);
-                // 仅缓存少量 stderr 以便在“无 stdout 输出”的启动失败场景下进行汇总反馈
-                let mut buf = stderr_buffer_for_stderr.lock().await;
-                if buf.len() < 20 {
-                    buf.push(line);
-                }
-            }
-        }
-        log::info!("[Codex] Stderr closed for session: {}", session_id_stderr);
-        // Signal that stderr is done (ignore send error if receiver dropped)
-        let _ = stderr_done_tx.send(());
-    });
-
-    // Spawn task to wait for process completion
-    // 🔧 FIX: Only wait for stdout to close, then send completion event immediately
-    // stderr may continue outputting logs (MCP servers, etc.) for a long time
-    let pid_for_cleanup = pid; // Copy PID for cleanup task
-    tokio::spawn(async move {
-        use crate::commands::claude::kill_process_tree;
-
-        let state: tauri::State<'_, CodexProcessState> = app_handle_complete.state();
-
-        // Only wait for stdout to close (stderr can continue logging)
-        let _ = done_rx.await;
-        log::info!("[Codex] Completion signaled for session: {}", session_id_complete);
-
-        // 若 stdout 完全无输出但 stderr 有内容，补发一次可诊断错误事件，避免前端表现为“无反应”
-        if !saw_stdout_for_complete.load(Ordering::Relaxed) {
-            let buf = stderr_buffer_for_complete.lock().await;
-            if !buf.is_empty() {
-                let detail = buf.join("\n");
-                emit_codex_error(
-                    &app_handle_complete,
-                    &session_id_complete,
-                    "Codex 启动失败或未产生任何输出",
-                    Some(&detail),
-                );
-            }
-        }
-
-        // 🔧 CRITICAL FIX: Emit completion event immediately after stdout closes
-        // Don't wait for process exit or stderr - those can take a long time
-        // stdout closing means all JSONL events have been sent, session is effectively complete
-        log::info!(
-            "[Codex] Sending completion event for session: {}",
-            session_id_complete
-        );
-        if let Err(e) =
-            app_handle_complete.emit(&format!("codex-complete:{}", session_id_complete), true)
-        {
-            log::error!("Failed to emit codex-complete (session-specific): {}", e);
-        }
-        if let Err(e) = app_handle_complete.emit("codex-complete", true) {
-            log::error!("Failed to emit codex-complete (global): {}", e);
-        }
-
-        // Continue waiting for process exit in background (with timeout protection)
-        // This ensures proper cleanup but doesn't block the completion event
-        // After turn completion, Codex should exit promptly; keep a short grace window to
-        // let it flush session files, then force-kill to prevent orphan node.exe accumulation.
-        let timeout_duration = tokio::time::Duration::from_secs(3);
-        let start_time = tokio::time::Instant::now();
-
-        loop {
-            let mut processes = state.processes.lock().await;
-
-            if let Some(handle) = processes.get_mut(&session_id_complete) {
-                match handle.child.try_wait() {
-                    Ok(Some(status)) => {
-                        log::info!("[Codex] Process exited with status: {}", status);
-                        processes.remove(&session_id_complete);
-                        break;
-                    }
-                    Ok(None) => {
-                        // Check timeout
-                        if start_time.elapsed() > timeout_duration {
-                            log::warn!(
-                                "[Codex] Process {} (PID: {}) did not exit within {}s after completion, force killing process tree",
-                                session_id_complete,
-                                pid_for_cleanup,
-                                timeout_duration.as_secs()
-                            );
-
-                            // 🔧 FIX: Kill entire process tree to prevent orphan child processes
-                            // Prefer Job Object termination (Windows) to ensure detached descendants are killed.
-                            let mut terminated_via_job = false;
-                            if let Some(job) = handle.job_object.as_ref() {
-                                match job.terminate_all(1) {
-                                    Ok(_) => {
-                                        terminated_via_job = true;
-                                        log::info!(
-                                            "[Codex] Terminated Job Object for PID: {}",
-                                            pid_for_cleanup
-                                        );
-                                    }
-                                    Err(e) => {
-                                        log::warn!(
-                                            "[Codex] Failed to terminate Job Object for PID {}: {}",
-                                            pid_for_cleanup,
-                                            e
-                                        );
-                                    }
-                                }
-                            }
-
-                            if !terminated_via_job {
-                                if let Err(e) = kill_process_tree(pid_for_cleanup) {
-                                    log::error!("[Codex] Failed to kill process tree: {}", e);
-                                    // Fallback: try to kill main process directly
-                                    if let Err(e2) = handle.child.kill().await {
-                                        log::error!("[Codex] Fallback kill also failed: {}", e2);
-                                    }
-                                } else {
-                                    log::info!(
-                                        "[Codex] Successfully killed process tree for PID: {}",
-                                        pid_for_cleanup
-                                    );
-                                }
-                            }
-                            processes.remove(&session_id_complete);
-                            break;
-                        }
-
-                        drop(processes);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    }
-                    Err(e) => {
-                        log::error!("[Codex] Error checking process status: {}", e);
-                        processes.remove(&session_id_complete);
-                        break;
-                    }
-                }
-            } else {
-                log::info!(
-                    "[Codex] Process {} was removed (cancelled)",
-                    session_id_complete
-                );
-                break;
-            }
-        }
-    });
-
-    Ok(())
-}
-
-fn emit_codex_error(app_handle: &AppHandle, session_id: &str, message: &str, detail: Option<&str>) {
-    let payload = serde_json::json!({
-        "session_id": session_id,
-        "error": {
-            "message": message,
-            "detail": detail,
-        }
-    });
-
-    let payload_str = serde_json::to_string(&payload).unwrap_or_else(|_| message.to_string());
-
-    let _ = app_handle.emit(&format!("codex-error:{}", session_id), &payload_str);
-    let _ = app_handle.emit("codex-error", &payload_str);
-}
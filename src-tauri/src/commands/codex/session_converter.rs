@@ -1,17 +1,19 @@
 use once_cell::sync::Lazy;
 /**
- * Claude ↔ Codex Session 转换模块
+ * Claude ↔ Codex ↔ Gemini Session 转换模块
  *
- * 实现 Claude 与 Codex 引擎之间的 Session 双向转换功能。
+ * 实现 Claude、Codex、Gemini 引擎之间的 Session 双向转换功能。
  * 支持：
  * - Claude → Codex：将 Claude session 转换为 Codex 可执行的 session
  * - Codex → Claude：将 Codex session 转换为 Claude 可加载的历史记录
+ * - Gemini ↔ Claude / Codex：读写 Gemini chats/session-*.json
+ *   （Gemini ↔ Codex 在内存中以 Claude 消息为中间格式）
  *
  * 核心特性：
  * - 自动识别引擎类型（UUID vs rollout-前缀）
  * - 生成新的 Session ID（避免冲突）
  * - 元数据中记录转换来源（可追溯）
- * - 工具调用名称映射（bash ↔ shell_command 等）
+ * - 工具调用名称映射（bash ↔ shell_command ↔ run_shell_command 等）
  * - 仅支持已完成的 Session 转换
  */
 use serde::{Deserialize, Serialize};
@@ -19,6 +21,8 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
 
+use super::super::gemini::types::GeminiSessionDetail;
+
 // ================================
 // 数据结构定义
 // ================================
@@ -27,7 +31,7 @@ use std::io::{BufRead, BufReader, Write};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConversionSource {
-    /// 源引擎类型: "claude" | "codex"
+    /// 源引擎类型: "claude" | "codex" | "gemini"
     pub engine: String,
     /// 源 Session ID
     pub session_id: String,
@@ -295,6 +299,134 @@ pub fn map_claude_to_codex_tool(claude_name: &str) -> String {
         .unwrap_or_else(|| claude_name.to_string())
 }
 
+/// Gemini → Claude 工具名称映射
+pub static GEMINI_TO_CLAUDE_TOOL_MAP: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert("run_shell_command", "bash");
+    m.insert("read_file", "read");
+    m.insert("read_many_files", "read");
+    m.insert("write_file", "write");
+    m.insert("replace", "edit");
+    m.insert("search_file_content", "grep");
+    m.insert("glob", "glob");
+    m.insert("list_directory", "ls");
+    m.insert("web_fetch", "webfetch");
+    m.insert("google_web_search", "websearch");
+    m.insert("write_todos", "todowrite");
+    m
+});
+
+/// Claude → Gemini 工具名称映射 (反向)
+pub static CLAUDE_TO_GEMINI_TOOL_MAP: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert("bash", "run_shell_command");
+    m.insert("read", "read_file");
+    m.insert("write", "write_file");
+    m.insert("edit", "replace");
+    m.insert("multiedit", "replace");
+    m.insert("grep", "search_file_content");
+    m.insert("glob", "glob");
+    m.insert("ls", "list_directory");
+    m.insert("webfetch", "web_fetch");
+    m.insert("websearch", "google_web_search");
+    m.insert("todowrite", "write_todos");
+    m
+});
+
+/// 映射 Gemini 工具名到 Claude 工具名
+/// MCP 工具 (mcp__ 前缀) 不进行映射
+pub fn map_gemini_to_claude_tool(gemini_name: &str) -> String {
+    if gemini_name.starts_with("mcp__") {
+        return gemini_name.to_string();
+    }
+    let lower = gemini_name.to_lowercase();
+    GEMINI_TO_CLAUDE_TOOL_MAP
+        .get(lower.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| gemini_name.to_string())
+}
+
+/// 映射 Claude 工具名到 Gemini 工具名
+/// MCP 工具 (mcp__ 前缀) 不进行映射
+pub fn map_claude_to_gemini_tool(claude_name: &str) -> String {
+    if claude_name.starts_with("mcp__") {
+        return claude_name.to_string();
+    }
+    let lower = claude_name.to_lowercase();
+    CLAUDE_TO_GEMINI_TOOL_MAP
+        .get(lower.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| claude_name.to_string())
+}
+
+// ================================
+// Claude 内容块解析
+// ================================
+
+/// 解析 content（支持字符串或数组格式）为 ClaudeContentBlock 数组
+fn parse_content_blocks(content: &Option<Value>) -> Vec<ClaudeContentBlock> {
+    let mut blocks = Vec::new();
+
+    if let Some(content_value) = content {
+        if let Some(text) = content_value.as_str() {
+            // 字符串格式 - 直接转为文本块
+            blocks.push(ClaudeContentBlock::Text {
+                text: text.to_string(),
+            });
+        } else if let Some(array) = content_value.as_array() {
+            // 数组格式 - 解析每个块
+            for item in array {
+                if let Some(block_type) = item.get("type").and_then(|t| t.as_str()) {
+                    match block_type {
+                        "text" => {
+                            if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
+                                blocks.push(ClaudeContentBlock::Text {
+                                    text: text.to_string(),
+                                });
+                            }
+                        }
+                        "tool_use" => {
+                            if let (Some(id), Some(name), Some(input)) = (
+                                item.get("id").and_then(|i| i.as_str()),
+                                item.get("name").and_then(|n| n.as_str()),
+                                item.get("input"),
+                            ) {
+                                blocks.push(ClaudeContentBlock::ToolUse {
+                                    id: id.to_string(),
+                                    name: name.to_string(),
+                                    input: input.clone(),
+                                });
+                            }
+                        }
+                        "tool_result" => {
+                            if let (Some(tool_use_id), Some(content)) = (
+                                item.get("tool_use_id").and_then(|t| t.as_str()),
+                                item.get("content"),
+                            ) {
+                                blocks.push(ClaudeContentBlock::ToolResult {
+                                    tool_use_id: tool_use_id.to_string(),
+                                    content: content.clone(),
+                                    is_error: item.get("is_error").and_then(|e| e.as_bool()),
+                                });
+                            }
+                        }
+                        "thinking" => {
+                            if let Some(thinking) = item.get("thinking").and_then(|t| t.as_str()) {
+                                blocks.push(ClaudeContentBlock::Thinking {
+                                    thinking: thinking.to_string(),
+                                });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
 // ================================
 // Claude → Codex 转换器
 // ================================
@@ -327,72 +459,6 @@ impl ClaudeToCodexConverter {
         }
     }
 
-    /// 解析 content（支持字符串或数组格式）为 ClaudeContentBlock 数组
-    fn parse_content_blocks(&self, content: &Option<Value>) -> Vec<ClaudeContentBlock> {
-        let mut blocks = Vec::new();
-
-        if let Some(content_value) = content {
-            if let Some(text) = content_value.as_str() {
-                // 字符串格式 - 直接转为文本块
-                blocks.push(ClaudeContentBlock::Text {
-                    text: text.to_string(),
-                });
-            } else if let Some(array) = content_value.as_array() {
-                // 数组格式 - 解析每个块
-                for item in array {
-                    if let Some(block_type) = item.get("type").and_then(|t| t.as_str()) {
-                        match block_type {
-                            "text" => {
-                                if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                                    blocks.push(ClaudeContentBlock::Text {
-                                        text: text.to_string(),
-                                    });
-                                }
-                            }
-                            "tool_use" => {
-                                if let (Some(id), Some(name), Some(input)) = (
-                                    item.get("id").and_then(|i| i.as_str()),
-                                    item.get("name").and_then(|n| n.as_str()),
-                                    item.get("input"),
-                                ) {
-                                    blocks.push(ClaudeContentBlock::ToolUse {
-                                        id: id.to_string(),
-                                        name: name.to_string(),
-                                        input: input.clone(),
-                                    });
-                                }
-                            }
-                            "tool_result" => {
-                                if let (Some(tool_use_id), Some(content)) = (
-                                    item.get("tool_use_id").and_then(|t| t.as_str()),
-                                    item.get("content"),
-                                ) {
-                                    blocks.push(ClaudeContentBlock::ToolResult {
-                                        tool_use_id: tool_use_id.to_string(),
-                                        content: content.clone(),
-                                        is_error: item.get("is_error").and_then(|e| e.as_bool()),
-                                    });
-                                }
-                            }
-                            "thinking" => {
-                                if let Some(thinking) =
-                                    item.get("thinking").and_then(|t| t.as_str())
-                                {
-                                    blocks.push(ClaudeContentBlock::Thinking {
-                                        thinking: thinking.to_string(),
-                                    });
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
-
-        blocks
-    }
-
     pub fn convert(&self) -> Result<ConversionResult, String> {
         log::info!(
             "Converting Claude session {} to Codex",
@@ -419,7 +485,7 @@ impl ClaudeToCodexConverter {
             })
             .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
         let model = claude_messages.iter().find_map(|m| m.model.clone());
-        codex_events.push(self.create_session_meta(&first_timestamp, model.as_deref()));
+        codex_events.push(self.create_session_meta(&first_timestamp, model.as_deref(), "claude"));
 
         // 3b. 转换每条消息（拆分多内容块为多个事件）
         for msg in &claude_messages {
@@ -511,7 +577,12 @@ impl ClaudeToCodexConverter {
     }
 
     /// 创建 session_meta 事件（Codex session 文件的首行）
-    fn create_session_meta(&self, timestamp: &str, model: Option<&str>) -> CodexEvent {
+    fn create_session_meta(
+        &self,
+        timestamp: &str,
+        model: Option<&str>,
+        source_engine: &str,
+    ) -> CodexEvent {
         CodexEvent {
             event_type: "session_meta".to_string(),
             timestamp: Some(timestamp.to_string()),
@@ -524,7 +595,7 @@ impl ClaudeToCodexConverter {
                 "source": "conversion",
                 "model_provider": model.map(|_| "converted").unwrap_or("unknown"),
                 "conversion_source": {
-                    "engine": "claude",
+                    "engine": source_engine,
                     "session_id": self.source_session_id,
                     "converted_at": chrono::Utc::now().to_rfc3339(),
                     "source_project_path": self.project_path
@@ -548,13 +619,24 @@ impl ClaudeToCodexConverter {
         match msg.message_type.as_str() {
             "user" => {
                 if let Some(ref message) = msg.message {
-                    let blocks = self.parse_content_blocks(&message.content);
-                    events.push(self.create_user_response_item(&blocks, &timestamp));
+                    let blocks = parse_content_blocks(&message.content);
+                    if blocks
+                        .iter()
+                        .any(|b| matches!(b, ClaudeContentBlock::Text { .. }))
+                    {
+                        events.push(self.create_user_response_item(&blocks, &timestamp));
+                    }
+                    // Claude 的 tool_result 位于 user 消息中，对应 Codex 的 function_call_output
+                    let results: Vec<ClaudeContentBlock> = blocks
+                        .into_iter()
+                        .filter(|b| matches!(b, ClaudeContentBlock::ToolResult { .. }))
+                        .collect();
+                    events.extend(self.convert_assistant_content(&results, &timestamp));
                 }
             }
             "assistant" => {
                 if let Some(ref message) = msg.message {
-                    let blocks = self.parse_content_blocks(&message.content);
+                    let blocks = parse_content_blocks(&message.content);
                     // 拆分多内容块为多个事件
                     events.extend(self.convert_assistant_content(&blocks, &timestamp));
                 }
@@ -621,8 +703,7 @@ impl ClaudeToCodexConverter {
                     });
                 }
                 ClaudeContentBlock::ToolUse { id, name, input } => {
-                    // 生成新的 call_id
-                    let new_id = format!("call_{}", uuid::Uuid::new_v4());
+                    // 沿用原 tool_use id 作为 call_id，function_call_output 才能对应上
                     let codex_tool_name = map_claude_to_codex_tool(name);
                     let arguments = serde_json::to_string(input).unwrap_or_default();
 
@@ -633,7 +714,7 @@ impl ClaudeToCodexConverter {
                             "type": "function_call",
                             "name": codex_tool_name,
                             "arguments": arguments,
-                            "call_id": new_id,
+                            "call_id": id,
                             "timestamp": timestamp,
                             "original_tool_use_id": id
                         })),
@@ -828,32 +909,14 @@ impl CodexToClaudeConverter {
         }
     }
 
-    pub fn convert(&self) -> Result<ConversionResult, String> {
-        log::info!(
-            "Converting Codex session {} to Claude",
-            self.source_session_id
-        );
-
-        // 1. 读取源 Codex session
-        let codex_events = self.read_codex_session()?;
-
-        // 2. 验证 session 已完成
-        self.validate_session_completed(&codex_events)?;
-
-        // 3. 转换事件为 Claude 消息
-        let mut claude_messages: Vec<ClaudeMessage> = Vec::new();
-
-        // 3a. 添加 file-history-snapshot 作为第一条消息（必需！）
-        let first_timestamp = codex_events
-            .first()
-            .and_then(|e| e.timestamp.clone())
-            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+    /// 创建 file-history-snapshot 消息（Claude session 的首条消息，必需！）
+    fn create_file_history_snapshot(&self, timestamp: &str) -> ClaudeMessage {
         let snapshot_uuid = uuid::Uuid::new_v4().to_string();
 
-        claude_messages.push(ClaudeMessage {
+        ClaudeMessage {
             message_type: "file-history-snapshot".to_string(),
             message: None,
-            timestamp: Some(first_timestamp.clone()),
+            timestamp: Some(timestamp.to_string()),
             uuid: Some(snapshot_uuid.clone()),
             parent_uuid: None,
             session_id: None,
@@ -878,13 +941,36 @@ impl CodexToClaudeConverter {
                     serde_json::json!({
                         "messageId": snapshot_uuid,
                         "trackedFileBackups": {},
-                        "timestamp": first_timestamp
+                        "timestamp": timestamp
                     }),
                 );
                 map.insert("isSnapshotUpdate".to_string(), Value::Bool(false));
                 map
             },
-        });
+        }
+    }
+
+    pub fn convert(&self) -> Result<ConversionResult, String> {
+        log::info!(
+            "Converting Codex session {} to Claude",
+            self.source_session_id
+        );
+
+        // 1. 读取源 Codex session
+        let codex_events = self.read_codex_session()?;
+
+        // 2. 验证 session 已完成
+        self.validate_session_completed(&codex_events)?;
+
+        // 3. 转换事件为 Claude 消息
+        let mut claude_messages: Vec<ClaudeMessage> = Vec::new();
+
+        // 3a. 添加 file-history-snapshot 作为第一条消息（必需！）
+        let first_timestamp = codex_events
+            .first()
+            .and_then(|e| e.timestamp.clone())
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        claude_messages.push(self.create_file_history_snapshot(&first_timestamp));
 
         // 3b. 转换 Codex 事件
         for event in &codex_events {
@@ -929,10 +1015,10 @@ impl CodexToClaudeConverter {
                 || format!("Codex session file not found: {}", self.source_session_id),
             )?;
 
-        let file = std::fs::File::open(&session_path)
+        // 已归档的 rollout (.jsonl.zst) 透明解压读取
+        let reader = super::super::session_archive::open_session_file(&session_path)
             .map_err(|e| format!("Failed to open session file: {}", e))?;
 
-        let reader = BufReader::new(file);
         let mut events = Vec::new();
 
         for line in reader.lines() {
@@ -1212,12 +1298,681 @@ impl CodexToClaudeConverter {
     }
 }
 
+// ================================
+// Gemini 会话读写
+// ================================
+
+/// 提取 Gemini 工具调用的输出
+/// result 为 [{ functionResponse: { response: { output } } }]，缺失时回退到 resultDisplay
+pub(crate) fn gemini_tool_output(tool_call: &Value) -> Option<Value> {
+    let output = tool_call
+        .get("result")
+        .and_then(|r| r.as_array())
+        .and_then(|parts| parts.first())
+        .and_then(|p| p.pointer("/functionResponse/response/output"))
+        .cloned();
+
+    output.or_else(|| tool_call.get("resultDisplay").cloned())
+}
+
+/// 将 Claude 消息序列转换为 Gemini chats 消息
+///
+/// Gemini 的一条 "gemini" 消息包含文本、thoughts 与 toolCalls（含结果），
+/// 因此连续的 assistant 消息会合并，tool_result 回填到对应的 toolCall 上。
+fn claude_messages_to_gemini(messages: &[ClaudeMessage]) -> Vec<Value> {
+    let mut gemini_messages: Vec<Value> = Vec::new();
+    // tool_use_id -> (消息索引, toolCalls 索引)
+    let mut pending_calls: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for msg in messages {
+        let Some(ref message) = msg.message else {
+            continue;
+        };
+        let timestamp = msg
+            .timestamp
+            .clone()
+            .or_else(|| msg.sent_at.clone())
+            .or_else(|| msg.received_at.clone())
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        let blocks = parse_content_blocks(&message.content);
+
+        match msg.message_type.as_str() {
+            "user" => {
+                let mut texts = Vec::new();
+                for block in blocks {
+                    match block {
+                        ClaudeContentBlock::Text { text } => texts.push(text),
+                        ClaudeContentBlock::ToolResult {
+                            tool_use_id,
+                            content,
+                            is_error,
+                        } => {
+                            let Some(&(msg_idx, call_idx)) = pending_calls.get(&tool_use_id) else {
+                                continue;
+                            };
+                            let output = match content {
+                                Value::String(s) => s,
+                                other => serde_json::to_string(&other).unwrap_or_default(),
+                            };
+                            let call = &mut gemini_messages[msg_idx]["toolCalls"][call_idx];
+                            let name = call["name"].clone();
+                            call["status"] = serde_json::json!(if is_error.unwrap_or(false) {
+                                "error"
+                            } else {
+                                "success"
+                            });
+                            call["result"] = serde_json::json!([{
+                                "functionResponse": {
+                                    "id": tool_use_id,
+                                    "name": name,
+                                    "response": { "output": output }
+                                }
+                            }]);
+                            call["resultDisplay"] = Value::String(output);
+                        }
+                        _ => {}
+                    }
+                }
+
+                let text = texts.join("\n\n");
+                if !text.trim().is_empty() {
+                    gemini_messages.push(serde_json::json!({
+                        "id": uuid::Uuid::new_v4().to_string(),
+                        "timestamp": timestamp,
+                        "type": "user",
+                        "content": text
+                    }));
+                }
+            }
+            "assistant" => {
+                if blocks.is_empty() {
+                    continue;
+                }
+
+                // 合并到上一条 gemini 消息；工具调用之后出现的文本另起一条
+                for block in blocks {
+                    let starts_turn = match gemini_messages.last() {
+                        Some(last) if last["type"] == "gemini" => {
+                            matches!(block, ClaudeContentBlock::Text { .. })
+                                && last["toolCalls"]
+                                    .as_array()
+                                    .is_some_and(|calls| !calls.is_empty())
+                        }
+                        _ => true,
+                    };
+                    if starts_turn {
+                        gemini_messages.push(serde_json::json!({
+                            "id": uuid::Uuid::new_v4().to_string(),
+                            "timestamp": timestamp,
+                            "type": "gemini",
+                            "content": "",
+                            "thoughts": [],
+                            "toolCalls": []
+                        }));
+                    }
+
+                    let msg_idx = gemini_messages.len() - 1;
+                    let current = &mut gemini_messages[msg_idx];
+                    match block {
+                        ClaudeContentBlock::Text { text } => {
+                            let content = current["content"].as_str().unwrap_or("").to_string();
+                            current["content"] = Value::String(if content.is_empty() {
+                                text
+                            } else {
+                                format!("{}\n\n{}", content, text)
+                            });
+                        }
+                        ClaudeContentBlock::Thinking { thinking } => {
+                            if let Some(thoughts) = current["thoughts"].as_array_mut() {
+                                thoughts.push(serde_json::json!({
+                                    "subject": "",
+                                    "description": thinking,
+                                    "timestamp": timestamp
+                                }));
+                            }
+                        }
+                        ClaudeContentBlock::ToolUse { id, name, input } => {
+                            if let Some(calls) = current["toolCalls"].as_array_mut() {
+                                pending_calls.insert(id.clone(), (msg_idx, calls.len()));
+                                calls.push(serde_json::json!({
+                                    "id": id,
+                                    "name": map_claude_to_gemini_tool(&name),
+                                    "args": input,
+                                    "status": "success",
+                                    "timestamp": timestamp
+                                }));
+                            }
+                        }
+                        ClaudeContentBlock::ToolResult { .. } => {}
+                    }
+                }
+
+                if let Some(current) = gemini_messages.last_mut() {
+                    if let Some(ref model) = msg.model {
+                        current["model"] = Value::String(model.clone());
+                    }
+                    if let Some(ref usage) = message.usage {
+                        current["tokens"] = serde_json::json!({
+                            "input": usage.input_tokens,
+                            "output": usage.output_tokens,
+                            "cached": usage.cache_read_tokens.unwrap_or(0),
+                            "total": usage.input_tokens + usage.output_tokens
+                        });
+                    }
+                }
+            }
+            _ => {
+                // 跳过 system / file-history-snapshot 等
+            }
+        }
+    }
+
+    // 与原生文件一致：没有内容时不输出 thoughts / toolCalls
+    for msg in &mut gemini_messages {
+        if let Some(obj) = msg.as_object_mut() {
+            obj.retain(|key, value| {
+                !(matches!(key.as_str(), "thoughts" | "toolCalls")
+                    && value.as_array().is_some_and(|a| a.is_empty()))
+            });
+        }
+    }
+
+    gemini_messages
+}
+
+/// 写入 Gemini session 文件（{gemini_dir}/tmp/{project_hash}/chats/session-*.json）
+///
+/// Gemini CLI 只能 `--resume latest`，因此 startTime 使用转换时间，
+/// 让转换结果成为该项目最新的会话。
+fn write_gemini_session(
+    project_path: &str,
+    new_session_id: &str,
+    messages: &[Value],
+    source: &ConversionSource,
+) -> Result<String, String> {
+    let chats_dir =
+        super::super::gemini::config::get_project_session_dir(project_path)?.join("chats");
+
+    std::fs::create_dir_all(&chats_dir)
+        .map_err(|e| format!("Failed to create chats directory: {}", e))?;
+
+    // 与 Gemini CLI 一致：session-{YYYY-MM-DDTHH-MM}-{session_id 前 8 位}.json
+    let now = chrono::Utc::now();
+    let file_path = chats_dir.join(format!(
+        "session-{}-{}.json",
+        now.format("%Y-%m-%dT%H-%M"),
+        &new_session_id[..8]
+    ));
+
+    let session = serde_json::json!({
+        "sessionId": new_session_id,
+        "projectHash": super::super::gemini::config::hash_project_path(project_path),
+        "startTime": now.to_rfc3339(),
+        "lastUpdated": now.to_rfc3339(),
+        "messages": messages,
+        "conversionSource": source
+    });
+
+    let content = serde_json::to_string_pretty(&session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+    std::fs::write(&file_path, content)
+        .map_err(|e| format!("Failed to write session file: {}", e))?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+// ================================
+// Gemini → Claude 转换器
+// ================================
+
+/// Gemini Session → Claude Session 转换器
+pub struct GeminiToClaudeConverter {
+    source_session_id: String,
+    project_path: String,           // 原始项目路径（Gemini 以其哈希作为目录名）
+    writer: CodexToClaudeConverter, // Claude 消息构造与写入
+}
+
+impl GeminiToClaudeConverter {
+    pub fn new(source_session_id: String, project_id: String, project_path: String) -> Self {
+        let writer = CodexToClaudeConverter::new(
+            source_session_id.clone(),
+            project_id,
+            project_path.clone(),
+        );
+        Self {
+            source_session_id,
+            project_path,
+            writer,
+        }
+    }
+
+    fn conversion_source(&self) -> ConversionSource {
+        ConversionSource {
+            engine: "gemini".to_string(),
+            session_id: self.source_session_id.clone(),
+            converted_at: chrono::Utc::now().to_rfc3339(),
+            source_project_path: self.project_path.clone(),
+        }
+    }
+
+    pub fn convert(&self) -> Result<ConversionResult, String> {
+        log::info!(
+            "Converting Gemini session {} to Claude",
+            self.source_session_id
+        );
+
+        // 1. 读取源 Gemini session
+        let detail = self.read_gemini_session()?;
+
+        // 2. 转换为 Claude 消息（首条为 file-history-snapshot）
+        let first_timestamp = detail
+            .messages
+            .first()
+            .and_then(|m| m.get("timestamp"))
+            .and_then(|t| t.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| detail.start_time.clone());
+
+        let mut claude_messages = vec![self.writer.create_file_history_snapshot(&first_timestamp)];
+        claude_messages.extend(self.build_claude_messages(&detail));
+
+        // 3. 写入目标文件
+        let target_path = self.writer.write_claude_session(&claude_messages)?;
+
+        log::info!(
+            "Successfully converted {} messages to Claude session {}",
+            claude_messages.len(),
+            self.writer.new_session_id
+        );
+
+        Ok(ConversionResult {
+            success: true,
+            new_session_id: self.writer.new_session_id.clone(),
+            target_engine: "claude".to_string(),
+            message_count: claude_messages.len(),
+            source: self.conversion_source(),
+            target_path,
+            error: None,
+        })
+    }
+
+    /// 读取 Gemini session（chats/session-*.json）
+    ///
+    /// 不要求 session 已完成：额度耗尽的会话通常以未得到回复的用户消息结尾，
+    /// 保留它以便在目标引擎中继续。
+    fn read_gemini_session(&self) -> Result<GeminiSessionDetail, String> {
+        let detail = super::super::gemini::config::read_session_detail(
+            &self.project_path,
+            &self.source_session_id,
+        )?;
+
+        if detail.messages.is_empty() {
+            return Err("Gemini session is empty".to_string());
+        }
+
+        log::info!(
+            "Read {} messages from Gemini session",
+            detail.messages.len()
+        );
+        Ok(detail)
+    }
+
+    /// 转换全部 Gemini 消息（不含 file-history-snapshot）
+    fn build_claude_messages(&self, detail: &GeminiSessionDetail) -> Vec<ClaudeMessage> {
+        let model = detail
+            .messages
+            .iter()
+            .find_map(|m| m.get("model").and_then(|v| v.as_str()))
+            .map(String::from);
+
+        // system init 消息记录转换来源
+        let mut init = self.writer.create_claude_message(
+            "system",
+            "system",
+            Vec::new(),
+            &detail.start_time,
+            model,
+        );
+        init.message = None;
+        init.subtype = Some("init".to_string());
+        init.conversion_source = Some(self.conversion_source());
+
+        let mut messages = vec![init];
+        for msg in &detail.messages {
+            messages.extend(self.convert_gemini_message(msg));
+        }
+        messages
+    }
+
+    /// 转换单条 Gemini 消息
+    /// 一条 "gemini" 消息拆分为：assistant(thinking + tool_use) → user(tool_result)... → assistant(text)
+    fn convert_gemini_message(&self, msg: &Value) -> Vec<ClaudeMessage> {
+        let mut messages = Vec::new();
+        let timestamp = msg
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .map(String::from)
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+        let model = msg.get("model").and_then(|m| m.as_str()).map(String::from);
+
+        match msg.get("type").and_then(|t| t.as_str()) {
+            Some("user") => {
+                if !content.trim().is_empty() {
+                    messages.push(self.writer.create_claude_message(
+                        "user",
+                        "user",
+                        vec![ClaudeContentBlock::Text {
+                            text: content.to_string(),
+                        }],
+                        &timestamp,
+                        None,
+                    ));
+                }
+            }
+            Some("gemini") => {
+                let mut blocks = Vec::new();
+                let mut results = Vec::new();
+
+                for thought in msg
+                    .get("thoughts")
+                    .and_then(|t| t.as_array())
+                    .into_iter()
+                    .flatten()
+                {
+                    let subject = thought.get("subject").and_then(|s| s.as_str());
+                    let description = thought
+                        .get("description")
+                        .and_then(|d| d.as_str())
+                        .unwrap_or("");
+                    let thinking = match subject.filter(|s| !s.is_empty()) {
+                        Some(subject) => format!("{}\n\n{}", subject, description),
+                        None => description.to_string(),
+                    };
+                    if !thinking.trim().is_empty() {
+                        blocks.push(ClaudeContentBlock::Thinking { thinking });
+                    }
+                }
+
+                for call in msg
+                    .get("toolCalls")
+                    .and_then(|t| t.as_array())
+                    .into_iter()
+                    .flatten()
+                {
+                    let Some(id) = call.get("id").and_then(|i| i.as_str()) else {
+                        continue;
+                    };
+                    let name = call.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                    blocks.push(ClaudeContentBlock::ToolUse {
+                        id: id.to_string(),
+                        name: map_gemini_to_claude_tool(name),
+                        input: call.get("args").cloned().unwrap_or(Value::Null),
+                    });
+
+                    if let Some(output) = gemini_tool_output(call) {
+                        let call_timestamp = call
+                            .get("timestamp")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or(&timestamp);
+                        // tool_result 必须在 user 消息中
+                        results.push(self.writer.create_claude_message(
+                            "user",
+                            "user",
+                            vec![ClaudeContentBlock::ToolResult {
+                                tool_use_id: id.to_string(),
+                                content: output,
+                                is_error: Some(
+                                    call.get("status").and_then(|s| s.as_str()) == Some("error"),
+                                ),
+                            }],
+                            call_timestamp,
+                            None,
+                        ));
+                    }
+                }
+
+                if !blocks.is_empty() {
+                    messages.push(self.writer.create_claude_message(
+                        "assistant",
+                        "assistant",
+                        blocks,
+                        &timestamp,
+                        model.clone(),
+                    ));
+                }
+                messages.extend(results);
+
+                if !content.trim().is_empty() {
+                    messages.push(self.writer.create_claude_message(
+                        "assistant",
+                        "assistant",
+                        vec![ClaudeContentBlock::Text {
+                            text: content.to_string(),
+                        }],
+                        &timestamp,
+                        model,
+                    ));
+                }
+
+                // tokens 记录在本轮最后一条 assistant 消息上
+                if let Some(tokens) = msg.get("tokens") {
+                    let count = |key: &str| tokens.get(key).and_then(|v| v.as_u64());
+                    if let Some(last) = messages
+                        .iter_mut()
+                        .rev()
+                        .find(|m| m.message_type == "assistant")
+                    {
+                        if let Some(ref mut message) = last.message {
+                            message.usage = Some(TokenUsage {
+                                input_tokens: count("input").unwrap_or(0),
+                                output_tokens: count("output").unwrap_or(0),
+                                cache_creation_tokens: None,
+                                cache_read_tokens: count("cached"),
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {
+                // 跳过 info / error 等 CLI 提示消息
+            }
+        }
+
+        messages
+    }
+}
+
+// ================================
+// Claude → Gemini 转换器
+// ================================
+
+/// Claude Session → Gemini Session 转换器
+pub struct ClaudeToGeminiConverter {
+    reader: ClaudeToCodexConverter, // Claude session 读取与校验
+    new_session_id: String,
+}
+
+impl ClaudeToGeminiConverter {
+    pub fn new(source_session_id: String, project_id: String, project_path: String) -> Self {
+        Self {
+            reader: ClaudeToCodexConverter::new(source_session_id, project_id, project_path),
+            new_session_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    pub fn convert(&self) -> Result<ConversionResult, String> {
+        log::info!(
+            "Converting Claude session {} to Gemini",
+            self.reader.source_session_id
+        );
+
+        // 1. 读取并验证源 Claude session
+        let claude_messages = self.reader.read_claude_session()?;
+        self.reader.validate_session_completed(&claude_messages)?;
+
+        // 2. 转换并写入
+        let gemini_messages = claude_messages_to_gemini(&claude_messages);
+        let source = ConversionSource {
+            engine: "claude".to_string(),
+            session_id: self.reader.source_session_id.clone(),
+            converted_at: chrono::Utc::now().to_rfc3339(),
+            source_project_path: self.reader.project_path.clone(),
+        };
+        let target_path = write_gemini_session(
+            &self.reader.project_path,
+            &self.new_session_id,
+            &gemini_messages,
+            &source,
+        )?;
+
+        log::info!(
+            "Successfully converted {} messages to Gemini session {}",
+            gemini_messages.len(),
+            self.new_session_id
+        );
+
+        Ok(ConversionResult {
+            success: true,
+            new_session_id: self.new_session_id.clone(),
+            target_engine: "gemini".to_string(),
+            message_count: gemini_messages.len(),
+            source,
+            target_path,
+            error: None,
+        })
+    }
+}
+
+// ================================
+// Gemini ↔ Codex 转换器（以 Claude 消息为中间格式）
+// ================================
+
+/// Gemini Session → Codex Session 转换器
+pub struct GeminiToCodexConverter {
+    reader: GeminiToClaudeConverter,
+    writer: ClaudeToCodexConverter,
+}
+
+impl GeminiToCodexConverter {
+    pub fn new(source_session_id: String, project_id: String, project_path: String) -> Self {
+        Self {
+            reader: GeminiToClaudeConverter::new(
+                source_session_id.clone(),
+                project_id.clone(),
+                project_path.clone(),
+            ),
+            writer: ClaudeToCodexConverter::new(source_session_id, project_id, project_path),
+        }
+    }
+
+    pub fn convert(&self) -> Result<ConversionResult, String> {
+        log::info!(
+            "Converting Gemini session {} to Codex",
+            self.reader.source_session_id
+        );
+
+        let detail = self.reader.read_gemini_session()?;
+        let claude_messages = self.reader.build_claude_messages(&detail);
+
+        let model = claude_messages.iter().find_map(|m| m.model.clone());
+        let mut codex_events =
+            vec![self
+                .writer
+                .create_session_meta(&detail.start_time, model.as_deref(), "gemini")];
+        for msg in &claude_messages {
+            codex_events.extend(self.writer.convert_claude_message(msg));
+        }
+
+        let target_path = self.writer.write_codex_session(&codex_events)?;
+
+        log::info!(
+            "Successfully converted {} messages to Codex session {}",
+            codex_events.len(),
+            self.writer.new_session_filename
+        );
+
+        Ok(ConversionResult {
+            success: true,
+            new_session_id: self.writer.new_session_filename.clone(),
+            target_engine: "codex".to_string(),
+            message_count: codex_events.len(),
+            source: self.reader.conversion_source(),
+            target_path,
+            error: None,
+        })
+    }
+}
+
+/// Codex Session → Gemini Session 转换器
+pub struct CodexToGeminiConverter {
+    reader: CodexToClaudeConverter,
+    new_session_id: String,
+}
+
+impl CodexToGeminiConverter {
+    pub fn new(source_session_id: String, project_id: String, project_path: String) -> Self {
+        Self {
+            reader: CodexToClaudeConverter::new(source_session_id, project_id, project_path),
+            new_session_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    pub fn convert(&self) -> Result<ConversionResult, String> {
+        log::info!(
+            "Converting Codex session {} to Gemini",
+            self.reader.source_session_id
+        );
+
+        let codex_events = self.reader.read_codex_session()?;
+        self.reader.validate_session_completed(&codex_events)?;
+        let claude_messages: Vec<ClaudeMessage> = codex_events
+            .iter()
+            .filter_map(|event| self.reader.convert_codex_event(event))
+            .collect();
+        let gemini_messages = claude_messages_to_gemini(&claude_messages);
+
+        let source = ConversionSource {
+            engine: "codex".to_string(),
+            session_id: self.reader.source_session_id.clone(),
+            converted_at: chrono::Utc::now().to_rfc3339(),
+            source_project_path: self.reader.project_path.clone(),
+        };
+        let target_path = write_gemini_session(
+            &self.reader.project_path,
+            &self.new_session_id,
+            &gemini_messages,
+            &source,
+        )?;
+
+        log::info!(
+            "Successfully converted {} events to Gemini session {}",
+            gemini_messages.len(),
+            self.new_session_id
+        );
+
+        Ok(ConversionResult {
+            success: true,
+            new_session_id: self.new_session_id.clone(),
+            target_engine: "gemini".to_string(),
+            message_count: gemini_messages.len(),
+            source,
+            target_path,
+            error: None,
+        })
+    }
+}
+
 // ================================
 // Tauri Commands
 // ================================
 
 /// 根据文件存在性判断 session 的源引擎类型
-fn detect_session_engine(session_id: &str, project_id: &str) -> Result<String, String> {
+fn detect_session_engine(
+    session_id: &str,
+    project_id: &str,
+    project_path: &str,
+) -> Result<String, String> {
     // 1. 检查是否为 Codex session（查找 sessions 目录）
     if let Ok(sessions_dir) = super::config::get_codex_sessions_dir() {
         if super::session::find_session_file(&sessions_dir, session_id).is_some() {
@@ -1236,8 +1991,13 @@ fn detect_session_engine(session_id: &str, project_id: &str) -> Result<String, S
         }
     }
 
+    // 3. 检查是否为 Gemini session（按项目路径哈希定位 chats 目录）
+    if super::super::gemini::config::read_session_detail(project_path, session_id).is_ok() {
+        return Ok("gemini".to_string());
+    }
+
     Err(format!(
-        "Session {} not found in either Claude or Codex directories",
+        "Session {} not found in Claude, Codex or Gemini directories",
         session_id
     ))
 }
@@ -1259,7 +2019,7 @@ pub async fn convert_session(
     );
 
     // 根据文件存在性检测源引擎
-    let source_engine = detect_session_engine(&session_id, &project_id)?;
+    let source_engine = detect_session_engine(&session_id, &project_id, &project_path)?;
 
     if source_engine == target_engine {
         return Err(format!(
@@ -1268,14 +2028,24 @@ pub async fn convert_session(
         ));
     }
 
-    match target_engine.as_str() {
-        "codex" => {
-            let converter = ClaudeToCodexConverter::new(session_id, project_id, project_path);
-            converter.convert()
+    match (source_engine.as_str(), target_engine.as_str()) {
+        ("claude", "codex") => {
+            ClaudeToCodexConverter::new(session_id, project_id, project_path).convert()
+        }
+        ("codex", "claude") => {
+            CodexToClaudeConverter::new(session_id, project_id, project_path).convert()
         }
-        "claude" => {
-            let converter = CodexToClaudeConverter::new(session_id, project_id, project_path);
-            converter.convert()
+        ("gemini", "claude") => {
+            GeminiToClaudeConverter::new(session_id, project_id, project_path).convert()
+        }
+        ("claude", "gemini") => {
+            ClaudeToGeminiConverter::new(session_id, project_id, project_path).convert()
+        }
+        ("gemini", "codex") => {
+            GeminiToCodexConverter::new(session_id, project_id, project_path).convert()
+        }
+        ("codex", "gemini") => {
+            CodexToGeminiConverter::new(session_id, project_id, project_path).convert()
         }
         _ => Err(format!("Unknown target engine: {}", target_engine)),
     }
@@ -1300,3 +2070,206 @@ pub async fn convert_codex_to_claude(
 ) -> Result<ConversionResult, String> {
     convert_session(session_id, "claude".to_string(), project_id, project_path).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claude_writer() -> CodexToClaudeConverter {
+        CodexToClaudeConverter::new(
+            "source".to_string(),
+            "-tmp-app".to_string(),
+            "/tmp/app".to_string(),
+        )
+    }
+
+    /// user → assistant(thinking + tool_use) → user(tool_result) → assistant(text)
+    fn claude_session() -> Vec<ClaudeMessage> {
+        let writer = claude_writer();
+        let ts = "2025-06-01T10:00:00Z";
+        vec![
+            writer.create_claude_message(
+                "user",
+                "user",
+                vec![ClaudeContentBlock::Text {
+                    text: "list the files".to_string(),
+                }],
+                ts,
+                None,
+            ),
+            writer.create_claude_message(
+                "assistant",
+                "assistant",
+                vec![
+                    ClaudeContentBlock::Thinking {
+                        thinking: "use ls".to_string(),
+                    },
+                    ClaudeContentBlock::ToolUse {
+                        id: "toolu_1".to_string(),
+                        name: "Bash".to_string(),
+                        input: serde_json::json!({ "command": "ls" }),
+                    },
+                ],
+                ts,
+                None,
+            ),
+            writer.create_claude_message(
+                "user",
+                "user",
+                vec![ClaudeContentBlock::ToolResult {
+                    tool_use_id: "toolu_1".to_string(),
+                    content: Value::String("a.txt".to_string()),
+                    is_error: Some(false),
+                }],
+                ts,
+                None,
+            ),
+            writer.create_claude_message(
+                "assistant",
+                "assistant",
+                vec![ClaudeContentBlock::Text {
+                    text: "There is one file.".to_string(),
+                }],
+                ts,
+                None,
+            ),
+        ]
+    }
+
+    fn claude_to_codex(messages: &[ClaudeMessage]) -> Vec<CodexEvent> {
+        let converter = ClaudeToCodexConverter::new(
+            "source".to_string(),
+            "-tmp-app".to_string(),
+            "/tmp/app".to_string(),
+        );
+        messages
+            .iter()
+            .flat_map(|m| converter.convert_claude_message(m))
+            .collect()
+    }
+
+    fn codex_to_claude(events: &[CodexEvent]) -> Vec<ClaudeMessage> {
+        let converter = claude_writer();
+        events
+            .iter()
+            .filter_map(|e| converter.convert_codex_event(e))
+            .collect()
+    }
+
+    fn gemini_to_claude(messages: &[Value]) -> Vec<ClaudeMessage> {
+        let converter = GeminiToClaudeConverter::new(
+            "source".to_string(),
+            "-tmp-app".to_string(),
+            "/tmp/app".to_string(),
+        );
+        messages
+            .iter()
+            .flat_map(|m| converter.convert_gemini_message(m))
+            .collect()
+    }
+
+    /// Role and content of every block, in order (tool names compared case-insensitively)
+    fn blocks(messages: &[ClaudeMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .filter_map(|m| m.message.as_ref().map(|c| (m.message_type.as_str(), c)))
+            .flat_map(|(role, content)| {
+                parse_content_blocks(&content.content)
+                    .into_iter()
+                    .map(move |block| match block {
+                        ClaudeContentBlock::Text { text } => format!("{} text: {}", role, text),
+                        ClaudeContentBlock::Thinking { thinking } => {
+                            format!("{} thinking: {}", role, thinking)
+                        }
+                        ClaudeContentBlock::ToolUse { id, name, input } => format!(
+                            "{} tool_use {} {}: {}",
+                            role,
+                            id,
+                            name.to_lowercase(),
+                            input
+                        ),
+                        ClaudeContentBlock::ToolResult {
+                            tool_use_id,
+                            content,
+                            ..
+                        } => format!("{} tool_result {}: {}", role, tool_use_id, content),
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn claude_codex_round_trip() {
+        let original = claude_session();
+        let codex = claude_to_codex(&original);
+        assert!(codex
+            .iter()
+            .any(|e| e.payload.as_ref().unwrap()["type"] == "function_call_output"));
+        assert_eq!(blocks(&codex_to_claude(&codex)), blocks(&original));
+    }
+
+    #[test]
+    fn claude_gemini_round_trip() {
+        let original = claude_session();
+        let gemini = claude_messages_to_gemini(&original);
+        let types: Vec<&str> = gemini.iter().filter_map(|m| m["type"].as_str()).collect();
+        assert_eq!(types, vec!["user", "gemini", "gemini"]);
+        assert_eq!(gemini[1]["toolCalls"][0]["name"], "run_shell_command");
+        assert_eq!(
+            gemini_tool_output(&gemini[1]["toolCalls"][0]).unwrap(),
+            "a.txt"
+        );
+        assert_eq!(blocks(&gemini_to_claude(&gemini)), blocks(&original));
+    }
+
+    #[test]
+    fn codex_gemini_round_trip() {
+        // Codex → Gemini and back both go through Claude messages
+        let codex = claude_to_codex(&claude_session());
+        let via_claude = codex_to_claude(&codex);
+        let gemini = claude_messages_to_gemini(&via_claude);
+        let back = claude_to_codex(&gemini_to_claude(&gemini));
+        assert_eq!(blocks(&codex_to_claude(&back)), blocks(&via_claude));
+    }
+
+    #[test]
+    fn gemini_round_trips_through_claude_and_codex() {
+        let gemini = vec![
+            serde_json::json!({
+                "id": "m1",
+                "timestamp": "2025-06-01T10:00:00Z",
+                "type": "user",
+                "content": "read the readme"
+            }),
+            serde_json::json!({
+                "id": "m2",
+                "timestamp": "2025-06-01T10:00:05Z",
+                "type": "gemini",
+                "content": "",
+                "toolCalls": [{
+                    "id": "read_file-1",
+                    "name": "read_file",
+                    "args": { "absolute_path": "/tmp/app/README.md" },
+                    "status": "success",
+                    "result": [{ "functionResponse": { "response": { "output": "# App" } } }]
+                }]
+            }),
+            serde_json::json!({
+                "id": "m3",
+                "timestamp": "2025-06-01T10:00:09Z",
+                "type": "gemini",
+                "content": "The readme is a title only."
+            }),
+        ];
+        let via_claude = gemini_to_claude(&gemini);
+
+        let back = claude_messages_to_gemini(&via_claude);
+        assert_eq!(back.len(), 3);
+        assert_eq!(back[1]["toolCalls"][0]["name"], "read_file");
+        assert_eq!(back[2]["content"], "The readme is a title only.");
+        assert_eq!(blocks(&gemini_to_claude(&back)), blocks(&via_claude));
+
+        let via_codex = codex_to_claude(&claude_to_codex(&via_claude));
+        assert_eq!(blocks(&via_codex), blocks(&via_claude));
+    }
+}
@@ -8,12 +8,17 @@
  * - Per-project statistics
  */
 use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
 
-use super::config::get_codex_sessions_dir;
+use super::super::engine::EngineKind;
+use super::super::pricing::{self, TokenCounts};
+use super::super::storage::AgentDb;
+use super::super::usage::UsageEntry;
+use super::super::usage_index;
 
 // ============================================================================
 // Types
@@ -83,349 +88,185 @@ pub struct CodexUsageStats {
 }
 
 // ============================================================================
-// Pricing (Must match frontend pricing.ts)
+// Pricing (shared catalog, see commands::pricing)
 // ============================================================================
 
-#[derive(Debug, Clone, Copy)]
-struct ModelPricing {
-    input: f64,
-    output: f64,
-    cache_read: f64,
+/// Cost of Codex usage as Codex reports it: `input_tokens` includes the
+/// cached part, which is only billed at the cache-read price
+pub(crate) fn calculate_cost(
+    model: &str,
+    input_tokens: u64,
+    output_tokens: u64,
+    cached_tokens: u64,
+    on: NaiveDate,
+) -> f64 {
+    let tokens = TokenCounts {
+        input: input_tokens.saturating_sub(cached_tokens),
+        output: output_tokens,
+        cache_write: 0,
+        cache_read: cached_tokens,
+    };
+    pricing::cost(EngineKind::Codex, model, &tokens, on).unwrap_or(0.0)
 }
 
-fn get_codex_pricing(model: &str) -> ModelPricing {
-    let normalized = model.to_lowercase();
-
-    // GPT-5.4 Pro (premium tier)
-    if normalized.contains("5.4-pro") || normalized.contains("5_4_pro") {
-        return ModelPricing {
-            input: 30.00,
-            output: 180.00,
-            cache_read: 3.00,
-        };
-    }
-
-    // GPT-5.4 Fast Mode (1.5x speed, 2x credit consumption)
-    if normalized.contains("5.4") && normalized.contains("fast") {
-        return ModelPricing {
-            input: 5.00,
-            output: 30.00,
-            cache_read: 0.50,
-        };
-    }
+// ============================================================================
+// Session Parsing
+// ============================================================================
 
-    // GPT-5.4 (latest flagship - March 2026)
-    if normalized.contains("gpt-5.4") || normalized.contains("gpt5.4")
-        || normalized.contains("gpt_5_4") || normalized.contains("5.4") {
-        return ModelPricing {
-            input: 2.50,
-            output: 15.00,
-            cache_read: 0.25,
-        };
-    }
+/// Incremental reader of one Codex rollout file
+///
+/// Starts from the `session_meta` line and is then fed the remaining lines;
+/// the accumulated state is stored with the file's usage index entry so
+/// appended lines can be parsed on their own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct CodexUsageScan {
+    session_id: String,
+    /// session_meta timestamp (RFC3339)
+    timestamp: String,
+    created_at: u64,
+    cwd: String,
+    total_input_tokens: u64,
+    total_output_tokens: u64,
+    total_cached_tokens: u64,
+    /// Sum of the per-turn costs
+    #[serde(default)]
+    total_cost: f64,
+    model: String,
+    first_message: Option<String>,
+    last_timestamp: Option<String>,
+    last_total_input_tokens: Option<u64>,
+    last_total_output_tokens: Option<u64>,
+    last_total_cached_tokens: Option<u64>,
+}
 
-    // GPT-5.3 Codex Spark (lightweight fast version)
-    if normalized.contains("5.3-codex-spark") || normalized.contains("5_3_codex_spark") {
-        return ModelPricing {
-            input: 1.50,
-            output: 12.00,
-            cache_read: 0.15,
-        };
-    }
+impl CodexUsageScan {
+    /// Start a scan from the first line of a rollout file
+    pub(crate) fn from_meta(first_line: &str) -> Option<Self> {
+        let meta: serde_json::Value = serde_json::from_str(first_line).ok()?;
 
-    // GPT-5.3 Codex (latest - February 2026)
-    if normalized.contains("5.3-codex") || normalized.contains("5_3_codex")
-        || normalized.contains("gpt-5.3") || normalized.contains("gpt5.3") {
-        return ModelPricing {
-            input: 2.00,
-            output: 16.00,
-            cache_read: 0.20,
-        };
-    }
+        if meta["type"].as_str()? != "session_meta" {
+            return None;
+        }
 
-    // GPT-5.2 Codex
-    if normalized.contains("5.2-codex") || normalized.contains("5_2_codex") {
-        return ModelPricing {
-            input: 1.75,
-            output: 14.00,
-            cache_read: 0.175,
+        let payload = &meta["payload"];
+        let session_id = payload["id"].as_str()?.to_string();
+        let timestamp_str = payload["timestamp"].as_str()?;
+        let created_at = chrono::DateTime::parse_from_rfc3339(timestamp_str)
+            .ok()?
+            .timestamp() as u64;
+
+        // Get cwd and convert from WSL path format if needed
+        let cwd_raw = payload["cwd"].as_str().unwrap_or("");
+        #[cfg(target_os = "windows")]
+        let cwd = {
+            if cwd_raw.starts_with("/mnt/") {
+                super::super::wsl_utils::wsl_to_windows_path(cwd_raw)
+            } else {
+                cwd_raw.to_string()
+            }
         };
+        #[cfg(not(target_os = "windows"))]
+        let cwd = cwd_raw.to_string();
+
+        Some(Self {
+            session_id,
+            timestamp: timestamp_str.to_string(),
+            created_at,
+            cwd,
+            model: "unknown".to_string(),
+            ..Default::default()
+        })
     }
 
-    // GPT-5.2 (non-codex naming)
-    if normalized.contains("gpt-5.2") || normalized.contains("gpt5.2") {
-        return ModelPricing {
-            input: 1.75,
-            output: 14.00,
-            cache_read: 0.175,
+    /// Accumulate the usage of one line after `session_meta`; returns the
+    /// usage of the turn the line reports, if any
+    pub(crate) fn feed(&mut self, line: &str) -> Option<UsageEntry> {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            return None;
         };
-    }
 
-    // GPT-5.1-Codex variants
-    if normalized.contains("5.1-codex-max") || normalized.contains("5_1_codex_max") {
-        return ModelPricing {
-            input: 1.25,
-            output: 10.00,
-            cache_read: 0.125,
-        };
-    }
-    if normalized.contains("5.1-codex-mini") || normalized.contains("5_1_codex_mini") {
-        return ModelPricing {
-            input: 0.25,
-            output: 2.00,
-            cache_read: 0.025,
-        };
-    }
-    if normalized.contains("5.1-codex") || normalized.contains("5_1_codex") {
-        return ModelPricing {
-            input: 1.25,
-            output: 10.00,
-            cache_read: 0.125,
-        };
-    }
+        // Update last timestamp
+        if let Some(ts) = event["timestamp"].as_str() {
+            self.last_timestamp = Some(ts.to_string());
+        }
 
-    // GPT-5.1 (non-codex naming)
-    if normalized.contains("gpt-5.1") || normalized.contains("gpt5.1") {
-        return ModelPricing {
-            input: 1.25,
-            output: 10.00,
-            cache_read: 0.125,
-        };
-    }
+        let event_type = event["type"].as_str().unwrap_or("");
 
-    // codex-mini-latest (default CLI model)
-    if normalized.contains("codex-mini-latest") || normalized.contains("codex_mini_latest") {
-        return ModelPricing {
-            input: 1.50,
-            output: 6.00,
-            cache_read: 0.375,
-        };
-    }
+        // Extract model from session_meta, model_selected, or turn_context
+        if event_type == "session_meta"
+            || event_type == "model_selected"
+            || event_type == "turn_context"
+        {
+            if let Some(m) = event["payload"]["model"].as_str() {
+                self.model = m.to_string();
+            }
+        }
 
-    // o4-mini
-    if normalized.contains("o4-mini") || normalized.contains("o4_mini") {
-        return ModelPricing {
-            input: 1.10,
-            output: 4.40,
-            cache_read: 0.275,
+        // (input, output, cached) of the turn this line reports
+        let mut turn: Option<(u64, u64, u64)> = None;
+        let get = |usage: &serde_json::Map<String, serde_json::Value>, key: &str| {
+            usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0)
         };
-    }
-
-    // Default to gpt-5.4 pricing
-    ModelPricing {
-        input: 2.50,
-        output: 15.00,
-        cache_read: 0.25,
-    }
-}
-
-fn calculate_cost(model: &str, input_tokens: u64, output_tokens: u64, cached_tokens: u64) -> f64 {
-    let pricing = get_codex_pricing(model);
-
-    let input_cost = (input_tokens as f64 / 1_000_000.0) * pricing.input;
-    let output_cost = (output_tokens as f64 / 1_000_000.0) * pricing.output;
-    let cache_cost = (cached_tokens as f64 / 1_000_000.0) * pricing.cache_read;
 
-    input_cost + output_cost + cache_cost
-}
-
-// ============================================================================
-// Session Parsing
-// ============================================================================
-
-fn parse_session_for_usage(path: &PathBuf) -> Option<CodexSessionUsage> {
-    let file = std::fs::File::open(path).ok()?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
-
-    // Read first line (session_meta)
-    let first_line = lines.next()?.ok()?;
-    let meta: serde_json::Value = serde_json::from_str(&first_line).ok()?;
-
-    if meta["type"].as_str()? != "session_meta" {
-        return None;
-    }
-
-    let payload = &meta["payload"];
-    let session_id = payload["id"].as_str()?.to_string();
-    let timestamp_str = payload["timestamp"].as_str()?;
-    let created_at = chrono::DateTime::parse_from_rfc3339(timestamp_str)
-        .ok()?
-        .timestamp() as u64;
-
-    // Get cwd and convert from WSL path format if needed
-    let cwd_raw = payload["cwd"].as_str().unwrap_or("");
-    #[cfg(target_os = "windows")]
-    let cwd = {
-        if cwd_raw.starts_with("/mnt/") {
-            super::super::wsl_utils::wsl_to_windows_path(cwd_raw)
-        } else {
-            cwd_raw.to_string()
+        // Extract usage from turn.completed events (incremental usage per turn)
+        if event_type == "turn.completed" {
+            if let Some(usage) = event["usage"].as_object() {
+                turn = Some((
+                    get(usage, "input_tokens"),
+                    get(usage, "output_tokens"),
+                    get(usage, "cached_input_tokens"),
+                ));
+            }
         }
-    };
-    #[cfg(not(target_os = "windows"))]
-    let cwd = cwd_raw.to_string();
-
-    // Initialize accumulators
-    let mut total_input_tokens: u64 = 0;
-    let mut total_output_tokens: u64 = 0;
-    let mut total_cached_tokens: u64 = 0;
-    let mut model: String = "unknown".to_string();
-    let mut first_message: Option<String> = None;
-    let mut last_timestamp: Option<String> = None;
-    let mut last_total_input_tokens: Option<u64> = None;
-    let mut last_total_output_tokens: Option<u64> = None;
-    let mut last_total_cached_tokens: Option<u64> = None;
-
-    // Parse all lines to extract usage data
-    for line_result in lines {
-        if let Ok(line) = line_result {
-            if let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) {
-                // Update last timestamp
-                if let Some(ts) = event["timestamp"].as_str() {
-                    last_timestamp = Some(ts.to_string());
-                }
-
-                let event_type = event["type"].as_str().unwrap_or("");
-
-                // Extract model from session_meta, model_selected, or turn_context
-                if event_type == "session_meta"
-                    || event_type == "model_selected"
-                    || event_type == "turn_context"
-                {
-                    if let Some(m) = event["payload"]["model"].as_str() {
-                        model = m.to_string();
-                    }
-                }
-
-                // Extract usage from turn.completed events (incremental usage per turn)
-                if event_type == "turn.completed" {
-                    if let Some(usage) = event["usage"].as_object() {
-                        if let Some(input) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
-                            total_input_tokens += input;
-                        }
-                        if let Some(output) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
-                            total_output_tokens += output;
-                        }
-                        if let Some(cached) =
-                            usage.get("cached_input_tokens").and_then(|v| v.as_u64())
-                        {
-                            total_cached_tokens += cached;
-                        }
-                    }
-                }
 
-                // Extract usage from token_count events (incremental)
-                if event_type == "token_count" {
-                    if let Some(payload_obj) = event["payload"].as_object() {
-                        if let Some(info) = payload_obj.get("info").and_then(|v| v.as_object()) {
-                            if let Some(input) = info.get("input_tokens").and_then(|v| v.as_u64()) {
-                                total_input_tokens += input;
-                            }
-                            if let Some(output) =
-                                info.get("output_tokens").and_then(|v| v.as_u64())
-                            {
-                                total_output_tokens += output;
-                            }
-                            if let Some(cached) = info
-                                .get("cached_input_tokens")
-                                .or_else(|| info.get("cached_tokens"))
-                                .and_then(|v| v.as_u64())
-                            {
-                                total_cached_tokens += cached;
-                            }
-                        }
-                    }
+        // Extract usage from token_count events (incremental)
+        if event_type == "token_count" {
+            if let Some(payload_obj) = event["payload"].as_object() {
+                if let Some(info) = payload_obj.get("info").and_then(|v| v.as_object()) {
+                    let cached = info
+                        .get("cached_input_tokens")
+                        .or_else(|| info.get("cached_tokens"))
+                        .and_then(|v| v.as_u64())
+                        .unwrap_or(0);
+                    turn = Some((
+                        get(info, "input_tokens"),
+                        get(info, "output_tokens"),
+                        cached,
+                    ));
                 }
+            }
+        }
 
-                // Extract usage from event_msg token_count events (current CLI format)
-                if event_type == "event_msg" {
-                    let payload_obj = event["payload"].as_object();
-                    let payload_type = payload_obj.and_then(|p| p.get("type")).and_then(|v| v.as_str());
-                    if payload_type == Some("token_count") {
-                        if let Some(info) = payload_obj.and_then(|p| p.get("info")).and_then(|v| v.as_object()) {
-                            let get_cached = |usage: &serde_json::Map<String, serde_json::Value>| {
-                                usage
-                                    .get("cached_input_tokens")
-                                    .or_else(|| usage.get("cached_tokens"))
-                                    .and_then(|v| v.as_u64())
-                                    .unwrap_or(0)
-                            };
-
-                            if let Some(last_usage) =
-                                info.get("last_token_usage").and_then(|v| v.as_object())
-                            {
-                                let input = last_usage
-                                    .get("input_tokens")
-                                    .and_then(|v| v.as_u64())
-                                    .unwrap_or(0);
-                                let output = last_usage
-                                    .get("output_tokens")
-                                    .and_then(|v| v.as_u64())
-                                    .unwrap_or(0);
-                                let cached = get_cached(last_usage);
-                                total_input_tokens += input;
-                                total_output_tokens += output;
-                                total_cached_tokens += cached;
-                            } else if let Some(total_usage) =
-                                info.get("total_token_usage").and_then(|v| v.as_object())
-                            {
-                                let input = total_usage
-                                    .get("input_tokens")
-                                    .and_then(|v| v.as_u64())
-                                    .unwrap_or(0);
-                                let output = total_usage
-                                    .get("output_tokens")
-                                    .and_then(|v| v.as_u64())
-                                    .unwrap_or(0);
-                                let cached = get_cached(total_usage);
-
-                                let delta_input = match last_total_input_tokens {
-                                    Some(prev) if input >= prev => input - prev,
-                                    Some(_) => input,
-                                    None => input,
-                                };
-                                let delta_output = match last_total_output_tokens {
-                                    Some(prev) if output >= prev => output - prev,
-                                    Some(_) => output,
-                                    None => output,
-                                };
-                                let delta_cached = match last_total_cached_tokens {
-                                    Some(prev) if cached >= prev => cached - prev,
-                                    Some(_) => cached,
-                                    None => cached,
-                                };
-
-                                total_input_tokens += delta_input;
-                                total_output_tokens += delta_output;
-                                total_cached_tokens += delta_cached;
-
-                                last_total_input_tokens = Some(input);
-                                last_total_output_tokens = Some(output);
-                                last_total_cached_tokens = Some(cached);
-                            }
-                        }
-                    }
+        // Extract usage from event_msg token_count events (current CLI format)
+        if event_type == "event_msg" {
+            let payload_obj = event["payload"].as_object();
+            let payload_type = payload_obj
+                .and_then(|p| p.get("type"))
+                .and_then(|v| v.as_str());
+            if payload_type == Some("token_count") {
+                if let Some(info) = payload_obj
+                    .and_then(|p| p.get("info"))
+                    .and_then(|v| v.as_object())
+                {
+                    turn = self.token_count_usage(info);
                 }
+            }
+        }
 
-                // Find first user message
-                if first_message.is_none() && event_type == "response_item" {
-                    if let Some(payload_obj) = event["payload"].as_object() {
-                        if payload_obj.get("role").and_then(|r| r.as_str()) == Some("user") {
-                            if let Some(content) =
-                                payload_obj.get("content").and_then(|c| c.as_array())
-                            {
-                                for item in content {
-                                    if item["type"].as_str() == Some("input_text") {
-                                        if let Some(text) = item["text"].as_str() {
-                                            if !text.contains("<environment_context>")
-                                                && !text.contains("# AGENTS.md")
-                                                && !text.trim().is_empty()
-                                            {
-                                                first_message = Some(text.to_string());
-                                                break;
-                                            }
-                                        }
+        // Find first user message
+        if self.first_message.is_none() && event_type == "response_item" {
+            if let Some(payload_obj) = event["payload"].as_object() {
+                if payload_obj.get("role").and_then(|r| r.as_str()) == Some("user") {
+                    if let Some(content) = payload_obj.get("content").and_then(|c| c.as_array()) {
+                        for item in content {
+                            if item["type"].as_str() == Some("input_text") {
+                                if let Some(text) = item["text"].as_str() {
+                                    if !text.contains("<environment_context>")
+                                        && !text.contains("# AGENTS.md")
+                                        && !text.trim().is_empty()
+                                    {
+                                        self.first_message = Some(text.to_string());
+                                        break;
                                     }
                                 }
                             }
@@ -434,73 +275,110 @@ fn parse_session_for_usage(path: &PathBuf) -> Option<CodexSessionUsage> {
                 }
             }
         }
-    }
-
-    let updated_at = last_timestamp
-        .as_ref()
-        .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
-        .map(|dt| dt.timestamp() as u64)
-        .unwrap_or(created_at);
 
-    let total_cost = calculate_cost(&model, total_input_tokens, total_output_tokens, total_cached_tokens);
-
-    Some(CodexSessionUsage {
-        session_id,
-        project_path: cwd,
-        model,
-        total_cost,
-        input_tokens: total_input_tokens,
-        output_tokens: total_output_tokens,
-        cached_input_tokens: total_cached_tokens,
-        created_at,
-        updated_at,
-        first_message,
-    })
-}
+        let (input, output, cached) = turn.filter(|&t| t != (0, 0, 0))?;
+        self.total_input_tokens += input;
+        self.total_output_tokens += output;
+        self.total_cached_tokens += cached;
+
+        let timestamp = self
+            .last_timestamp
+            .clone()
+            .unwrap_or_else(|| self.timestamp.clone());
+        // 分档价格按单轮计算，会话总价是各轮之和
+        let cost = calculate_cost(
+            &self.model,
+            input,
+            output,
+            cached,
+            pricing::usage_date(&timestamp),
+        );
+        self.total_cost += cost;
+        Some(UsageEntry {
+            cost,
+            timestamp,
+            model: self.model.clone(),
+            // input_tokens includes the cached part
+            input_tokens: input.saturating_sub(cached),
+            output_tokens: output,
+            cache_creation_tokens: 0,
+            cache_read_tokens: cached,
+            session_id: self.session_id.clone(),
+            project_path: self.cwd.clone(),
+        })
+    }
 
-fn collect_all_sessions() -> Vec<CodexSessionUsage> {
-    let sessions_dir = match get_codex_sessions_dir() {
-        Ok(dir) => dir,
-        Err(_) => return Vec::new(),
-    };
+    /// Usage of a `token_count` event: its `last_token_usage`, or the growth of
+    /// its cumulative `total_token_usage`
+    fn token_count_usage(
+        &mut self,
+        info: &serde_json::Map<String, serde_json::Value>,
+    ) -> Option<(u64, u64, u64)> {
+        let get_cached = |usage: &serde_json::Map<String, serde_json::Value>| {
+            usage
+                .get("cached_input_tokens")
+                .or_else(|| usage.get("cached_tokens"))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+        };
+        let get = |usage: &serde_json::Map<String, serde_json::Value>, key: &str| {
+            usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0)
+        };
 
-    if !sessions_dir.exists() {
-        return Vec::new();
+        if let Some(last_usage) = info.get("last_token_usage").and_then(|v| v.as_object()) {
+            Some((
+                get(last_usage, "input_tokens"),
+                get(last_usage, "output_tokens"),
+                get_cached(last_usage),
+            ))
+        } else if let Some(total_usage) = info.get("total_token_usage").and_then(|v| v.as_object())
+        {
+            let input = get(total_usage, "input_tokens");
+            let output = get(total_usage, "output_tokens");
+            let cached = get_cached(total_usage);
+
+            // Cumulative counters; a drop means the counter was reset
+            let delta = |current: u64, previous: Option<u64>| match previous {
+                Some(prev) if current >= prev => current - prev,
+                _ => current,
+            };
+            let usage = (
+                delta(input, self.last_total_input_tokens),
+                delta(output, self.last_total_output_tokens),
+                delta(cached, self.last_total_cached_tokens),
+            );
+
+            self.last_total_input_tokens = Some(input);
+            self.last_total_output_tokens = Some(output);
+            self.last_total_cached_tokens = Some(cached);
+            Some(usage)
+        } else {
+            None
+        }
     }
 
-    let mut sessions = Vec::new();
-
-    // Walk through date-organized directories (2025/11/23/rollout-xxx.jsonl)
-    if let Ok(entries) = std::fs::read_dir(&sessions_dir) {
-        for year_entry in entries.flatten() {
-            if let Ok(month_entries) = std::fs::read_dir(year_entry.path()) {
-                for month_entry in month_entries.flatten() {
-                    if let Ok(day_entries) = std::fs::read_dir(month_entry.path()) {
-                        for day_entry in day_entries.flatten() {
-                            if day_entry.path().is_dir() {
-                                if let Ok(file_entries) = std::fs::read_dir(day_entry.path()) {
-                                    for file_entry in file_entries.flatten() {
-                                        let path = file_entry.path();
-                                        if path.extension().and_then(|s| s.to_str())
-                                            == Some("jsonl")
-                                        {
-                                            if let Some(session) = parse_session_for_usage(&path) {
-                                                sessions.push(session);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// Session usage of the lines seen so far
+    pub(crate) fn finish(&self) -> CodexSessionUsage {
+        let updated_at = self
+            .last_timestamp
+            .as_ref()
+            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+            .map(|dt| dt.timestamp() as u64)
+            .unwrap_or(self.created_at);
+
+        CodexSessionUsage {
+            session_id: self.session_id.clone(),
+            project_path: self.cwd.clone(),
+            model: self.model.clone(),
+            total_cost: self.total_cost,
+            input_tokens: self.total_input_tokens,
+            output_tokens: self.total_output_tokens,
+            cached_input_tokens: self.total_cached_tokens,
+            created_at: self.created_at,
+            updated_at,
+            first_message: self.first_message.clone(),
         }
     }
-
-    // Sort by creation time (newest first)
-    sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-    sessions
 }
 
 // ============================================================================
@@ -510,6 +388,16 @@ fn collect_all_sessions() -> Vec<CodexSessionUsage> {
 /// Get Codex usage statistics
 #[tauri::command]
 pub async fn get_codex_usage_stats(
+    db: State<'_, AgentDb>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<CodexUsageStats, String> {
+    codex_usage_stats(&db.0, start_date, end_date)
+}
+
+/// Codex usage statistics, optionally limited to an inclusive date range
+pub fn codex_usage_stats(
+    db: &Mutex<Connection>,
     start_date: Option<String>,
     end_date: Option<String>,
 ) -> Result<CodexUsageStats, String> {
@@ -519,7 +407,7 @@ pub async fn get_codex_usage_stats(
         end_date
     );
 
-    let all_sessions = collect_all_sessions();
+    let all_sessions = usage_index::codex_sessions(db)?;
 
     // Filter by date range if provided
     let filtered_sessions: Vec<CodexSessionUsage> = if let (Some(start), Some(end)) =
@@ -0,0 +1,789 @@
+//! Local Control API
+//!
+//! Opt-in HTTP/WebSocket server bound to 127.0.0.1 so editor plugins and
+//! scripts on the same machine can drive the sessions the GUI is running.
+//!
+//! ## Endpoints (all require `Authorization: Bearer <token>`)
+//!
+//! - `GET  /api/health`                      – liveness + version
+//! - `GET  /api/runs`                        – ProcessRegistry runs
+//! - `GET  /api/runs/{runId}`                – status + session ID of a run started here
+//! - `POST /api/sessions`                    – `{ engine, projectPath, prompt, ... }`
+//! - `POST /api/sessions/{id}/resume`        – same body as above
+//! - `POST /api/sessions/{id}/cancel`        – `{ engine }`
+//! - `GET  /ws?engine=claude&session_id=...` – JSONL stream (one line per text frame)
+//!
+//! WebSocket clients may pass `?token=` instead of the header, since browser
+//! WebSocket APIs can't set custom headers. Without `session_id` the stream
+//! carries every session of the engine (the global `{engine}-output` event).
+//!
+//! Starting or resuming a session answers `{ runId, engine, sessionId }`.
+//! Codex/Gemini session IDs are known right away; a new Claude session's ID
+//! only once the CLI has initialized, so it is `null` until `GET /api/runs/{runId}`
+//! reports it.
+//!
+//! Config: ~/.anycode/control_api.json (disabled by default)
+
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Listener, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use super::engine::{runner_for, spawn_watched, EngineKind, EngineRequest};
+use crate::process::ProcessRegistryState;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// Default port (unassigned by IANA, unlikely to clash with dev servers)
+const DEFAULT_PORT: u16 = 47821;
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Finished runs kept for `GET /api/runs/{runId}`
+const MAX_FINISHED_RUNS: usize = 100;
+
+// ============================================================================
+// Config
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Bearer token; generated on first load
+    #[serde(default)]
+    pub token: String,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+impl Default for ControlApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("control_api.json"))
+}
+
+fn generate_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Load the config, creating a token the first time
+pub fn load_control_api_config() -> Result<ControlApiConfig, String> {
+    let path = get_config_path()?;
+    let mut config: ControlApiConfig = load_json_config(&path)?;
+    if config.token.is_empty() {
+        config.token = generate_token();
+        save_json_config(&config, &path)?;
+    }
+    Ok(config)
+}
+
+// ============================================================================
+// Server Lifecycle
+// ============================================================================
+
+struct RunningServer {
+    port: u16,
+    task: tauri::async_runtime::JoinHandle<()>,
+    /// Closes the WebSocket streams opened through this server
+    shutdown: watch::Sender<bool>,
+}
+
+/// Handle to the running server (None = stopped)
+#[derive(Default)]
+pub struct ControlApiState(Mutex<Option<RunningServer>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlApiStatus {
+    #[serde(flatten)]
+    pub config: ControlApiConfig,
+    /// Port the server is actually listening on
+    pub listening_port: Option<u16>,
+}
+
+/// Stop any running server and start a new one if the config enables it
+async fn restart_server(
+    app: &AppHandle,
+    state: &ControlApiState,
+    config: &ControlApiConfig,
+) -> Result<Option<u16>, String> {
+    let mut running = state.0.lock().await;
+    if let Some(server) = running.take() {
+        // Streams authorized with the old token must not outlive the server
+        let _ = server.shutdown.send(true);
+        // Wait for the accept loop to drop its listener so the port can be rebound
+        server.task.abort();
+        let _ = server.task.await;
+        log::info!("[ControlAPI] Stopped server on port {}", server.port);
+    }
+
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    // Loopback only: the API can run arbitrary prompts with the user's credentials
+    let listener = TcpListener::bind(("127.0.0.1", config.port))
+        .await
+        .map_err(|e| format!("Failed to bind 127.0.0.1:{}: {}", config.port, e))?;
+    let app = app.clone();
+    let token = config.token.clone();
+    let (shutdown, shutdown_rx) = watch::channel(false);
+
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let app = app.clone();
+                    let token = token.clone();
+                    let shutdown = shutdown_rx.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = handle_connection(app, stream, token, shutdown).await {
+                            log::debug!("[ControlAPI] Connection error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("[ControlAPI] Accept failed: {}", e),
+            }
+        }
+    });
+
+    log::info!("[ControlAPI] Listening on 127.0.0.1:{}", config.port);
+    *running = Some(RunningServer {
+        port: config.port,
+        task,
+        shutdown,
+    });
+    Ok(Some(config.port))
+}
+
+/// Called from setup: start the server when it was left enabled
+pub async fn start_control_api(app: AppHandle) {
+    let config = match load_control_api_config() {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("[ControlAPI] Failed to load config: {}", e);
+            return;
+        }
+    };
+    if !config.enabled {
+        return;
+    }
+    let state = app.state::<ControlApiState>();
+    if let Err(e) = restart_server(&app, &state, &config).await {
+        log::error!("[ControlAPI] Failed to start: {}", e);
+    }
+}
+
+// ============================================================================
+// HTTP
+// ============================================================================
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<HttpRequest, String> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("Connection closed".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err("Request header too large".to_string());
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_uppercase();
+    let target = parts.next().unwrap_or("/");
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let query = query_string
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.to_string(),
+                urlencoding::decode(v)
+                    .map(|s| s.into_owned())
+                    .unwrap_or_else(|_| v.to_string()),
+            )
+        })
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(HttpRequest {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+async fn write_json(stream: &mut TcpStream, status: u16, body: &Value) -> Result<(), String> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Compare without short-circuiting so the token can't be guessed byte by byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Only accept loopback Host headers (blocks DNS-rebinding from web pages)
+fn is_loopback_host(host: Option<&str>) -> bool {
+    let Some(host) = host else {
+        return false;
+    };
+    let name = if host.starts_with('[') {
+        host.split(']')
+            .next()
+            .map(|h| format!("{}]", h))
+            .unwrap_or_default()
+    } else {
+        host.split(':').next().unwrap_or_default().to_string()
+    };
+    matches!(name.as_str(), "127.0.0.1" | "localhost" | "[::1]")
+}
+
+fn is_authorized(request: &HttpRequest, token: &str) -> bool {
+    let provided = request
+        .header("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| {
+            // Query token is only accepted for the WebSocket upgrade
+            (request.path == "/ws")
+                .then(|| request.query.get("token").map(String::as_str))
+                .flatten()
+        });
+    provided.is_some_and(|p| constant_time_eq(p.trim().as_bytes(), token.as_bytes()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionBody {
+    engine: EngineKind,
+    #[serde(flatten)]
+    request: EngineRequest,
+}
+
+#[derive(Deserialize)]
+struct CancelBody {
+    engine: EngineKind,
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(request: &HttpRequest) -> Result<T, String> {
+    serde_json::from_slice(&request.body).map_err(|e| format!("Invalid request body: {}", e))
+}
+
+// ============================================================================
+// Run Handles
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ControlRun {
+    run_id: String,
+    engine: EngineKind,
+    /// Session ID the `/ws` stream takes: the CLI session ID (Claude) or the
+    /// backend session ID (Codex/Gemini), once known
+    session_id: Option<String>,
+    status: RunStatus,
+    started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Runs started through the API, by run ID
+static RUNS: Lazy<std::sync::Mutex<HashMap<String, ControlRun>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn update_run(run_id: &str, update: impl FnOnce(&mut ControlRun)) {
+    if let Some(run) = RUNS.lock().unwrap().get_mut(run_id) {
+        update(run);
+    }
+}
+
+/// Drop the oldest finished runs beyond `MAX_FINISHED_RUNS`
+fn prune_runs(runs: &mut HashMap<String, ControlRun>) {
+    let mut finished: Vec<(chrono::DateTime<chrono::Utc>, String)> = runs
+        .values()
+        .filter(|run| run.status != RunStatus::Running)
+        .map(|run| (run.started_at, run.run_id.clone()))
+        .collect();
+    if finished.len() <= MAX_FINISHED_RUNS {
+        return;
+    }
+    finished.sort();
+    for (_, run_id) in finished.iter().take(finished.len() - MAX_FINISHED_RUNS) {
+        runs.remove(run_id);
+    }
+}
+
+/// Start (or resume) a run and track it under a new run ID
+async fn start_run(
+    app: &AppHandle,
+    engine: EngineKind,
+    request: EngineRequest,
+    resume_session_id: Option<&str>,
+) -> Result<ControlRun, String> {
+    let run_id = format!("run-{}", uuid::Uuid::new_v4());
+    let mut watch = spawn_watched(app, engine, request, resume_session_id, false).await?;
+
+    let run = ControlRun {
+        run_id: run_id.clone(),
+        engine,
+        session_id: watch
+            .session_id()
+            .or_else(|| resume_session_id.map(String::from)),
+        status: RunStatus::Running,
+        started_at: chrono::Utc::now(),
+    };
+    {
+        let mut runs = RUNS.lock().unwrap();
+        runs.insert(run_id.clone(), run.clone());
+        prune_runs(&mut runs);
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let success = loop {
+            tokio::select! {
+                success = watch.wait() => break success,
+                _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                    if let Some(sid) = watch.session_id() {
+                        update_run(&run_id, |run| run.session_id = Some(sid));
+                    }
+                }
+            }
+        };
+        let session_id = watch.session_id();
+        update_run(&run_id, |run| {
+            if session_id.is_some() {
+                run.session_id = session_id;
+            }
+            run.status = if success {
+                RunStatus::Completed
+            } else {
+                RunStatus::Failed
+            };
+        });
+    });
+
+    Ok(run)
+}
+
+async fn handle_connection(
+    app: AppHandle,
+    mut stream: TcpStream,
+    token: String,
+    shutdown: watch::Receiver<bool>,
+) -> Result<(), String> {
+    let request = read_request(&mut stream).await?;
+
+    if !is_loopback_host(request.header("host")) {
+        return write_json(&mut stream, 403, &json!({ "error": "Forbidden host" })).await;
+    }
+    if !is_authorized(&request, &token) {
+        return write_json(&mut stream, 401, &json!({ "error": "Unauthorized" })).await;
+    }
+
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    let result: Result<(u16, Value), String> = match (request.method.as_str(), segments.as_slice())
+    {
+        ("GET", ["ws"]) => return stream_session_output(app, stream, &request, shutdown).await,
+        ("GET", ["api", "health"]) => Ok((
+            200,
+            json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }),
+        )),
+        ("GET", ["api", "runs"]) => {
+            let registry = app.state::<ProcessRegistryState>();
+            registry
+                .0
+                .get_running_processes()
+                .map(|runs| (200, json!(runs)))
+        }
+        ("GET", ["api", "runs", run_id]) => match RUNS.lock().unwrap().get(*run_id) {
+            Some(run) => Ok((200, json!(run))),
+            None => Ok((404, json!({ "error": "Unknown run" }))),
+        },
+        ("POST", ["api", "sessions"]) => match parse_body::<SessionBody>(&request) {
+            Ok(mut body) => {
+                match super::session_worktree::prepare_request(body.engine, &mut body.request) {
+                    Err(e) => Ok((400, json!({ "error": e }))),
+                    Ok(()) => {
+                        log::info!(
+                            "[ControlAPI] Start {} session in {}",
+                            body.engine,
+                            body.request.project_path
+                        );
+                        start_run(&app, body.engine, body.request, None)
+                            .await
+                            .map(|run| (202, json!(run)))
+                    }
+                }
+            }
+            Err(e) => Ok((400, json!({ "error": e }))),
+        },
+        ("POST", ["api", "sessions", session_id, "resume"]) => {
+            match parse_body::<SessionBody>(&request) {
+                Ok(body) => {
+                    log::info!("[ControlAPI] Resume {} session {}", body.engine, session_id);
+                    start_run(&app, body.engine, body.request, Some(session_id))
+                        .await
+                        .map(|run| (202, json!(run)))
+                }
+                Err(e) => Ok((400, json!({ "error": e }))),
+            }
+        }
+        ("POST", ["api", "sessions", session_id, "cancel"]) => {
+            match parse_body::<CancelBody>(&request) {
+                Ok(body) => {
+                    log::info!("[ControlAPI] Cancel {} session {}", body.engine, session_id);
+                    runner_for(body.engine)
+                        .cancel(&app, Some(session_id.to_string()))
+                        .await
+                        .map(|_| (200, json!({ "cancelled": true })))
+                }
+                Err(e) => Ok((400, json!({ "error": e }))),
+            }
+        }
+        _ => Ok((404, json!({ "error": "Not found" }))),
+    };
+
+    match result {
+        Ok((status, body)) => write_json(&mut stream, status, &body).await,
+        Err(e) => write_json(&mut stream, 500, &json!({ "error": e })).await,
+    }
+}
+
+// ============================================================================
+// WebSocket
+// ============================================================================
+
+/// Turn an event payload back into the raw JSONL line the engine printed
+///
+/// Session events carry the line as a JSON string; global Claude events wrap it
+/// as `{ tab_id, payload }`.
+fn payload_to_line(payload: &str) -> String {
+    match serde_json::from_str::<Value>(payload) {
+        Ok(Value::String(line)) => line,
+        Ok(Value::Object(obj)) => match obj.get("payload") {
+            Some(Value::String(line)) => line.clone(),
+            _ => payload.to_string(),
+        },
+        _ => payload.to_string(),
+    }
+}
+
+async fn stream_session_output(
+    app: AppHandle,
+    mut stream: TcpStream,
+    request: &HttpRequest,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), String> {
+    let key = match request.header("sec-websocket-key") {
+        Some(key)
+            if request
+                .header("upgrade")
+                .is_some_and(|u| u.eq_ignore_ascii_case("websocket")) =>
+        {
+            key.to_string()
+        }
+        _ => {
+            return write_json(
+                &mut stream,
+                400,
+                &json!({ "error": "Expected WebSocket upgrade" }),
+            )
+            .await
+        }
+    };
+    let engine: EngineKind = match request.query.get("engine").map(|e| e.parse()) {
+        Some(Ok(engine)) => engine,
+        Some(Err(e)) => return write_json(&mut stream, 400, &json!({ "error": e })).await,
+        None => EngineKind::Claude,
+    };
+    let session_id = request.query.get("session_id").filter(|s| !s.is_empty());
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    let (mut sink, mut source) = ws.split();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Option<String>>();
+    let (output_event, complete_event) = match session_id {
+        Some(sid) => (
+            format!("{}-output:{}", engine, sid),
+            Some(format!("{}-complete:{}", engine, sid)),
+        ),
+        None => (format!("{}-output", engine), None),
+    };
+
+    let output_tx = tx.clone();
+    let mut listeners = vec![app.listen_any(output_event.clone(), move |event| {
+        let _ = output_tx.send(Some(payload_to_line(event.payload())));
+    })];
+    if let Some(complete_event) = complete_event {
+        // A session-scoped stream ends with its session
+        listeners.push(app.listen_any(complete_event, move |_| {
+            let _ = tx.send(None);
+        }));
+    }
+    log::info!("[ControlAPI] WebSocket subscribed to {}", output_event);
+
+    loop {
+        tokio::select! {
+            line = rx.recv() => match line {
+                Some(Some(line)) => {
+                    if sink.send(Message::Text(line)).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+            incoming = source.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+            // Server stopped or restarted (e.g. token regenerated)
+            _ = shutdown.changed() => {
+                let _ = sink.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+
+    for id in listeners {
+        app.unlisten(id);
+    }
+    log::info!("[ControlAPI] WebSocket for {} closed", output_event);
+    Ok(())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get the control API config and whether the server is listening
+#[tauri::command]
+pub async fn get_control_api_status(
+    state: State<'_, ControlApiState>,
+) -> Result<ControlApiStatus, String> {
+    let config = load_control_api_config()?;
+    let listening_port = state.0.lock().await.as_ref().map(|s| s.port);
+    Ok(ControlApiStatus {
+        config,
+        listening_port,
+    })
+}
+
+/// Enable/disable the server or change its port (restarts it)
+#[tauri::command]
+pub async fn update_control_api_config(
+    app: AppHandle,
+    state: State<'_, ControlApiState>,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<ControlApiStatus, String> {
+    let mut config = load_control_api_config()?;
+    config.enabled = enabled;
+    if let Some(port) = port {
+        if port < 1024 {
+            return Err("Port must be 1024 or higher".to_string());
+        }
+        config.port = port;
+    }
+    save_json_config(&config, &get_config_path()?)?;
+
+    let listening_port = restart_server(&app, &state, &config).await?;
+    Ok(ControlApiStatus {
+        config,
+        listening_port,
+    })
+}
+
+/// Issue a new token; clients holding the old one are rejected immediately
+#[tauri::command]
+pub async fn regenerate_control_api_token(
+    app: AppHandle,
+    state: State<'_, ControlApiState>,
+) -> Result<ControlApiStatus, String> {
+    let mut config = load_control_api_config()?;
+    config.token = generate_token();
+    save_json_config(&config, &get_config_path()?)?;
+
+    let listening_port = restart_server(&app, &state, &config).await?;
+    Ok(ControlApiStatus {
+        config,
+        listening_port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_host() {
+        assert!(is_loopback_host(Some("127.0.0.1:47821")));
+        assert!(is_loopback_host(Some("localhost")));
+        assert!(is_loopback_host(Some("[::1]:47821")));
+        assert!(!is_loopback_host(Some("evil.example.com:47821")));
+        assert!(!is_loopback_host(Some("127.0.0.1.evil.com")));
+        assert!(!is_loopback_host(None));
+    }
+
+    #[test]
+    fn test_payload_to_line() {
+        assert_eq!(
+            payload_to_line(r#""{\"type\":\"system\"}""#),
+            r#"{"type":"system"}"#
+        );
+        assert_eq!(
+            payload_to_line(r#"{"tab_id":"t1","payload":"{\"type\":\"result\"}"}"#),
+            r#"{"type":"result"}"#
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+    }
+
+    #[test]
+    fn test_prune_runs_keeps_running_and_newest_finished() {
+        let start = chrono::Utc::now();
+        let mut runs = HashMap::new();
+        for i in 0..MAX_FINISHED_RUNS + 2 {
+            let run = ControlRun {
+                run_id: format!("run-{}", i),
+                engine: EngineKind::Codex,
+                session_id: None,
+                status: if i == 0 {
+                    RunStatus::Running
+                } else {
+                    RunStatus::Completed
+                },
+                started_at: start + chrono::Duration::seconds(i as i64),
+            };
+            runs.insert(run.run_id.clone(), run);
+        }
+
+        prune_runs(&mut runs);
+
+        assert_eq!(runs.len(), MAX_FINISHED_RUNS + 1);
+        assert!(runs.contains_key("run-0"));
+        assert!(!runs.contains_key("run-1"));
+        assert!(runs.contains_key("run-2"));
+    }
+}
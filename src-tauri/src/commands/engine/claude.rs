@@ -0,0 +1,75 @@
+//! Claude Code engine adapter
+//!
+//! Claude already streams ClaudeStreamMessage JSON, so parsing is a plain
+//! JSON decode. Its runner (`claude/cli_runner.rs`) tracks each child in
+//! `ClaudeProcessState`, an `EngineProcessState` like Codex / Gemini, and
+//! registers the run in the ProcessRegistry once the init message arrives.
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tauri::{AppHandle, Runtime};
+
+use super::{Engine, EngineKind, EngineRequest, EngineRunner};
+use crate::commands::claude::{cancel_claude_execution, execute_claude_code, resume_claude_code};
+
+/// Default model alias when the request doesn't specify one
+const DEFAULT_CLAUDE_MODEL: &str = "sonnet";
+
+pub struct ClaudeEngine;
+
+impl Engine for ClaudeEngine {
+    fn kind(&self) -> EngineKind {
+        EngineKind::Claude
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Value> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        serde_json::from_str::<Value>(trimmed).ok()
+    }
+}
+
+#[async_trait]
+impl<R: Runtime> EngineRunner<R> for ClaudeEngine {
+    async fn spawn(&self, app: &AppHandle<R>, request: EngineRequest) -> Result<(), String> {
+        execute_claude_code(
+            app.clone(),
+            request.project_path,
+            request.prompt,
+            request
+                .model
+                .unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string()),
+            Some(request.plan_mode),
+            request.max_thinking_tokens,
+            request.tab_id,
+        )
+        .await
+    }
+
+    async fn resume(
+        &self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        request: EngineRequest,
+    ) -> Result<(), String> {
+        resume_claude_code(
+            app.clone(),
+            request.project_path,
+            session_id.to_string(),
+            request.prompt,
+            request
+                .model
+                .unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string()),
+            Some(request.plan_mode),
+            request.max_thinking_tokens,
+            request.tab_id,
+        )
+        .await
+    }
+
+    async fn cancel(&self, app: &AppHandle<R>, session_id: Option<String>) -> Result<(), String> {
+        cancel_claude_execution(app.clone(), session_id).await
+    }
+}
@@ -0,0 +1,743 @@
+//! OpenAI Codex engine
+//!
+//! Owns the `codex exec` runner: the `execute_codex` / `resume_codex` /
+//! `cancel_codex` commands are thin wrappers around `CodexEngine`.
+//!
+//! `parse_line` is a backend port of the essential cases handled by the
+//! frontend `codexConverter.ts`: thread/session start, agent messages,
+//! reasoning, command executions, turn usage and errors.
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use super::process::attach_job_object;
+use super::timeout::{self, RunActivity};
+use super::{Engine, EngineKind, EngineRequest, EngineRunner};
+use crate::commands::budget::{self, RunBudget};
+use crate::commands::claude::{apply_no_window_async, apply_process_group_async};
+use crate::commands::codex::session::CodexProcessHandle;
+use crate::commands::codex::{build_codex_command, CodexExecutionOptions, CodexProcessState};
+use crate::commands::session_archive;
+use crate::commands::usage_recorder::{self, UsageRecord};
+
+pub struct CodexEngine;
+
+impl CodexEngine {
+    fn build_options(request: EngineRequest) -> CodexExecutionOptions {
+        CodexExecutionOptions {
+            project_path: request.project_path,
+            prompt: request.prompt,
+            mode: request.codex_mode.unwrap_or_default(),
+            model: request.model,
+            json: true,
+            output_schema: None,
+            output_file: None,
+            skip_git_repo_check: false,
+            api_key: None,
+            session_id: None,
+            resume_last: false,
+        }
+    }
+
+    /// Start `codex exec` (or `codex exec resume <id>`) and stream it to the frontend
+    pub async fn execute<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        options: CodexExecutionOptions,
+        resume_session_id: Option<&str>,
+    ) -> Result<(), String> {
+        let (cmd, prompt) =
+            build_codex_command(&options, resume_session_id.is_some(), resume_session_id)?;
+
+        // Backend channel ID; the real thread ID follows in codex-cli-session-id
+        let session_id = format!("codex-{}", uuid::Uuid::new_v4());
+        run_codex_process(
+            session_id,
+            cmd,
+            prompt,
+            options.project_path,
+            options.model.unwrap_or_default(),
+            app.clone(),
+        )
+        .await
+    }
+
+    /// Resume a thread, inside its worktree when it was started in isolation mode
+    pub async fn resume_session<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        mut options: CodexExecutionOptions,
+    ) -> Result<(), String> {
+        options.project_path = crate::commands::session_worktree::resolve_project_path(
+            session_id,
+            options.project_path,
+        );
+        // The CLI cannot resume an archived (compressed) session file
+        session_archive::rehydrate(EngineKind::Codex, session_id, None, None)?;
+        self.execute(app, options, Some(session_id)).await
+    }
+}
+
+impl Engine for CodexEngine {
+    fn kind(&self) -> EngineKind {
+        EngineKind::Codex
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Value> {
+        let event: Value = serde_json::from_str(line.trim()).ok()?;
+        convert_codex_event(&event)
+    }
+}
+
+#[async_trait]
+impl<R: Runtime> EngineRunner<R> for CodexEngine {
+    async fn spawn(&self, app: &AppHandle<R>, request: EngineRequest) -> Result<(), String> {
+        self.execute(app, Self::build_options(request), None).await
+    }
+
+    async fn resume(
+        &self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        request: EngineRequest,
+    ) -> Result<(), String> {
+        let mut options = Self::build_options(request);
+        options.session_id = Some(session_id.to_string());
+        self.resume_session(app, session_id, options).await
+    }
+
+    async fn cancel(&self, app: &AppHandle<R>, session_id: Option<String>) -> Result<(), String> {
+        let state: tauri::State<'_, CodexProcessState> = app.state();
+        let handles = state.take(session_id.as_deref()).await;
+
+        if handles.is_empty() {
+            if let Some(sid) = session_id {
+                log::warn!("No running process found for session: {}", sid);
+            }
+        }
+
+        // Kill the entire process tree (parent + all children)
+        for (sid, handle) in handles {
+            handle.terminate("Codex", &sid).await;
+        }
+
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Process Runner
+// ============================================================================
+
+/// Executes a Codex process and streams output to frontend
+async fn run_codex_process<R: Runtime>(
+    session_id: String,
+    mut cmd: Command,
+    prompt: Option<String>,
+    project_path: String,
+    model: String,
+    app_handle: AppHandle<R>,
+) -> Result<(), String> {
+    budget::check_before_run(EngineKind::Codex, &project_path)?;
+
+    // 启动流程一开始就发送 session_init，确保即使启动失败也能让前端拿到 session_id 做隔离与错误反馈
+    let init_payload = serde_json::json!({
+        "type": "session_init",
+        "session_id": session_id,
+        "project_path": project_path
+    });
+    if let Err(e) = app_handle.emit("codex-session-init", init_payload) {
+        log::error!("Failed to emit codex-session-init: {}", e);
+    }
+    log::info!("Codex session initialized with ID: {}", session_id);
+
+    // Setup stdio
+    cmd.stdin(Stdio::piped()); // Enable stdin to pass prompt
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    // Fix: Apply platform-specific no-window configuration to hide console
+    // This prevents the terminal window from flashing when starting Codex sessions
+    apply_no_window_async(&mut cmd);
+    // Own process group on Unix so cancel can signal MCP servers too
+    apply_process_group_async(&mut cmd);
+
+    // Spawn process
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            emit_codex_error(
+                &app_handle,
+                &session_id,
+                "启动 Codex 失败",
+                Some(&e.to_string()),
+            );
+            // 这里不返回错误给前端（避免覆盖错误事件的可诊断信息），统一走事件通道
+            return Ok(());
+        }
+    };
+
+    // Get process PID for proper cleanup (needed to kill child processes)
+    let pid = match child.id() {
+        Some(pid) => pid,
+        None => {
+            emit_codex_error(
+                &app_handle,
+                &session_id,
+                "启动 Codex 失败：无法获取进程 PID",
+                None,
+            );
+            let _ = child.kill().await;
+            return Ok(());
+        }
+    };
+    log::info!("[Codex] Spawned process with PID: {}", pid);
+
+    let job_object = attach_job_object("Codex", pid);
+
+    let task = prompt.clone().unwrap_or_default();
+
+    // FIX: Write prompt to stdin if provided
+    // This avoids command line length limits and special character issues
+    if let Some(prompt_text) = prompt {
+        if let Some(mut stdin) = child.stdin.take() {
+            use tokio::io::AsyncWriteExt;
+
+            log::debug!("Writing prompt to stdin ({} bytes)", prompt_text.len());
+
+            if let Err(e) = stdin.write_all(prompt_text.as_bytes()).await {
+                log::error!("Failed to write prompt to stdin: {}", e);
+                let _ = child.kill().await;
+                emit_codex_error(
+                    &app_handle,
+                    &session_id,
+                    "Codex 写入 stdin 失败",
+                    Some(&e.to_string()),
+                );
+                return Ok(());
+            }
+
+            // Close stdin to signal end of input
+            drop(stdin);
+            log::debug!("Stdin closed successfully");
+        } else {
+            log::error!("Failed to get stdin handle");
+            let _ = child.kill().await;
+            emit_codex_error(
+                &app_handle,
+                &session_id,
+                "Codex 启动失败：无法获取 stdin 句柄",
+                None,
+            );
+            return Ok(());
+        }
+    }
+
+    // Extract stdout and stderr
+    let stdout = match child.stdout.take() {
+        Some(stdout) => stdout,
+        None => {
+            emit_codex_error(
+                &app_handle,
+                &session_id,
+                "启动 Codex 失败：无法捕获 stdout",
+                None,
+            );
+            let _ = child.kill().await;
+            return Ok(());
+        }
+    };
+    let stderr = match child.stderr.take() {
+        Some(stderr) => stderr,
+        None => {
+            emit_codex_error(
+                &app_handle,
+                &session_id,
+                "启动 Codex 失败：无法捕获 stderr",
+                None,
+            );
+            let _ = child.kill().await;
+            return Ok(());
+        }
+    };
+
+    // Store process in state with PID for proper cleanup
+    let mut handle = CodexProcessHandle {
+        child,
+        pid,
+        job_object,
+        registration: None,
+    };
+    handle.register(
+        &app_handle,
+        EngineKind::Codex,
+        &session_id,
+        &project_path,
+        &task,
+        &model,
+    );
+    let state: tauri::State<'_, CodexProcessState> = app_handle.state();
+    state.insert(session_id.clone(), handle).await;
+
+    // Clone handles for async tasks
+    let app_handle_stdout = app_handle.clone();
+    let app_handle_complete = app_handle.clone();
+    let session_id_stdout = session_id.clone(); // Clone for stdout task
+    let session_id_stderr = session_id.clone(); // Clone for stderr task
+    let session_id_complete = session_id.clone();
+
+    // 用于判断是否收到了任何 stdout 事件；仅当 stdout 完全无输出且存在 stderr 时，才触发 codex-error
+    let saw_stdout = Arc::new(AtomicBool::new(false));
+    let saw_stdout_for_complete = saw_stdout.clone();
+    let stderr_buffer: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let stderr_buffer_for_stderr = stderr_buffer.clone();
+    let stderr_buffer_for_complete = stderr_buffer.clone();
+
+    // 🔧 FIX: Use channels to track stdout/stderr closure for timeout detection
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    let (stderr_done_tx, _stderr_done_rx) = tokio::sync::oneshot::channel();
+
+    // Kill the run once it exceeds its wall-clock / inactivity limits
+    let activity = RunActivity::new();
+    let activity_stdout = activity.clone();
+    let activity_complete = activity.clone();
+    let app_handle_timeout = app_handle.clone();
+    let session_id_timeout = session_id.clone();
+    timeout::supervise(
+        &app_handle,
+        EngineKind::Codex,
+        pid,
+        project_path.clone(),
+        activity,
+        move || async move {
+            let state: tauri::State<'_, CodexProcessState> = app_handle_timeout.state();
+            for (sid, handle) in state.take(Some(&session_id_timeout)).await {
+                handle.terminate("Codex", &sid).await;
+            }
+        },
+    );
+
+    // Per-turn usage is recorded and charged against the cost budgets
+    let mut run_budget = RunBudget::new(EngineKind::Codex, &project_path);
+    let project_path_usage = project_path.clone();
+
+    // Spawn task to read stdout (JSONL events)
+    // FIX: Emit to both session-specific and global channels for proper multi-tab isolation
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stdout).lines();
+        let mut done_tx = Some(done_tx);
+        let mut cli_session_id: Option<String> = None;
+        while let Ok(Some(line)) = reader.next_line().await {
+            activity_stdout.touch();
+            if !line.trim().is_empty() {
+                saw_stdout.store(true, Ordering::Relaxed);
+
+                // Map backend channel ID -> real Codex thread ID (from thread.started)
+                if cli_session_id.is_none() {
+                    if let Some(thread_id) = CodexEngine
+                        .parse_line(&line)
+                        .and_then(|msg| CodexEngine.extract_session_id(&msg))
+                    {
+                        log::info!("[Codex] Detected real CLI session ID: {}", thread_id);
+                        let payload = serde_json::json!({
+                            "backend_session_id": session_id_stdout,
+                            "cli_session_id": thread_id,
+                        });
+                        if let Err(e) = app_handle_stdout.emit("codex-cli-session-id", &payload) {
+                            log::error!("Failed to emit codex-cli-session-id: {}", e);
+                        }
+                        activity_stdout.set_session_id(&thread_id);
+                        app_handle_stdout
+                            .state::<CodexProcessState>()
+                            .set_cli_session_id(&session_id_stdout, &thread_id)
+                            .await;
+                        cli_session_id = Some(thread_id);
+                    }
+                }
+
+                // Use trace level to avoid flooding logs in debug mode
+                log::trace!("Codex output: {}", line);
+                // Emit to session-specific channel first (for multi-tab isolation)
+                if let Err(e) =
+                    app_handle_stdout.emit(&format!("codex-output:{}", session_id_stdout), &line)
+                {
+                    log::error!("Failed to emit codex-output (session-specific): {}", e);
+                }
+                // Also emit to global channel for backward compatibility
+                if let Err(e) = app_handle_stdout.emit("codex-output", &line) {
+                    log::error!("Failed to emit codex-output (global): {}", e);
+                }
+
+                let usage_session_id = cli_session_id.as_deref().unwrap_or(&session_id_stdout);
+                if let Some(record) = UsageRecord::from_codex_line(
+                    &line,
+                    &model,
+                    usage_session_id,
+                    &project_path_usage,
+                ) {
+                    let cost = record.cost;
+                    usage_recorder::record_usage(&app_handle_stdout, record);
+                    run_budget.charge(&app_handle_stdout, &session_id_stdout, cost);
+                }
+
+                // Detect turn completion to trigger backend cleanup even if stdout never closes.
+                if done_tx.is_some() {
+                    let is_done_event = serde_json::from_str::<serde_json::Value>(&line)
+                        .ok()
+                        .and_then(|v| {
+                            v.get("type")
+                                .and_then(|t| t.as_str())
+                                .map(|s| s.to_string())
+                        })
+                        .map(|t| matches!(t.as_str(), "turn.completed" | "turn.failed" | "error"))
+                        .unwrap_or(false);
+
+                    if is_done_event {
+                        log::info!(
+                            "[Codex] Detected completion event on stdout for session: {}",
+                            session_id_stdout
+                        );
+                        if let Some(tx) = done_tx.take() {
+                            let _ = tx.send(());
+                        }
+                    }
+                }
+            }
+        }
+        log::info!("[Codex] Stdout closed for session: {}", session_id_stdout);
+        // Fallback: stdout closed, treat as completion if not already signaled.
+        if let Some(tx) = done_tx.take() {
+            let _ = tx.send(());
+        }
+    });
+
+    // Spawn task to read stderr (log errors, suppress debug output)
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            // Log error messages for debugging
+            if !line.trim().is_empty() {
+                log::warn!("Codex stderr: {}", line);
+                // 仅缓存少量 stderr 以便在“无 stdout 输出”的启动失败场景下进行汇总反馈
+                let mut buf = stderr_buffer_for_stderr.lock().await;
+                if buf.len() < 20 {
+                    buf.push(line);
+                }
+            }
+        }
+        log::info!("[Codex] Stderr closed for session: {}", session_id_stderr);
+        // Signal that stderr is done (ignore send error if receiver dropped)
+        let _ = stderr_done_tx.send(());
+    });
+
+    // Spawn task to wait for process completion
+    // 🔧 FIX: Only wait for stdout to close, then send completion event immediately
+    // stderr may continue outputting logs (MCP servers, etc.) for a long time
+    let pid_for_cleanup = pid; // Copy PID for cleanup task
+    tokio::spawn(async move {
+        let state: tauri::State<'_, CodexProcessState> = app_handle_complete.state();
+
+        // Only wait for stdout to close (stderr can continue logging)
+        let _ = done_rx.await;
+        activity_complete.finish();
+        log::info!(
+            "[Codex] Completion signaled for session: {}",
+            session_id_complete
+        );
+        let success = activity_complete.timed_out().is_none();
+
+        // 若 stdout 完全无输出但 stderr 有内容，补发一次可诊断错误事件，避免前端表现为“无反应”
+        if !saw_stdout_for_complete.load(Ordering::Relaxed) {
+            let buf = stderr_buffer_for_complete.lock().await;
+            if !buf.is_empty() {
+                let detail = buf.join("\n");
+                emit_codex_error(
+                    &app_handle_complete,
+                    &session_id_complete,
+                    "Codex 启动失败或未产生任何输出",
+                    Some(&detail),
+                );
+            }
+        }
+
+        // 🔧 CRITICAL FIX: Emit completion event immediately after stdout closes
+        // Don't wait for process exit or stderr - those can take a long time
+        // stdout closing means all JSONL events have been sent, session is effectively complete
+        log::info!(
+            "[Codex] Sending completion event for session: {}",
+            session_id_complete
+        );
+        if let Err(e) =
+            app_handle_complete.emit(&format!("codex-complete:{}", session_id_complete), success)
+        {
+            log::error!("Failed to emit codex-complete (session-specific): {}", e);
+        }
+        if let Err(e) = app_handle_complete.emit("codex-complete", success) {
+            log::error!("Failed to emit codex-complete (global): {}", e);
+        }
+
+        // Continue waiting for process exit in background (with timeout protection)
+        // This ensures proper cleanup but doesn't block the completion event
+        // After turn completion, Codex should exit promptly; keep a short grace window to
+        // let it flush session files, then force-kill to prevent orphan node.exe accumulation.
+        let timeout_duration = tokio::time::Duration::from_secs(3);
+        let start_time = tokio::time::Instant::now();
+
+        loop {
+            let mut processes = state.processes.lock().await;
+
+            if let Some(handle) = processes.get_mut(&session_id_complete) {
+                match handle.child.try_wait() {
+                    Ok(Some(status)) => {
+                        log::info!("[Codex] Process exited with status: {}", status);
+                        processes.remove(&session_id_complete);
+                        break;
+                    }
+                    Ok(None) => {
+                        // Check timeout
+                        if start_time.elapsed() > timeout_duration {
+                            log::warn!(
+                                "[Codex] Process {} (PID: {}) did not exit within {}s after completion, force killing process tree",
+                                session_id_complete,
+                                pid_for_cleanup,
+                                timeout_duration.as_secs()
+                            );
+
+                            // 🔧 FIX: Kill entire process tree to prevent orphan child processes.
+                            // Take the handle out first so the state lock isn't held during
+                            // the termination grace period.
+                            if let Some(handle) = processes.remove(&session_id_complete) {
+                                drop(processes);
+                                handle.terminate("Codex", &session_id_complete).await;
+                            }
+                            break;
+                        }
+
+                        drop(processes);
+                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    }
+                    Err(e) => {
+                        log::error!("[Codex] Error checking process status: {}", e);
+                        processes.remove(&session_id_complete);
+                        break;
+                    }
+                }
+            } else {
+                log::info!(
+                    "[Codex] Process {} was removed (cancelled)",
+                    session_id_complete
+                );
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn emit_codex_error<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    session_id: &str,
+    message: &str,
+    detail: Option<&str>,
+) {
+    let payload = serde_json::json!({
+        "session_id": session_id,
+        "error": {
+            "message": message,
+            "detail": detail,
+        }
+    });
+
+    let payload_str = serde_json::to_string(&payload).unwrap_or_else(|_| message.to_string());
+
+    let _ = app_handle.emit(&format!("codex-error:{}", session_id), &payload_str);
+    let _ = app_handle.emit("codex-error", &payload_str);
+}
+
+// ============================================================================
+// Event Conversion
+// ============================================================================
+
+/// Convert one Codex JSONL event to the unified message format
+pub fn convert_codex_event(event: &Value) -> Option<Value> {
+    let event_type = event.get("type").and_then(|t| t.as_str())?;
+    let timestamp = event
+        .get("timestamp")
+        .cloned()
+        .unwrap_or_else(|| json!(chrono::Utc::now().to_rfc3339()));
+
+    let message = match event_type {
+        "thread.started" => json!({
+            "type": "system",
+            "subtype": "init",
+            "result": "Codex session started",
+            "session_id": event.get("thread_id"),
+            "timestamp": timestamp,
+        }),
+        "session_meta" => {
+            let payload = event.get("payload")?;
+            json!({
+                "type": "system",
+                "subtype": "init",
+                "session_id": payload.get("id"),
+                "model": payload.get("model"),
+                "timestamp": payload.get("timestamp").cloned().unwrap_or(timestamp),
+            })
+        }
+        "turn.completed" => json!({
+            "type": "result",
+            "subtype": "success",
+            "usage": event.get("usage"),
+            "timestamp": timestamp,
+        }),
+        "turn.failed" | "error" => {
+            let message = event
+                .get("error")
+                .and_then(|e| e.get("message"))
+                .or_else(|| event.get("message"))
+                .and_then(|m| m.as_str())
+                .unwrap_or("Unknown error");
+            json!({
+                "type": "system",
+                "subtype": "error",
+                "error": { "message": message },
+                "timestamp": timestamp,
+            })
+        }
+        "item.completed" => convert_codex_item(event.get("item")?, timestamp)?,
+        _ => return None,
+    };
+
+    let mut message = message;
+    if let Some(obj) = message.as_object_mut() {
+        obj.insert("engine".to_string(), json!("codex"));
+    }
+    Some(message)
+}
+
+fn convert_codex_item(item: &Value, timestamp: Value) -> Option<Value> {
+    let item_type = item
+        .get("type")
+        .or_else(|| item.get("item_type"))
+        .and_then(|t| t.as_str())?;
+    let text = item.get("text").and_then(|t| t.as_str()).unwrap_or("");
+
+    match item_type {
+        "agent_message" => Some(json!({
+            "type": "assistant",
+            "message": {
+                "role": "assistant",
+                "content": [{ "type": "text", "text": text }]
+            },
+            "timestamp": timestamp,
+        })),
+        "reasoning" => Some(json!({
+            "type": "thinking",
+            "content": text,
+            "timestamp": timestamp,
+        })),
+        "command_execution" => {
+            let tool_use_id = format!(
+                "codex_cmd_{}",
+                item.get("id").and_then(|v| v.as_str()).unwrap_or("")
+            );
+            Some(json!({
+                "type": "assistant",
+                "message": {
+                    "role": "assistant",
+                    "content": [
+                        {
+                            "type": "tool_use",
+                            "id": tool_use_id,
+                            "name": "bash",
+                            "input": { "command": item.get("command") }
+                        },
+                        {
+                            "type": "tool_result",
+                            "tool_use_id": tool_use_id,
+                            "content": [{
+                                "type": "text",
+                                "text": item.get("aggregated_output").and_then(|v| v.as_str()).unwrap_or("")
+                            }],
+                            "is_error": item.get("status").and_then(|v| v.as_str()) == Some("failed")
+                        }
+                    ]
+                },
+                "timestamp": timestamp,
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_started_becomes_init_with_session_id() {
+        let line =
+            r#"{"type":"thread.started","thread_id":"0199a213-81c0-7800-8aa1-bbab2a035a53"}"#;
+        let message = CodexEngine.parse_line(line).unwrap();
+
+        assert_eq!(message["type"], "system");
+        assert_eq!(message["subtype"], "init");
+        assert_eq!(message["engine"], "codex");
+        assert_eq!(
+            CodexEngine.extract_session_id(&message).as_deref(),
+            Some("0199a213-81c0-7800-8aa1-bbab2a035a53")
+        );
+    }
+
+    #[test]
+    fn failed_command_pairs_tool_use_with_error_result() {
+        let event = json!({
+            "type": "item.completed",
+            "item": {
+                "id": "item_3",
+                "type": "command_execution",
+                "command": "cargo test",
+                "aggregated_output": "error[E0425]",
+                "status": "failed"
+            }
+        });
+        let message = convert_codex_event(&event).unwrap();
+        let content = &message["message"]["content"];
+
+        assert_eq!(content[0]["type"], "tool_use");
+        assert_eq!(content[0]["id"], "codex_cmd_item_3");
+        assert_eq!(content[0]["input"]["command"], "cargo test");
+        assert_eq!(content[1]["tool_use_id"], "codex_cmd_item_3");
+        assert_eq!(content[1]["content"][0]["text"], "error[E0425]");
+        assert_eq!(content[1]["is_error"], true);
+    }
+
+    #[test]
+    fn turn_events_and_unknown_types() {
+        let done = json!({
+            "type": "turn.completed",
+            "usage": { "input_tokens": 1200, "cached_input_tokens": 800, "output_tokens": 40 }
+        });
+        let result = convert_codex_event(&done).unwrap();
+        assert_eq!(result["type"], "result");
+        assert_eq!(result["usage"]["cached_input_tokens"], 800);
+
+        let failed = json!({ "type": "turn.failed", "error": { "message": "rate limited" } });
+        assert_eq!(
+            convert_codex_event(&failed).unwrap()["error"]["message"],
+            "rate limited"
+        );
+
+        assert!(convert_codex_event(&json!({ "type": "item.started" })).is_none());
+        assert!(CodexEngine.parse_line("not json").is_none());
+    }
+}
@@ -0,0 +1,691 @@
+//! Google Gemini CLI engine
+//!
+//! Owns the Gemini runner: the `execute_gemini` / `cancel_gemini` commands
+//! are thin wrappers around `GeminiEngine`. Parsing reuses the existing
+//! `gemini::parser` conversion, so `parse_line` and the streaming runner
+//! produce identical unified messages.
+
+use async_trait::async_trait;
+use serde_json::Value;
+use std::process::Stdio;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use super::process::attach_job_object;
+use super::timeout::{self, RunActivity};
+use super::{Engine, EngineKind, EngineRequest, EngineRunner};
+use crate::commands::budget::{self, RunBudget};
+use crate::commands::claude::{apply_no_window_async, apply_process_group_async};
+use crate::commands::gemini::build_gemini_command;
+use crate::commands::gemini::parser::{
+    convert_raw_to_unified_message, convert_to_unified_message, parse_gemini_line,
+    parse_gemini_line_flexible,
+};
+use crate::commands::gemini::session::{is_slash_command, try_load_latest_session_token_usage};
+use crate::commands::gemini::types::{
+    GeminiExecutionOptions, GeminiProcessHandle, GeminiProcessState, GeminiStreamEvent,
+};
+use crate::commands::session_archive;
+use crate::commands::usage_recorder::{self, UsageRecord};
+
+pub struct GeminiEngine;
+
+impl GeminiEngine {
+    fn build_options(request: EngineRequest) -> GeminiExecutionOptions {
+        GeminiExecutionOptions {
+            project_path: request.project_path,
+            prompt: request.prompt,
+            // None lets execute_gemini fall back to ~/.anycode/gemini.json defaults
+            model: request.model,
+            approval_mode: request.approval_mode,
+            include_directories: None,
+            session_id: None,
+            debug: false,
+        }
+    }
+
+    /// Start (or resume) a Gemini run and stream it to the frontend
+    pub async fn execute<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        mut options: GeminiExecutionOptions,
+    ) -> Result<(), String> {
+        if let Some(session_id) = options.session_id.as_deref() {
+            // Resumed sessions started in isolation mode run inside their worktree
+            options.project_path = crate::commands::session_worktree::resolve_project_path(
+                session_id,
+                options.project_path,
+            );
+            // The CLI only sees plain chat files, so restore an archived one first
+            session_archive::rehydrate(
+                EngineKind::Gemini,
+                session_id,
+                None,
+                Some(&options.project_path),
+            )?;
+        }
+
+        let (cmd, model) = build_gemini_command(&options)?;
+
+        // Execute process with prompt via stdin
+        run_gemini_process(
+            cmd,
+            options.project_path,
+            model,
+            Some(options.prompt),
+            app.clone(),
+        )
+        .await
+    }
+}
+
+impl Engine for GeminiEngine {
+    fn kind(&self) -> EngineKind {
+        EngineKind::Gemini
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Value> {
+        if let Ok(event) = parse_gemini_line(line) {
+            Some(convert_to_unified_message(&event))
+        } else {
+            parse_gemini_line_flexible(line)
+                .ok()
+                .map(|raw| convert_raw_to_unified_message(&raw))
+        }
+    }
+}
+
+#[async_trait]
+impl<R: Runtime> EngineRunner<R> for GeminiEngine {
+    async fn spawn(&self, app: &AppHandle<R>, request: EngineRequest) -> Result<(), String> {
+        self.execute(app, Self::build_options(request)).await
+    }
+
+    async fn resume(
+        &self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        request: EngineRequest,
+    ) -> Result<(), String> {
+        // Gemini CLI only resumes "latest"; the ID just switches execute into resume mode
+        let mut options = Self::build_options(request);
+        options.session_id = Some(session_id.to_string());
+        self.execute(app, options).await
+    }
+
+    async fn cancel(&self, app: &AppHandle<R>, session_id: Option<String>) -> Result<(), String> {
+        let state: tauri::State<'_, GeminiProcessState> = app.state();
+        let handles = state.take(session_id.as_deref()).await;
+
+        if handles.is_empty() {
+            // Nothing was running; don't tell the UI a run was cancelled
+            if let Some(ref sid) = session_id {
+                log::warn!("No running process found for session: {}", sid);
+            }
+            return Ok(());
+        }
+
+        for (sid, handle) in handles {
+            // Kill the process tree - JobObject also terminates child processes (MCP servers, node.exe, etc.)
+            handle.terminate("Gemini", &sid).await;
+            let _ = app.emit(&format!("gemini-cancelled:{}", sid), true);
+        }
+        let _ = app.emit("gemini-cancelled", true);
+
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Process Runner
+// ============================================================================
+
+/// Execute a Gemini process and stream output to frontend
+///
+/// 🔥 斜杠命令支持：斜杠命令通过 -p 参数传递（触发命令解析），普通 prompt 通过 stdin 管道传递
+/// 这样既支持斜杠命令，又避免操作系统命令行长度限制（Windows ~8KB, Linux/macOS ~128KB-2MB）
+async fn run_gemini_process<R: Runtime>(
+    mut cmd: Command,
+    project_path: String,
+    model: String,
+    prompt: Option<String>,
+    app_handle: AppHandle<R>,
+) -> Result<(), String> {
+    budget::check_before_run(EngineKind::Gemini, &project_path)?;
+
+    // 🔥 关键修复：检测斜杠命令，通过 -p 参数传递以触发命令解析
+    // Gemini CLI 在非交互模式下支持斜杠命令（自 v0.1.59 起，PR #8305）
+    let use_p_flag = prompt
+        .as_ref()
+        .map(|p| is_slash_command(p))
+        .unwrap_or(false);
+
+    if use_p_flag {
+        if let Some(ref prompt_text) = prompt {
+            log::info!(
+                "Detected slash command, using -p flag: {}",
+                prompt_text.trim()
+            );
+            cmd.arg("-p");
+            cmd.arg(prompt_text);
+        }
+    }
+
+    // Setup stdio - use piped stdin to pass prompt (supports multiline content)
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    // Apply platform-specific no-window configuration
+    apply_no_window_async(&mut cmd);
+    // Own process group on Unix so cancel can signal MCP servers too
+    apply_process_group_async(&mut cmd);
+
+    // Spawn process
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn gemini: {}", e))?;
+
+    let task = prompt.clone().unwrap_or_default();
+
+    // 🔥 修复：只有非斜杠命令才通过 stdin 传递
+    // 斜杠命令已经通过 -p 参数传递，避免重复
+    if !use_p_flag {
+        if let Some(prompt_text) = prompt {
+            if let Some(mut stdin) = child.stdin.take() {
+                use tokio::io::AsyncWriteExt;
+
+                log::debug!("Writing prompt to stdin ({} bytes)", prompt_text.len());
+
+                if let Err(e) = stdin.write_all(prompt_text.as_bytes()).await {
+                    log::error!("Failed to write prompt to stdin: {}", e);
+                    return Err(format!("Failed to write prompt to stdin: {}", e));
+                }
+
+                // Close stdin to signal end of input
+                drop(stdin);
+                log::debug!("Stdin closed successfully");
+            } else {
+                log::error!("Failed to get stdin handle");
+                return Err("Failed to get stdin handle".to_string());
+            }
+        }
+    } else {
+        // 斜杠命令模式：关闭 stdin
+        if let Some(stdin) = child.stdin.take() {
+            drop(stdin);
+            log::debug!("Stdin closed for slash command mode");
+        }
+    }
+
+    // Extract stdout and stderr
+    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+
+    // Get process PID for proper cleanup (needed to kill child processes)
+    let pid = child
+        .id()
+        .ok_or("Failed to get process ID - process may have already exited")?;
+    log::info!("[Gemini] Spawned process with PID: {}", pid);
+
+    let job_object = attach_job_object("Gemini", pid);
+
+    // Generate session ID
+    let session_id = format!("gemini-{}", uuid::Uuid::new_v4());
+
+    // Store process in state with PID and JobObject for proper cleanup
+    let mut handle = GeminiProcessHandle {
+        child,
+        pid,
+        job_object,
+        registration: None,
+    };
+    handle.register(
+        &app_handle,
+        EngineKind::Gemini,
+        &session_id,
+        &project_path,
+        &task,
+        &model,
+    );
+    let state: tauri::State<'_, GeminiProcessState> = app_handle.state();
+    state.insert(session_id.clone(), handle).await;
+
+    // Emit session init event
+    let init_payload = serde_json::json!({
+        "type": "system",
+        "subtype": "init",
+        "session_id": session_id,
+        "model": model,
+        "project_path": project_path,
+        "geminiMetadata": {
+            "provider": "gemini",
+            "eventType": "session_init"
+        }
+    });
+
+    if let Err(e) = app_handle.emit("gemini-session-init", &init_payload) {
+        log::error!("Failed to emit gemini-session-init: {}", e);
+    }
+
+    // Also emit as gemini-output for unified handling
+    let init_line = serde_json::to_string(&init_payload).unwrap_or_default();
+    let _ = app_handle.emit(&format!("gemini-output:{}", session_id), &init_line);
+    let _ = app_handle.emit("gemini-output", &init_line);
+
+    log::info!("Gemini session initialized with ID: {}", session_id);
+
+    // 🔧 FIX: Use channels to track stdout/stderr closure for timeout detection
+    let (stdout_done_tx, stdout_done_rx) = tokio::sync::oneshot::channel();
+    let (stderr_done_tx, stderr_done_rx) = tokio::sync::oneshot::channel();
+
+    // Clone handles for async tasks
+    let app_handle_stdout = app_handle.clone();
+    let app_handle_stderr = app_handle.clone();
+    let app_handle_complete = app_handle.clone();
+    let session_id_stdout = session_id.clone();
+    let session_id_stderr = session_id.clone();
+    let session_id_complete = session_id.clone();
+
+    // Kill the run once it exceeds its wall-clock / inactivity limits
+    let activity = RunActivity::new();
+    let activity_stdout = activity.clone();
+    let app_handle_timeout = app_handle.clone();
+    let session_id_timeout = session_id.clone();
+    timeout::supervise(
+        &app_handle,
+        EngineKind::Gemini,
+        pid,
+        project_path.clone(),
+        activity.clone(),
+        move || async move {
+            let state: tauri::State<'_, GeminiProcessState> = app_handle_timeout.state();
+            for (sid, handle) in state.take(Some(&session_id_timeout)).await {
+                handle.terminate("Gemini", &sid).await;
+            }
+        },
+    );
+
+    // Spawn task to read stdout (JSONL events)
+    let model_for_messages = model.clone();
+    let project_path_for_usage = project_path.clone();
+    let mut run_budget = RunBudget::new(EngineKind::Gemini, &project_path);
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stdout).lines();
+        let mut real_cli_session_id_emitted = false;
+        let mut real_cli_session_id: Option<String> = None;
+        // Track tool calls to enrich tool_result payloads (e.g., read_file returning empty output)
+        let mut tool_calls: std::collections::HashMap<String, (String, serde_json::Value)> =
+            std::collections::HashMap::new();
+
+        while let Ok(Some(line)) = reader.next_line().await {
+            activity_stdout.touch();
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Use trace level to avoid flooding logs in debug mode
+            log::trace!("Gemini output: {}", line);
+
+            // Try to parse and convert to unified format
+            let mut unified_message = if let Ok(mut event) = parse_gemini_line(&line) {
+                // 🔧 FIX: Check if this is an init event with real Gemini CLI session ID
+                if !real_cli_session_id_emitted {
+                    if let GeminiStreamEvent::Init {
+                        session_id: Some(ref cli_session_id),
+                        ..
+                    } = event
+                    {
+                        real_cli_session_id = Some(cli_session_id.clone());
+                        activity_stdout.set_session_id(cli_session_id);
+                        app_handle_stdout
+                            .state::<GeminiProcessState>()
+                            .set_cli_session_id(&session_id_stdout, cli_session_id)
+                            .await;
+                        // Emit the real Gemini CLI session ID to frontend
+                        log::info!("[Gemini] Detected real CLI session ID: {}", cli_session_id);
+                        let cli_session_payload = serde_json::json!({
+                            "backend_session_id": session_id_stdout,
+                            "cli_session_id": cli_session_id,
+                        });
+                        if let Err(e) =
+                            app_handle_stdout.emit("gemini-cli-session-id", &cli_session_payload)
+                        {
+                            log::error!("Failed to emit gemini-cli-session-id: {}", e);
+                        }
+                        real_cli_session_id_emitted = true;
+                    }
+                }
+
+                // Ensure result events have usageMetadata (cache/thoughts/tool breakdown) when available in history.
+                if let GeminiStreamEvent::Result { usage_metadata, .. } = &mut event {
+                    if usage_metadata.is_none() {
+                        if let Some(ref cli_session_id) = real_cli_session_id {
+                            if let Some(enriched) = try_load_latest_session_token_usage(
+                                &project_path_for_usage,
+                                cli_session_id,
+                            )
+                            .await
+                            {
+                                *usage_metadata = Some(enriched);
+                            }
+                        }
+                    }
+                }
+
+                // Record the run's usage and charge it against the cost budgets
+                let usage_session_id = real_cli_session_id.as_deref().unwrap_or(&session_id_stdout);
+                if let Some(record) = UsageRecord::from_gemini_event(
+                    &event,
+                    &model_for_messages,
+                    usage_session_id,
+                    &project_path_for_usage,
+                ) {
+                    let cost = record.cost;
+                    usage_recorder::record_usage(&app_handle_stdout, record);
+                    run_budget.charge(&app_handle_stdout, &session_id_stdout, cost);
+                }
+
+                // Record tool_use params for later enrichment of tool_result
+                if let GeminiStreamEvent::ToolUse {
+                    tool_name,
+                    tool_id,
+                    parameters,
+                    ..
+                } = &event
+                {
+                    tool_calls.insert(tool_id.clone(), (tool_name.clone(), parameters.clone()));
+                }
+
+                // Enrich tool_result with inline file content if CLI returned empty output
+                if let GeminiStreamEvent::ToolResult {
+                    tool_id,
+                    output,
+                    status: _status,
+                    ..
+                } = &mut event
+                {
+                    if let Some((tool_name, params)) = tool_calls.get(tool_id).cloned() {
+                        let is_read_tool = {
+                            let name_lower = tool_name.to_lowercase();
+                            name_lower == "read" || name_lower == "read_file"
+                        };
+
+                        let output_empty = output.is_null()
+                            || output.as_str().map(|s| s.is_empty()).unwrap_or(false);
+
+                        if is_read_tool && output_empty {
+                            let file_path = params
+                                .get("file_path")
+                                .and_then(|v| v.as_str())
+                                .or_else(|| params.get("path").and_then(|v| v.as_str()));
+
+                            if let Some(path) = file_path {
+                                match tokio::fs::read_to_string(path).await {
+                                    Ok(content) => {
+                                        // Wrap as functionResponse to align with frontend parser
+                                        *output = serde_json::json!([{
+                                            "functionResponse": {
+                                                "id": tool_id,
+                                                "name": tool_name,
+                                                "response": { "output": content }
+                                            }
+                                        }]);
+                                        log::info!("[Gemini] Filled empty tool_result output for {} from path {}", tool_id, path);
+                                    }
+                                    Err(err) => {
+                                        log::warn!(
+                                            "[Gemini] Failed to read file for tool_result {}: {}",
+                                            tool_id,
+                                            err
+                                        );
+                                        // Keep original empty output; frontend will handle gracefully
+                                    }
+                                }
+                            } else {
+                                log::warn!(
+                                    "[Gemini] No file_path found for tool_result {}",
+                                    tool_id
+                                );
+                            }
+                        }
+
+                        // Optionally add status-based log for visibility
+                        if output_empty && !is_read_tool {
+                            log::debug!(
+                                "[Gemini] tool_result {} had empty output (tool: {})",
+                                tool_id,
+                                tool_name
+                            );
+                        }
+                    } else {
+                        // No prior tool_use recorded; keep original
+                        log::debug!(
+                            "[Gemini] tool_result {} without prior tool_use record",
+                            tool_id
+                        );
+                    }
+                }
+
+                convert_to_unified_message(&event)
+            } else if let Ok(raw) = parse_gemini_line_flexible(&line) {
+                // 🔧 FIX: Also check raw JSON for init event with session_id
+                if !real_cli_session_id_emitted {
+                    if raw.get("type").and_then(|t| t.as_str()) == Some("init") {
+                        if let Some(cli_session_id) = raw.get("session_id").and_then(|s| s.as_str())
+                        {
+                            real_cli_session_id = Some(cli_session_id.to_string());
+                            activity_stdout.set_session_id(cli_session_id);
+                            app_handle_stdout
+                                .state::<GeminiProcessState>()
+                                .set_cli_session_id(&session_id_stdout, cli_session_id)
+                                .await;
+                            log::info!(
+                                "[Gemini] Detected real CLI session ID (raw): {}",
+                                cli_session_id
+                            );
+                            let cli_session_payload = serde_json::json!({
+                                "backend_session_id": session_id_stdout,
+                                "cli_session_id": cli_session_id,
+                            });
+                            if let Err(e) = app_handle_stdout
+                                .emit("gemini-cli-session-id", &cli_session_payload)
+                            {
+                                log::error!("Failed to emit gemini-cli-session-id: {}", e);
+                            }
+                            real_cli_session_id_emitted = true;
+                        }
+                    }
+                }
+                convert_raw_to_unified_message(&raw)
+            } else {
+                // Fallback: emit raw line as system message
+                serde_json::json!({
+                    "type": "system",
+                    "subtype": "raw",
+                    "content": line,
+                    "geminiMetadata": {
+                        "provider": "gemini",
+                        "eventType": "raw"
+                    }
+                })
+            };
+
+            // Ensure engine/model are present for consistent frontend cost/context calculations
+            if let Some(obj) = unified_message.as_object_mut() {
+                obj.entry("engine")
+                    .or_insert_with(|| serde_json::Value::String("gemini".to_string()));
+
+                let should_set_model = match obj.get("model") {
+                    None => true,
+                    Some(v) => {
+                        v.is_null() || v.as_str().map(|s| s.trim().is_empty()).unwrap_or(false)
+                    }
+                };
+                if should_set_model {
+                    obj.insert(
+                        "model".to_string(),
+                        serde_json::Value::String(model_for_messages.clone()),
+                    );
+                }
+            }
+
+            let unified_line = serde_json::to_string(&unified_message).unwrap_or(line.clone());
+
+            // Emit to session-specific channel
+            if let Err(e) = app_handle_stdout.emit(
+                &format!("gemini-output:{}", session_id_stdout),
+                &unified_line,
+            ) {
+                log::error!("Failed to emit gemini-output (session): {}", e);
+            }
+
+            // Also emit to global channel
+            if let Err(e) = app_handle_stdout.emit("gemini-output", &unified_line) {
+                log::error!("Failed to emit gemini-output (global): {}", e);
+            }
+        }
+
+        log::info!("[Gemini] Stdout closed for session: {}", session_id_stdout);
+        // Signal that stdout is done (ignore send error if receiver dropped)
+        let _ = stdout_done_tx.send(());
+    });
+
+    // Spawn task to read stderr
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(stderr).lines();
+
+        while let Ok(Some(line)) = reader.next_line().await {
+            if !line.trim().is_empty() {
+                log::warn!("Gemini stderr: {}", line);
+
+                // Emit stderr as error event
+                let error_message = serde_json::json!({
+                    "type": "system",
+                    "subtype": "error",
+                    "error": {
+                        "message": line
+                    },
+                    "geminiMetadata": {
+                        "provider": "gemini",
+                        "eventType": "stderr"
+                    }
+                });
+
+                let error_line = serde_json::to_string(&error_message).unwrap_or(line.clone());
+
+                let _ = app_handle_stderr
+                    .emit(&format!("gemini-error:{}", session_id_stderr), &error_line);
+                let _ = app_handle_stderr.emit("gemini-error", &error_line);
+            }
+        }
+
+        log::info!("[Gemini] Stderr closed for session: {}", session_id_stderr);
+        // Signal that stderr is done (ignore send error if receiver dropped)
+        let _ = stderr_done_tx.send(());
+    });
+
+    // Spawn task to wait for process completion
+    // 🔧 FIX: Add timeout mechanism - if stdout/stderr are closed but process doesn't exit within 30s, force completion
+    let state_complete = app_handle.state::<GeminiProcessState>();
+    let processes_complete = state_complete.processes.clone();
+
+    tokio::spawn(async move {
+        // Wait for both stdout and stderr to close
+        let _ = tokio::join!(stdout_done_rx, stderr_done_rx);
+        activity.finish();
+        log::info!(
+            "[Gemini] Both stdout and stderr closed for session: {}",
+            session_id_complete
+        );
+
+        // After streams close, give process up to 30 seconds to exit gracefully
+        let timeout_duration = tokio::time::Duration::from_secs(30);
+
+        // Try to wait for process with timeout
+        let wait_result = tokio::time::timeout(timeout_duration, async {
+            let mut processes = processes_complete.lock().await;
+            if let Some(mut handle) = processes.remove(&session_id_complete) {
+                let result = handle.child.wait().await;
+                // JobObject is dropped here when handle goes out of scope,
+                // ensuring all child processes (MCP servers, node.exe, etc.) are terminated
+                log::debug!("[Gemini] Process handle dropped, JobObject cleaning up child processes for PID: {}", handle.pid);
+                result
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Process not found",
+                ))
+            }
+        })
+        .await;
+
+        let (success, exit_code) = match wait_result {
+            Ok(Ok(status)) => {
+                let success = status.success();
+                log::info!(
+                    "[Gemini] Process exited with status: {} (success: {})",
+                    status,
+                    success
+                );
+                (success, status.code())
+            }
+            Ok(Err(e)) => {
+                log::error!("[Gemini] Failed to wait for process: {}", e);
+                (false, None)
+            }
+            Err(_) => {
+                // Timeout occurred
+                log::warn!(
+                    "[Gemini] Process {} did not exit within {}s after streams closed, assuming hung - forcing completion",
+                    session_id_complete,
+                    timeout_duration.as_secs()
+                );
+                // Try to kill the hung process
+                let mut processes = processes_complete.lock().await;
+                if let Some(mut handle) = processes.remove(&session_id_complete) {
+                    if let Err(e) = handle.child.kill().await {
+                        log::error!("[Gemini] Failed to kill hung process: {}", e);
+                    }
+                    // JobObject is dropped here, killing all child processes
+                    log::info!(
+                        "[Gemini] Force-dropped JobObject for hung process PID: {}",
+                        handle.pid
+                    );
+                }
+                (false, None)
+            }
+        };
+
+        // A run killed by its time limits failed, whatever the exit status says
+        let stop_reason = activity.timed_out().map(|reason| reason.stop_reason());
+        let success = success && stop_reason.is_none();
+
+        // Emit completion event
+        let complete_payload = serde_json::json!({
+            "type": "result",
+            "status": if success { "success" } else { "error" },
+            "geminiMetadata": {
+                "provider": "gemini",
+                "eventType": "complete",
+                "exitCode": exit_code,
+                "stopReason": stop_reason
+            }
+        });
+
+        let complete_line = serde_json::to_string(&complete_payload).unwrap_or_default();
+
+        let _ = app_handle_complete.emit(
+            &format!("gemini-output:{}", session_id_complete),
+            &complete_line,
+        );
+        let _ = app_handle_complete.emit("gemini-output", &complete_line);
+
+        let _ =
+            app_handle_complete.emit(&format!("gemini-complete:{}", session_id_complete), success);
+        let _ = app_handle_complete.emit("gemini-complete", success);
+    });
+
+    Ok(())
+}
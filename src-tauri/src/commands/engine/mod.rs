@@ -0,0 +1,250 @@
+//! Unified Engine Abstraction
+//!
+//! One `Engine` trait in front of Claude, Codex and Gemini, so cross-engine
+//! features (queues, budgets, hooks, headless CLI) can be written once against
+//! the trait instead of three times. The Codex and Gemini runners live on
+//! their engines (`codex.rs`, `gemini.rs`); Claude's runner stays in
+//! `claude/cli_runner.rs`. Every engine tracks its children in an
+//! `EngineProcessState` (`process.rs`).
+//!
+//! ## Contract
+//!
+//! - `spawn` / `resume` / `cancel` (`EngineRunner`) drive the CLI process and
+//!   keep emitting the engine's existing frontend events (`claude-output`,
+//!   `codex-output`, ...). They are generic over the Tauri runtime, so the
+//!   headless CLI and the engine harness run them on a windowless app.
+//! - `parse_line` turns one raw stdout line into the unified ClaudeStreamMessage
+//!   shape the frontend already renders.
+//! - `extract_session_id` pulls the CLI-side session ID out of a unified message.
+
+pub mod claude;
+pub mod codex;
+pub mod gemini;
+pub mod process;
+pub mod run;
+pub mod timeout;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, Runtime};
+
+pub use claude::ClaudeEngine;
+pub use codex::CodexEngine;
+pub use gemini::GeminiEngine;
+pub use process::{EngineProcessHandle, EngineProcessState};
+pub use run::{spawn_watched, unified_message};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Supported execution engines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    Claude,
+    Codex,
+    Gemini,
+}
+
+impl EngineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineKind::Claude => "claude",
+            EngineKind::Codex => "codex",
+            EngineKind::Gemini => "gemini",
+        }
+    }
+}
+
+impl std::fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for EngineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "claude" => Ok(EngineKind::Claude),
+            "codex" => Ok(EngineKind::Codex),
+            "gemini" => Ok(EngineKind::Gemini),
+            other => Err(format!("Unknown engine: {}", other)),
+        }
+    }
+}
+
+/// Engine-agnostic execution request
+///
+/// Common fields are shared by every engine; the engine-specific knobs are
+/// optional and ignored by engines that don't understand them.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineRequest {
+    /// Project/working directory path
+    pub project_path: String,
+
+    /// User prompt
+    pub prompt: String,
+
+    /// Model to use (engine default when omitted)
+    pub model: Option<String>,
+
+    /// Frontend tab that owns this run (Claude global events carry it)
+    pub tab_id: Option<String>,
+
+    /// Claude: use the native plan permission mode
+    #[serde(default)]
+    pub plan_mode: bool,
+
+    /// Claude: maxThinkingTokens override
+    pub max_thinking_tokens: Option<u32>,
+
+    /// Codex: execution mode ("read-only" | "full-auto" | "danger-full-access")
+    pub codex_mode: Option<super::codex::CodexExecutionMode>,
+
+    /// Gemini: approval mode ("default" | "auto_edit" | "yolo")
+    pub approval_mode: Option<String>,
+
+    /// Run a new session in its own git worktree and branch (see session_worktree.rs)
+    #[serde(default)]
+    pub worktree_isolation: bool,
+}
+
+// ============================================================================
+// Engine Trait
+// ============================================================================
+
+/// Common interface implemented by every CLI engine
+pub trait Engine: Send + Sync {
+    /// Which engine this is
+    fn kind(&self) -> EngineKind;
+
+    /// Convert a raw stdout line into a unified message (None = skip the line)
+    fn parse_line(&self, line: &str) -> Option<Value>;
+
+    /// Extract the CLI session ID from a unified message, if it carries one
+    fn extract_session_id(&self, message: &Value) -> Option<String> {
+        if message.get("type").and_then(|t| t.as_str()) == Some("system")
+            && message.get("subtype").and_then(|t| t.as_str()) == Some("init")
+        {
+            return message
+                .get("session_id")
+                .and_then(|s| s.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from);
+        }
+        None
+    }
+}
+
+/// Process side of an engine, on any Tauri runtime
+#[async_trait]
+pub trait EngineRunner<R: Runtime>: Engine {
+    /// Start a new session
+    async fn spawn(&self, app: &AppHandle<R>, request: EngineRequest) -> Result<(), String>;
+
+    /// Resume an existing CLI session by its ID
+    async fn resume(
+        &self,
+        app: &AppHandle<R>,
+        session_id: &str,
+        request: EngineRequest,
+    ) -> Result<(), String>;
+
+    /// Cancel one session, or every session of this engine when `session_id` is None
+    async fn cancel(&self, app: &AppHandle<R>, session_id: Option<String>) -> Result<(), String>;
+}
+
+/// Returns the runner of an engine for the app's runtime
+pub fn runner_for<R: Runtime>(kind: EngineKind) -> Arc<dyn EngineRunner<R>> {
+    match kind {
+        EngineKind::Claude => Arc::new(ClaudeEngine),
+        EngineKind::Codex => Arc::new(CodexEngine),
+        EngineKind::Gemini => Arc::new(GeminiEngine),
+    }
+}
+
+/// Terminate every engine process tree, MCP servers included (app exit)
+///
+/// The registry's Drop cleanup is not guaranteed to run when Tauri exits the
+/// process, and the per-engine process states have no such hook at all.
+/// Everything is terminated concurrently, so exit waits for one grace period
+/// rather than one per process.
+pub async fn terminate_all_processes<R: Runtime>(app: &AppHandle<R>) {
+    let mut handles = Vec::new();
+    if let Some(state) = app.try_state::<super::claude::ClaudeProcessState>() {
+        handles.extend(state.take(None).await.into_iter().map(|h| ("Claude", h)));
+    }
+    if let Some(state) = app.try_state::<super::codex::CodexProcessState>() {
+        handles.extend(state.take(None).await.into_iter().map(|h| ("Codex", h)));
+    }
+    if let Some(state) = app.try_state::<super::gemini::GeminiProcessState>() {
+        handles.extend(state.take(None).await.into_iter().map(|h| ("Gemini", h)));
+    }
+    let engine_processes = futures::future::join_all(
+        handles
+            .into_iter()
+            .map(|(label, (sid, handle))| async move { handle.terminate(label, &sid).await }),
+    );
+
+    let registry_processes = async {
+        if let Some(registry) = app.try_state::<crate::process::ProcessRegistryState>() {
+            if let Err(e) = registry.0.kill_all_processes().await {
+                log::warn!("[Engine] Failed to kill Claude processes on exit: {}", e);
+            }
+        }
+    };
+
+    futures::future::join(engine_processes, registry_processes).await;
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Start a session on any engine
+#[tauri::command]
+pub async fn engine_execute<R: Runtime>(
+    app: AppHandle<R>,
+    engine: EngineKind,
+    mut request: EngineRequest,
+) -> Result<(), String> {
+    super::session_worktree::prepare_request(engine, &mut request)?;
+    let runner = runner_for::<R>(engine);
+    log::info!(
+        "[Engine] execute on {}: project_path={}, model={:?}, prompt_len={}",
+        runner.kind(),
+        request.project_path,
+        request.model,
+        request.prompt.len()
+    );
+    runner.spawn(&app, request).await
+}
+
+/// Resume a session on any engine
+#[tauri::command]
+pub async fn engine_resume<R: Runtime>(
+    app: AppHandle<R>,
+    engine: EngineKind,
+    session_id: String,
+    request: EngineRequest,
+) -> Result<(), String> {
+    log::info!("[Engine] resume {} session: {}", engine, session_id);
+    runner_for(engine).resume(&app, &session_id, request).await
+}
+
+/// Cancel a session on any engine
+#[tauri::command]
+pub async fn engine_cancel<R: Runtime>(
+    app: AppHandle<R>,
+    engine: EngineKind,
+    session_id: Option<String>,
+) -> Result<(), String> {
+    log::info!("[Engine] cancel {} session: {:?}", engine, session_id);
+    runner_for(engine).cancel(&app, session_id).await
+}
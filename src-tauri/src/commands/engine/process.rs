@@ -0,0 +1,219 @@
+//! Shared process bookkeeping for engines that own their child processes
+//!
+//! Claude, Codex and Gemini each wrap `EngineProcessState`, keeping separate
+//! Tauri-managed instances so their `last_session_id` never collides.
+//!
+//! Codex / Gemini runs are also entered in the ProcessRegistry
+//! (`RunRegistration`), so they are persisted for orphan recovery and sampled
+//! by the resource monitor. Claude registers itself once its init message
+//! names the session.
+
+use std::collections::HashMap;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+use super::EngineKind;
+use crate::commands::claude::kill_process_tree;
+use crate::process::{JobObject, ProcessRegistry, ProcessRegistryState, ProcessType};
+
+/// Assign a spawned process to a Job Object (Windows) / track its process group (Unix)
+///
+/// Call it right after spawn, so descendants the CLI starts later (MCP servers,
+/// detached node.exe) are covered and die with the job.
+pub fn attach_job_object(label: &str, pid: u32) -> Option<Arc<JobObject>> {
+    match JobObject::create() {
+        Ok(job) => match job.assign_process_by_pid(pid) {
+            Ok(_) => {
+                log::info!("[{}] Assigned PID {} to Job Object for cleanup", label, pid);
+                Some(Arc::new(job))
+            }
+            Err(e) => {
+                log::warn!(
+                    "[{}] Failed to assign PID {} to Job Object: {}",
+                    label,
+                    pid,
+                    e
+                );
+                None
+            }
+        },
+        Err(e) => {
+            log::warn!("[{}] Failed to create Job Object: {}", label, e);
+            None
+        }
+    }
+}
+
+/// Child process handle with PID for proper cleanup
+pub struct EngineProcessHandle {
+    pub child: Child,
+    pub pid: u32,
+    /// Job Object (Windows) / process group (Unix); kills all child processes when dropped.
+    /// Shared with the registry entry so kill_process reaches the whole tree too.
+    pub job_object: Option<Arc<JobObject>>,
+    /// Registry entry of this run; dropped (unregistered) together with the handle
+    pub registration: Option<RunRegistration>,
+}
+
+impl EngineProcessHandle {
+    /// Enter this run in the ProcessRegistry under its backend session ID
+    pub fn register<R: Runtime>(
+        &mut self,
+        app: &AppHandle<R>,
+        engine: EngineKind,
+        session_id: &str,
+        project_path: &str,
+        task: &str,
+        model: &str,
+    ) {
+        let Some(registry) = app.try_state::<ProcessRegistryState>() else {
+            return;
+        };
+        let registry = registry.0.clone();
+        match registry.register_session(
+            ProcessType::EngineSession {
+                engine,
+                session_id: session_id.to_string(),
+            },
+            self.pid,
+            project_path.to_string(),
+            task.to_string(),
+            model.to_string(),
+            self.job_object.clone(),
+        ) {
+            Ok(run_id) => {
+                log::info!(
+                    "[{}] Registered session {} as run {} (PID: {})",
+                    engine,
+                    session_id,
+                    run_id,
+                    self.pid
+                );
+                self.registration = Some(RunRegistration { registry, run_id });
+            }
+            Err(e) => log::warn!("[{}] Failed to register PID {}: {}", engine, self.pid, e),
+        }
+    }
+
+    /// Kill the whole process tree, falling back to killing the direct child
+    pub async fn terminate(mut self, label: &str, session_id: &str) {
+        let pid = self.pid;
+        log::info!(
+            "[{}] Killing process tree for session: {} (PID: {})",
+            label,
+            session_id,
+            pid
+        );
+
+        let mut terminated_via_job = false;
+        if let Some(job) = self.job_object.clone() {
+            match job.terminate_all_async(1).await {
+                Ok(_) => terminated_via_job = true,
+                Err(e) => log::warn!("[{}] Failed to terminate Job Object: {}", label, e),
+            }
+        }
+
+        if !terminated_via_job {
+            if let Err(e) = kill_process_tree(pid) {
+                log::error!(
+                    "[{}] Failed to kill process tree for session {}: {}",
+                    label,
+                    session_id,
+                    e
+                );
+                if let Err(e2) = self.child.kill().await {
+                    log::error!("[{}] Fallback kill also failed: {}", label, e2);
+                }
+            }
+        }
+
+        // Release the registry's share first, so the JobObject is really dropped here,
+        // killing any remaining descendants (MCP servers, node.exe, etc.)
+        drop(self.registration);
+        drop(self.job_object);
+    }
+}
+
+/// ProcessRegistry entry of a Codex / Gemini run, removed again on drop
+pub struct RunRegistration {
+    registry: Arc<ProcessRegistry>,
+    run_id: i64,
+}
+
+impl Drop for RunRegistration {
+    fn drop(&mut self) {
+        if let Err(e) = self.registry.unregister_process(self.run_id) {
+            log::warn!("Failed to unregister run {}: {}", self.run_id, e);
+        }
+    }
+}
+
+/// Per-engine map of running processes keyed by backend session ID
+#[derive(Default)]
+pub struct EngineProcessState {
+    pub processes: Arc<Mutex<HashMap<String, EngineProcessHandle>>>,
+    pub last_session_id: Arc<Mutex<Option<String>>>,
+}
+
+impl EngineProcessState {
+    /// Point the registry entry of a running session at the ID the CLI reported
+    pub async fn set_cli_session_id(&self, session_id: &str, cli_session_id: &str) {
+        let processes = self.processes.lock().await;
+        if let Some(registration) = processes
+            .get(session_id)
+            .and_then(|h| h.registration.as_ref())
+        {
+            if let Err(e) = registration
+                .registry
+                .set_session_id(registration.run_id, cli_session_id)
+            {
+                log::warn!("Failed to update run {}: {}", registration.run_id, e);
+            }
+        }
+    }
+
+    /// Track a freshly spawned process and mark it as the latest session
+    pub async fn insert(&self, session_id: String, handle: EngineProcessHandle) {
+        self.processes
+            .lock()
+            .await
+            .insert(session_id.clone(), handle);
+        *self.last_session_id.lock().await = Some(session_id);
+    }
+
+    /// Remove one session (or every session when None) and hand the handles back
+    pub async fn take(&self, session_id: Option<&str>) -> Vec<(String, EngineProcessHandle)> {
+        let mut processes = self.processes.lock().await;
+        match session_id {
+            Some(sid) => processes
+                .remove(sid)
+                .map(|handle| vec![(sid.to_string(), handle)])
+                .unwrap_or_default(),
+            None => processes.drain().collect(),
+        }
+    }
+
+    /// Wait for a tracked process to exit, then drop its handle
+    ///
+    /// Returns None when the handle was taken (cancelled) before it exited.
+    pub async fn wait(&self, session_id: &str) -> Option<std::io::Result<ExitStatus>> {
+        loop {
+            {
+                let mut processes = self.processes.lock().await;
+                let handle = processes.get_mut(session_id)?;
+                match handle.child.try_wait() {
+                    Ok(None) => {}
+                    result => {
+                        processes.remove(session_id);
+                        return result.transpose();
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Run tracking across engines
+//!
+//! `Engine::spawn` returns as soon as the CLI process is started. Callers that
+//! need to know when a run ends (prompt queue, fan-out) use `spawn_watched`,
+//! which registers the completion listeners before spawning so a fast exit
+//! can't be missed:
+//!
+//! - Claude: global `claude-output` / `claude-complete` filtered by `tab_id`
+//! - Codex / Gemini: the backend session ID announced in `{engine}-session-init`
+//!   (emitted while the spawn call is still running), then
+//!   `{engine}-output:{id}` / `{engine}-complete:{id}`
+
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, EventId, Listener, Runtime};
+use tokio::sync::oneshot;
+
+use super::{runner_for, CodexEngine, Engine, EngineKind, EngineRequest};
+use crate::commands::claude::extract_init_session_id;
+
+type Slot<T> = Arc<Mutex<Option<T>>>;
+
+/// Codex/Gemini spawns are serialized so each session-init maps to one run
+static SPAWN_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+/// Listeners for one run; unregistered on drop
+pub struct RunWatch<R: Runtime> {
+    app: AppHandle<R>,
+    listeners: Arc<Mutex<Vec<EventId>>>,
+    done: oneshot::Receiver<bool>,
+    session_id: Slot<String>,
+    output: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+impl<R: Runtime> RunWatch<R> {
+    /// Wait for the run to finish; true when the CLI reported success
+    pub async fn wait(&mut self) -> bool {
+        (&mut self.done).await.unwrap_or(false)
+    }
+
+    /// CLI session ID (Claude) or backend session ID (Codex/Gemini), once known
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    /// Raw output lines collected so far (empty unless collection was requested)
+    pub fn take_output(&self) -> Vec<String> {
+        self.output
+            .as_ref()
+            .map(|lines| std::mem::take(&mut *lines.lock().unwrap()))
+            .unwrap_or_default()
+    }
+}
+
+impl<R: Runtime> Drop for RunWatch<R> {
+    fn drop(&mut self) {
+        for id in self.listeners.lock().unwrap().drain(..) {
+            self.app.unlisten(id);
+        }
+    }
+}
+
+/// Event payloads are JSON-encoded; output events carry the line as a string
+fn payload_string(payload: &str) -> String {
+    serde_json::from_str::<String>(payload).unwrap_or_else(|_| payload.to_string())
+}
+
+/// Turn one collected output line into a unified message
+///
+/// Claude and Gemini output events already carry unified messages; Codex
+/// events carry the raw JSONL line.
+pub fn unified_message(engine: EngineKind, line: &str) -> Option<Value> {
+    match engine {
+        EngineKind::Codex => CodexEngine.parse_line(line),
+        _ => serde_json::from_str::<Value>(line).ok(),
+    }
+}
+
+fn watch_run<R: Runtime>(
+    app: &AppHandle<R>,
+    engine: EngineKind,
+    tab_id: &str,
+    collect_output: bool,
+) -> RunWatch<R> {
+    let (done_tx, done) = oneshot::channel::<bool>();
+    let done_tx: Slot<oneshot::Sender<bool>> = Arc::new(Mutex::new(Some(done_tx)));
+    let session_id: Slot<String> = Arc::new(Mutex::new(None));
+    let output = collect_output.then(|| Arc::new(Mutex::new(Vec::new())));
+    let listeners = Arc::new(Mutex::new(Vec::new()));
+
+    match engine {
+        EngineKind::Claude => {
+            let tab = tab_id.to_string();
+            let sid_slot = session_id.clone();
+            let output_lines = output.clone();
+            let id = app.listen_any("claude-output", move |event| {
+                let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+                    return;
+                };
+                if payload.get("tab_id").and_then(|t| t.as_str()) != Some(tab.as_str()) {
+                    return;
+                }
+                let Some(line) = payload.get("payload").and_then(|l| l.as_str()) else {
+                    return;
+                };
+                {
+                    let mut slot = sid_slot.lock().unwrap();
+                    if slot.is_none() {
+                        *slot = extract_init_session_id(line);
+                    }
+                }
+                if let Some(lines) = &output_lines {
+                    lines.lock().unwrap().push(line.to_string());
+                }
+            });
+            listeners.lock().unwrap().push(id);
+
+            let tab = tab_id.to_string();
+            let tx = done_tx.clone();
+            let id = app.listen_any("claude-complete", move |event| {
+                let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+                    return;
+                };
+                if payload.get("tab_id").and_then(|t| t.as_str()) == Some(tab.as_str()) {
+                    let success = payload.get("payload").and_then(|p| p.as_bool());
+                    if let Some(tx) = tx.lock().unwrap().take() {
+                        let _ = tx.send(success.unwrap_or(false));
+                    }
+                }
+            });
+            listeners.lock().unwrap().push(id);
+        }
+        EngineKind::Codex | EngineKind::Gemini => {
+            let app_for_init = app.clone();
+            let sid_slot = session_id.clone();
+            let tx = done_tx.clone();
+            let listeners_for_init = listeners.clone();
+            let output_lines = output.clone();
+            let id = app.listen_any(format!("{}-session-init", engine), move |event| {
+                let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+                    return;
+                };
+                let Some(sid) = payload.get("session_id").and_then(|s| s.as_str()) else {
+                    return;
+                };
+                {
+                    let mut slot = sid_slot.lock().unwrap();
+                    if slot.is_some() {
+                        return;
+                    }
+                    *slot = Some(sid.to_string());
+                }
+
+                // Listening from inside a handler is queued by Tauri and active before the next emit
+                let mut ids = listeners_for_init.lock().unwrap();
+                if let Some(lines) = output_lines.clone() {
+                    ids.push(app_for_init.listen_any(
+                        format!("{}-output:{}", engine, sid),
+                        move |event| {
+                            lines.lock().unwrap().push(payload_string(event.payload()));
+                        },
+                    ));
+                }
+                let tx = tx.clone();
+                ids.push(app_for_init.once_any(
+                    format!("{}-complete:{}", engine, sid),
+                    move |event| {
+                        let success = event.payload().trim() == "true";
+                        if let Some(tx) = tx.lock().unwrap().take() {
+                            let _ = tx.send(success);
+                        }
+                    },
+                ));
+            });
+            listeners.lock().unwrap().push(id);
+        }
+    }
+
+    RunWatch {
+        app: app.clone(),
+        listeners,
+        done,
+        session_id,
+        output,
+    }
+}
+
+/// Start (or resume) a run and return a watch that resolves when it ends
+///
+/// Claude runs without a `tab_id` get a generated one so their global events
+/// can be told apart.
+pub async fn spawn_watched<R: Runtime>(
+    app: &AppHandle<R>,
+    engine: EngineKind,
+    mut request: EngineRequest,
+    resume_session_id: Option<&str>,
+    collect_output: bool,
+) -> Result<RunWatch<R>, String> {
+    let tab_id = request
+        .tab_id
+        .get_or_insert_with(|| format!("run-{}", uuid::Uuid::new_v4()))
+        .clone();
+    if resume_session_id.is_none() {
+        crate::commands::session_worktree::prepare_request(engine, &mut request)?;
+    }
+    let runner = runner_for::<R>(engine);
+
+    let _spawn_guard = SPAWN_LOCK.lock().await;
+    let watch = watch_run(app, engine, &tab_id, collect_output);
+    match resume_session_id {
+        Some(sid) => runner.resume(app, sid, request).await?,
+        None => runner.spawn(app, request).await?,
+    }
+    Ok(watch)
+}
@@ -0,0 +1,369 @@
+//! Wall-clock and inactivity limits for engine runs
+//!
+//! Every CLI run (Claude, Codex, Gemini) gets a supervisor task next to its
+//! stdout reader. The reader calls `RunActivity::touch` for each line; the
+//! supervisor kills the process tree once the run exceeds its maximum
+//! duration or goes too long without a stdout line. The reason is recorded
+//! on the run's registry entry (found by PID) and, once the CLI announced a
+//! session ID, on the prompt's git record; a `session-timeout` event is
+//! emitted either way.
+//!
+//! - Config: ~/.anycode/run_timeouts.json
+//! - Claude's `timeout_seconds` (execution config) overrides the wall-clock
+//!   limit for Claude runs
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use super::EngineKind;
+use crate::commands::claude::encode_project_path;
+use crate::commands::{codex, gemini, prompt_tracker};
+use crate::process::ProcessRegistryState;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// How often the supervisor compares a run against its limits
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Both limits are opt-in: long silent steps (builds, test suites) are normal
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunTimeoutConfig {
+    /// Maximum run time; None = unlimited
+    #[serde(default)]
+    pub max_run_minutes: Option<u64>,
+    /// Kill a run after this long without a stdout line; None = never
+    #[serde(default)]
+    pub inactivity_minutes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutReason {
+    WallClock,
+    Inactivity,
+}
+
+impl TimeoutReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeoutReason::WallClock => "wall_clock",
+            TimeoutReason::Inactivity => "inactivity",
+        }
+    }
+
+    /// Value stored as `stop_reason` in the registry and git records
+    pub fn stop_reason(&self) -> String {
+        format!("{}_timeout", self.as_str())
+    }
+}
+
+/// Effective limits of one run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunLimits {
+    pub max_duration: Option<Duration>,
+    pub inactivity: Option<Duration>,
+}
+
+impl RunLimits {
+    pub fn for_engine(engine: EngineKind) -> Self {
+        let config = load_config();
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let mut limits = Self {
+            max_duration: config.max_run_minutes.filter(|m| *m > 0).map(minutes),
+            inactivity: config.inactivity_minutes.filter(|m| *m > 0).map(minutes),
+        };
+
+        if engine == EngineKind::Claude {
+            if let Some(seconds) = prompt_tracker::load_execution_config()
+                .ok()
+                .and_then(|c| c.timeout_seconds)
+                .filter(|s| *s > 0)
+            {
+                limits.max_duration = Some(Duration::from_secs(seconds as u64));
+            }
+        }
+        limits
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_duration.is_none() && self.inactivity.is_none()
+    }
+
+    fn limit(&self, reason: TimeoutReason) -> Option<Duration> {
+        match reason {
+            TimeoutReason::WallClock => self.max_duration,
+            TimeoutReason::Inactivity => self.inactivity,
+        }
+    }
+}
+
+/// Payload of `session-timeout`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTimeoutEvent {
+    pub engine: EngineKind,
+    /// CLI session ID, when the CLI announced one before the timeout
+    pub session_id: Option<String>,
+    pub project_path: String,
+    pub reason: TimeoutReason,
+    pub limit_secs: u64,
+    pub elapsed_secs: u64,
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("run_timeouts.json"))
+}
+
+fn load_config() -> RunTimeoutConfig {
+    get_config_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[Timeout] Failed to load config, using defaults: {}", e);
+            RunTimeoutConfig::default()
+        })
+}
+
+// ============================================================================
+// Run activity
+// ============================================================================
+
+struct ActivityState {
+    started: Instant,
+    last_output: Mutex<Instant>,
+    session_id: Mutex<Option<String>>,
+    finished: AtomicBool,
+    timed_out: Mutex<Option<TimeoutReason>>,
+}
+
+/// Shared between a run's stdout reader and its supervisor
+#[derive(Clone)]
+pub struct RunActivity(Arc<ActivityState>);
+
+impl Default for RunActivity {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self(Arc::new(ActivityState {
+            started: now,
+            last_output: Mutex::new(now),
+            session_id: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            timed_out: Mutex::new(None),
+        }))
+    }
+}
+
+impl RunActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A stdout line arrived
+    pub fn touch(&self) {
+        *self.0.last_output.lock().unwrap() = Instant::now();
+    }
+
+    /// Remember the CLI session ID once the CLI announces it
+    pub fn set_session_id(&self, session_id: &str) {
+        *self.0.session_id.lock().unwrap() = Some(session_id.to_string());
+    }
+
+    /// The run ended on its own; stops the supervisor
+    pub fn finish(&self) {
+        self.0.finished.store(true, Ordering::Relaxed);
+    }
+
+    /// Set when the supervisor killed the run
+    pub fn timed_out(&self) -> Option<TimeoutReason> {
+        *self.0.timed_out.lock().unwrap()
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.0.session_id.lock().unwrap().clone()
+    }
+
+    fn breached(&self, limits: &RunLimits) -> Option<TimeoutReason> {
+        let elapsed = self.0.started.elapsed();
+        let idle = self.0.last_output.lock().unwrap().elapsed();
+        if limits.max_duration.is_some_and(|max| elapsed >= max) {
+            Some(TimeoutReason::WallClock)
+        } else if limits.inactivity.is_some_and(|max| idle >= max) {
+            Some(TimeoutReason::Inactivity)
+        } else {
+            None
+        }
+    }
+}
+
+// ============================================================================
+// Supervisor
+// ============================================================================
+
+/// Watch a run and call `kill` once it exceeds the engine's limits
+///
+/// Returns immediately when no limit applies.
+pub fn supervise<R, F, Fut>(
+    app: &AppHandle<R>,
+    engine: EngineKind,
+    pid: u32,
+    project_path: String,
+    activity: RunActivity,
+    kill: F,
+) where
+    R: Runtime,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let limits = RunLimits::for_engine(engine);
+    if limits.is_unlimited() {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let reason = loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            if activity.0.finished.load(Ordering::Relaxed) {
+                return;
+            }
+            if let Some(reason) = activity.breached(&limits) {
+                break reason;
+            }
+        };
+        *activity.0.timed_out.lock().unwrap() = Some(reason);
+
+        let session_id = activity.session_id();
+        let event = SessionTimeoutEvent {
+            engine,
+            session_id: session_id.clone(),
+            project_path,
+            reason,
+            limit_secs: limits.limit(reason).unwrap_or_default().as_secs(),
+            elapsed_secs: activity.0.started.elapsed().as_secs(),
+        };
+        log::warn!(
+            "[Timeout] Stopping {} run {:?} after {}s: {} limit of {}s reached",
+            engine,
+            session_id,
+            event.elapsed_secs,
+            reason.as_str(),
+            event.limit_secs
+        );
+
+        // Recorded before the kill: the registry entry goes away with the process
+        record_stop_reason(
+            &app,
+            engine,
+            pid,
+            session_id.as_deref(),
+            &event.project_path,
+            reason,
+        );
+        kill().await;
+
+        if let Err(e) = app.emit("session-timeout", &event) {
+            log::warn!("[Timeout] Failed to emit session-timeout: {}", e);
+        }
+    });
+}
+
+fn record_stop_reason<R: Runtime>(
+    app: &AppHandle<R>,
+    engine: EngineKind,
+    pid: u32,
+    session_id: Option<&str>,
+    project_path: &str,
+    reason: TimeoutReason,
+) {
+    let stop_reason = reason.stop_reason();
+
+    // Claude runs are registered once the CLI announces its session; Codex and
+    // Gemini runs right after the spawn
+    if let Some(registry) = app.try_state::<ProcessRegistryState>() {
+        match registry.0.set_stop_reason_by_pid(pid, &stop_reason) {
+            Ok(true) => {}
+            Ok(false) => log::debug!("[Timeout] No registry entry for PID {}", pid),
+            Err(e) => log::warn!("[Timeout] Failed to annotate PID {}: {}", pid, e),
+        }
+    }
+
+    // The git record is keyed by the CLI session ID
+    let Some(session_id) = session_id else {
+        return;
+    };
+    let recorded = match engine {
+        EngineKind::Claude => {
+            let project_id = encode_project_path(project_path);
+            prompt_tracker::record_stop_reason(session_id, &project_id, &stop_reason)
+                .map_err(|e| e.to_string())
+        }
+        EngineKind::Codex => codex::git_ops::record_codex_stop_reason(session_id, &stop_reason),
+        EngineKind::Gemini => gemini::git_ops::record_gemini_stop_reason(session_id, &stop_reason),
+    };
+
+    match recorded {
+        Ok(true) => {}
+        Ok(false) => log::debug!("[Timeout] No git record to annotate for {}", session_id),
+        Err(e) => log::warn!("[Timeout] Failed to record stop reason: {}", e),
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_run_timeout_config() -> Result<RunTimeoutConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_run_timeout_config(
+    config: RunTimeoutConfig,
+) -> Result<RunTimeoutConfig, String> {
+    save_json_config(&config, get_config_path()?)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_clock_wins_over_inactivity() {
+        let activity = RunActivity::new();
+        let hour = Duration::from_secs(3600);
+
+        let idle = RunLimits {
+            max_duration: Some(hour),
+            inactivity: Some(Duration::ZERO),
+        };
+        assert_eq!(activity.breached(&idle), Some(TimeoutReason::Inactivity));
+
+        let both = RunLimits {
+            max_duration: Some(Duration::ZERO),
+            inactivity: Some(Duration::ZERO),
+        };
+        assert_eq!(activity.breached(&both), Some(TimeoutReason::WallClock));
+
+        activity.touch();
+        let generous = RunLimits {
+            max_duration: None,
+            inactivity: Some(hour),
+        };
+        assert_eq!(activity.breached(&generous), None);
+        assert_eq!(
+            TimeoutReason::Inactivity.stop_reason(),
+            "inactivity_timeout"
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! Multi-Engine Fan-out
+//!
+//! Sends one prompt to Claude, Codex and Gemini at the same time, each in its
+//! own `git worktree` created from the project's current HEAD, and collects a
+//! side-by-side comparison (diff stats, cost, final message).
+//!
+//! Nothing touches the project's own working tree until `merge_fan_out_run`
+//! is called for the winning engine; the project must already be a git
+//! repository with at least one commit.
+//!
+//! Layout:
+//! - Worktrees: ~/.anycode/worktrees/{project}-{fan_out_id}-{engine}
+//! - Branches:  anycode/fanout-{fan_out_id}-{engine}
+//! - Results:   ~/.anycode/fan-outs/{fan_out_id}.json
+
+use chrono::{DateTime, Local, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use super::codex::CodexExecutionMode;
+use super::engine::{spawn_watched, unified_message, EngineKind, EngineRequest};
+use super::git_stats::{get_git_diff_stats, GitDiffStats};
+use super::pricing::{self, TokenCounts};
+use super::simple_git;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Outcome of one engine's run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FanOutRun {
+    pub engine: EngineKind,
+    pub branch: String,
+    pub worktree_path: String,
+    pub session_id: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Last assistant text of the run
+    pub final_message: Option<String>,
+    pub model: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    /// Changes relative to the fan-out base commit
+    pub diff_stats: Option<GitDiffStats>,
+    pub duration_ms: u64,
+}
+
+/// Comparison of every engine's run for one prompt
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FanOutResult {
+    pub id: String,
+    pub project_path: String,
+    pub prompt: String,
+    pub base_commit: String,
+    /// Branch checked out in the project when the fan-out was created
+    pub source_branch: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub runs: Vec<FanOutRun>,
+    /// Engine whose branch was merged, once `merge_fan_out_run` succeeds
+    pub merged_engine: Option<EngineKind>,
+}
+
+fn get_anycode_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode"))
+}
+
+fn get_result_path(fan_out_id: &str) -> Result<PathBuf, String> {
+    Ok(get_anycode_dir()?
+        .join("fan-outs")
+        .join(format!("{}.json", fan_out_id)))
+}
+
+fn load_result(fan_out_id: &str) -> Result<FanOutResult, String> {
+    let result: FanOutResult = load_json_config(get_result_path(fan_out_id)?)?;
+    if result.id.is_empty() {
+        return Err(format!("Fan-out not found: {}", fan_out_id));
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Output Summary
+// ============================================================================
+
+#[derive(Default)]
+struct RunSummary {
+    final_message: Option<String>,
+    model: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cached_tokens: u64,
+    /// Claude only
+    cache_creation_tokens: u64,
+    /// Claude reports the cost itself
+    reported_cost: Option<f64>,
+}
+
+fn summarize_output(engine: EngineKind, lines: &[String]) -> RunSummary {
+    let mut summary = RunSummary::default();
+
+    for line in lines {
+        let Some(message) = unified_message(engine, line) else {
+            continue;
+        };
+
+        if summary.model.is_none() {
+            summary.model = message
+                .get("model")
+                .and_then(|m| m.as_str())
+                .filter(|m| !m.is_empty())
+                .map(String::from);
+        }
+
+        match message.get("type").and_then(|t| t.as_str()) {
+            Some("assistant") => {
+                let text: String = message
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+                    .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if !text.trim().is_empty() {
+                    summary.final_message = Some(text);
+                }
+            }
+            Some("result") => {
+                if let Some(cost) = message.get("total_cost_usd").and_then(|c| c.as_f64()) {
+                    summary.reported_cost = Some(summary.reported_cost.unwrap_or(0.0) + cost);
+                }
+                if let Some(usage) = message.get("usage") {
+                    let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                    summary.input_tokens += get("input_tokens");
+                    summary.output_tokens += get("output_tokens");
+                    // Codex: cached_input_tokens; Claude: cache_read_input_tokens
+                    summary.cached_tokens +=
+                        get("cached_input_tokens") + get("cache_read_input_tokens");
+                    summary.cache_creation_tokens += get("cache_creation_input_tokens");
+                }
+                if summary.final_message.is_none() {
+                    summary.final_message = message
+                        .get("result")
+                        .and_then(|r| r.as_str())
+                        .map(String::from);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    summary
+}
+
+fn estimate_cost(engine: EngineKind, summary: &RunSummary) -> f64 {
+    if let Some(cost) = summary.reported_cost {
+        return cost;
+    }
+    let model = summary.model.as_deref().unwrap_or_default();
+    let today = Local::now().date_naive();
+    match engine {
+        // Providers behind some proxies leave out total_cost_usd
+        EngineKind::Claude => {
+            let tokens = TokenCounts {
+                input: summary.input_tokens,
+                output: summary.output_tokens,
+                cache_write: summary.cache_creation_tokens,
+                cache_read: summary.cached_tokens,
+            };
+            pricing::cost(EngineKind::Claude, model, &tokens, today).unwrap_or(0.0)
+        }
+        EngineKind::Codex => super::codex::usage::calculate_cost(
+            model,
+            summary.input_tokens,
+            summary.output_tokens,
+            summary.cached_tokens,
+            today,
+        ),
+        EngineKind::Gemini => super::gemini::usage::calculate_cost(
+            model,
+            summary.input_tokens,
+            summary.output_tokens,
+            today,
+        ),
+    }
+}
+
+// ============================================================================
+// Fan-out
+// ============================================================================
+
+/// HEAD of the project the worktrees are created from
+///
+/// Unlike the rewind paths this never runs `git init` or commits: the user's
+/// checkout is only read here.
+fn base_commit(project_path: &str) -> Result<String, String> {
+    if !simple_git::is_git_repo(project_path) {
+        return Err(format!(
+            "Fan-out needs a git repository, {} is not one",
+            project_path
+        ));
+    }
+    simple_git::git_current_commit(project_path)
+        .map_err(|e| format!("Fan-out needs a commit to start from: {}", e))
+}
+
+fn engine_request(base: &EngineRequest, engine: EngineKind, worktree_path: &str) -> EngineRequest {
+    let mut request = base.clone();
+    request.project_path = worktree_path.to_string();
+    // Each run gets its own tab so Claude's global events can be told apart
+    request.tab_id = None;
+    // Already isolated in the fan-out worktree
+    request.worktree_isolation = false;
+    // The worktree is disposable, so let the CLIs edit without prompting
+    if engine == EngineKind::Codex && request.codex_mode.is_none() {
+        request.codex_mode = Some(CodexExecutionMode::FullAuto);
+    }
+    if engine == EngineKind::Gemini && request.approval_mode.is_none() {
+        request.approval_mode = Some("auto_edit".to_string());
+    }
+    request
+}
+
+async fn run_in_worktree(
+    app: &AppHandle,
+    engine: EngineKind,
+    request: EngineRequest,
+    branch: String,
+    worktree_path: String,
+    base_commit: &str,
+) -> FanOutRun {
+    let started = std::time::Instant::now();
+    let mut run = FanOutRun {
+        engine,
+        branch,
+        worktree_path: worktree_path.clone(),
+        session_id: None,
+        success: false,
+        error: None,
+        final_message: None,
+        model: request.model.clone(),
+        input_tokens: 0,
+        output_tokens: 0,
+        cost_usd: 0.0,
+        diff_stats: None,
+        duration_ms: 0,
+    };
+
+    match spawn_watched(app, engine, request, None, true).await {
+        Ok(mut watch) => {
+            run.success = watch.wait().await;
+            run.session_id = watch.session_id();
+
+            let summary = summarize_output(engine, &watch.take_output());
+            run.cost_usd = estimate_cost(engine, &summary);
+            run.final_message = summary.final_message;
+            run.model = run.model.or(summary.model);
+            run.input_tokens = summary.input_tokens;
+            run.output_tokens = summary.output_tokens;
+            if !run.success {
+                run.error = Some(format!("{} exited with an error", engine));
+            }
+        }
+        Err(e) => run.error = Some(e),
+    }
+
+    // Commit inside the worktree so the branch holds the run's changes
+    let message = format!("[Any Code] fan-out: {}", engine);
+    if let Err(e) = simple_git::git_commit_changes(&worktree_path, &message) {
+        log::warn!("[FanOut] Failed to commit {} worktree: {}", engine, e);
+    }
+    run.diff_stats = get_git_diff_stats(worktree_path, base_commit.to_string(), None)
+        .await
+        .map_err(|e| log::warn!("[FanOut] Failed to get diff stats for {}: {}", engine, e))
+        .ok();
+
+    run.duration_ms = started.elapsed().as_millis() as u64;
+    run
+}
+
+/// Run one prompt on several engines in parallel, each in its own worktree
+///
+/// Resolves when every run has finished. Live output still flows through the
+/// usual `{engine}-output` events.
+#[tauri::command]
+pub async fn fan_out_prompt(
+    app: AppHandle,
+    request: EngineRequest,
+    engines: Option<Vec<EngineKind>>,
+    models: Option<HashMap<EngineKind, String>>,
+) -> Result<FanOutResult, String> {
+    let project_path = request.project_path.clone();
+    if request.prompt.trim().is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+    let engines = engines
+        .filter(|e| !e.is_empty())
+        .unwrap_or_else(|| vec![EngineKind::Claude, EngineKind::Codex, EngineKind::Gemini]);
+    let models = models.unwrap_or_default();
+
+    let base_commit = base_commit(&project_path)?;
+    let source_branch = simple_git::git_current_branch(&project_path).unwrap_or(None);
+
+    let fan_out_id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let project_name = Path::new(&project_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    let worktrees_dir = get_anycode_dir()?.join("worktrees");
+    std::fs::create_dir_all(&worktrees_dir)
+        .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+
+    log::info!(
+        "[FanOut] {}: {:?} on {} from {}",
+        fan_out_id,
+        engines,
+        project_path,
+        base_commit
+    );
+
+    let mut runs = Vec::new();
+    let mut created: Vec<(String, String)> = Vec::new();
+    for engine in &engines {
+        let branch = format!("anycode/fanout-{}-{}", fan_out_id, engine);
+        let worktree_path = worktrees_dir
+            .join(format!("{}-{}-{}", project_name, fan_out_id, engine))
+            .to_string_lossy()
+            .to_string();
+
+        if let Err(e) =
+            simple_git::git_worktree_add(&project_path, &worktree_path, &branch, &base_commit)
+        {
+            // Don't leave half a fan-out behind
+            for (path, branch) in &created {
+                let _ = simple_git::git_worktree_remove(&project_path, path);
+                let _ = simple_git::git_branch_delete(&project_path, branch);
+            }
+            return Err(e);
+        }
+        created.push((worktree_path.clone(), branch.clone()));
+
+        let mut engine_request = engine_request(&request, *engine, &worktree_path);
+        if let Some(model) = models.get(engine) {
+            engine_request.model = Some(model.clone());
+        }
+        runs.push(run_in_worktree(
+            &app,
+            *engine,
+            engine_request,
+            branch,
+            worktree_path,
+            &base_commit,
+        ));
+    }
+
+    let result = FanOutResult {
+        id: fan_out_id,
+        project_path,
+        prompt: request.prompt,
+        base_commit: base_commit.clone(),
+        source_branch,
+        created_at: Some(Utc::now()),
+        runs: join_all(runs).await,
+        merged_engine: None,
+    };
+    save_json_config(&result, get_result_path(&result.id)?)?;
+
+    let _ = app.emit("fan-out-complete", &result);
+    Ok(result)
+}
+
+/// Load a previous fan-out's comparison
+#[tauri::command]
+pub async fn get_fan_out_result(fan_out_id: String) -> Result<FanOutResult, String> {
+    load_result(&fan_out_id)
+}
+
+fn remove_worktrees(result: &FanOutResult) {
+    for run in &result.runs {
+        if let Err(e) = simple_git::git_worktree_remove(&result.project_path, &run.worktree_path) {
+            log::warn!(
+                "[FanOut] Failed to remove worktree {}: {}",
+                run.worktree_path,
+                e
+            );
+        }
+        if let Err(e) = simple_git::git_branch_delete(&result.project_path, &run.branch) {
+            log::warn!("[FanOut] Failed to delete branch {}: {}", run.branch, e);
+        }
+    }
+}
+
+/// Merge one engine's branch into the branch the fan-out was created from
+///
+/// The project must be clean and still on `source_branch`: a conflicting
+/// merge is aborted, which could lose uncommitted work there.
+fn merge_run(result: &FanOutResult, engine: EngineKind) -> Result<String, String> {
+    let run = result
+        .runs
+        .iter()
+        .find(|r| r.engine == engine)
+        .ok_or_else(|| format!("Fan-out {} has no {} run", result.id, engine))?;
+    simple_git::git_check_merge_target(&result.project_path, result.source_branch.as_deref())?;
+
+    let message = format!("[Any Code] Merge {} fan-out result ({})", engine, result.id);
+    simple_git::git_merge_branch(&result.project_path, &run.branch, &message)
+}
+
+/// Merge the chosen engine's branch into the project
+///
+/// Refused while the project has uncommitted changes or is on another branch
+/// than the one the fan-out started from. With `cleanup` (default) every
+/// worktree and branch of the fan-out is removed after a successful merge.
+/// Returns the merge commit.
+#[tauri::command]
+pub async fn merge_fan_out_run(
+    fan_out_id: String,
+    engine: EngineKind,
+    cleanup: Option<bool>,
+) -> Result<String, String> {
+    let mut result = load_result(&fan_out_id)?;
+    let commit = merge_run(&result, engine)?;
+    log::info!("[FanOut] Merged {} of {} as {}", engine, fan_out_id, commit);
+
+    result.merged_engine = Some(engine);
+    if cleanup.unwrap_or(true) {
+        remove_worktrees(&result);
+    }
+    save_json_config(&result, get_result_path(&fan_out_id)?)?;
+    Ok(commit)
+}
+
+/// Throw away every worktree and branch of a fan-out without merging
+#[tauri::command]
+pub async fn discard_fan_out(fan_out_id: String) -> Result<(), String> {
+    let result = load_result(&fan_out_id)?;
+    remove_worktrees(&result);
+    std::fs::remove_file(get_result_path(&fan_out_id)?)
+        .map_err(|e| format!("Failed to remove fan-out record: {}", e))?;
+    log::info!("[FanOut] Discarded {}", fan_out_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn lines(values: &[Value]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn claude_cost_falls_back_to_the_pricing_catalog() {
+        let result = serde_json::json!({
+            "type": "result",
+            "result": "Done.",
+            "usage": {
+                "input_tokens": 1000,
+                "output_tokens": 1000,
+                "cache_creation_input_tokens": 0,
+                "cache_read_input_tokens": 0
+            }
+        });
+        let init = serde_json::json!({
+            "type": "system",
+            "subtype": "init",
+            "model": "claude-sonnet-4-5-20250929"
+        });
+
+        let summary = summarize_output(EngineKind::Claude, &lines(&[init, result.clone()]));
+        assert_eq!(summary.final_message.as_deref(), Some("Done."));
+        // Sonnet 4.5: $3 / $15 per million tokens
+        let cost = estimate_cost(EngineKind::Claude, &summary);
+        assert!((cost - 0.018).abs() < 1e-9, "cost = {}", cost);
+
+        let mut reported = result;
+        reported["total_cost_usd"] = serde_json::json!(0.5);
+        let summary = summarize_output(EngineKind::Claude, &lines(&[reported]));
+        assert_eq!(estimate_cost(EngineKind::Claude, &summary), 0.5);
+    }
+
+    #[test]
+    fn codex_summary_reads_raw_events() {
+        let events = [
+            serde_json::json!({ "type": "thread.started", "thread_id": "t1" }),
+            serde_json::json!({
+                "type": "item.completed",
+                "item": { "id": "item_0", "type": "agent_message", "text": "All tests pass." }
+            }),
+            serde_json::json!({
+                "type": "turn.completed",
+                "usage": { "input_tokens": 500, "cached_input_tokens": 200, "output_tokens": 50 }
+            }),
+        ];
+
+        let summary = summarize_output(EngineKind::Codex, &lines(&events));
+        assert_eq!(summary.final_message.as_deref(), Some("All tests pass."));
+        assert_eq!(summary.input_tokens, 500);
+        assert_eq!(summary.output_tokens, 50);
+        assert_eq!(summary.cached_tokens, 200);
+    }
+
+    #[test]
+    fn base_commit_never_initializes_the_project() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        assert!(base_commit(path).is_err());
+        assert!(!dir.path().join(".git").exists());
+    }
+
+    #[test]
+    fn merge_refuses_a_dirty_project_and_leaves_it_alone() {
+        let git = |dir: &Path, args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let dir = tempfile::TempDir::new().unwrap();
+        let project = dir.path().join("project");
+        let worktree = dir.path().join("codex");
+        std::fs::create_dir(&project).unwrap();
+        git(&project, &["init", "-q", "-b", "main"]);
+        std::fs::write(project.join("a.txt"), "one\n").unwrap();
+        git(&project, &["add", "."]);
+        git(&project, &["commit", "-qm", "one"]);
+
+        let project_path = project.to_string_lossy().to_string();
+        let base = base_commit(&project_path).unwrap();
+        let branch = "anycode/fanout-f1-codex".to_string();
+        simple_git::git_worktree_add(&project_path, worktree.to_str().unwrap(), &branch, &base)
+            .unwrap();
+        std::fs::write(worktree.join("a.txt"), "two\n").unwrap();
+        git(&worktree, &["commit", "-qam", "two"]);
+
+        let result = FanOutResult {
+            id: "f1".to_string(),
+            project_path: project_path.clone(),
+            base_commit: base.clone(),
+            source_branch: Some("main".to_string()),
+            runs: vec![FanOutRun {
+                engine: EngineKind::Codex,
+                branch,
+                worktree_path: worktree.to_string_lossy().to_string(),
+                session_id: None,
+                success: true,
+                error: None,
+                final_message: None,
+                model: None,
+                input_tokens: 0,
+                output_tokens: 0,
+                cost_usd: 0.0,
+                diff_stats: None,
+                duration_ms: 0,
+            }],
+            ..Default::default()
+        };
+
+        std::fs::write(project.join("a.txt"), "mine\n").unwrap();
+        let err = merge_run(&result, EngineKind::Codex).unwrap_err();
+        assert!(err.contains("uncommitted changes"), "{}", err);
+        assert_eq!(git(&project, &["rev-parse", "HEAD"]), base);
+        assert_eq!(
+            std::fs::read_to_string(project.join("a.txt")).unwrap(),
+            "mine\n"
+        );
+        assert!(git(&project, &["status", "--porcelain"]).contains("a.txt"));
+    }
+}
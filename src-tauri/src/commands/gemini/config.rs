@@ -8,6 +8,9 @@ use std::fs;
 use std::path::PathBuf;
 use tokio::sync::OnceCell;
 
+use crate::commands::engine::EngineKind;
+use crate::commands::session_archive::{is_session_file, read_session_file, unarchived_path};
+use crate::commands::trash::{move_to_trash, TrashItem, TrashItemKind};
 use crate::commands::wsl_utils;
 
 /// 全局 Gemini WSL 模式配置缓存
@@ -291,8 +294,8 @@ pub fn list_session_files(project_path: &str) -> Result<Vec<GeminiSessionInfo>,
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
 
-        if path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let file_name = path
+        if is_session_file(&path, "json") {
+            let file_name = unarchived_path(&path)
                 .file_name()
                 .and_then(|s| s.to_str())
                 .unwrap_or("")
@@ -350,7 +353,7 @@ pub fn read_session_detail(
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
 
-        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+        if is_session_file(&path, "json") {
             if let Ok(detail) = read_session_detail_from_path(&path) {
                 if detail.session_id == session_id {
                     return Ok(detail);
@@ -365,7 +368,7 @@ pub fn read_session_detail(
 /// Helper function to read session detail from a specific file path
 fn read_session_detail_from_path(path: &PathBuf) -> Result<GeminiSessionDetail, String> {
     let content =
-        fs::read_to_string(path).map_err(|e| format!("Failed to read session file: {}", e))?;
+        read_session_file(path).map_err(|e| format!("Failed to read session file: {}", e))?;
 
     serde_json::from_str(&content).map_err(|e| format!("Failed to parse session file: {}", e))
 }
@@ -465,12 +468,27 @@ pub fn delete_session(project_path: &str, session_id: &str) -> Result<(), String
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let path = entry.path();
 
-        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+        if is_session_file(&path, "json") {
             if let Ok(detail) = read_session_detail_from_path(&path) {
                 if detail.session_id == session_id {
-                    fs::remove_file(&path)
-                        .map_err(|e| format!("Failed to delete session file: {}", e))?;
-                    log::info!("Deleted Gemini session: {} at {:?}", session_id, path);
+                    // Move the chat file and its git records to the recycle bin
+                    let git_records_file = super::git_ops::get_gemini_git_records_dir()?
+                        .join(format!("{}.json", session_id));
+                    let item = TrashItem {
+                        kind: TrashItemKind::Session,
+                        engine: EngineKind::Gemini,
+                        project_id: None,
+                        project_path: Some(project_path.to_string()),
+                        session_id: Some(session_id.to_string()),
+                        title: detail
+                            .messages
+                            .iter()
+                            .find(|m| m["type"].as_str() == Some("user"))
+                            .and_then(|m| m["content"].as_str())
+                            .map(str::to_string),
+                    };
+                    move_to_trash(item, &[path.clone(), git_records_file])?;
+                    log::info!("Moved Gemini session {} to trash: {:?}", session_id, path);
                     return Ok(());
                 }
             }
@@ -13,11 +13,14 @@ use std::fs;
 use std::path::PathBuf;
 
 // Import simple_git for rewind operations
+use super::super::session_worktree::resolve_project_path;
 use super::super::simple_git;
 // Import rewind helpers/types shared with Claude
+use super::super::engine::EngineKind;
 use super::super::prompt_tracker::{
     load_execution_config, PromptRecord as ClaudePromptRecord, RewindCapabilities, RewindMode,
 };
+use super::super::session_archive::rehydrate;
 // Import Gemini config helpers
 use super::config::get_gemini_dir;
 
@@ -36,6 +39,9 @@ pub struct GeminiPromptGitRecord {
     pub commit_before: String,
     pub commit_after: Option<String>,
     pub timestamp: String,
+    /// Why the run was stopped early (e.g. "inactivity_timeout")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
 }
 
 /// Collection of Git records for a Gemini session
@@ -200,6 +206,18 @@ pub fn truncate_gemini_git_records(session_id: &str, prompt_index: usize) -> Res
     Ok(())
 }
 
+/// Note why the latest prompt's run was stopped; false when there is no record
+pub fn record_gemini_stop_reason(session_id: &str, reason: &str) -> Result<bool, String> {
+    let mut git_records = load_gemini_git_records(session_id)?;
+    // Records are appended per prompt, so the last one belongs to the current run
+    match git_records.records.last_mut() {
+        Some(record) => record.stop_reason = Some(reason.to_string()),
+        None => return Ok(false),
+    }
+    save_gemini_git_records(session_id, &git_records)?;
+    Ok(true)
+}
+
 // ============================================================================
 // Prompt Extraction from Gemini Session Files
 // ============================================================================
@@ -300,6 +318,7 @@ pub async fn get_gemini_prompt_list(
     session_id: String,
     project_path: String,
 ) -> Result<Vec<PromptRecord>, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     extract_gemini_prompts(&session_id, &project_path)
 }
 
@@ -331,6 +350,7 @@ pub async fn check_gemini_rewind_capabilities(
     project_path: String,
     prompt_index: usize,
 ) -> Result<RewindCapabilities, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Gemini Rewind] Checking capabilities for session {} prompt #{}",
         session_id,
@@ -413,6 +433,7 @@ pub async fn record_gemini_prompt_sent(
     project_path: String,
     _prompt_text: String,
 ) -> Result<usize, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Gemini Record] Recording prompt sent for session: {}",
         session_id
@@ -442,35 +463,45 @@ pub async fn record_gemini_prompt_sent(
     let commit_before = simple_git::git_current_commit(&project_path)
         .map_err(|e| format!("Failed to get current commit: {}", e))?;
 
-    // Load existing records
-    let mut git_records = load_gemini_git_records(&session_id)?;
+    let prompt_index = load_gemini_git_records(&session_id)?.records.len();
+    save_gemini_prompt_sent_record(&session_id, &project_path, prompt_index, commit_before)?;
+
+    Ok(prompt_index)
+}
+
+/// Save the git record of a prompt about to run from `commit_before`
+///
+/// The prompt queue calls this directly for new sessions, with the HEAD it
+/// captured before the run, since the session ID only exists afterwards.
+pub(crate) fn save_gemini_prompt_sent_record(
+    session_id: &str,
+    project_path: &str,
+    prompt_index: usize,
+    commit_before: String,
+) -> Result<(), String> {
+    let mut git_records = load_gemini_git_records(session_id)?;
 
     // Update project path if needed
     if git_records.project_path.is_empty() {
-        git_records.project_path = project_path.clone();
+        git_records.project_path = project_path.to_string();
     }
 
-    // Calculate prompt index
-    let prompt_index = git_records.records.len();
-
-    // Create new record
-    let record = GeminiPromptGitRecord {
-        prompt_index,
-        commit_before: commit_before.clone(),
-        commit_after: None,
-        timestamp: Utc::now().to_rfc3339(),
-    };
-
-    git_records.records.push(record);
-    save_gemini_git_records(&session_id, &git_records)?;
-
     log::info!(
         "[Gemini Record] Recorded prompt #{} with commit_before: {}",
         prompt_index,
         &commit_before[..8.min(commit_before.len())]
     );
-
-    Ok(prompt_index)
+    git_records
+        .records
+        .retain(|r| r.prompt_index != prompt_index);
+    git_records.records.push(GeminiPromptGitRecord {
+        prompt_index,
+        commit_before,
+        commit_after: None,
+        timestamp: Utc::now().to_rfc3339(),
+        stop_reason: None,
+    });
+    save_gemini_git_records(session_id, &git_records)
 }
 
 /// Record a Gemini prompt completion (called after AI response)
@@ -481,6 +512,7 @@ pub async fn record_gemini_prompt_completed(
     prompt_index: usize,
     prompt_text: Option<String>,
 ) -> Result<(), String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Gemini Record] Recording prompt #{} completed for session: {}",
         prompt_index,
@@ -634,6 +666,86 @@ pub fn truncate_gemini_session_to_prompt(
     Ok(())
 }
 
+/// Copy a Gemini session up to (not including) a prompt into a new chat file
+///
+/// The source chat file and its git records are not modified. The fork gets
+/// the current time as startTime, so it becomes the project's latest session
+/// (Gemini CLI can only `--resume latest`). Returns the new session ID.
+pub fn fork_gemini_session_at_prompt(
+    session_id: &str,
+    project_path: &str,
+    prompt_index: usize,
+) -> Result<String, String> {
+    let sessions_dir = get_gemini_sessions_dir(project_path)?;
+    let session_file = find_gemini_session_file(&sessions_dir, session_id)?;
+
+    let content = fs::read_to_string(&session_file)
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+    let mut session_data: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse session JSON: {}", e))?;
+
+    let messages = session_data
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .ok_or_else(|| "No messages array found in session".to_string())?;
+
+    // Same counting as extract_gemini_prompts: non-empty user messages only
+    let fork_at = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| {
+            message.get("type").and_then(|t| t.as_str()) == Some("user")
+                && !message
+                    .get("content")
+                    .and_then(|c| c.as_str())
+                    .unwrap_or("")
+                    .trim()
+                    .is_empty()
+        })
+        .nth(prompt_index)
+        .map(|(idx, _)| idx)
+        .ok_or_else(|| format!("Prompt #{} not found in session", prompt_index))?;
+
+    let kept: Vec<serde_json::Value> = messages.iter().take(fork_at).cloned().collect();
+
+    let new_session_id = uuid::Uuid::new_v4().to_string();
+    let now = Utc::now();
+    session_data["sessionId"] = serde_json::Value::String(new_session_id.clone());
+    session_data["startTime"] = serde_json::Value::String(now.to_rfc3339());
+    session_data["lastUpdated"] = serde_json::Value::String(now.to_rfc3339());
+    session_data["messages"] = serde_json::Value::Array(kept);
+
+    // 与 Gemini CLI 一致：session-{YYYY-MM-DDTHH-MM}-{session_id 前 8 位}.json
+    let file_path = sessions_dir.join(format!(
+        "session-{}-{}.json",
+        now.format("%Y-%m-%dT%H-%M"),
+        &new_session_id[..8]
+    ));
+    let new_content = serde_json::to_string_pretty(&session_data)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+    fs::write(&file_path, new_content)
+        .map_err(|e| format!("Failed to write forked session: {}", e))?;
+
+    // Carry over git records for the prompts that were copied
+    let mut git_records = load_gemini_git_records(session_id)?;
+    git_records
+        .records
+        .retain(|r| r.prompt_index < prompt_index);
+    if !git_records.records.is_empty() {
+        git_records.session_id = new_session_id.clone();
+        save_gemini_git_records(&new_session_id, &git_records)?;
+    }
+
+    log::info!(
+        "[Gemini Fork] Forked session {} at prompt #{} into {:?}",
+        session_id,
+        prompt_index,
+        file_path
+    );
+
+    Ok(new_session_id)
+}
+
 // ============================================================================
 // Revert Operations
 // ============================================================================
@@ -646,6 +758,8 @@ pub async fn revert_gemini_to_prompt(
     prompt_index: usize,
     mode: RewindMode,
 ) -> Result<String, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
+    rehydrate(EngineKind::Gemini, &session_id, None, Some(&project_path))?;
     log::info!(
         "[Gemini Rewind] Reverting session {} to prompt #{} with mode: {:?}",
         session_id,
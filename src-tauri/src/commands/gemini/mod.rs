@@ -39,6 +39,7 @@ pub use config::{
     update_gemini_config,
 };
 pub use session::{cancel_gemini, check_gemini_installed, execute_gemini};
+pub(crate) use session::build_gemini_command;
 
 // Re-export Gemini Rewind commands
 pub use git_ops::{
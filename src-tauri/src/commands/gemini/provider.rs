@@ -12,6 +12,8 @@ use std::fs;
 use std::path::PathBuf;
 
 use super::config::get_gemini_dir;
+use crate::commands::engine::EngineKind;
+use crate::commands::provider_history;
 use crate::commands::wsl_utils;
 
 // ============================================================================
@@ -232,6 +234,7 @@ pub async fn get_current_gemini_provider_config() -> Result<CurrentGeminiProvide
 #[tauri::command]
 pub async fn switch_gemini_provider(config: GeminiProviderConfig) -> Result<String, String> {
     log::info!("[Gemini Provider] Switching to provider: {}", config.name);
+    let previous = provider_history::current_base_url(EngineKind::Gemini).await;
 
     // Check WSL mode
     let wsl_runtime = wsl_utils::get_gemini_wsl_runtime();
@@ -290,6 +293,7 @@ pub async fn switch_gemini_provider(config: GeminiProviderConfig) -> Result<Stri
 
     // Write settings.json
     write_settings_file(&settings_path, &settings)?;
+    provider_history::record_switch(EngineKind::Gemini, previous).await;
 
     log::info!(
         "[Gemini Provider] Successfully switched to: {}",
@@ -460,6 +464,7 @@ pub async fn reorder_gemini_provider_configs(ids: Vec<String>) -> Result<String,
 #[tauri::command]
 pub async fn clear_gemini_provider_config() -> Result<String, String> {
     log::info!("[Gemini Provider] Clearing config");
+    let previous = provider_history::current_base_url(EngineKind::Gemini).await;
 
     let env_path = get_gemini_env_path()?;
     let settings_path = get_gemini_settings_path()?;
@@ -471,6 +476,7 @@ pub async fn clear_gemini_provider_config() -> Result<String, String> {
     let mut settings = read_settings_file(&settings_path)?;
     set_auth_type_in_settings(&mut settings, "oauth-personal");
     write_settings_file(&settings_path, &settings)?;
+    provider_history::record_switch(EngineKind::Gemini, previous).await;
 
     log::info!("[Gemini Provider] Successfully cleared config");
     Ok("成功清理 Gemini 配置，已切换回官方 OAuth 模式".to_string())
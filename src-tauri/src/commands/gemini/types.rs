@@ -329,33 +329,20 @@ pub struct GeminiSession {
 // Process State
 // ============================================================================
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::process::Child;
-use tokio::sync::Mutex;
-
-use crate::process::JobObject;
+use crate::commands::engine::{EngineProcessHandle, EngineProcessState};
 
 /// Gemini process handle with PID for proper cleanup
-pub struct GeminiProcessHandle {
-    pub child: Child,
-    pub pid: u32,
-    /// Windows Job Object (kills all child processes when dropped); no-op on non-Windows.
-    pub job_object: Option<JobObject>,
-}
+pub type GeminiProcessHandle = EngineProcessHandle;
 
 /// Global state to track Gemini processes
-pub struct GeminiProcessState {
-    pub processes: Arc<Mutex<HashMap<String, GeminiProcessHandle>>>,
-    pub last_session_id: Arc<Mutex<Option<String>>>,
-}
+#[derive(Default)]
+pub struct GeminiProcessState(pub EngineProcessState);
 
-impl Default for GeminiProcessState {
-    fn default() -> Self {
-        Self {
-            processes: Arc::new(Mutex::new(HashMap::new())),
-            last_session_id: Arc::new(Mutex::new(None)),
-        }
+impl std::ops::Deref for GeminiProcessState {
+    type Target = EngineProcessState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
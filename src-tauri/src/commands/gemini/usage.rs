@@ -8,12 +8,19 @@
  * - Per-project statistics
  */
 use chrono::NaiveDate;
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 use std::path::PathBuf;
-
-use super::config::get_gemini_dir;
+use std::sync::Mutex;
+use tauri::State;
+
+use super::super::engine::EngineKind;
+use super::super::pricing::{self, TokenCounts};
+use super::super::session_archive::read_session_file;
+use super::super::storage::AgentDb;
+use super::super::usage::UsageEntry;
+use super::super::usage_index;
 use super::types::GeminiSessionDetail;
 
 // ============================================================================
@@ -82,98 +89,21 @@ pub struct GeminiUsageStats {
 }
 
 // ============================================================================
-// Pricing (Must match frontend pricing.ts)
+// Pricing (shared catalog, see commands::pricing)
 // ============================================================================
 
-#[derive(Debug, Clone, Copy)]
-struct ModelPricing {
-    input: f64,
-    output: f64,
-    cache_read: f64,
-}
-
-fn get_gemini_pricing(model: &str) -> ModelPricing {
-    let normalized = model.to_lowercase();
-
-    // Gemini 3.1 Pro Preview (Latest - February 2026)
-    if normalized.contains("gemini-3.1-pro") || normalized.contains("gemini_3_1_pro")
-        || normalized.contains("3.1-pro") {
-        return ModelPricing {
-            input: 2.50,
-            output: 15.00,
-            cache_read: 0.25,
-        };
-    }
-
-    // Gemini 3 Pro Preview
-    if normalized.contains("gemini-3-pro") || normalized.contains("gemini_3_pro") {
-        return ModelPricing {
-            input: 2.00,
-            output: 12.00,
-            cache_read: 0.20,
-        };
-    }
-
-    // Gemini 2.5 Pro
-    if normalized.contains("2.5-pro") || normalized.contains("2_5_pro") {
-        return ModelPricing {
-            input: 1.25,
-            output: 10.00,
-            cache_read: 0.125,
-        };
-    }
-
-    // Gemini 2.5 Flash-Lite
-    if normalized.contains("2.5-flash-lite") || normalized.contains("2_5_flash_lite") {
-        return ModelPricing {
-            input: 0.10,
-            output: 0.40,
-            cache_read: 0.01,
-        };
-    }
-
-    // Gemini 2.5 Flash
-    if normalized.contains("2.5-flash") || normalized.contains("2_5_flash") {
-        return ModelPricing {
-            input: 0.30,
-            output: 2.50,
-            cache_read: 0.03,
-        };
-    }
-
-    // Gemini 2.0 Flash
-    if normalized.contains("2.0-flash") || normalized.contains("2_0_flash") {
-        return ModelPricing {
-            input: 0.10,
-            output: 0.40,
-            cache_read: 0.025,
-        };
-    }
-
-    // Gemini 3 Flash (default for new sessions)
-    if normalized.contains("gemini-3-flash") || normalized.contains("gemini_3_flash") {
-        return ModelPricing {
-            input: 0.30,
-            output: 2.50,
-            cache_read: 0.03,
-        };
-    }
-
-    // Default to Gemini 2.5 Pro pricing
-    ModelPricing {
-        input: 1.25,
-        output: 10.00,
-        cache_read: 0.125,
-    }
-}
-
-fn calculate_cost(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
-    let pricing = get_gemini_pricing(model);
-
-    let input_cost = (input_tokens as f64 / 1_000_000.0) * pricing.input;
-    let output_cost = (output_tokens as f64 / 1_000_000.0) * pricing.output;
-
-    input_cost + output_cost
+pub(crate) fn calculate_cost(
+    model: &str,
+    input_tokens: u64,
+    output_tokens: u64,
+    on: NaiveDate,
+) -> f64 {
+    let tokens = TokenCounts {
+        input: input_tokens,
+        output: output_tokens,
+        ..Default::default()
+    };
+    pricing::cost(EngineKind::Gemini, model, &tokens, on).unwrap_or(0.0)
 }
 
 // ============================================================================
@@ -182,22 +112,28 @@ fn calculate_cost(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
 
 fn read_session_detail_from_path(path: &PathBuf) -> Result<GeminiSessionDetail, String> {
     let content =
-        fs::read_to_string(path).map_err(|e| format!("Failed to read session file: {}", e))?;
+        read_session_file(path).map_err(|e| format!("Failed to read session file: {}", e))?;
 
     serde_json::from_str(&content).map_err(|e| format!("Failed to parse session file: {}", e))
 }
 
-fn parse_session_for_usage(
+/// Usage of one message, with its message ID
+pub(crate) type MessageUsage = (UsageEntry, Option<String>);
+
+/// Session totals, and the usage of each message that reports tokens
+pub(crate) fn parse_session_for_usage(
     path: &PathBuf,
     project_hash: &str,
-) -> Option<GeminiSessionUsage> {
+) -> Option<(GeminiSessionUsage, Vec<MessageUsage>)> {
     let detail = read_session_detail_from_path(path).ok()?;
 
     // Extract token usage from messages
     let mut total_input_tokens: u64 = 0;
     let mut total_output_tokens: u64 = 0;
+    let mut total_cost = 0.0;
     let mut model = "gemini-3-flash".to_string();
     let mut first_message: Option<String> = None;
+    let mut entries = Vec::new();
 
     for message in &detail.messages {
         // Extract model if available
@@ -207,11 +143,33 @@ fn parse_session_for_usage(
 
         // Extract tokens if available
         if let Some(tokens) = message.get("tokens").and_then(|v| v.as_object()) {
-            if let Some(input) = tokens.get("input").and_then(|v| v.as_u64()) {
-                total_input_tokens += input;
-            }
-            if let Some(output) = tokens.get("output").and_then(|v| v.as_u64()) {
-                total_output_tokens += output;
+            let input = tokens.get("input").and_then(|v| v.as_u64()).unwrap_or(0);
+            let output = tokens.get("output").and_then(|v| v.as_u64()).unwrap_or(0);
+            total_input_tokens += input;
+            total_output_tokens += output;
+
+            if input > 0 || output > 0 {
+                let timestamp = message
+                    .get("timestamp")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&detail.start_time)
+                    .to_string();
+                // 分档价格按单条消息计算，会话总价是各条之和
+                let cost = calculate_cost(&model, input, output, pricing::usage_date(&timestamp));
+                total_cost += cost;
+                let entry = UsageEntry {
+                    cost,
+                    timestamp,
+                    model: model.clone(),
+                    input_tokens: input,
+                    output_tokens: output,
+                    cache_creation_tokens: 0,
+                    cache_read_tokens: 0,
+                    session_id: detail.session_id.clone(),
+                    project_path: String::new(),
+                };
+                let message_id = message.get("id").and_then(|v| v.as_str());
+                entries.push((entry, message_id.map(str::to_string)));
             }
         }
 
@@ -233,9 +191,7 @@ fn parse_session_for_usage(
         return None;
     }
 
-    let total_cost = calculate_cost(&model, total_input_tokens, total_output_tokens);
-
-    Some(GeminiSessionUsage {
+    let usage = GeminiSessionUsage {
         session_id: detail.session_id,
         project_path: String::new(), // Will be populated later if we can find it
         project_hash: project_hash.to_string(),
@@ -245,62 +201,8 @@ fn parse_session_for_usage(
         output_tokens: total_output_tokens,
         start_time: detail.start_time,
         first_message,
-    })
-}
-
-fn collect_all_sessions() -> Vec<GeminiSessionUsage> {
-    let gemini_dir = match get_gemini_dir() {
-        Ok(dir) => dir,
-        Err(_) => return Vec::new(),
     };
-
-    let tmp_dir = gemini_dir.join("tmp");
-    if !tmp_dir.exists() {
-        return Vec::new();
-    }
-
-    let mut sessions = Vec::new();
-
-    // Iterate over all project hash directories
-    if let Ok(entries) = fs::read_dir(&tmp_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if !path.is_dir() {
-                continue;
-            }
-
-            let project_hash = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_string();
-
-            // Check chats/ subdirectory
-            let chats_dir = path.join("chats");
-            if !chats_dir.exists() {
-                continue;
-            }
-
-            if let Ok(chat_entries) = fs::read_dir(&chats_dir) {
-                for chat_entry in chat_entries.flatten() {
-                    let chat_path = chat_entry.path();
-                    if chat_path.extension().and_then(|s| s.to_str()) == Some("json") {
-                        if let Some(mut session) = parse_session_for_usage(&chat_path, &project_hash)
-                        {
-                            // Try to find project path from session data
-                            // For now, use the hash as identifier
-                            session.project_path = format!("project:{}", project_hash);
-                            sessions.push(session);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Sort by start time (newest first)
-    sessions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
-    sessions
+    Some((usage, entries))
 }
 
 // ============================================================================
@@ -310,6 +212,16 @@ fn collect_all_sessions() -> Vec<GeminiSessionUsage> {
 /// Get Gemini usage statistics
 #[tauri::command]
 pub async fn get_gemini_usage_stats(
+    db: State<'_, AgentDb>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<GeminiUsageStats, String> {
+    gemini_usage_stats(&db.0, start_date, end_date)
+}
+
+/// Gemini usage statistics, optionally limited to an inclusive date range
+pub fn gemini_usage_stats(
+    db: &Mutex<Connection>,
     start_date: Option<String>,
     end_date: Option<String>,
 ) -> Result<GeminiUsageStats, String> {
@@ -319,7 +231,7 @@ pub async fn get_gemini_usage_stats(
         end_date
     );
 
-    let all_sessions = collect_all_sessions();
+    let all_sessions = usage_index::gemini_sessions(db)?;
 
     // Filter by date range if provided
     let filtered_sessions: Vec<GeminiSessionUsage> = if let (Some(start), Some(end)) =
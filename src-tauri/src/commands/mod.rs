@@ -1,22 +1,42 @@
 pub mod acemcp;
+pub mod budget; // 费用预算护栏 (单次运行 / 项目 / 每日)
 pub mod claude;
 pub mod clipboard;
 pub mod codex; // OpenAI Codex integration
 pub mod context_commands;
 pub mod context_manager;
+pub mod control_api; // 本地 HTTP/WebSocket 控制接口
+pub mod engine; // 统一引擎抽象 (Claude / Codex / Gemini)
 pub mod enhanced_hooks;
 pub mod extensions;
+pub mod fan_out; // 同一提示词多引擎并行对比 (git worktree)
 pub mod file_operations;
 pub mod gemini; // Google Gemini CLI integration
 pub mod git_stats;
 pub mod mcp;
+pub mod orphan_processes; // 崩溃后遗留的 CLI 进程
 pub mod permission_config;
+pub mod pricing; // 模型定价目录（内置默认 + 用户覆盖）
+pub mod prompt_queue; // 按项目排队执行提示词
 pub mod prompt_tracker;
 pub mod provider;
+pub mod provider_history; // 供应商切换记录（用量按 base URL 归属）
+pub mod resource_monitor; // 会话进程树 CPU / 内存 / MCP 监控
+pub mod session_archive; // 历史会话 zstd 压缩归档
+pub mod session_export; // 会话导出 (Markdown / HTML / JSON)
+pub mod session_fork; // 从任意提示词无损分叉会话
+pub mod session_search; // 跨引擎会话全文检索 (FTS5)
+pub mod session_worktree; // 会话级 git worktree 隔离
 pub mod simple_git;
+pub mod spend_alerts; // 费用 / 余额阈值提醒（桌面通知）
 pub mod storage;
 pub mod translator;
+pub mod trash; // 会话 / 项目回收站
+pub mod unified_usage; // 跨引擎统一用量统计
 pub mod url_utils; // API URL 规范化工具
 pub mod usage;
+pub mod usage_export; // 用量导出 (CSV / JSON，按成本中心分摊)
+pub mod usage_index; // 用量增量索引（只解析追加内容）
+pub mod usage_recorder; // 实时用量写入 usage_entries
 pub mod window; // 多窗口管理
 pub mod wsl_utils; // WSL 兼容性工具
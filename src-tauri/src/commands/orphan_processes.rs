@@ -0,0 +1,278 @@
+//! Orphaned CLI processes (崩溃/重启后遗留的进程)
+//!
+//! `ProcessRegistry` mirrors its entries into agents.db, so processes started
+//! by a previous app instance are known again after a crash. These commands
+//! list them, kill them, or re-attach to their session file.
+//!
+//! Re-attaching is read-only: the orphan's stdout went to the dead instance,
+//! so whatever it appends to its session file is emitted as
+//! `orphan-session-output:{run_id}` until the process exits
+//! (`orphan-session-exited:{run_id}`). Payloads are in the engine's on-disk
+//! format:
+//!
+//! - Claude / Codex: new JSONL lines of the session / rollout file
+//! - Gemini: each new entry of the chat file's `messages`, as JSON
+//!
+//! Earlier history is loaded as usual through the engine's history command.
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::engine::EngineKind;
+use crate::process::{ProcessInfo, ProcessRegistryState, ProcessType};
+
+const TAIL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// run_ids that already have a tail task
+static TAILING: Lazy<Mutex<HashSet<i64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanAttachment {
+    pub run_id: i64,
+    pub engine: EngineKind,
+    pub session_id: String,
+    /// Claude: encoded project directory; Codex / Gemini: the project path
+    pub project_id: String,
+    pub session_file: String,
+}
+
+/// Where an orphan's new output is read from
+enum SessionSource {
+    /// Append-only JSONL (Claude session, Codex rollout)
+    Jsonl {
+        path: PathBuf,
+        offset: u64,
+        pending: Vec<u8>,
+    },
+    /// Gemini rewrites its chat JSON on every message
+    GeminiChat {
+        project_path: String,
+        session_id: String,
+        seen: usize,
+    },
+}
+
+impl SessionSource {
+    fn jsonl(path: PathBuf) -> Self {
+        let offset = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        SessionSource::Jsonl {
+            path,
+            offset,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Output written since the last poll
+    fn poll(&mut self) -> Result<Vec<String>, String> {
+        match self {
+            SessionSource::Jsonl {
+                path,
+                offset,
+                pending,
+            } => {
+                let chunk = read_from(path, *offset).map_err(|e| e.to_string())?;
+                *offset += chunk.len() as u64;
+                pending.extend_from_slice(&chunk);
+                // Only emit complete lines; keep a partially written one for later
+                let Some(end) = pending.iter().rposition(|b| *b == b'\n') else {
+                    return Ok(Vec::new());
+                };
+                let complete: Vec<u8> = pending.drain(..=end).collect();
+                Ok(String::from_utf8_lossy(&complete)
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(String::from)
+                    .collect())
+            }
+            SessionSource::GeminiChat {
+                project_path,
+                session_id,
+                seen,
+            } => {
+                let detail = super::gemini::config::read_session_detail(project_path, session_id)?;
+                let new = detail
+                    .messages
+                    .iter()
+                    .skip(*seen)
+                    .map(|m| m.to_string())
+                    .collect();
+                *seen = detail.messages.len();
+                Ok(new)
+            }
+        }
+    }
+}
+
+fn find_claude_session_file(session_id: &str) -> Result<PathBuf, String> {
+    let claude_dir = super::claude::get_claude_dir().map_err(|e| e.to_string())?;
+    let pattern = claude_dir
+        .join("projects")
+        .join("*")
+        .join(format!("{}.jsonl", session_id));
+    glob::glob(&pattern.to_string_lossy())
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .next()
+        .ok_or_else(|| format!("Session file not found for: {}", session_id))
+}
+
+fn find_codex_session_file(session_id: &str) -> Result<PathBuf, String> {
+    let sessions_dir = super::codex::config::get_codex_sessions_dir()?;
+    super::codex::session::find_session_file(&sessions_dir, session_id)
+        .ok_or_else(|| format!("Session file not found for: {}", session_id))
+}
+
+/// Bytes appended to `path` since `offset`
+fn read_from(path: &Path, offset: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+async fn tail_session(app: AppHandle, run_id: i64, mut source: SessionSource) {
+    let output_event = format!("orphan-session-output:{}", run_id);
+
+    loop {
+        tokio::time::sleep(TAIL_INTERVAL).await;
+
+        // Check liveness before reading so the final output is still emitted
+        let alive = app
+            .state::<ProcessRegistryState>()
+            .0
+            .get_orphan_process(run_id)
+            .is_ok_and(|p| p.is_some());
+
+        match source.poll() {
+            Ok(lines) => {
+                for line in lines {
+                    let _ = app.emit(&output_event, line);
+                }
+            }
+            Err(e) => {
+                log::warn!("[Orphans] Stopped tailing orphan {}: {}", run_id, e);
+                break;
+            }
+        }
+
+        if !alive {
+            break;
+        }
+    }
+
+    log::info!("[Orphans] Stopped tailing orphan {}", run_id);
+    let _ = app.emit(&format!("orphan-session-exited:{}", run_id), run_id);
+    if let Ok(mut tailing) = TAILING.lock() {
+        tailing.remove(&run_id);
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Processes started by a previous app instance that are still running
+#[tauri::command]
+pub async fn list_orphan_processes(
+    registry: State<'_, ProcessRegistryState>,
+) -> Result<Vec<ProcessInfo>, String> {
+    registry.0.get_orphan_processes()
+}
+
+/// Kill an orphan and its child processes
+#[tauri::command]
+pub async fn kill_orphan_process(
+    registry: State<'_, ProcessRegistryState>,
+    run_id: i64,
+) -> Result<bool, String> {
+    registry.0.kill_orphan_process(run_id)
+}
+
+/// Tail an orphaned session read-only until its process exits
+#[tauri::command]
+pub async fn attach_orphan_session(
+    app: AppHandle,
+    registry: State<'_, ProcessRegistryState>,
+    run_id: i64,
+) -> Result<OrphanAttachment, String> {
+    let info = registry
+        .0
+        .get_orphan_process(run_id)?
+        .ok_or_else(|| format!("Orphan process {} is no longer running", run_id))?;
+
+    let engine = info.process_type.engine();
+    let session_id = match info.process_type {
+        ProcessType::ClaudeSession { session_id }
+        | ProcessType::EngineSession { session_id, .. } => session_id,
+        ProcessType::AgentRun { agent_name, .. } => {
+            return Err(format!(
+                "Agent run '{}' has no session file to attach to",
+                agent_name
+            ))
+        }
+    };
+    if session_id.starts_with(&format!("{}-", engine)) {
+        // Still the backend channel ID: the CLI died before reporting its own
+        return Err(format!(
+            "Orphan process {} never reported a {} session ID",
+            run_id, engine
+        ));
+    }
+
+    let (source, project_id, session_file) = match engine {
+        EngineKind::Claude => {
+            let file = find_claude_session_file(&session_id)?;
+            let project_id = file
+                .parent()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            (SessionSource::jsonl(file.clone()), project_id, file)
+        }
+        EngineKind::Codex => {
+            let file = find_codex_session_file(&session_id)?;
+            (SessionSource::jsonl(file.clone()), info.project_path, file)
+        }
+        EngineKind::Gemini => {
+            let mut source = SessionSource::GeminiChat {
+                project_path: info.project_path.clone(),
+                session_id: session_id.clone(),
+                seen: 0,
+            };
+            // Skip what is already on disk; history covers it
+            source.poll()?;
+            let file =
+                super::gemini::config::get_project_session_dir(&info.project_path)?.join("chats");
+            (source, info.project_path, file)
+        }
+    };
+
+    let newly_attached = TAILING.lock().map_err(|e| e.to_string())?.insert(run_id);
+    if newly_attached {
+        log::info!(
+            "[Orphans] Tailing {} session {} of orphan {} (PID: {})",
+            engine,
+            session_id,
+            run_id,
+            info.pid
+        );
+        tauri::async_runtime::spawn(tail_session(app, run_id, source));
+    }
+
+    Ok(OrphanAttachment {
+        run_id,
+        engine,
+        session_id,
+        project_id,
+        session_file: session_file.to_string_lossy().to_string(),
+    })
+}
@@ -158,11 +158,8 @@ pub fn build_execution_args(config: &ClaudeExecutionConfig, model: &str) -> Vec<
         args.push("--verbose".to_string());
     }
 
-    // 添加超时参数
-    if let Some(timeout) = config.timeout_seconds {
-        args.push("--timeout".to_string());
-        args.push(timeout.to_string());
-    }
+    // timeout_seconds 不再作为 CLI 参数传递（Claude CLI 没有 --timeout），
+    // 改由 engine::timeout 的运行监督任务强制执行
 
     // 添加token限制
     if let Some(max_tokens) = config.max_tokens {
@@ -0,0 +1,460 @@
+//! Model pricing catalog
+//!
+//! One catalog prices the usage of all three engines. The defaults ship with
+//! the app (`pricing/default_pricing.json`); entries in
+//! `~/.anycode/pricing.json` are checked first, so prices can be corrected and
+//! custom provider models added without a release.
+//!
+//! Each entry:
+//! - matches models by glob `patterns` and/or a `regex` (case-insensitive,
+//!   against the normalized model name), optionally limited to one `engine`
+//! - applies from `effectiveFrom`; entries sharing a `name` are versions of
+//!   the same price, and the latest one in effect on the usage date wins
+//! - may carry `tiers` that replace the base prices once the prompt exceeds
+//!   `abovePromptTokens` (long-context surcharges)
+//!
+//! The first entry (in catalog order) that matches and is in effect decides
+//! which model a name resolves to. All prices are USD per million tokens.
+
+use chrono::{DateTime, Local, NaiveDate};
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::engine::EngineKind;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+const DEFAULT_CATALOG: &str = include_str!("../../pricing/default_pricing.json");
+
+/// How often the overrides file is checked for changes
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingCatalog {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub models: Vec<PricingEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingEntry {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<EngineKind>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<NaiveDate>,
+    #[serde(flatten)]
+    pub prices: Prices,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<PricingTier>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Prices {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_write: f64,
+    #[serde(default)]
+    pub cache_read: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingTier {
+    /// Applies when input + cache tokens of a request exceed this
+    pub above_prompt_tokens: u64,
+    #[serde(flatten)]
+    pub prices: Prices,
+}
+
+/// Tokens of one priced unit (message, turn or session)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenCounts {
+    pub input: u64,
+    pub output: u64,
+    pub cache_write: u64,
+    pub cache_read: u64,
+}
+
+impl TokenCounts {
+    fn prompt_tokens(&self) -> u64 {
+        self.input + self.cache_write + self.cache_read
+    }
+}
+
+/// Defaults and user overrides, as returned to the settings UI
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricingCatalogView {
+    pub defaults: PricingCatalog,
+    pub overrides: PricingCatalog,
+}
+
+// ============================================================================
+// Compiled catalog
+// ============================================================================
+
+struct CompiledEntry {
+    entry: PricingEntry,
+    globs: Vec<glob::Pattern>,
+    regex: Option<Regex>,
+}
+
+impl CompiledEntry {
+    fn compile(entry: PricingEntry) -> Result<Self, String> {
+        let globs = entry
+            .patterns
+            .iter()
+            .map(|p| {
+                glob::Pattern::new(&p.to_lowercase())
+                    .map_err(|e| format!("{}: invalid pattern '{}': {}", entry.name, p, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let regex = entry
+            .regex
+            .as_deref()
+            .map(|r| {
+                RegexBuilder::new(r)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("{}: invalid regex '{}': {}", entry.name, r, e))
+            })
+            .transpose()?;
+        Ok(Self {
+            entry,
+            globs,
+            regex,
+        })
+    }
+
+    fn matches(&self, engine: EngineKind, normalized: &str, on: NaiveDate) -> bool {
+        self.entry.engine.map_or(true, |e| e == engine)
+            && self.entry.effective_from.map_or(true, |from| from <= on)
+            && (self.globs.iter().any(|g| g.matches(normalized))
+                || self.regex.as_ref().is_some_and(|r| r.is_match(normalized)))
+    }
+}
+
+fn compile_catalog(catalog: PricingCatalog) -> Result<Vec<CompiledEntry>, String> {
+    catalog
+        .models
+        .into_iter()
+        .map(CompiledEntry::compile)
+        .collect()
+}
+
+/// Resolve within one list: the first match picks the model, the latest
+/// version of it in effect picks the price
+fn resolve_in(
+    entries: &[CompiledEntry],
+    engine: EngineKind,
+    normalized: &str,
+    on: NaiveDate,
+) -> Option<PricingEntry> {
+    let first = entries.iter().find(|e| e.matches(engine, normalized, on))?;
+    entries
+        .iter()
+        .filter(|e| e.entry.name == first.entry.name && e.matches(engine, normalized, on))
+        .max_by_key(|e| e.entry.effective_from)
+        .map(|e| e.entry.clone())
+}
+
+struct Catalog {
+    overrides: Vec<CompiledEntry>,
+    defaults: Vec<CompiledEntry>,
+}
+
+impl Catalog {
+    fn resolve(&self, engine: EngineKind, model: &str, on: NaiveDate) -> Option<PricingEntry> {
+        let normalized = normalize_model(model);
+        resolve_in(&self.overrides, engine, &normalized, on)
+            .or_else(|| resolve_in(&self.defaults, engine, &normalized, on))
+    }
+}
+
+/// Lowercase and strip Bedrock / Vertex AI decorations
+/// (`anthropic.claude-sonnet-4-5-20250929-v1:0`, `claude-sonnet-4-5@20250929`)
+fn normalize_model(model: &str) -> String {
+    let mut normalized = model
+        .to_lowercase()
+        .replace("anthropic.", "")
+        .replace("-v1:0", "");
+    if let Some(pos) = normalized.find('@') {
+        normalized.truncate(pos);
+    }
+    normalized
+}
+
+// ============================================================================
+// Loading
+// ============================================================================
+
+struct CatalogCache {
+    catalog: Option<Arc<Catalog>>,
+    overrides_modified: Option<SystemTime>,
+    checked_at: Option<Instant>,
+}
+
+static CACHE: Lazy<Mutex<CatalogCache>> = Lazy::new(|| {
+    Mutex::new(CatalogCache {
+        catalog: None,
+        overrides_modified: None,
+        checked_at: None,
+    })
+});
+
+fn get_overrides_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("pricing.json"))
+}
+
+fn default_catalog() -> PricingCatalog {
+    serde_json::from_str(DEFAULT_CATALOG).expect("bundled pricing catalog is valid JSON")
+}
+
+fn load_overrides() -> PricingCatalog {
+    get_overrides_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[Pricing] Failed to load overrides, using defaults: {}", e);
+            PricingCatalog::default()
+        })
+}
+
+fn build_catalog() -> Catalog {
+    // Invalid user entries are skipped so one typo doesn't zero every price
+    let overrides = load_overrides()
+        .models
+        .into_iter()
+        .filter_map(|entry| match CompiledEntry::compile(entry) {
+            Ok(compiled) => Some(compiled),
+            Err(e) => {
+                log::warn!("[Pricing] Skipping override {}", e);
+                None
+            }
+        })
+        .collect();
+    Catalog {
+        overrides,
+        defaults: compile_catalog(default_catalog()).expect("bundled pricing catalog compiles"),
+    }
+}
+
+fn overrides_modified() -> Option<SystemTime> {
+    get_overrides_path()
+        .ok()
+        .and_then(|path| std::fs::metadata(path).ok())
+        .and_then(|meta| meta.modified().ok())
+}
+
+/// Current catalog; rebuilt when the overrides file changes on disk
+fn catalog() -> Arc<Catalog> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let due = cache
+        .checked_at
+        .map_or(true, |at| at.elapsed() >= RELOAD_CHECK_INTERVAL);
+    if due {
+        cache.checked_at = Some(Instant::now());
+        let modified = overrides_modified();
+        if cache.catalog.is_none() || modified != cache.overrides_modified {
+            cache.catalog = Some(Arc::new(build_catalog()));
+            cache.overrides_modified = modified;
+        }
+    }
+    cache
+        .catalog
+        .clone()
+        .unwrap_or_else(|| Arc::new(build_catalog()))
+}
+
+fn invalidate() {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.catalog = None;
+    cache.checked_at = None;
+}
+
+// ============================================================================
+// Pricing
+// ============================================================================
+
+/// Local date of an RFC 3339 timestamp; today when it can't be parsed
+pub fn usage_date(timestamp: &str) -> NaiveDate {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|dt| dt.with_timezone(&Local).date_naive())
+        .unwrap_or_else(|_| Local::now().date_naive())
+}
+
+/// Catalog entry pricing `model` on `on`
+pub fn resolve(engine: EngineKind, model: &str, on: NaiveDate) -> Option<PricingEntry> {
+    catalog().resolve(engine, model, on)
+}
+
+fn price_with(entry: &PricingEntry, tokens: &TokenCounts) -> f64 {
+    let prompt = tokens.prompt_tokens();
+    let prices = entry
+        .tiers
+        .iter()
+        .filter(|t| prompt > t.above_prompt_tokens)
+        .max_by_key(|t| t.above_prompt_tokens)
+        .map_or(entry.prices, |t| t.prices);
+
+    (tokens.input as f64 * prices.input
+        + tokens.output as f64 * prices.output
+        + tokens.cache_write as f64 * prices.cache_write
+        + tokens.cache_read as f64 * prices.cache_read)
+        / 1_000_000.0
+}
+
+/// Cost in USD; None when no catalog entry matches the model
+pub fn cost(engine: EngineKind, model: &str, tokens: &TokenCounts, on: NaiveDate) -> Option<f64> {
+    resolve(engine, model, on).map(|entry| price_with(&entry, tokens))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_pricing_catalog() -> Result<PricingCatalogView, String> {
+    Ok(PricingCatalogView {
+        defaults: default_catalog(),
+        overrides: load_overrides(),
+    })
+}
+
+/// Replace the user overrides; rejects invalid patterns instead of skipping them
+#[tauri::command]
+pub async fn update_pricing_overrides(overrides: PricingCatalog) -> Result<PricingCatalog, String> {
+    compile_catalog(overrides.clone())?;
+    save_json_config(&overrides, get_overrides_path()?)?;
+    invalidate();
+    Ok(overrides)
+}
+
+/// Which entry prices a model today (settings UI preview)
+#[tauri::command]
+pub async fn resolve_model_pricing(
+    engine: EngineKind,
+    model: String,
+) -> Result<Option<PricingEntry>, String> {
+    Ok(resolve(engine, &model, Local::now().date_naive()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults_only() -> Catalog {
+        Catalog {
+            overrides: Vec::new(),
+            defaults: compile_catalog(default_catalog()).unwrap(),
+        }
+    }
+
+    fn input_price(catalog: &Catalog, engine: EngineKind, model: &str) -> Option<f64> {
+        let today = Local::now().date_naive();
+        catalog
+            .resolve(engine, model, today)
+            .map(|e| e.prices.input)
+    }
+
+    #[test]
+    fn defaults_resolve_known_models() {
+        let catalog = defaults_only();
+        let claude = |m| input_price(&catalog, EngineKind::Claude, m);
+        assert_eq!(
+            claude("anthropic.claude-opus-4-1-20250805-v1:0"),
+            Some(15.0)
+        );
+        assert_eq!(claude("claude-haiku-4-5@20251001"), Some(1.0));
+        assert_eq!(claude("claude-sonnet-4-6"), Some(3.0));
+        assert_eq!(claude("my-proxy-model"), None);
+
+        assert_eq!(
+            input_price(&catalog, EngineKind::Codex, "gpt-5.1-codex-mini"),
+            Some(0.25)
+        );
+        assert_eq!(
+            input_price(&catalog, EngineKind::Codex, "custom"),
+            Some(2.5)
+        );
+        assert_eq!(
+            input_price(&catalog, EngineKind::Gemini, "gemini-2.5-flash-lite"),
+            Some(0.1)
+        );
+    }
+
+    #[test]
+    fn versions_and_tiers() {
+        let entry = |from: &str, input: f64| PricingEntry {
+            name: "Proxy".to_string(),
+            engine: None,
+            patterns: vec!["proxy-*".to_string()],
+            regex: None,
+            effective_from: Some(from.parse().unwrap()),
+            prices: Prices {
+                input,
+                output: 0.0,
+                cache_write: 0.0,
+                cache_read: 0.0,
+            },
+            tiers: vec![PricingTier {
+                above_prompt_tokens: 1000,
+                prices: Prices {
+                    input: input * 2.0,
+                    ..Default::default()
+                },
+            }],
+        };
+        let entries = compile_catalog(PricingCatalog {
+            models: vec![entry("2026-01-01", 1.0), entry("2026-03-01", 3.0)],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let on = |date: &str| {
+            resolve_in(
+                &entries,
+                EngineKind::Claude,
+                "proxy-large",
+                date.parse().unwrap(),
+            )
+            .map(|e| e.prices.input)
+        };
+        assert_eq!(on("2025-12-31"), None);
+        assert_eq!(on("2026-02-15"), Some(1.0));
+        assert_eq!(on("2026-04-01"), Some(3.0));
+
+        let latest = resolve_in(
+            &entries,
+            EngineKind::Claude,
+            "proxy-large",
+            "2026-04-01".parse().unwrap(),
+        )
+        .unwrap();
+        let tokens = |input| TokenCounts {
+            input,
+            ..Default::default()
+        };
+        assert_eq!(price_with(&latest, &tokens(1000)), 0.003);
+        assert_eq!(price_with(&latest, &tokens(1_000_000)), 6.0);
+    }
+}
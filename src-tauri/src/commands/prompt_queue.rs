@@ -0,0 +1,675 @@
+//! Prompt Queue
+//!
+//! Schedules prompts per project path instead of starting another CLI process
+//! for every send. Without it, two agents can edit the same tree at once and the
+//! per-prompt git records written by `prompt_tracker` interleave.
+//!
+//! - Global and per-project concurrency caps (~/.anycode/prompt_queue.json)
+//! - Higher `priority` runs first; equal priorities keep queue order, which
+//!   `reorder_prompt_queue` can change
+//! - Every change emits `prompt-queue-state` with a full snapshot, and each
+//!   finished run emits `prompt-queue-item-finished`
+//!
+//! With the default per-project cap of 1, runs are bracketed by their engine's
+//! prompt-sent / prompt-completed git records here, so each record covers
+//! exactly one run.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::claude::encode_project_path;
+use super::codex::git_ops::{
+    record_codex_prompt_completed, record_codex_prompt_sent, save_codex_prompt_sent_record,
+};
+use super::engine::{spawn_watched, EngineKind, EngineRequest};
+use super::gemini::git_ops::{
+    record_gemini_prompt_completed, record_gemini_prompt_sent, save_gemini_prompt_sent_record,
+};
+use super::prompt_tracker::{
+    load_execution_config, mark_prompt_completed, record_prompt_sent, save_prompt_sent_record,
+};
+use super::session_worktree::resolve_project_path;
+use super::simple_git;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptQueueConfig {
+    /// Max runs across all projects
+    #[serde(default = "default_max_global")]
+    pub max_global: usize,
+    /// Max runs per project path (1 = strictly serial)
+    #[serde(default = "default_max_per_project")]
+    pub max_per_project: usize,
+}
+
+fn default_max_global() -> usize {
+    3
+}
+
+fn default_max_per_project() -> usize {
+    1
+}
+
+impl Default for PromptQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_global: default_max_global(),
+            max_per_project: default_max_per_project(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueItemStatus {
+    Queued,
+    Running,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedPrompt {
+    pub id: String,
+    pub engine: EngineKind,
+    pub request: EngineRequest,
+    /// CLI session to resume (None = new session)
+    pub session_id: Option<String>,
+    pub priority: i32,
+    pub status: QueueItemStatus,
+    pub enqueued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+impl QueuedPrompt {
+    fn project_key(&self) -> String {
+        project_key(&self.request.project_path)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptQueueSnapshot {
+    pub config: PromptQueueConfig,
+    pub running: usize,
+    pub queued: usize,
+    /// Running items first, then queued items in the order they will start
+    pub items: Vec<QueuedPrompt>,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    /// Queue order; position breaks priority ties
+    items: Vec<QueuedPrompt>,
+    config: Option<PromptQueueConfig>,
+}
+
+/// Tauri-managed queue state
+#[derive(Default)]
+pub struct PromptQueueState(Arc<Mutex<QueueInner>>);
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("prompt_queue.json"))
+}
+
+/// Paths differing only by a trailing separator are the same project
+fn project_key(project_path: &str) -> String {
+    project_path.trim_end_matches(['/', '\\']).to_string()
+}
+
+// ============================================================================
+// Scheduling
+// ============================================================================
+
+impl QueueInner {
+    fn config(&mut self) -> PromptQueueConfig {
+        if self.config.is_none() {
+            let config = get_config_path()
+                .and_then(|path| load_json_config(&path))
+                .unwrap_or_else(|e| {
+                    log::warn!("[PromptQueue] Failed to load config, using defaults: {}", e);
+                    PromptQueueConfig::default()
+                });
+            self.config = Some(config);
+        }
+        self.config.clone().unwrap_or_default()
+    }
+
+    fn snapshot(&mut self) -> PromptQueueSnapshot {
+        let config = self.config();
+        let (running, queued): (Vec<_>, Vec<_>) = self
+            .items
+            .iter()
+            .cloned()
+            .partition(|item| item.status == QueueItemStatus::Running);
+
+        let mut queued = queued;
+        sort_by_start_order(&mut queued);
+
+        PromptQueueSnapshot {
+            config,
+            running: running.len(),
+            queued: queued.len(),
+            items: running.into_iter().chain(queued).collect(),
+        }
+    }
+
+    /// Mark every item that may start now as running and return them
+    fn take_startable(&mut self) -> Vec<QueuedPrompt> {
+        let config = self.config();
+        let max_global = config.max_global.max(1);
+        let max_per_project = config.max_per_project.max(1);
+
+        let mut running_per_project: HashMap<String, usize> = HashMap::new();
+        for item in &self.items {
+            if item.status == QueueItemStatus::Running {
+                *running_per_project.entry(item.project_key()).or_default() += 1;
+            }
+        }
+        let mut running_total: usize = running_per_project.values().sum();
+        let mut started = Vec::new();
+
+        while running_total < max_global {
+            // Highest priority first; among equals the earliest in the queue
+            let next = self
+                .items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.status == QueueItemStatus::Queued)
+                .filter(|(_, item)| {
+                    running_per_project
+                        .get(&item.project_key())
+                        .copied()
+                        .unwrap_or(0)
+                        < max_per_project
+                })
+                .max_by(|(ia, a), (ib, b)| a.priority.cmp(&b.priority).then(ib.cmp(ia)))
+                .map(|(index, _)| index);
+
+            let Some(index) = next else {
+                break;
+            };
+
+            let item = &mut self.items[index];
+            item.status = QueueItemStatus::Running;
+            item.started_at = Some(Utc::now());
+            *running_per_project.entry(item.project_key()).or_default() += 1;
+            running_total += 1;
+            started.push(item.clone());
+        }
+
+        started
+    }
+}
+
+fn sort_by_start_order(items: &mut [QueuedPrompt]) {
+    // Stable sort keeps queue order within a priority
+    items.sort_by_key(|item| std::cmp::Reverse(item.priority));
+}
+
+fn emit_queue_state(app: &AppHandle, snapshot: &PromptQueueSnapshot) {
+    if let Err(e) = app.emit("prompt-queue-state", snapshot) {
+        log::warn!("[PromptQueue] Failed to emit prompt-queue-state: {}", e);
+    }
+}
+
+/// Start whatever the caps allow and broadcast the new queue state
+fn schedule(app: &AppHandle) {
+    let state = app.state::<PromptQueueState>();
+    let (started, snapshot) = {
+        let mut inner = state.0.lock().unwrap();
+        let started = inner.take_startable();
+        (started, inner.snapshot())
+    };
+
+    for item in started {
+        log::info!(
+            "[PromptQueue] Starting {} on {} in {}",
+            item.id,
+            item.engine,
+            item.request.project_path
+        );
+        let app = app.clone();
+        let exclusive = snapshot.config.max_per_project <= 1;
+        tauri::async_runtime::spawn(async move {
+            let result = run_item(&app, &item, exclusive).await;
+            finish_item(&app, &item, result);
+        });
+    }
+
+    emit_queue_state(app, &snapshot);
+}
+
+fn finish_item(app: &AppHandle, item: &QueuedPrompt, result: Result<Option<String>, String>) {
+    {
+        let state = app.state::<PromptQueueState>();
+        let mut inner = state.0.lock().unwrap();
+        inner.items.retain(|i| i.id != item.id);
+    }
+
+    match &result {
+        Ok(_) => log::info!("[PromptQueue] {} finished", item.id),
+        Err(e) => log::warn!("[PromptQueue] {} failed: {}", item.id, e),
+    }
+    let payload = json!({
+        "id": item.id,
+        "engine": item.engine,
+        "projectPath": item.request.project_path,
+        "sessionId": result.as_ref().ok().cloned().flatten().or_else(|| item.session_id.clone()),
+        "success": result.is_ok(),
+        "error": result.as_ref().err(),
+    });
+    let _ = app.emit("prompt-queue-item-finished", payload);
+
+    schedule(app);
+}
+
+// ============================================================================
+// Running One Item
+// ============================================================================
+
+/// Run one queued prompt to completion; returns the CLI session ID when known
+async fn run_item(
+    app: &AppHandle,
+    item: &QueuedPrompt,
+    exclusive: bool,
+) -> Result<Option<String>, String> {
+    let request = item.request.clone();
+    let records = if exclusive {
+        Some(RunRecords::begin(item.engine, item.session_id.as_deref(), &request).await)
+    } else {
+        None
+    };
+
+    let mut watch = spawn_watched(
+        app,
+        item.engine,
+        request.clone(),
+        item.session_id.as_deref(),
+        false,
+    )
+    .await?;
+    let success = watch.wait().await;
+    let session_id = item.session_id.clone().or_else(|| watch.session_id());
+    drop(watch);
+
+    if let Some(records) = records {
+        records.finish(session_id.as_deref(), &request).await;
+    }
+
+    if success {
+        Ok(session_id)
+    } else {
+        Err(format!("{} run exited with an error", item.engine))
+    }
+}
+
+/// Prompt-sent / prompt-completed git records bracketing one run
+///
+/// Shared with the headless CLI, whose runs are recorded like queued ones.
+pub struct RunRecords {
+    engine: EngineKind,
+    project_id: String,
+    prompt_index: Option<usize>,
+    head_before: Option<String>,
+}
+
+impl RunRecords {
+    /// Record the prompt before the run starts
+    pub async fn begin(
+        engine: EngineKind,
+        session_id: Option<&str>,
+        request: &EngineRequest,
+    ) -> Self {
+        let project_id = encode_project_path(&request.project_path);
+        let mut records = Self {
+            engine,
+            project_id,
+            prompt_index: None,
+            head_before: None,
+        };
+
+        match session_id {
+            Some(sid) => {
+                records.prompt_index = record_sent(engine, sid, &records.project_id, request)
+                    .await
+                    .map_err(|e| log::warn!("[PromptQueue] Failed to record prompt: {}", e))
+                    .ok();
+            }
+            // New session: the ID is only known once the CLI started, so keep
+            // the HEAD the run starts from and record it afterwards
+            None => records.head_before = capture_head(&request.project_path),
+        }
+        records
+    }
+
+    /// Commit the run's changes and complete its record once the run ended
+    pub async fn finish(mut self, session_id: Option<&str>, request: &EngineRequest) {
+        let Some(sid) = session_id else {
+            return;
+        };
+        if let Some(commit_before) = self.head_before.take() {
+            self.prompt_index =
+                record_first_prompt(self.engine, sid, &self.project_id, request, commit_before)
+                    .map_err(|e| log::warn!("[PromptQueue] Failed to record prompt: {}", e))
+                    .ok();
+        }
+        if let Some(index) = self.prompt_index {
+            if let Err(e) =
+                record_completed(self.engine, sid, &self.project_id, request, index).await
+            {
+                log::warn!("[PromptQueue] Failed to mark prompt completed: {}", e);
+            }
+        }
+    }
+}
+
+/// HEAD of the project before a new session starts (None = git records disabled)
+fn capture_head(project_path: &str) -> Option<String> {
+    match load_execution_config() {
+        Ok(config) if config.disable_rewind_git_operations => return None,
+        Ok(_) => {}
+        Err(e) => {
+            log::warn!("[PromptQueue] Failed to load execution config: {}", e);
+            return None;
+        }
+    }
+    simple_git::ensure_git_repo(project_path)
+        .and_then(|_| simple_git::git_current_commit(project_path))
+        .map_err(|e| log::warn!("[PromptQueue] Failed to read HEAD: {}", e))
+        .ok()
+}
+
+/// Record a prompt to an existing session through the engine's prompt tracker
+async fn record_sent(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: &str,
+    request: &EngineRequest,
+) -> Result<usize, String> {
+    let (sid, path, prompt) = (
+        session_id.to_string(),
+        request.project_path.clone(),
+        request.prompt.clone(),
+    );
+    match engine {
+        EngineKind::Claude => record_prompt_sent(sid, project_id.to_string(), path, prompt).await,
+        EngineKind::Codex => record_codex_prompt_sent(sid, path, prompt).await,
+        EngineKind::Gemini => record_gemini_prompt_sent(sid, path, prompt).await,
+    }
+}
+
+/// Record the first prompt of a new session, which ran from `commit_before`
+fn record_first_prompt(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: &str,
+    request: &EngineRequest,
+    commit_before: String,
+) -> Result<usize, String> {
+    let path = resolve_project_path(session_id, request.project_path.clone());
+    match engine {
+        EngineKind::Claude => save_prompt_sent_record(session_id, project_id, 0, commit_before),
+        EngineKind::Codex => save_codex_prompt_sent_record(session_id, &path, 0, commit_before),
+        EngineKind::Gemini => save_gemini_prompt_sent_record(session_id, &path, 0, commit_before),
+    }?;
+    Ok(0)
+}
+
+/// Commit the run's changes and complete its git record
+async fn record_completed(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: &str,
+    request: &EngineRequest,
+    prompt_index: usize,
+) -> Result<(), String> {
+    let (sid, path, prompt) = (
+        session_id.to_string(),
+        request.project_path.clone(),
+        Some(request.prompt.clone()),
+    );
+    match engine {
+        EngineKind::Claude => {
+            mark_prompt_completed(sid, project_id.to_string(), path, prompt_index, prompt).await
+        }
+        EngineKind::Codex => record_codex_prompt_completed(sid, path, prompt_index, prompt).await,
+        EngineKind::Gemini => record_gemini_prompt_completed(sid, path, prompt_index, prompt).await,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Add a prompt to its project's queue; it starts as soon as the caps allow
+#[tauri::command]
+pub async fn enqueue_prompt(
+    app: AppHandle,
+    state: State<'_, PromptQueueState>,
+    engine: EngineKind,
+    request: EngineRequest,
+    session_id: Option<String>,
+    priority: Option<i32>,
+) -> Result<QueuedPrompt, String> {
+    if request.project_path.trim().is_empty() {
+        return Err("Project path is required".to_string());
+    }
+
+    let item = QueuedPrompt {
+        id: uuid::Uuid::new_v4().to_string(),
+        engine,
+        request,
+        session_id: session_id.filter(|s| !s.is_empty()),
+        priority: priority.unwrap_or(0),
+        status: QueueItemStatus::Queued,
+        enqueued_at: Utc::now(),
+        started_at: None,
+    };
+    log::info!(
+        "[PromptQueue] Enqueued {} ({}) for {}",
+        item.id,
+        engine,
+        item.request.project_path
+    );
+
+    state.0.lock().unwrap().items.push(item.clone());
+    schedule(&app);
+    Ok(item)
+}
+
+/// Current queue snapshot
+#[tauri::command]
+pub async fn get_prompt_queue(
+    state: State<'_, PromptQueueState>,
+) -> Result<PromptQueueSnapshot, String> {
+    Ok(state.0.lock().unwrap().snapshot())
+}
+
+/// Drop a queued prompt (running prompts are stopped with engine_cancel)
+#[tauri::command]
+pub async fn remove_queued_prompt(
+    app: AppHandle,
+    state: State<'_, PromptQueueState>,
+    id: String,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut inner = state.0.lock().unwrap();
+        let item = inner
+            .items
+            .iter()
+            .find(|i| i.id == id)
+            .ok_or_else(|| format!("Queued prompt not found: {}", id))?;
+        if item.status == QueueItemStatus::Running {
+            return Err("Prompt is already running; cancel the session instead".to_string());
+        }
+        inner.items.retain(|i| i.id != id);
+        inner.snapshot()
+    };
+    emit_queue_state(&app, &snapshot);
+    Ok(())
+}
+
+/// Change a queued prompt's priority
+#[tauri::command]
+pub async fn set_queued_prompt_priority(
+    app: AppHandle,
+    state: State<'_, PromptQueueState>,
+    id: String,
+    priority: i32,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut inner = state.0.lock().unwrap();
+        let item = inner
+            .items
+            .iter_mut()
+            .find(|i| i.id == id)
+            .ok_or_else(|| format!("Queued prompt not found: {}", id))?;
+        item.priority = priority;
+        inner.snapshot()
+    };
+    emit_queue_state(&app, &snapshot);
+    Ok(())
+}
+
+/// Reorder a project's queued prompts; `ids` lists them in the desired order
+///
+/// IDs that are missing from the list keep their relative order after the listed ones.
+#[tauri::command]
+pub async fn reorder_prompt_queue(
+    app: AppHandle,
+    state: State<'_, PromptQueueState>,
+    project_path: String,
+    ids: Vec<String>,
+) -> Result<(), String> {
+    let key = project_key(&project_path);
+    let snapshot = {
+        let mut inner = state.0.lock().unwrap();
+        let in_project = |item: &QueuedPrompt| {
+            item.status == QueueItemStatus::Queued && item.project_key() == key
+        };
+
+        // Slots this project's queued items occupy, refilled in the new order
+        let slots: Vec<usize> = inner
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| in_project(item))
+            .map(|(index, _)| index)
+            .collect();
+        let mut project_items: Vec<QueuedPrompt> = slots
+            .iter()
+            .map(|&index| inner.items[index].clone())
+            .collect();
+        project_items.sort_by_key(|item| {
+            ids.iter()
+                .position(|id| *id == item.id)
+                .unwrap_or(usize::MAX)
+        });
+
+        for (slot, item) in slots.into_iter().zip(project_items) {
+            inner.items[slot] = item;
+        }
+        inner.snapshot()
+    };
+    emit_queue_state(&app, &snapshot);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_prompt_queue_config(
+    state: State<'_, PromptQueueState>,
+) -> Result<PromptQueueConfig, String> {
+    Ok(state.0.lock().unwrap().config())
+}
+
+/// Save new caps and start anything the higher limits now allow
+#[tauri::command]
+pub async fn update_prompt_queue_config(
+    app: AppHandle,
+    state: State<'_, PromptQueueState>,
+    config: PromptQueueConfig,
+) -> Result<(), String> {
+    if config.max_global == 0 || config.max_per_project == 0 {
+        return Err("Concurrency limits must be at least 1".to_string());
+    }
+    save_json_config(&config, &get_config_path()?)?;
+    state.0.lock().unwrap().config = Some(config);
+    schedule(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, project: &str, priority: i32) -> QueuedPrompt {
+        QueuedPrompt {
+            id: id.to_string(),
+            engine: EngineKind::Claude,
+            request: EngineRequest {
+                project_path: project.to_string(),
+                prompt: "test".to_string(),
+                ..Default::default()
+            },
+            session_id: None,
+            priority,
+            status: QueueItemStatus::Queued,
+            enqueued_at: Utc::now(),
+            started_at: None,
+        }
+    }
+
+    fn queue(items: Vec<QueuedPrompt>, max_global: usize, max_per_project: usize) -> QueueInner {
+        QueueInner {
+            items,
+            config: Some(PromptQueueConfig {
+                max_global,
+                max_per_project,
+            }),
+        }
+    }
+
+    fn ids(items: &[QueuedPrompt]) -> Vec<&str> {
+        items.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_per_project_cap() {
+        let mut q = queue(
+            vec![
+                item("a1", "/a", 0),
+                item("a2", "/a/", 0),
+                item("b1", "/b", 0),
+            ],
+            3,
+            1,
+        );
+        assert_eq!(ids(&q.take_startable()), vec!["a1", "b1"]);
+        // Nothing else fits until a run finishes
+        assert!(q.take_startable().is_empty());
+
+        q.items.retain(|i| i.id != "a1");
+        assert_eq!(ids(&q.take_startable()), vec!["a2"]);
+    }
+
+    #[test]
+    fn test_global_cap_and_priority() {
+        let mut q = queue(
+            vec![item("a", "/a", 0), item("b", "/b", 5), item("c", "/c", 5)],
+            2,
+            1,
+        );
+        assert_eq!(ids(&q.take_startable()), vec!["b", "c"]);
+    }
+}
@@ -7,7 +7,10 @@ use std::fs;
 use std::path::PathBuf;
 
 use super::claude::get_claude_dir;
+use super::engine::EngineKind;
 use super::permission_config::ClaudeExecutionConfig;
+use super::session_archive::{locate_session_file, read_session_file, rehydrate};
+use super::session_worktree::resolve_project_path;
 use super::simple_git;
 
 /// Rewind mode for reverting prompts
@@ -68,6 +71,9 @@ pub struct GitRecord {
     pub commit_after: Option<String>,
     /// Timestamp when prompt was sent
     pub timestamp: i64,
+    /// Why the run was stopped early (e.g. "inactivity_timeout")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
 }
 
 /// Load execution config from file
@@ -168,6 +174,19 @@ fn get_git_record(
     Ok(records.get(&prompt_index).cloned())
 }
 
+/// Note why the latest prompt's run was stopped; false when there is no record
+pub fn record_stop_reason(session_id: &str, project_id: &str, reason: &str) -> Result<bool> {
+    let mut records = load_git_records(session_id, project_id)?;
+    let Some(latest) = records.keys().max().copied() else {
+        return Ok(false);
+    };
+    if let Some(record) = records.get_mut(&latest) {
+        record.stop_reason = Some(reason.to_string());
+    }
+    save_git_records(session_id, project_id, &records)?;
+    Ok(true)
+}
+
 fn build_prompt_commit_message(
     prefix: &str,
     prompt_text: Option<&str>,
@@ -500,6 +519,75 @@ fn truncate_session_to_prompt(
     Ok(())
 }
 
+/// First `line_count` lines of a session, rewritten to belong to `new_session_id`
+fn fork_session_lines(content: &str, line_count: usize, new_session_id: &str) -> String {
+    // 每一行都带 sessionId，改写为新会话 ID（无法解析的行原样保留）
+    let mut new_content = String::new();
+    for line in content.lines().take(line_count) {
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(mut msg) => {
+                if msg.get("sessionId").is_some() {
+                    msg["sessionId"] = serde_json::Value::String(new_session_id.to_string());
+                }
+                new_content.push_str(&msg.to_string());
+            }
+            Err(_) => new_content.push_str(line),
+        }
+        new_content.push('\n');
+    }
+    new_content
+}
+
+/// Copy the session up to (not including) a prompt into a new session file
+///
+/// Unlike `truncate_session_to_prompt`, the original JSONL and its git records
+/// are left as they are. Returns the new session ID.
+pub fn fork_session_at_prompt(
+    session_id: &str,
+    project_id: &str,
+    prompt_index: usize,
+) -> Result<String, String> {
+    let prompts = extract_prompts_from_jsonl(session_id, project_id)
+        .map_err(|e| format!("Failed to extract prompts from JSONL: {}", e))?;
+    let prompt = prompts
+        .get(prompt_index)
+        .ok_or_else(|| format!("Prompt #{} not found", prompt_index))?;
+
+    let claude_dir = get_claude_dir().map_err(|e| format!("Failed to get claude dir: {}", e))?;
+    let project_dir = claude_dir.join("projects").join(project_id);
+    // 源会话可能已被归档压缩；分叉出的新会话始终写为普通文件
+    let content = read_session_file(&project_dir.join(format!("{}.jsonl", session_id)))
+        .map_err(|e| format!("Failed to read session file: {}", e))?;
+
+    let new_session_id = uuid::Uuid::new_v4().to_string();
+    let new_content = fork_session_lines(&content, prompt.line_number, &new_session_id);
+
+    fs::write(
+        project_dir.join(format!("{}.jsonl", new_session_id)),
+        new_content,
+    )
+    .map_err(|e| format!("Failed to write forked session: {}", e))?;
+
+    // Carry over git records for the prompts that were copied
+    let mut records = load_git_records(session_id, project_id)
+        .map_err(|e| format!("Failed to load git records: {}", e))?;
+    records.retain(|idx, _| *idx < prompt_index);
+    if !records.is_empty() {
+        save_git_records(&new_session_id, project_id, &records)
+            .map_err(|e| format!("Failed to save git records: {}", e))?;
+    }
+
+    log::info!(
+        "[Fork] Forked session {} at prompt #{} into {} ({} lines)",
+        session_id,
+        prompt_index,
+        new_session_id,
+        prompt.line_number
+    );
+
+    Ok(new_session_id)
+}
+
 /// Record a prompt being sent
 #[tauri::command]
 pub async fn record_prompt_sent(
@@ -508,6 +596,7 @@ pub async fn record_prompt_sent(
     project_path: String,
     _prompt_text: String,
 ) -> Result<usize, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!(
         "[Record Prompt] Recording prompt sent for session: {}",
         session_id
@@ -553,25 +642,37 @@ pub async fn record_prompt_sent(
         prompt_index
     );
 
-    // Create git record
-    let git_record = GitRecord {
-        commit_before: commit_before.clone(),
-        commit_after: None,
-        timestamp: Utc::now().timestamp(),
-    };
+    save_prompt_sent_record(&session_id, &project_id, prompt_index, commit_before)?;
 
-    // 🔧 FIX: Save git record using prompt_index as key (not hash!)
-    // This is reliable and not affected by translation/encoding/escaping
-    save_git_record(&session_id, &project_id, prompt_index, git_record)
-        .map_err(|e| format!("Failed to save git record: {}", e))?;
+    Ok(prompt_index)
+}
 
+/// Save the git record of a prompt about to run from `commit_before`
+///
+/// Also used by the prompt queue for new sessions, whose ID (and so record
+/// file) is only known after the run, with the HEAD captured before it.
+pub(crate) fn save_prompt_sent_record(
+    session_id: &str,
+    project_id: &str,
+    prompt_index: usize,
+    commit_before: String,
+) -> Result<(), String> {
     log::info!(
-        "[Record Prompt] ✅ Saved git record for prompt #{} with commit_before: {}",
+        "[Record Prompt] Saving git record for prompt #{} with commit_before: {}",
         prompt_index,
         commit_before
     );
+    let git_record = GitRecord {
+        commit_before,
+        commit_after: None,
+        timestamp: Utc::now().timestamp(),
+        stop_reason: None,
+    };
 
-    Ok(prompt_index)
+    // 🔧 FIX: Save git record using prompt_index as key (not hash!)
+    // This is reliable and not affected by translation/encoding/escaping
+    save_git_record(session_id, project_id, prompt_index, git_record)
+        .map_err(|e| format!("Failed to save git record: {}", e))
 }
 
 /// Mark a prompt as completed (after AI finishes)
@@ -583,6 +684,7 @@ pub async fn mark_prompt_completed(
     prompt_index: usize,
     prompt_text: Option<String>,
 ) -> Result<(), String> {
+    let project_path = resolve_project_path(&session_id, project_path);
     log::info!("Marking prompt #{} completed", prompt_index);
 
     // Check if Git operations are disabled in config
@@ -650,6 +752,8 @@ pub async fn revert_to_prompt(
     prompt_index: usize,
     mode: RewindMode,
 ) -> Result<String, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
+    rehydrate(EngineKind::Claude, &session_id, Some(&project_id), None)?;
     log::info!(
         "Reverting to prompt #{} in session: {} with mode: {:?}",
         prompt_index,
@@ -1205,11 +1309,12 @@ fn extract_prompts_from_jsonl(session_id: &str, project_id: &str) -> Result<Vec<
         .join(project_id)
         .join(format!("{}.jsonl", session_id));
 
-    if !session_path.exists() {
+    // 已归档的会话以 .jsonl.zst 存放，同样可以读取
+    if locate_session_file(&session_path).is_none() {
         return Ok(Vec::new());
     }
 
-    let content = fs::read_to_string(&session_path).context("Failed to read session file")?;
+    let content = read_session_file(&session_path).context("Failed to read session file")?;
 
     let mut prompts = Vec::new();
     let mut prompt_index = 0;
@@ -1388,3 +1493,32 @@ pub async fn get_unified_prompt_list(
 
     Ok(prompts)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fork_lines_keep_history_before_prompt_under_new_id() {
+        let content = [
+            r#"{"type":"summary","summary":"demo"}"#,
+            r#"{"type":"user","sessionId":"old","message":{"role":"user","content":"first"}}"#,
+            r#"{"type":"assistant","sessionId":"old","message":{"role":"assistant","content":"ok"}}"#,
+            "not json",
+            r#"{"type":"user","sessionId":"old","message":{"role":"user","content":"second"}}"#,
+        ]
+        .join("\n");
+
+        let forked = fork_session_lines(&content, 4, "new");
+        let lines: Vec<&str> = forked.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(!lines[0].contains("sessionId"));
+        for line in &lines[1..3] {
+            let msg: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(msg["sessionId"], "new");
+        }
+        assert_eq!(lines[3], "not json");
+        assert!(forked.ends_with('\n'));
+    }
+}
@@ -2,8 +2,10 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
-use tauri::{command, AppHandle};
+use tauri::{command, AppHandle, Runtime};
 
+use super::engine::EngineKind;
+use super::provider_history;
 use super::url_utils::{normalize_api_url, normalize_base_url, ApiEndpointType};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -278,8 +280,8 @@ pub fn get_current_provider_config() -> Result<CurrentConfig, String> {
 
 // 切换代理商配置（写入settings.json的env字段）
 #[command]
-pub async fn switch_provider_config(
-    _app: AppHandle,
+pub async fn switch_provider_config<R: Runtime>(
+    _app: AppHandle<R>,
     config: ProviderConfig,
 ) -> Result<String, String> {
     log::info!(
@@ -291,6 +293,8 @@ pub async fn switch_provider_config(
     // 验证第三方API配置
     validate_third_party_config(&config)?;
 
+    let previous = provider_history::current_base_url(EngineKind::Claude).await;
+
     let mut settings = load_settings()?;
 
     // 确保env字段存在
@@ -410,6 +414,7 @@ pub async fn switch_provider_config(
 
     // 保存设置
     save_settings(&settings)?;
+    provider_history::record_switch(EngineKind::Claude, previous).await;
 
     log::info!("代理商配置切换完成: {}", config.name);
 
@@ -461,6 +466,7 @@ fn validate_third_party_config(config: &ProviderConfig) -> Result<(), String> {
 pub async fn clear_provider_config(_app: AppHandle) -> Result<String, String> {
     log::info!("开始清理代理商配置");
 
+    let previous = provider_history::current_base_url(EngineKind::Claude).await;
     let mut settings = load_settings()?;
 
     // 如果有env字段，清理ANTHROPIC相关变量
@@ -481,6 +487,7 @@ pub async fn clear_provider_config(_app: AppHandle) -> Result<String, String> {
 
     // 保存设置
     save_settings(&settings)?;
+    provider_history::record_switch(EngineKind::Claude, previous).await;
 
     log::info!("代理商配置清理完成");
 
@@ -0,0 +1,173 @@
+//! Provider switch history
+//!
+//! Session history files don't record which endpoint served a request, so
+//! every provider switch (including a reset to the official endpoint) is
+//! logged with its time. Usage can then be attributed to the base URL that
+//! was active when it happened.
+//!
+//! The first logged switch of an engine also records the provider it
+//! replaced, dated at the Unix epoch, so older usage gets attributed too.
+//!
+//! - Log: ~/.anycode/provider_history.json
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::engine::EngineKind;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderSwitch {
+    pub engine: EngineKind,
+    pub base_url: String,
+    pub switched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderHistory {
+    #[serde(default)]
+    pub switches: Vec<ProviderSwitch>,
+}
+
+impl ProviderHistory {
+    /// Base URL `engine` used at `at`; None when nothing was logged before it
+    pub fn base_url_at(&self, engine: EngineKind, at: DateTime<Utc>) -> Option<&str> {
+        self.switches
+            .iter()
+            .filter(|s| s.engine == engine && s.switched_at <= at)
+            .max_by_key(|s| s.switched_at)
+            .map(|s| s.base_url.as_str())
+    }
+
+    /// Log a switch from `previous` to `current`; returns whether anything changed
+    fn push(
+        &mut self,
+        engine: EngineKind,
+        previous: &str,
+        current: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let logged = self.base_url_at(engine, now).map(str::to_string);
+        if logged.is_none() {
+            self.switches.push(ProviderSwitch {
+                engine,
+                base_url: previous.to_string(),
+                switched_at: DateTime::<Utc>::UNIX_EPOCH,
+            });
+        }
+        if logged.as_deref().unwrap_or(previous) == current {
+            return logged.is_none();
+        }
+        self.switches.push(ProviderSwitch {
+            engine,
+            base_url: current.to_string(),
+            switched_at: now,
+        });
+        true
+    }
+}
+
+fn get_history_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("provider_history.json"))
+}
+
+pub fn load_history() -> ProviderHistory {
+    get_history_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!(
+                "[ProviderHistory] Failed to load history, starting empty: {}",
+                e
+            );
+            ProviderHistory::default()
+        })
+}
+
+// ============================================================================
+// Current provider
+// ============================================================================
+
+/// Endpoint an engine talks to when no provider is configured
+pub fn official_base_url(engine: EngineKind) -> &'static str {
+    match engine {
+        EngineKind::Claude => "https://api.anthropic.com",
+        EngineKind::Codex => "https://api.openai.com/v1",
+        EngineKind::Gemini => "https://generativelanguage.googleapis.com",
+    }
+}
+
+/// Base URL the engine is configured with right now
+pub async fn current_base_url(engine: EngineKind) -> String {
+    let configured = match engine {
+        EngineKind::Claude => super::provider::get_current_provider_config()
+            .ok()
+            .and_then(|c| c.anthropic_base_url),
+        EngineKind::Codex => super::codex::config::get_current_codex_config()
+            .await
+            .ok()
+            .and_then(|c| c.base_url),
+        EngineKind::Gemini => super::gemini::provider::get_current_gemini_provider_config()
+            .await
+            .ok()
+            .and_then(|c| c.base_url),
+    };
+    configured
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| official_base_url(engine).to_string())
+}
+
+/// Log a provider change of `engine`
+///
+/// `previous` is what `current_base_url` returned before the change was written.
+pub async fn record_switch(engine: EngineKind, previous: String) {
+    let current = current_base_url(engine).await;
+    let mut history = load_history();
+    if !history.push(engine, &previous, &current, Utc::now()) {
+        return;
+    }
+    let saved = get_history_path().and_then(|path| save_json_config(&history, path));
+    if let Err(e) = saved {
+        log::warn!("[ProviderHistory] Failed to save history: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn first_switch_also_logs_the_replaced_provider() {
+        let mut history = ProviderHistory::default();
+        let june = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let july = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+
+        assert!(history.push(EngineKind::Claude, "https://a", "https://b", june));
+        // Re-applying the active provider logs nothing
+        assert!(!history.push(EngineKind::Claude, "https://b", "https://b", july));
+        assert!(history.push(EngineKind::Claude, "https://b", "https://c", july));
+
+        let may = Utc.with_ymd_and_hms(2025, 5, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            history.base_url_at(EngineKind::Claude, may),
+            Some("https://a")
+        );
+        assert_eq!(
+            history.base_url_at(EngineKind::Claude, june),
+            Some("https://b")
+        );
+        assert_eq!(
+            history.base_url_at(EngineKind::Claude, july),
+            Some("https://c")
+        );
+        assert_eq!(history.base_url_at(EngineKind::Codex, july), None);
+    }
+}
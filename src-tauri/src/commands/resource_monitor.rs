@@ -0,0 +1,279 @@
+//! Per-session resource monitor
+//!
+//! Periodically samples every run in `ProcessRegistry` (Claude, Codex and
+//! Gemini sessions, agent runs) for CPU, RSS, child processes and MCP servers
+//! (see `process::resources`), and stores the result on its `ProcessInfo`, so
+//! it shows up in `list_process_resources`.
+//!
+//! When a run crosses one of the configured thresholds a
+//! `session-resource-alert` event is emitted. Each metric alerts once per
+//! crossing and re-arms after dropping back below the threshold.
+//!
+//! - Config: ~/.anycode/resource_monitor.json
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::process::resources::{ProcTable, ProcessResources, ResourceSampler};
+use crate::process::{ProcessInfo, ProcessRegistryState, ProcessType};
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+const MIN_INTERVAL_SECS: u64 = 1;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Thresholds of 0 are disabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceMonitorConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// CPU of the whole process tree, 100 = one core
+    #[serde(default = "default_cpu_percent")]
+    pub cpu_percent: f64,
+    #[serde(default = "default_rss_mb")]
+    pub rss_mb: u64,
+    #[serde(default = "default_child_processes")]
+    pub child_processes: usize,
+    #[serde(default = "default_mcp_servers")]
+    pub mcp_servers: usize,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval_secs() -> u64 {
+    5
+}
+
+fn default_cpu_percent() -> f64 {
+    400.0
+}
+
+fn default_rss_mb() -> u64 {
+    4096
+}
+
+fn default_child_processes() -> usize {
+    64
+}
+
+fn default_mcp_servers() -> usize {
+    16
+}
+
+impl Default for ResourceMonitorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            interval_secs: default_interval_secs(),
+            cpu_percent: default_cpu_percent(),
+            rss_mb: default_rss_mb(),
+            child_processes: default_child_processes(),
+            mcp_servers: default_mcp_servers(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceAlert {
+    pub run_id: i64,
+    pub session_id: Option<String>,
+    pub pid: u32,
+    /// "cpu" | "memory" | "children" | "mcpServers"
+    pub metric: &'static str,
+    pub value: f64,
+    pub threshold: f64,
+    pub resources: ProcessResources,
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("resource_monitor.json"))
+}
+
+fn load_config() -> ResourceMonitorConfig {
+    get_config_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!(
+                "[ResourceMonitor] Failed to load config, using defaults: {}",
+                e
+            );
+            ResourceMonitorConfig::default()
+        })
+}
+
+// ============================================================================
+// Sampling
+// ============================================================================
+
+/// (metric, value, threshold) for every enabled threshold
+fn measurements(
+    config: &ResourceMonitorConfig,
+    resources: &ProcessResources,
+) -> Vec<(&'static str, f64, f64)> {
+    [
+        ("cpu", resources.cpu_percent, config.cpu_percent),
+        (
+            "memory",
+            resources.rss_bytes as f64 / (1024.0 * 1024.0),
+            config.rss_mb as f64,
+        ),
+        (
+            "children",
+            resources.child_processes as f64,
+            config.child_processes as f64,
+        ),
+        (
+            "mcpServers",
+            resources.mcp_servers.len() as f64,
+            config.mcp_servers as f64,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, _, threshold)| *threshold > 0.0)
+    .collect()
+}
+
+fn session_id_of(info: &ProcessInfo) -> Option<String> {
+    match &info.process_type {
+        ProcessType::ClaudeSession { session_id }
+        | ProcessType::EngineSession { session_id, .. } => Some(session_id.clone()),
+        ProcessType::AgentRun { .. } => None,
+    }
+}
+
+/// Sample all registered runs once; returns alerts for new threshold crossings
+fn sample_round(
+    app: &AppHandle,
+    config: &ResourceMonitorConfig,
+    sampler: &mut ResourceSampler,
+    alerted: &mut HashSet<(i64, &'static str)>,
+) -> Result<Vec<ResourceAlert>, String> {
+    let registry = app.state::<ProcessRegistryState>();
+    let processes = registry.0.get_running_processes()?;
+    let table = ProcTable::read();
+
+    let mut alerts = Vec::new();
+    for info in &processes {
+        let resources = sampler.sample(&table, info.pid);
+        registry
+            .0
+            .update_resources(info.run_id, resources.clone())?;
+        let Some(resources) = resources else {
+            continue;
+        };
+
+        for (metric, value, threshold) in measurements(config, &resources) {
+            if value < threshold {
+                alerted.remove(&(info.run_id, metric));
+            } else if alerted.insert((info.run_id, metric)) {
+                alerts.push(ResourceAlert {
+                    run_id: info.run_id,
+                    session_id: session_id_of(info),
+                    pid: info.pid,
+                    metric,
+                    value,
+                    threshold,
+                    resources: resources.clone(),
+                });
+            }
+        }
+    }
+
+    let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+    sampler.retain(&pids);
+    alerted.retain(|(run_id, _)| processes.iter().any(|p| p.run_id == *run_id));
+    Ok(alerts)
+}
+
+/// Start the background sampler; the config is re-read every round
+pub fn start_resource_monitor(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut sampler = ResourceSampler::new();
+        let mut alerted = HashSet::new();
+
+        loop {
+            let config = load_config();
+            if config.enabled {
+                // Reading the process table is blocking I/O; keep it off the async workers
+                let app_round = app.clone();
+                let config_round = config.clone();
+                let round = tauri::async_runtime::spawn_blocking(move || {
+                    let result =
+                        sample_round(&app_round, &config_round, &mut sampler, &mut alerted);
+                    (sampler, alerted, result)
+                })
+                .await;
+                let result = match round {
+                    Ok((round_sampler, round_alerted, result)) => {
+                        sampler = round_sampler;
+                        alerted = round_alerted;
+                        result
+                    }
+                    Err(e) => {
+                        sampler = ResourceSampler::new();
+                        alerted = HashSet::new();
+                        Err(format!("sampling task failed: {}", e))
+                    }
+                };
+                match result {
+                    Ok(alerts) => {
+                        for alert in alerts {
+                            log::warn!(
+                                "[ResourceMonitor] Run {} (PID: {}) crossed {} threshold: {:.1} >= {}",
+                                alert.run_id,
+                                alert.pid,
+                                alert.metric,
+                                alert.value,
+                                alert.threshold
+                            );
+                            let _ = app.emit("session-resource-alert", &alert);
+                        }
+                    }
+                    Err(e) => log::warn!("[ResourceMonitor] Sampling failed: {}", e),
+                }
+            }
+            let interval = config.interval_secs.max(MIN_INTERVAL_SECS);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// All registered runs (sessions and agent runs) with their latest sample
+#[tauri::command]
+pub async fn list_process_resources(
+    registry: State<'_, ProcessRegistryState>,
+) -> Result<Vec<ProcessInfo>, String> {
+    let mut processes = registry.0.get_running_processes()?;
+    processes.sort_by_key(|p| p.started_at);
+    Ok(processes)
+}
+
+#[tauri::command]
+pub async fn get_resource_monitor_config() -> Result<ResourceMonitorConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_resource_monitor_config(
+    config: ResourceMonitorConfig,
+) -> Result<ResourceMonitorConfig, String> {
+    save_json_config(&config, get_config_path()?)?;
+    Ok(config)
+}
@@ -0,0 +1,470 @@
+//! Session Archive (历史会话压缩归档)
+//!
+//! Sessions untouched for `archiveAfterDays` are compressed in place with
+//! zstd: `{id}.jsonl` becomes `{id}.jsonl.zst` (Gemini: `session-*.json.zst`),
+//! keeping the original modification time so listings keep their order.
+//!
+//! Readers go through `open_session_file` / `read_session_file`, which accept
+//! either form, so history views, usage stats and search see archived sessions
+//! like any other. The CLIs cannot read archives, so the resume, rewind and
+//! fork paths of every engine call `rehydrate` to restore the plain file first.
+//!
+//! - Config: ~/.anycode/session_archive.json (disabled by default)
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::engine::EngineKind;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+pub const ARCHIVE_EXTENSION: &str = "zst";
+
+const COMPRESSION_LEVEL: i32 = 9;
+
+/// How often the background archiver looks for old sessions
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionArchiveConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Sessions not modified for this many days are compressed
+    #[serde(default = "default_archive_after_days")]
+    pub archive_after_days: u32,
+}
+
+fn default_archive_after_days() -> u32 {
+    30
+}
+
+impl Default for SessionArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            archive_after_days: default_archive_after_days(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionArchiveStats {
+    pub archived: usize,
+    pub failed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("session_archive.json"))
+}
+
+fn load_config() -> SessionArchiveConfig {
+    get_config_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[Archive] Failed to load config, using defaults: {}", e);
+            SessionArchiveConfig::default()
+        })
+}
+
+// ============================================================================
+// Transparent reading
+// ============================================================================
+
+/// `a.jsonl` → `a.jsonl.zst`
+pub fn archived_path(path: &Path) -> PathBuf {
+    let mut archived = path.as_os_str().to_owned();
+    archived.push(".");
+    archived.push(ARCHIVE_EXTENSION);
+    PathBuf::from(archived)
+}
+
+pub fn is_archived(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(ARCHIVE_EXTENSION)
+}
+
+/// `a.jsonl.zst` → `a.jsonl`; plain paths are returned unchanged
+pub fn unarchived_path(path: &Path) -> PathBuf {
+    if is_archived(path) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Whether `path` is a session file with extension `ext`, plain or archived
+pub fn is_session_file(path: &Path, ext: &str) -> bool {
+    unarchived_path(path).extension().and_then(|e| e.to_str()) == Some(ext)
+}
+
+/// File stem without the archive extension (`{id}.jsonl.zst` → `{id}`)
+pub fn session_file_stem(path: &Path) -> Option<String> {
+    unarchived_path(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(str::to_string)
+}
+
+/// The file that currently holds a session: the plain file, else its archive
+pub fn locate_session_file(path: &Path) -> Option<PathBuf> {
+    if path.exists() {
+        return Some(path.to_path_buf());
+    }
+    let archived = archived_path(path);
+    archived.exists().then_some(archived)
+}
+
+/// Open a session file (plain or archived path) for line-by-line reading
+pub fn open_session_file(path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+    let path = locate_session_file(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{:?}", path)))?;
+    let file = fs::File::open(&path)?;
+    if is_archived(&path) {
+        Ok(Box::new(BufReader::new(zstd::Decoder::new(file)?)))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+pub fn read_session_file(path: &Path) -> io::Result<String> {
+    let mut content = String::new();
+    open_session_file(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+// ============================================================================
+// Compression
+// ============================================================================
+
+/// Write `from` through `transform` into `to`, atomically and with `from`'s mtime
+fn rewrite_file(
+    from: &Path,
+    to: &Path,
+    transform: impl FnOnce(fs::File, fs::File) -> io::Result<fs::File>,
+) -> Result<u64, String> {
+    let modified = fs::metadata(from).and_then(|m| m.modified()).ok();
+    let mut tmp = to.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let result = (|| {
+        let input = fs::File::open(from)?;
+        let output = transform(input, fs::File::create(&tmp)?)?;
+        output.sync_all()?;
+        if let Some(modified) = modified {
+            output.set_modified(modified)?;
+        }
+        drop(output);
+        fs::rename(&tmp, to)?;
+        fs::remove_file(from)?;
+        fs::metadata(to).map(|m| m.len())
+    })();
+
+    result.map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("Failed to rewrite {:?}: {}", from, e)
+    })
+}
+
+/// Compress a plain session file into `{path}.zst`; returns the archive size
+pub fn compress_session_file(path: &Path) -> Result<u64, String> {
+    rewrite_file(path, &archived_path(path), |mut input, output| {
+        let mut encoder = zstd::Encoder::new(output, COMPRESSION_LEVEL)?;
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()
+    })
+}
+
+/// Restore an archive to the plain file next to it; returns the plain path
+pub fn decompress_session_file(archive: &Path) -> Result<PathBuf, String> {
+    let plain = unarchived_path(archive);
+    rewrite_file(archive, &plain, |input, mut output| {
+        let mut decoder = zstd::Decoder::new(input)?;
+        io::copy(&mut decoder, &mut output)?;
+        Ok(output)
+    })?;
+    Ok(plain)
+}
+
+// ============================================================================
+// Archiving policy
+// ============================================================================
+
+/// Plain history files of the three engines (Claude subagent files included)
+fn plain_session_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if let Ok(claude_dir) = super::claude::get_claude_dir() {
+        let pattern = claude_dir.join("projects").join("*").join("*.jsonl");
+        files.extend(
+            glob::glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten(),
+        );
+    }
+
+    if let Ok(sessions_dir) = super::codex::get_codex_sessions_dir() {
+        files.extend(
+            walkdir::WalkDir::new(&sessions_dir)
+                .into_iter()
+                .flatten()
+                .map(|e| e.into_path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl")),
+        );
+    }
+
+    if let Ok(gemini_dir) = super::gemini::config::get_gemini_dir() {
+        let pattern = gemini_dir
+            .join("tmp")
+            .join("*")
+            .join("chats")
+            .join("session-*.json");
+        files.extend(
+            glob::glob(&pattern.to_string_lossy())
+                .into_iter()
+                .flatten()
+                .flatten(),
+        );
+    }
+
+    files
+}
+
+fn archive_files_older_than(files: Vec<PathBuf>, cutoff: SystemTime) -> SessionArchiveStats {
+    let mut stats = SessionArchiveStats::default();
+
+    for path in files {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.modified().is_ok_and(|m| m < cutoff) {
+            continue;
+        }
+
+        match compress_session_file(&path) {
+            Ok(size) => {
+                stats.archived += 1;
+                stats.bytes_before += metadata.len();
+                stats.bytes_after += size;
+            }
+            Err(e) => {
+                log::warn!("[Archive] {}", e);
+                stats.failed += 1;
+            }
+        }
+    }
+
+    stats
+}
+
+/// Compress every session not modified for `days` days
+pub fn archive_old_sessions(days: u32) -> SessionArchiveStats {
+    let cutoff = SystemTime::now() - Duration::from_secs(days as u64 * 24 * 60 * 60);
+    let stats = archive_files_older_than(plain_session_files(), cutoff);
+    log::info!(
+        "[Archive] Archived {} sessions ({} -> {} bytes, {} failed)",
+        stats.archived,
+        stats.bytes_before,
+        stats.bytes_after,
+        stats.failed
+    );
+    stats
+}
+
+/// Archive old sessions at startup and once a day, when enabled
+pub fn start_session_archiver() {
+    std::thread::spawn(|| loop {
+        let config = load_config();
+        if config.enabled {
+            archive_old_sessions(config.archive_after_days);
+        }
+        std::thread::sleep(ARCHIVE_INTERVAL);
+    });
+}
+
+// ============================================================================
+// Rehydration
+// ============================================================================
+
+/// Archives in `dir` whose plain content satisfies `belongs`
+fn archives_in(dir: &Path, belongs: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_archived(p) && belongs(p.as_path()))
+        .collect()
+}
+
+fn first_line_session_id(path: &Path) -> Option<String> {
+    let first_line = open_session_file(path).ok()?.lines().next()?.ok()?;
+    let json: serde_json::Value = serde_json::from_str(&first_line).ok()?;
+    json.get("sessionId")
+        .and_then(|s| s.as_str())
+        .map(str::to_string)
+}
+
+/// Archived files that make up a session (main file first)
+fn session_archives(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: Option<&str>,
+    project_path: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    match engine {
+        EngineKind::Claude => {
+            let project_id = project_id
+                .ok_or_else(|| "projectId is required for Claude sessions".to_string())?;
+            let project_dir = super::claude::get_claude_dir()
+                .map_err(|e| e.to_string())?
+                .join("projects")
+                .join(project_id);
+
+            let main = archived_path(&project_dir.join(format!("{}.jsonl", session_id)));
+            let mut archives: Vec<PathBuf> = main.exists().then_some(main).into_iter().collect();
+            archives.extend(archives_in(&project_dir, |p| {
+                session_file_stem(p).is_some_and(|s| s.starts_with("agent-"))
+                    && first_line_session_id(p).as_deref() == Some(session_id)
+            }));
+            Ok(archives)
+        }
+        EngineKind::Codex => {
+            let sessions_dir = super::codex::get_codex_sessions_dir()?;
+            Ok(super::codex::find_session_file(&sessions_dir, session_id)
+                .filter(|p| is_archived(p))
+                .into_iter()
+                .collect())
+        }
+        EngineKind::Gemini => {
+            let project_path = project_path
+                .ok_or_else(|| "projectPath is required for Gemini sessions".to_string())?;
+            let chats_dir =
+                super::gemini::config::get_project_session_dir(project_path)?.join("chats");
+            Ok(archives_in(&chats_dir, |p| {
+                read_session_file(p)
+                    .ok()
+                    .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+                    .is_some_and(|d| d["sessionId"].as_str() == Some(session_id))
+            }))
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_session_archive_config() -> Result<SessionArchiveConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_session_archive_config(
+    config: SessionArchiveConfig,
+) -> Result<SessionArchiveConfig, String> {
+    save_json_config(&config, get_config_path()?)?;
+    Ok(config)
+}
+
+/// Archive now; `older_than_days` defaults to the configured threshold
+#[tauri::command]
+pub async fn archive_sessions_now(
+    older_than_days: Option<u32>,
+) -> Result<SessionArchiveStats, String> {
+    let days = older_than_days.unwrap_or_else(|| load_config().archive_after_days);
+    tokio::task::spawn_blocking(move || archive_old_sessions(days))
+        .await
+        .map_err(|e| format!("Archive task failed: {}", e))
+}
+
+/// Decompress an archived session so the CLI can resume it
+///
+/// Returns the number of files restored (0 when the session was not archived).
+/// Claude needs `project_id`, Gemini `project_path`.
+pub fn rehydrate(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: Option<&str>,
+    project_path: Option<&str>,
+) -> Result<usize, String> {
+    let archives = session_archives(engine, session_id, project_id, project_path)?;
+
+    for archive in &archives {
+        decompress_session_file(archive)?;
+    }
+
+    if !archives.is_empty() {
+        log::info!(
+            "[Archive] Rehydrated {} session {} ({} files)",
+            engine.as_str(),
+            session_id,
+            archives.len()
+        );
+    }
+    Ok(archives.len())
+}
+
+#[tauri::command]
+pub async fn rehydrate_session(
+    engine: EngineKind,
+    session_id: String,
+    project_id: Option<String>,
+    project_path: Option<String>,
+) -> Result<usize, String> {
+    rehydrate(
+        engine,
+        &session_id,
+        project_id.as_deref(),
+        project_path.as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_round_trip_keeps_content_and_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("abc.jsonl");
+        let content = "{\"type\":\"user\"}\n".repeat(100);
+        fs::write(&plain, &content).unwrap();
+        let old = SystemTime::now() - Duration::from_secs(90 * 24 * 60 * 60);
+        fs::File::options()
+            .write(true)
+            .open(&plain)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let stats = archive_files_older_than(vec![plain.clone()], SystemTime::now());
+        assert_eq!(stats.archived, 1);
+        assert!(stats.bytes_after < stats.bytes_before);
+
+        let archive = archived_path(&plain);
+        assert!(!plain.exists() && archive.exists());
+        assert_eq!(fs::metadata(&archive).unwrap().modified().unwrap(), old);
+        assert!(is_session_file(&archive, "jsonl"));
+        assert_eq!(session_file_stem(&archive).as_deref(), Some("abc"));
+        // Readers accept both the plain path and the archive path
+        assert_eq!(read_session_file(&plain).unwrap(), content);
+        assert_eq!(read_session_file(&archive).unwrap(), content);
+
+        assert_eq!(decompress_session_file(&archive).unwrap(), plain);
+        assert_eq!(fs::read_to_string(&plain).unwrap(), content);
+        assert!(!archive.exists());
+    }
+}
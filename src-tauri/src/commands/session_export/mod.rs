@@ -0,0 +1,144 @@
+//! Session Export
+//!
+//! Turns a Claude, Codex or Gemini session into something that can be attached
+//! to a PR or an incident report: Markdown, a self-contained HTML page, or the
+//! normalized JSON transcript itself.
+//!
+//! - `transcript`: loads each engine's on-disk history into one `Transcript`
+//! - `render`:     Markdown / HTML / JSON writers
+
+mod render;
+mod transcript;
+
+pub(crate) use render::escape_html;
+pub use render::ExportFormat;
+pub use transcript::{Transcript, TranscriptBlock, TranscriptRole};
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::engine::EngineKind;
+
+/// What to keep in the exported transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    pub include_tool_calls: bool,
+    pub include_thinking: bool,
+    /// Tool outputs longer than this are cut, with a note of how much was left out
+    pub max_tool_output_lines: Option<usize>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            include_tool_calls: true,
+            include_thinking: true,
+            max_tool_output_lines: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionExport {
+    pub format: ExportFormat,
+    pub content: String,
+    /// Set when the export was also written to disk
+    pub output_path: Option<String>,
+    pub message_count: usize,
+}
+
+/// Load a session from its engine's history files
+///
+/// Claude needs `project_id` (the ~/.claude/projects directory name), Gemini
+/// needs `project_path` (hashed into ~/.gemini/tmp); Codex finds the rollout by ID.
+pub async fn load_transcript(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: Option<&str>,
+    project_path: Option<&str>,
+) -> Result<Transcript, String> {
+    match engine {
+        EngineKind::Claude => {
+            let project_id = project_id
+                .ok_or_else(|| "projectId is required for Claude sessions".to_string())?;
+            let messages =
+                super::claude::load_session_history(session_id.to_string(), project_id.to_string())
+                    .await?;
+            Ok(Transcript::from_claude(session_id, &messages))
+        }
+        EngineKind::Codex => {
+            let events = super::codex::load_codex_session_history(session_id.to_string()).await?;
+            Ok(Transcript::from_codex(session_id, &events))
+        }
+        EngineKind::Gemini => {
+            let project_path = project_path
+                .ok_or_else(|| "projectPath is required for Gemini sessions".to_string())?;
+            let detail = super::gemini::config::read_session_detail(project_path, session_id)?;
+            Ok(Transcript::from_gemini(&detail, project_path))
+        }
+    }
+}
+
+/// `{engine}-{first 8 chars of the ID}.{ext}`, used when `output_path` is a directory
+fn default_file_name(engine: EngineKind, session_id: &str, format: ExportFormat) -> String {
+    let short_id: String = session_id.chars().take(8).collect();
+    format!("{}-{}.{}", engine.as_str(), short_id, format.extension())
+}
+
+/// Export a session; the rendered content is always returned and also written
+/// to `output_path` (a file, or a directory to put a default-named file in)
+#[tauri::command]
+pub async fn export_session(
+    engine: EngineKind,
+    session_id: String,
+    project_id: Option<String>,
+    project_path: Option<String>,
+    format: ExportFormat,
+    options: Option<ExportOptions>,
+    output_path: Option<String>,
+) -> Result<SessionExport, String> {
+    log::info!(
+        "Exporting {} session {} as {:?}",
+        engine.as_str(),
+        session_id,
+        format
+    );
+
+    let mut transcript = load_transcript(
+        engine,
+        &session_id,
+        project_id.as_deref(),
+        project_path.as_deref(),
+    )
+    .await?;
+    transcript.apply_options(&options.unwrap_or_default());
+
+    let content = render::render(&transcript, format)?;
+
+    let output_path = match output_path {
+        Some(path) => {
+            let mut path = PathBuf::from(path);
+            if path.is_dir() {
+                path.push(default_file_name(engine, &session_id, format));
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create export directory: {}", e))?;
+            }
+            std::fs::write(&path, &content)
+                .map_err(|e| format!("Failed to write export: {}", e))?;
+            log::info!("Session export written to {}", path.display());
+            Some(path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    Ok(SessionExport {
+        format,
+        content,
+        output_path,
+        message_count: transcript.message_count(),
+    })
+}
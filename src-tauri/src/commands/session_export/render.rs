@@ -0,0 +1,302 @@
+//! Markdown / HTML / JSON writers for a `Transcript`
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::transcript::{Transcript, TranscriptBlock, TranscriptRole};
+use crate::commands::engine::EngineKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+pub fn render(transcript: &Transcript, format: ExportFormat) -> Result<String, String> {
+    match format {
+        ExportFormat::Markdown => Ok(render_markdown(transcript)),
+        ExportFormat::Html => Ok(render_html(transcript)),
+        ExportFormat::Json => serde_json::to_string_pretty(transcript)
+            .map_err(|e| format!("Failed to serialize transcript: {}", e)),
+    }
+}
+
+fn engine_label(engine: EngineKind) -> &'static str {
+    match engine {
+        EngineKind::Claude => "Claude",
+        EngineKind::Codex => "Codex",
+        EngineKind::Gemini => "Gemini",
+    }
+}
+
+fn role_label(role: TranscriptRole) -> &'static str {
+    match role {
+        TranscriptRole::User => "User",
+        TranscriptRole::Assistant => "Assistant",
+    }
+}
+
+/// `key: value` header lines shared by Markdown and HTML
+fn metadata(transcript: &Transcript) -> Vec<(&'static str, String)> {
+    let mut rows = vec![
+        ("Engine", engine_label(transcript.engine).to_string()),
+        ("Session", transcript.session_id.clone()),
+    ];
+    let optional = [
+        ("Project", &transcript.project_path),
+        ("Model", &transcript.model),
+        ("Started", &transcript.started_at),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            rows.push((key, value.clone()));
+        }
+    }
+    rows
+}
+
+fn tool_input_text(input: &Value) -> String {
+    match input {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+fn omitted_note(omitted_lines: Option<usize>) -> Option<String> {
+    omitted_lines.map(|n| format!("… {} more line{} omitted", n, if n == 1 { "" } else { "s" }))
+}
+
+// ============================================================================
+// Markdown
+// ============================================================================
+
+/// Code fence longer than any backtick run inside `content`
+fn fence_for(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn code_block(content: &str, lang: &str) -> String {
+    let fence = fence_for(content);
+    format!("{}{}\n{}\n{}\n", fence, lang, content, fence)
+}
+
+fn render_markdown(transcript: &Transcript) -> String {
+    let mut out = format!("# {} session\n\n", engine_label(transcript.engine));
+    for (key, value) in metadata(transcript) {
+        out.push_str(&format!("- **{}:** {}\n", key, value));
+    }
+
+    for entry in &transcript.entries {
+        out.push_str("\n---\n\n");
+        out.push_str(&format!("## {}", role_label(entry.role)));
+        if let Some(ref timestamp) = entry.timestamp {
+            out.push_str(&format!(" · {}", timestamp));
+        }
+        out.push_str("\n\n");
+
+        for block in &entry.blocks {
+            match block {
+                TranscriptBlock::Text { text } => {
+                    out.push_str(text.trim_end());
+                    out.push_str("\n\n");
+                }
+                TranscriptBlock::Thinking { text } => {
+                    out.push_str("<details>\n<summary>Thinking</summary>\n\n");
+                    out.push_str(text.trim_end());
+                    out.push_str("\n\n</details>\n\n");
+                }
+                TranscriptBlock::ToolCall { name, input, .. } => {
+                    out.push_str(&format!("**Tool call: `{}`**\n\n", name));
+                    let lang = if input.is_string() { "" } else { "json" };
+                    out.push_str(&code_block(&tool_input_text(input), lang));
+                    out.push('\n');
+                }
+                TranscriptBlock::ToolResult {
+                    output,
+                    is_error,
+                    omitted_lines,
+                    ..
+                } => {
+                    let summary = if *is_error {
+                        "Tool error"
+                    } else {
+                        "Tool output"
+                    };
+                    out.push_str(&format!("<details>\n<summary>{}</summary>\n\n", summary));
+                    out.push_str(&code_block(output, ""));
+                    if let Some(note) = omitted_note(*omitted_lines) {
+                        out.push_str(&format!("\n_{}_\n", note));
+                    }
+                    out.push_str("\n</details>\n\n");
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// ============================================================================
+// HTML
+// ============================================================================
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = r#"
+body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #1f2328; background: #fff; }
+h1 { font-size: 1.5rem; }
+dl.meta { display: grid; grid-template-columns: max-content 1fr; gap: .25rem 1rem; font-size: .9rem; }
+dl.meta dt { font-weight: 600; }
+dl.meta dd { margin: 0; word-break: break-all; }
+.turn { border: 1px solid #d0d7de; border-radius: 8px; margin: 1rem 0; padding: .75rem 1rem; }
+.turn.user { background: #f6f8fa; }
+.turn header { font-weight: 600; margin-bottom: .5rem; }
+.turn header time { font-weight: 400; color: #656d76; font-size: .85rem; margin-left: .5rem; }
+.text { white-space: pre-wrap; line-height: 1.5; }
+.tool-call { font-weight: 600; margin-top: .75rem; }
+pre { background: #f6f8fa; border: 1px solid #d0d7de; border-radius: 6px; padding: .5rem .75rem; overflow-x: auto; font-size: .85rem; }
+details { margin: .5rem 0; }
+summary { cursor: pointer; color: #656d76; }
+details.error summary { color: #cf222e; }
+.omitted { color: #656d76; font-style: italic; font-size: .85rem; }
+@media (prefers-color-scheme: dark) {
+  body { color: #e6edf3; background: #0d1117; }
+  .turn { border-color: #30363d; }
+  .turn.user, pre { background: #161b22; border-color: #30363d; }
+}
+"#;
+
+fn render_html(transcript: &Transcript) -> String {
+    let title = format!(
+        "{} session {}",
+        engine_label(transcript.engine),
+        transcript.session_id
+    );
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape_html(&title),
+        HTML_STYLE
+    );
+
+    out.push_str(&format!(
+        "<h1>{} session</h1>\n<dl class=\"meta\">\n",
+        engine_label(transcript.engine)
+    ));
+    for (key, value) in metadata(transcript) {
+        out.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            key,
+            escape_html(&value)
+        ));
+    }
+    out.push_str("</dl>\n");
+
+    for entry in &transcript.entries {
+        let role = role_label(entry.role);
+        out.push_str(&format!(
+            "<section class=\"turn {}\">\n<header>{}",
+            role.to_lowercase(),
+            role
+        ));
+        if let Some(ref timestamp) = entry.timestamp {
+            out.push_str(&format!("<time>{}</time>", escape_html(timestamp)));
+        }
+        out.push_str("</header>\n");
+
+        for block in &entry.blocks {
+            match block {
+                TranscriptBlock::Text { text } => {
+                    out.push_str(&format!(
+                        "<div class=\"text\">{}</div>\n",
+                        escape_html(text)
+                    ));
+                }
+                TranscriptBlock::Thinking { text } => {
+                    out.push_str(&format!(
+                        "<details><summary>Thinking</summary><div class=\"text\">{}</div></details>\n",
+                        escape_html(text)
+                    ));
+                }
+                TranscriptBlock::ToolCall { name, input, .. } => {
+                    out.push_str(&format!(
+                        "<div class=\"tool-call\">Tool call: <code>{}</code></div>\n<pre>{}</pre>\n",
+                        escape_html(name),
+                        escape_html(&tool_input_text(input))
+                    ));
+                }
+                TranscriptBlock::ToolResult {
+                    output,
+                    is_error,
+                    omitted_lines,
+                    ..
+                } => {
+                    let (class, summary) = if *is_error {
+                        (" class=\"error\"", "Tool error")
+                    } else {
+                        ("", "Tool output")
+                    };
+                    out.push_str(&format!(
+                        "<details{}><summary>{}</summary><pre>{}</pre>",
+                        class,
+                        summary,
+                        escape_html(output)
+                    ));
+                    if let Some(note) = omitted_note(*omitted_lines) {
+                        out.push_str(&format!("<div class=\"omitted\">{}</div>", note));
+                    }
+                    out.push_str("</details>\n");
+                }
+            }
+        }
+        out.push_str("</section>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_fence_outgrows_backticks_in_content() {
+        assert_eq!(fence_for("plain"), "```");
+        assert_eq!(fence_for("has ``` inside"), "````");
+        assert!(code_block("a ```` b", "").starts_with("`````\n"));
+    }
+
+    #[test]
+    fn html_output_is_escaped() {
+        assert_eq!(
+            escape_html("<script>alert('x') && \"y\"</script>"),
+            "&lt;script&gt;alert(&#39;x&#39;) &amp;&amp; &quot;y&quot;&lt;/script&gt;"
+        );
+    }
+}
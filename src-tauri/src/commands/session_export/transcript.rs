@@ -0,0 +1,561 @@
+//! Normalized transcript model shared by all export formats
+//!
+//! Each engine's history is folded into the same shape: a list of user and
+//! assistant turns made of text, thinking, tool call and tool result blocks.
+//! Tool results always belong to the assistant turn that issued the call,
+//! even though Claude and Codex record them as separate user/output items.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::ExportOptions;
+use crate::commands::codex::session_converter::gemini_tool_output;
+use crate::commands::engine::EngineKind;
+use crate::commands::gemini::types::GeminiSessionDetail;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub engine: EngineKind,
+    pub session_id: String,
+    pub project_path: Option<String>,
+    pub model: Option<String>,
+    pub started_at: Option<String>,
+    pub entries: Vec<TranscriptEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptRole {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptEntry {
+    pub role: TranscriptRole,
+    pub timestamp: Option<String>,
+    pub blocks: Vec<TranscriptBlock>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        text: String,
+    },
+    ToolCall {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    ToolResult {
+        #[serde(rename = "toolCallId")]
+        tool_call_id: String,
+        output: String,
+        #[serde(rename = "isError")]
+        is_error: bool,
+        /// Lines cut by `maxToolOutputLines`
+        #[serde(rename = "omittedLines", skip_serializing_if = "Option::is_none")]
+        omitted_lines: Option<usize>,
+    },
+}
+
+impl Transcript {
+    fn new(engine: EngineKind, session_id: &str) -> Self {
+        Self {
+            engine,
+            session_id: session_id.to_string(),
+            project_path: None,
+            model: None,
+            started_at: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Append a block, continuing the last turn when the role matches
+    fn push(&mut self, role: TranscriptRole, timestamp: Option<&str>, block: TranscriptBlock) {
+        let role = match block {
+            TranscriptBlock::ToolResult { .. } => TranscriptRole::Assistant,
+            _ => role,
+        };
+        if self.started_at.is_none() {
+            self.started_at = timestamp.map(String::from);
+        }
+
+        match self.entries.last_mut() {
+            Some(last) if last.role == role => last.blocks.push(block),
+            _ => self.entries.push(TranscriptEntry {
+                role,
+                timestamp: timestamp.map(String::from),
+                blocks: vec![block],
+            }),
+        }
+    }
+
+    pub fn message_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drop or shorten blocks according to the export options
+    pub fn apply_options(&mut self, options: &ExportOptions) {
+        for entry in &mut self.entries {
+            entry.blocks.retain(|block| match block {
+                TranscriptBlock::Thinking { .. } => options.include_thinking,
+                TranscriptBlock::ToolCall { .. } | TranscriptBlock::ToolResult { .. } => {
+                    options.include_tool_calls
+                }
+                TranscriptBlock::Text { .. } => true,
+            });
+
+            if let Some(max_lines) = options.max_tool_output_lines {
+                for block in &mut entry.blocks {
+                    if let TranscriptBlock::ToolResult {
+                        output,
+                        omitted_lines,
+                        ..
+                    } = block
+                    {
+                        let total = output.lines().count();
+                        if total > max_lines {
+                            *output = output
+                                .lines()
+                                .take(max_lines)
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            *omitted_lines = Some(total - max_lines);
+                        }
+                    }
+                }
+            }
+        }
+        self.entries.retain(|entry| !entry.blocks.is_empty());
+    }
+
+    // ========================================================================
+    // Loaders
+    // ========================================================================
+
+    /// From Claude JSONL entries (`load_session_history`)
+    pub fn from_claude(session_id: &str, messages: &[Value]) -> Self {
+        let mut transcript = Self::new(EngineKind::Claude, session_id);
+
+        for msg in messages {
+            // Subagent messages are summarized by their Task tool result; meta entries are CLI noise
+            if msg.get("parent_tool_use_id").is_some()
+                || msg.get("isMeta").and_then(|m| m.as_bool()) == Some(true)
+            {
+                continue;
+            }
+            let role = match msg.get("type").and_then(|t| t.as_str()) {
+                Some("user") => TranscriptRole::User,
+                Some("assistant") => TranscriptRole::Assistant,
+                _ => continue,
+            };
+
+            if transcript.project_path.is_none() {
+                transcript.project_path = msg.get("cwd").and_then(|c| c.as_str()).map(String::from);
+            }
+            if transcript.model.is_none() {
+                transcript.model = msg
+                    .pointer("/message/model")
+                    .and_then(|m| m.as_str())
+                    .filter(|m| !m.starts_with('<'))
+                    .map(String::from);
+            }
+            let timestamp = ["timestamp", "sentAt", "receivedAt"]
+                .iter()
+                .find_map(|key| msg.get(*key).and_then(|t| t.as_str()));
+
+            let content = msg.pointer("/message/content");
+            if let Some(text) = content.and_then(|c| c.as_str()) {
+                if !text.trim().is_empty() {
+                    transcript.push(
+                        role,
+                        timestamp,
+                        TranscriptBlock::Text {
+                            text: text.to_string(),
+                        },
+                    );
+                }
+                continue;
+            }
+
+            for item in content.and_then(|c| c.as_array()).into_iter().flatten() {
+                let block = match item.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => TranscriptBlock::Text {
+                        text: str_field(item, "text"),
+                    },
+                    Some("thinking") => TranscriptBlock::Thinking {
+                        text: str_field(item, "thinking"),
+                    },
+                    Some("tool_use") => TranscriptBlock::ToolCall {
+                        id: str_field(item, "id"),
+                        name: str_field(item, "name"),
+                        input: item.get("input").cloned().unwrap_or(Value::Null),
+                    },
+                    Some("tool_result") => TranscriptBlock::ToolResult {
+                        tool_call_id: str_field(item, "tool_use_id"),
+                        output: item.get("content").map(content_text).unwrap_or_default(),
+                        is_error: item.get("is_error").and_then(|e| e.as_bool()) == Some(true),
+                        omitted_lines: None,
+                    },
+                    _ => continue,
+                };
+                transcript.push(role, timestamp, block);
+            }
+        }
+
+        transcript
+    }
+
+    /// From Codex rollout events (`load_codex_session_history`)
+    pub fn from_codex(session_id: &str, events: &[Value]) -> Self {
+        let mut transcript = Self::new(EngineKind::Codex, session_id);
+
+        for event in events {
+            let timestamp = event.get("timestamp").and_then(|t| t.as_str());
+            let Some(payload) = event.get("payload") else {
+                continue;
+            };
+
+            match event.get("type").and_then(|t| t.as_str()) {
+                Some("session_meta") => {
+                    transcript.project_path = payload
+                        .get("cwd")
+                        .and_then(|c| c.as_str())
+                        .map(String::from);
+                    transcript.started_at = payload
+                        .get("timestamp")
+                        .and_then(|t| t.as_str())
+                        .or(timestamp)
+                        .map(String::from);
+                }
+                Some("turn_context") if transcript.model.is_none() => {
+                    transcript.model = payload
+                        .get("model")
+                        .and_then(|m| m.as_str())
+                        .map(String::from);
+                }
+                Some("response_item") => {
+                    if let Some((role, block)) = codex_response_item(payload) {
+                        transcript.push(role, timestamp, block);
+                    }
+                }
+                // event_msg duplicates response_item content for the live UI
+                _ => {}
+            }
+        }
+
+        transcript
+    }
+
+    /// From a Gemini chats/session-*.json file (`get_gemini_session_detail`)
+    pub fn from_gemini(detail: &GeminiSessionDetail, project_path: &str) -> Self {
+        let mut transcript = Self::new(EngineKind::Gemini, &detail.session_id);
+        transcript.project_path = Some(project_path.to_string());
+        transcript.started_at = Some(detail.start_time.clone());
+
+        for msg in &detail.messages {
+            let timestamp = msg.get("timestamp").and_then(|t| t.as_str());
+            let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+
+            match msg.get("type").and_then(|t| t.as_str()) {
+                Some("user") if !content.trim().is_empty() => {
+                    transcript.push(
+                        TranscriptRole::User,
+                        timestamp,
+                        TranscriptBlock::Text {
+                            text: content.to_string(),
+                        },
+                    );
+                }
+                Some("gemini") => {
+                    if transcript.model.is_none() {
+                        transcript.model =
+                            msg.get("model").and_then(|m| m.as_str()).map(String::from);
+                    }
+
+                    for thought in msg
+                        .get("thoughts")
+                        .and_then(|t| t.as_array())
+                        .into_iter()
+                        .flatten()
+                    {
+                        let subject = str_field(thought, "subject");
+                        let description = str_field(thought, "description");
+                        let text = if subject.is_empty() {
+                            description
+                        } else {
+                            format!("**{}**\n{}", subject, description)
+                        };
+                        transcript.push(
+                            TranscriptRole::Assistant,
+                            timestamp,
+                            TranscriptBlock::Thinking { text },
+                        );
+                    }
+
+                    // Gemini stores the text that precedes the calls on the same message
+                    if !content.trim().is_empty() {
+                        transcript.push(
+                            TranscriptRole::Assistant,
+                            timestamp,
+                            TranscriptBlock::Text {
+                                text: content.to_string(),
+                            },
+                        );
+                    }
+
+                    for call in msg
+                        .get("toolCalls")
+                        .and_then(|t| t.as_array())
+                        .into_iter()
+                        .flatten()
+                    {
+                        let id = str_field(call, "id");
+                        transcript.push(
+                            TranscriptRole::Assistant,
+                            timestamp,
+                            TranscriptBlock::ToolCall {
+                                id: id.clone(),
+                                name: str_field(call, "name"),
+                                input: call.get("args").cloned().unwrap_or(Value::Null),
+                            },
+                        );
+                        if let Some(output) = gemini_tool_output(call) {
+                            transcript.push(
+                                TranscriptRole::Assistant,
+                                timestamp,
+                                TranscriptBlock::ToolResult {
+                                    tool_call_id: id,
+                                    output: content_text(&output),
+                                    is_error: call.get("status").and_then(|s| s.as_str())
+                                        == Some("error"),
+                                    omitted_lines: None,
+                                },
+                            );
+                        }
+                    }
+                }
+                // info / error lines are CLI status messages
+                _ => {}
+            }
+        }
+
+        transcript
+    }
+}
+
+/// Convert one Codex `response_item` payload
+fn codex_response_item(payload: &Value) -> Option<(TranscriptRole, TranscriptBlock)> {
+    match payload.get("type")?.as_str()? {
+        "message" => {
+            let role = match payload.get("role")?.as_str()? {
+                "user" => TranscriptRole::User,
+                "assistant" => TranscriptRole::Assistant,
+                // developer / system prompts are not part of the conversation
+                _ => return None,
+            };
+            let text = payload
+                .get("content")?
+                .as_array()?
+                .iter()
+                .filter_map(|c| c.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            // Codex injects AGENTS.md and the environment as user messages
+            let injected = [
+                "<environment_context>",
+                "<user_instructions>",
+                "# AGENTS.md",
+            ];
+            if text.trim().is_empty() || injected.iter().any(|p| text.starts_with(p)) {
+                return None;
+            }
+            Some((role, TranscriptBlock::Text { text }))
+        }
+        "reasoning" => {
+            let text = payload
+                .get("summary")?
+                .as_array()?
+                .iter()
+                .filter_map(|s| s.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            if text.trim().is_empty() {
+                return None;
+            }
+            Some((
+                TranscriptRole::Assistant,
+                TranscriptBlock::Thinking { text },
+            ))
+        }
+        "function_call" | "custom_tool_call" => {
+            // function_call carries JSON `arguments`, custom_tool_call a raw `input` (apply_patch)
+            let input = match payload.get("arguments").and_then(|a| a.as_str()) {
+                Some(arguments) => serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| Value::String(arguments.to_string())),
+                None => payload.get("input").cloned().unwrap_or(Value::Null),
+            };
+            Some((
+                TranscriptRole::Assistant,
+                TranscriptBlock::ToolCall {
+                    id: str_field(payload, "call_id"),
+                    name: str_field(payload, "name"),
+                    input,
+                },
+            ))
+        }
+        "function_call_output" | "custom_tool_call_output" => {
+            let raw = payload.get("output")?;
+            // Shell output is wrapped as {"output": "...", "metadata": {"exit_code": 0}}
+            let wrapped = raw
+                .as_str()
+                .and_then(|s| serde_json::from_str::<Value>(s).ok())
+                .filter(|v| v.get("output").is_some());
+            let (output, is_error) = match wrapped {
+                Some(wrapped) => (
+                    content_text(&wrapped["output"]),
+                    wrapped
+                        .pointer("/metadata/exit_code")
+                        .and_then(|c| c.as_i64())
+                        .is_some_and(|code| code != 0),
+                ),
+                None => (content_text(raw), false),
+            };
+            Some((
+                TranscriptRole::Assistant,
+                TranscriptBlock::ToolResult {
+                    tool_call_id: str_field(payload, "call_id"),
+                    output,
+                    is_error,
+                    omitted_lines: None,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Tool output as plain text (string, or an array of text parts)
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| match part.get("text").and_then(|t| t.as_str()) {
+                Some(text) => text.to_string(),
+                None => format!(
+                    "[{}]",
+                    part.get("type").and_then(|t| t.as_str()).unwrap_or("data")
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claude_history() -> Vec<Value> {
+        vec![
+            json!({"type": "user", "cwd": "/work/app", "timestamp": "2025-06-01T10:00:00Z",
+                   "message": {"role": "user", "content": "List the tests"}}),
+            json!({"type": "assistant", "timestamp": "2025-06-01T10:00:02Z",
+            "message": {"role": "assistant", "model": "claude-sonnet-4-5", "content": [
+                {"type": "thinking", "thinking": "Use ls"},
+                {"type": "tool_use", "id": "toolu_1", "name": "Bash", "input": {"command": "ls tests"}}
+            ]}}),
+            json!({"type": "user", "timestamp": "2025-06-01T10:00:03Z",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "a.rs\nb.rs\nc.rs"}
+            ]}}),
+            json!({"type": "assistant", "parent_tool_use_id": "toolu_9",
+                   "message": {"role": "assistant", "content": "subagent chatter"}}),
+            json!({"type": "assistant", "timestamp": "2025-06-01T10:00:04Z",
+                   "message": {"role": "assistant", "content": [{"type": "text", "text": "Three files."}]}}),
+        ]
+    }
+
+    #[test]
+    fn claude_tool_results_stay_in_the_assistant_turn() {
+        let transcript = Transcript::from_claude("s1", &claude_history());
+
+        assert_eq!(transcript.project_path.as_deref(), Some("/work/app"));
+        assert_eq!(transcript.model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(transcript.message_count(), 2);
+        assert_eq!(transcript.entries[1].role, TranscriptRole::Assistant);
+        assert_eq!(transcript.entries[1].blocks.len(), 4);
+    }
+
+    #[test]
+    fn options_drop_thinking_and_truncate_outputs() {
+        let mut transcript = Transcript::from_claude("s1", &claude_history());
+        transcript.apply_options(&ExportOptions {
+            include_tool_calls: true,
+            include_thinking: false,
+            max_tool_output_lines: Some(1),
+        });
+
+        let blocks = &transcript.entries[1].blocks;
+        assert_eq!(blocks.len(), 3);
+        match &blocks[1] {
+            TranscriptBlock::ToolResult {
+                output,
+                omitted_lines,
+                ..
+            } => {
+                assert_eq!(output, "a.rs");
+                assert_eq!(*omitted_lines, Some(2));
+            }
+            other => panic!("expected tool result, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn codex_skips_injected_context_and_unwraps_shell_output() {
+        let events = vec![
+            json!({"type": "session_meta", "payload": {"id": "c1", "cwd": "/work/app", "timestamp": "2025-06-01T10:00:00Z"}}),
+            json!({"type": "response_item", "payload": {"type": "message", "role": "user",
+                   "content": [{"type": "input_text", "text": "<environment_context>\n  <cwd>/work/app</cwd>\n</environment_context>"}]}}),
+            json!({"type": "response_item", "payload": {"type": "message", "role": "user",
+                   "content": [{"type": "input_text", "text": "Run the tests"}]}}),
+            json!({"type": "response_item", "payload": {"type": "function_call", "name": "shell",
+                   "arguments": "{\"command\":[\"cargo\",\"test\"]}", "call_id": "call_1"}}),
+            json!({"type": "response_item", "payload": {"type": "function_call_output", "call_id": "call_1",
+                   "output": "{\"output\":\"1 failed\",\"metadata\":{\"exit_code\":101}}"}}),
+        ];
+        let transcript = Transcript::from_codex("c1", &events);
+
+        assert_eq!(transcript.message_count(), 2);
+        match &transcript.entries[1].blocks[..] {
+            [TranscriptBlock::ToolCall { input, .. }, TranscriptBlock::ToolResult {
+                output, is_error, ..
+            }] => {
+                assert_eq!(input["command"][0], "cargo");
+                assert_eq!(output, "1 failed");
+                assert!(*is_error);
+            }
+            other => panic!("unexpected blocks: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Session Fork (从任意提示词分叉会话)
+//!
+//! Non-destructive counterpart of `RewindMode::ConversationOnly`: instead of
+//! truncating the session in place, the history before prompt N is copied into
+//! a new session of the same engine, together with the git records of the
+//! copied prompts. The original session and its git records are not touched,
+//! so the first attempt stays available next to the alternative.
+//!
+//! Optionally the code is forked too: `commit_before` of prompt N is checked
+//! out on a new branch. That requires a clean working tree, since uncommitted
+//! changes would otherwise be carried onto the fork branch.
+
+use serde::Serialize;
+
+use super::engine::EngineKind;
+use super::prompt_tracker::{load_execution_config, PromptRecord};
+use super::session_worktree::{attach_forked_session, resolve_project_path};
+use super::{codex, gemini, prompt_tracker, session_archive, simple_git};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFork {
+    pub engine: EngineKind,
+    pub source_session_id: String,
+    /// ID of the new session, resumable with the engine's usual resume flow
+    pub session_id: String,
+    pub prompt_index: usize,
+    /// Text of prompt N, for pre-filling the input of the fork
+    pub prompt_text: String,
+    /// Branch created at `commit_before` of prompt N, if one was requested
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+}
+
+/// Check out `commit_before` on a new branch, refusing when the tree is dirty
+fn checkout_fork_branch(
+    project_path: &str,
+    branch: &str,
+    commit_before: &str,
+) -> Result<(), String> {
+    if simple_git::git_has_uncommitted_changes(project_path)? {
+        return Err("无法创建分支：工作区有未提交的更改，请先提交或暂存后再分叉。".to_string());
+    }
+    simple_git::git_checkout_new_branch(project_path, branch, commit_before)
+}
+
+async fn load_prompts(
+    engine: EngineKind,
+    session_id: &str,
+    project_id: Option<&str>,
+    project_path: &str,
+) -> Result<Vec<PromptRecord>, String> {
+    match engine {
+        EngineKind::Claude => {
+            let project_id = project_id
+                .ok_or_else(|| "projectId is required for Claude sessions".to_string())?;
+            prompt_tracker::get_unified_prompt_list(session_id.to_string(), project_id.to_string())
+                .await
+        }
+        EngineKind::Codex => codex::git_ops::extract_codex_prompts(session_id),
+        EngineKind::Gemini => {
+            gemini::git_ops::get_gemini_prompt_list(
+                session_id.to_string(),
+                project_path.to_string(),
+            )
+            .await
+        }
+    }
+}
+
+/// Fork a session at prompt N: the new session ends right before prompt N
+#[tauri::command]
+pub async fn fork_session_at_prompt(
+    engine: EngineKind,
+    session_id: String,
+    project_id: Option<String>,
+    project_path: String,
+    prompt_index: usize,
+    branch: Option<String>,
+) -> Result<SessionFork, String> {
+    let project_path = resolve_project_path(&session_id, project_path);
+    log::info!(
+        "[Fork] Forking {} session {} at prompt #{}",
+        engine.as_str(),
+        session_id,
+        prompt_index
+    );
+
+    // The fork is resumed by the CLI later, so work on the plain session files
+    session_archive::rehydrate(
+        engine,
+        &session_id,
+        project_id.as_deref(),
+        Some(&project_path),
+    )?;
+
+    let prompts = load_prompts(engine, &session_id, project_id.as_deref(), &project_path).await?;
+    let prompt = prompts
+        .get(prompt_index)
+        .ok_or_else(|| format!("Prompt #{} not found", prompt_index))?;
+
+    // Check out the code first: if that fails, no orphan fork is left behind
+    let branch = branch
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty());
+    let commit = match branch {
+        Some(ref branch) => {
+            let execution_config = load_execution_config()
+                .map_err(|e| format!("Failed to load execution config: {}", e))?;
+            if execution_config.disable_rewind_git_operations {
+                return Err("无法创建分支：Git 操作已在配置中禁用。".to_string());
+            }
+
+            let commit_before = prompt.git_commit_before.as_str();
+            if commit_before.is_empty() || commit_before == "NONE" {
+                return Err(format!(
+                    "无法创建分支：提示词 #{} 没有关联的 Git 记录",
+                    prompt_index
+                ));
+            }
+
+            checkout_fork_branch(&project_path, branch, commit_before)?;
+            Some(commit_before.to_string())
+        }
+        None => None,
+    };
+
+    let new_session_id = match engine {
+        EngineKind::Claude => {
+            let project_id = project_id
+                .as_deref()
+                .ok_or_else(|| "projectId is required for Claude sessions".to_string())?;
+            prompt_tracker::fork_session_at_prompt(&session_id, project_id, prompt_index)?
+        }
+        EngineKind::Codex => {
+            codex::git_ops::fork_codex_session_at_prompt(&session_id, prompt_index)?
+        }
+        EngineKind::Gemini => gemini::git_ops::fork_gemini_session_at_prompt(
+            &session_id,
+            &project_path,
+            prompt_index,
+        )?,
+    };
+
+    // A fork of a worktree session keeps running in that worktree
+    attach_forked_session(&session_id, &new_session_id);
+
+    Ok(SessionFork {
+        engine,
+        source_session_id: session_id,
+        session_id: new_session_id,
+        prompt_index,
+        prompt_text: prompt.text.clone(),
+        branch,
+        commit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &str, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn repo_with_two_commits() -> (tempfile::TempDir, String) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap().to_string();
+        git(&path, &["init", "-q"]);
+        std::fs::write(dir.path().join("a.txt"), "one").unwrap();
+        git(&path, &["add", "-A"]);
+        git(&path, &["commit", "-q", "-m", "one"]);
+        std::fs::write(dir.path().join("a.txt"), "two").unwrap();
+        git(&path, &["commit", "-q", "-am", "two"]);
+        (dir, path)
+    }
+
+    #[test]
+    fn fork_branch_starts_at_commit_before() {
+        let (dir, path) = repo_with_two_commits();
+        let first = git(&path, &["rev-parse", "HEAD~1"]);
+
+        checkout_fork_branch(&path, "fork-1", &first).unwrap();
+
+        assert_eq!(git(&path, &["rev-parse", "--abbrev-ref", "HEAD"]), "fork-1");
+        assert_eq!(git(&path, &["rev-parse", "HEAD"]), first);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "one"
+        );
+    }
+
+    #[test]
+    fn fork_branch_refuses_dirty_tree() {
+        let (dir, path) = repo_with_two_commits();
+        let first = git(&path, &["rev-parse", "HEAD~1"]);
+        let branch = git(&path, &["rev-parse", "--abbrev-ref", "HEAD"]);
+        std::fs::write(dir.path().join("a.txt"), "work in progress").unwrap();
+
+        assert!(checkout_fork_branch(&path, "fork-1", &first).is_err());
+
+        // Nothing moved: same branch, no stash, the edit is still in place
+        assert_eq!(git(&path, &["rev-parse", "--abbrev-ref", "HEAD"]), branch);
+        assert_eq!(git(&path, &["stash", "list"]), "");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "work in progress"
+        );
+    }
+}
@@ -0,0 +1,774 @@
+//! Full-text Session Search
+//!
+//! Indexes the user prompts, assistant text and tool inputs of every Claude,
+//! Codex and Gemini session into an FTS5 table in `agents.db`, so a session can
+//! be found by what was said in it rather than by opening them one by one.
+//!
+//! The index is incremental: `session_search_files` remembers the mtime/size
+//! of each history file, and a refresh only re-reads files that changed (or
+//! drops those that disappeared). A background thread refreshes on startup,
+//! after every `*-complete` event and every few minutes.
+//!
+//! Tables:
+//! - `session_search_files`: one row per history file (engine, session, project)
+//! - `session_search_docs`:  one row per indexed block (kind, timestamp)
+//! - `session_search`:       FTS5 (trigram) over the block text, rowid = docs.id
+
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Listener, Manager, State};
+
+use super::engine::EngineKind;
+use super::gemini::types::GeminiSessionDetail;
+use super::session_archive::{
+    is_session_file, open_session_file, read_session_file, session_file_stem,
+};
+use super::session_export::{escape_html, Transcript, TranscriptBlock, TranscriptRole};
+use super::storage::AgentDb;
+
+/// Long tool inputs (file writes, patches) only need their head to be findable
+const MAX_DOC_CHARS: usize = 8_000;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+const DEFAULT_LIMIT: usize = 50;
+
+// Snippet markers; replaced by <mark> once the surrounding text is escaped
+const MARK_START: char = '\u{E000}';
+const MARK_END: char = '\u{E001}';
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSearchHit {
+    pub engine: EngineKind,
+    pub session_id: String,
+    pub project_path: Option<String>,
+    pub timestamp: Option<String>,
+    /// "prompt" | "response" | "tool"
+    pub kind: String,
+    /// HTML-escaped text with matches wrapped in `<mark>`
+    pub snippet: String,
+    /// Higher is better (negated bm25)
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndexStats {
+    pub scanned_files: usize,
+    pub indexed_files: usize,
+    pub removed_files: usize,
+    pub failed_files: usize,
+}
+
+/// A history file found on disk
+struct SessionFile {
+    path: PathBuf,
+    engine: EngineKind,
+    modified_at: i64,
+    size: i64,
+}
+
+// ============================================================================
+// Schema
+// ============================================================================
+
+/// Create the search tables (called from `storage::init_database`)
+pub fn create_search_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS session_search_files (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            path TEXT NOT NULL UNIQUE,
+            engine TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            project_path TEXT,
+            modified_at INTEGER NOT NULL,
+            size INTEGER NOT NULL,
+            indexed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS session_search_docs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            file_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            timestamp TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_session_search_docs_file
+            ON session_search_docs(file_id);
+        CREATE VIRTUAL TABLE IF NOT EXISTS session_search
+            USING fts5(content, tokenize = 'trigram');",
+    )
+}
+
+// ============================================================================
+// Discovery
+// ============================================================================
+
+pub(crate) fn file_meta(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_millis() as i64;
+    Some((modified, metadata.len() as i64))
+}
+
+fn push_file(files: &mut Vec<SessionFile>, path: PathBuf, engine: EngineKind) {
+    if let Some((modified_at, size)) = file_meta(&path) {
+        files.push(SessionFile {
+            path,
+            engine,
+            modified_at,
+            size,
+        });
+    }
+}
+
+/// All history files of the three engines
+fn discover_session_files() -> Vec<SessionFile> {
+    let mut files = Vec::new();
+
+    // Claude: ~/.claude/projects/{project_id}/{session_id}.jsonl (agent-*.jsonl are subagents)
+    if let Ok(claude_dir) = super::claude::get_claude_dir() {
+        let pattern = claude_dir.join("projects").join("*").join("*.jsonl*");
+        for path in glob::glob(&pattern.to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|p| is_session_file(p, "jsonl"))
+        {
+            let is_agent = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("agent-"));
+            if !is_agent {
+                push_file(&mut files, path, EngineKind::Claude);
+            }
+        }
+    }
+
+    // Codex: {sessions_dir}/YYYY/MM/DD/rollout-*.jsonl
+    if let Ok(sessions_dir) = super::codex::get_codex_sessions_dir() {
+        for entry in walkdir::WalkDir::new(&sessions_dir).into_iter().flatten() {
+            if is_session_file(entry.path(), "jsonl") {
+                push_file(&mut files, entry.into_path(), EngineKind::Codex);
+            }
+        }
+    }
+
+    // Gemini: ~/.gemini/tmp/{project_hash}/chats/session-*.json
+    if let Ok(gemini_dir) = super::gemini::config::get_gemini_dir() {
+        let pattern = gemini_dir
+            .join("tmp")
+            .join("*")
+            .join("chats")
+            .join("session-*.json*");
+        for path in glob::glob(&pattern.to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|p| is_session_file(p, "json"))
+        {
+            push_file(&mut files, path, EngineKind::Gemini);
+        }
+    }
+
+    files
+}
+
+// ============================================================================
+// Indexing
+// ============================================================================
+
+fn read_jsonl(path: &Path) -> Result<Vec<Value>, String> {
+    let reader =
+        open_session_file(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    Ok(reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Parse one history file into a transcript
+///
+/// Gemini files only carry the project hash; `gemini_projects` maps hashes of
+/// project paths seen in Claude/Codex sessions back to the path.
+fn load_transcript(
+    file: &SessionFile,
+    gemini_projects: &HashMap<String, String>,
+) -> Result<Transcript, String> {
+    match file.engine {
+        EngineKind::Claude => {
+            let session_id = session_file_stem(&file.path).unwrap_or_default();
+            Ok(Transcript::from_claude(
+                &session_id,
+                &read_jsonl(&file.path)?,
+            ))
+        }
+        EngineKind::Codex => {
+            let events = read_jsonl(&file.path)?;
+            let session_id = events
+                .first()
+                .and_then(|e| e.pointer("/payload/id"))
+                .and_then(|id| id.as_str())
+                .ok_or_else(|| "Codex rollout has no session_meta".to_string())?
+                .to_string();
+            Ok(Transcript::from_codex(&session_id, &events))
+        }
+        EngineKind::Gemini => {
+            let content = read_session_file(&file.path)
+                .map_err(|e| format!("Failed to read {:?}: {}", file.path, e))?;
+            let detail: GeminiSessionDetail = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {:?}: {}", file.path, e))?;
+            let project_path = gemini_projects
+                .get(&detail.project_hash)
+                .map(String::as_str)
+                .unwrap_or_default();
+            let mut transcript = Transcript::from_gemini(&detail, project_path);
+            if project_path.is_empty() {
+                transcript.project_path = None;
+            }
+            Ok(transcript)
+        }
+    }
+}
+
+/// Collect string leaves of a tool input (`{"command": "cargo test"}` → "cargo test")
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) => out.push(s.clone()),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        Value::Number(n) => out.push(n.to_string()),
+        _ => {}
+    }
+}
+
+/// (kind, timestamp, text) rows for a transcript: prompts, responses, tool inputs
+fn documents(transcript: &Transcript) -> Vec<(&'static str, Option<String>, String)> {
+    let mut docs = Vec::new();
+    for entry in &transcript.entries {
+        for block in &entry.blocks {
+            let (kind, text) = match block {
+                TranscriptBlock::Text { text } if entry.role == TranscriptRole::User => {
+                    ("prompt", text.clone())
+                }
+                TranscriptBlock::Text { text } => ("response", text.clone()),
+                TranscriptBlock::ToolCall { name, input, .. } => {
+                    let mut parts = vec![name.clone()];
+                    collect_strings(input, &mut parts);
+                    ("tool", parts.join(" "))
+                }
+                _ => continue,
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            let text = match text.char_indices().nth(MAX_DOC_CHARS) {
+                Some((cut, _)) => text[..cut].to_string(),
+                None => text,
+            };
+            docs.push((kind, entry.timestamp.clone(), text));
+        }
+    }
+    docs
+}
+
+fn remove_file_rows(conn: &Connection, file_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "DELETE FROM session_search WHERE rowid IN
+            (SELECT id FROM session_search_docs WHERE file_id = ?1)",
+        params![file_id],
+    )?;
+    conn.execute(
+        "DELETE FROM session_search_docs WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    conn.execute(
+        "DELETE FROM session_search_files WHERE id = ?1",
+        params![file_id],
+    )?;
+    Ok(())
+}
+
+/// Replace everything indexed for `file` with `transcript`
+fn index_transcript(
+    conn: &mut Connection,
+    file: &SessionFile,
+    transcript: &Transcript,
+) -> SqliteResult<()> {
+    let path = file.path.to_string_lossy().to_string();
+    let tx = conn.transaction()?;
+
+    let existing: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM session_search_files WHERE path = ?1",
+            params![path],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(file_id) = existing {
+        remove_file_rows(&tx, file_id)?;
+    }
+
+    tx.execute(
+        "INSERT INTO session_search_files (path, engine, session_id, project_path, modified_at, size)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            path,
+            transcript.engine.as_str(),
+            transcript.session_id,
+            transcript.project_path,
+            file.modified_at,
+            file.size
+        ],
+    )?;
+    let file_id = tx.last_insert_rowid();
+
+    for (kind, timestamp, text) in documents(transcript) {
+        tx.execute(
+            "INSERT INTO session_search_docs (file_id, kind, timestamp) VALUES (?1, ?2, ?3)",
+            params![file_id, kind, timestamp],
+        )?;
+        tx.execute(
+            "INSERT INTO session_search (rowid, content) VALUES (?1, ?2)",
+            params![tx.last_insert_rowid(), text],
+        )?;
+    }
+
+    tx.commit()
+}
+
+/// Project paths of the indexed sessions, keyed by their Gemini project hash
+///
+/// Gemini history only records the hash of the project path; this maps it back.
+pub(crate) fn project_paths_by_hash(conn: &Connection) -> SqliteResult<HashMap<String, String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT project_path FROM session_search_files WHERE project_path IS NOT NULL",
+    )?;
+    let paths = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(paths
+        .filter_map(Result::ok)
+        .map(|p| (super::gemini::config::hash_project_path(&p), p))
+        .collect())
+}
+
+static REFRESH_LOCK: Mutex<()> = Mutex::new(());
+
+/// Bring the index up to date with the history files on disk
+///
+/// Parsing happens without holding the database lock; each changed file is
+/// written in its own short transaction.
+pub fn refresh_index(db: &Mutex<Connection>) -> Result<SearchIndexStats, String> {
+    let _guard = REFRESH_LOCK.lock().map_err(|e| e.to_string())?;
+    let files = discover_session_files();
+    let mut stats = SearchIndexStats {
+        scanned_files: files.len(),
+        ..Default::default()
+    };
+
+    let (known, mut gemini_projects) = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id, path, modified_at, size FROM session_search_files")
+            .map_err(|e| e.to_string())?;
+        let known: HashMap<String, (i64, i64, i64)> = stmt
+            .query_map([], |row| {
+                Ok((row.get(1)?, (row.get(0)?, row.get(2)?, row.get(3)?)))
+            })
+            .map_err(|e| e.to_string())?
+            .filter_map(Result::ok)
+            .collect();
+
+        let projects = project_paths_by_hash(&conn).map_err(|e| e.to_string())?;
+        (known, projects)
+    };
+
+    // Gemini last, so its project hashes can resolve against paths indexed in this pass
+    let mut changed: Vec<&SessionFile> = files
+        .iter()
+        .filter(|f| {
+            let path = f.path.to_string_lossy();
+            let up_to_date = matches!(
+                known.get(path.as_ref()),
+                Some(&(_, modified_at, size)) if modified_at == f.modified_at && size == f.size
+            );
+            !up_to_date
+        })
+        .collect();
+    changed.sort_by_key(|f| f.engine == EngineKind::Gemini);
+
+    for file in changed {
+        let transcript = match load_transcript(file, &gemini_projects) {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                log::debug!("[SessionSearch] Skipping {:?}: {}", file.path, e);
+                stats.failed_files += 1;
+                continue;
+            }
+        };
+        if let Some(ref project_path) = transcript.project_path {
+            gemini_projects
+                .entry(super::gemini::config::hash_project_path(project_path))
+                .or_insert_with(|| project_path.clone());
+        }
+
+        let mut conn = db.lock().map_err(|e| e.to_string())?;
+        match index_transcript(&mut conn, file, &transcript) {
+            Ok(()) => stats.indexed_files += 1,
+            Err(e) => {
+                log::warn!("[SessionSearch] Failed to index {:?}: {}", file.path, e);
+                stats.failed_files += 1;
+            }
+        }
+    }
+
+    // Files that no longer exist
+    let on_disk: HashSet<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    for (path, (file_id, _, _)) in &known {
+        if !on_disk.contains(path) {
+            remove_file_rows(&conn, *file_id).map_err(|e| e.to_string())?;
+            stats.removed_files += 1;
+        }
+    }
+
+    if stats.indexed_files > 0 || stats.removed_files > 0 {
+        log::info!("[SessionSearch] Index refreshed: {:?}", stats);
+    }
+    Ok(stats)
+}
+
+// ============================================================================
+// Query
+// ============================================================================
+
+/// Turn free text into an FTS5 query: every term must match, as a literal
+///
+/// The trigram tokenizer needs at least 3 characters per term, so shorter
+/// terms are left to `short_terms`.
+fn build_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|t| t.chars().count() >= 3)
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
+
+/// Terms under 3 characters (e.g. "登录"), matched with LIKE instead
+fn short_terms(query: &str) -> Vec<&str> {
+    query
+        .split_whitespace()
+        .filter(|t| t.chars().count() < 3)
+        .collect()
+}
+
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn highlight_snippet(raw: &str) -> String {
+    escape_html(raw)
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+/// Snippet around the first occurrence of `term`, for hits found without MATCH
+/// (`snippet()` only works on a full-text query)
+fn like_snippet(content: &str, term: &str) -> String {
+    const CONTEXT_CHARS: usize = 32;
+
+    // LIKE ignores ASCII case; ASCII lowercasing keeps the byte offsets
+    let Some(start) = content
+        .to_ascii_lowercase()
+        .find(&term.to_ascii_lowercase())
+    else {
+        return highlight_snippet(&content.chars().take(CONTEXT_CHARS * 2).collect::<String>());
+    };
+    let end = start + term.len();
+
+    let before: Vec<char> = content[..start].chars().collect();
+    let from = before.len().saturating_sub(CONTEXT_CHARS);
+    let mut raw = String::new();
+    if from > 0 {
+        raw.push('…');
+    }
+    raw.extend(&before[from..]);
+    raw.push(MARK_START);
+    raw.push_str(&content[start..end]);
+    raw.push(MARK_END);
+    let mut after = content[end..].chars();
+    raw.extend(after.by_ref().take(CONTEXT_CHARS));
+    if after.next().is_some() {
+        raw.push('…');
+    }
+    highlight_snippet(&raw)
+}
+
+pub fn search(
+    conn: &Connection,
+    query: &str,
+    engine: Option<EngineKind>,
+    project_path: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SessionSearchHit>, String> {
+    let match_query = build_match_query(query);
+    let short_terms = short_terms(query);
+    if match_query.is_none() && short_terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![
+        Box::new(engine.map(|e| e.as_str())),
+        Box::new(project_path.map(str::to_string)),
+        Box::new(limit as i64),
+    ];
+    let mut conditions = vec![
+        "(?1 IS NULL OR f.engine = ?1)".to_string(),
+        "(?2 IS NULL OR f.project_path = ?2)".to_string(),
+    ];
+    let ranked = match_query.is_some();
+    if let Some(match_query) = match_query {
+        params.push(Box::new(match_query));
+        conditions.push(format!("session_search MATCH ?{}", params.len()));
+    }
+    for term in &short_terms {
+        params.push(Box::new(like_pattern(term)));
+        conditions.push(format!(
+            "session_search.content LIKE ?{} ESCAPE '\\'",
+            params.len()
+        ));
+    }
+
+    // Without a MATCH there is no bm25 or snippet(): newest hits first,
+    // and the snippet is cut around the first short term
+    let (columns, order) = if ranked {
+        (
+            format!(
+                "snippet(session_search, 0, '{}', '{}', '…', 64), bm25(session_search)",
+                MARK_START, MARK_END
+            ),
+            "bm25(session_search)",
+        )
+    } else {
+        (
+            "session_search.content, 0.0".to_string(),
+            "d.timestamp DESC",
+        )
+    };
+    let sql = format!(
+        "SELECT f.engine, f.session_id, f.project_path, d.timestamp, d.kind, {}
+         FROM session_search
+         JOIN session_search_docs d ON d.id = session_search.rowid
+         JOIN session_search_files f ON f.id = d.file_id
+         WHERE {}
+         ORDER BY {}
+         LIMIT ?3",
+        columns,
+        conditions.join(" AND "),
+        order
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| {
+                let engine: String = row.get(0)?;
+                let text: String = row.get(5)?;
+                let bm25: f64 = row.get(6)?;
+                Ok(SessionSearchHit {
+                    engine: engine.parse().unwrap_or(EngineKind::Claude),
+                    session_id: row.get(1)?,
+                    project_path: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    kind: row.get(4)?,
+                    snippet: if ranked {
+                        highlight_snippet(&text)
+                    } else {
+                        like_snippet(&text, short_terms[0])
+                    },
+                    score: -bm25,
+                })
+            },
+        )
+        .map_err(|e| format!("Search failed: {}", e))?;
+
+    rows.collect::<SqliteResult<Vec<_>>>()
+        .map_err(|e| format!("Search failed: {}", e))
+}
+
+// ============================================================================
+// Background indexer + Tauri commands
+// ============================================================================
+
+/// Index once at startup, then again after sessions complete and periodically
+pub fn start_search_indexer(app: &AppHandle) {
+    let (tx, rx) = mpsc::channel::<()>();
+    for event in ["claude-complete", "codex-complete", "gemini-complete"] {
+        let tx = tx.clone();
+        app.listen_any(event, move |_| {
+            let _ = tx.send(());
+        });
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let db = app.state::<AgentDb>();
+        if let Err(e) = refresh_index(&db.0) {
+            log::warn!("[SessionSearch] Index refresh failed: {}", e);
+        }
+
+        match rx.recv_timeout(REFRESH_INTERVAL) {
+            Ok(()) => {
+                // Coalesce bursts (several sessions finishing together)
+                std::thread::sleep(Duration::from_secs(2));
+                while rx.try_recv().is_ok() {}
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    });
+}
+
+/// Ranked full-text hits across all sessions
+#[tauri::command]
+pub async fn search_sessions(
+    db: State<'_, AgentDb>,
+    query: String,
+    engine: Option<EngineKind>,
+    project_path: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SessionSearchHit>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    search(
+        &conn,
+        &query,
+        engine,
+        project_path.as_deref(),
+        limit.unwrap_or(DEFAULT_LIMIT),
+    )
+}
+
+/// Re-scan history files now instead of waiting for the background indexer
+#[tauri::command]
+pub async fn refresh_session_search_index(app: AppHandle) -> Result<SearchIndexStats, String> {
+    tauri::async_runtime::spawn_blocking(move || refresh_index(&app.state::<AgentDb>().0))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn match_query_quotes_terms_and_drops_short_ones() {
+        assert_eq!(
+            build_match_query("OAuth refresh \"bug\" to").as_deref(),
+            Some("\"OAuth\" AND \"refresh\" AND \"\"\"bug\"\"\"")
+        );
+        assert_eq!(build_match_query("to be"), None);
+    }
+
+    #[test]
+    fn indexed_sessions_are_searchable_and_replaced_on_change() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_search_tables(&conn).unwrap();
+
+        let file = SessionFile {
+            path: PathBuf::from("/tmp/claude/s1.jsonl"),
+            engine: EngineKind::Claude,
+            modified_at: 1,
+            size: 10,
+        };
+        let history = vec![
+            json!({"type": "user", "cwd": "/work/app", "timestamp": "2025-06-01T10:00:00Z",
+                   "message": {"role": "user", "content": "Fix the OAuth refresh <bug>"}}),
+            json!({"type": "assistant", "timestamp": "2025-06-01T10:00:05Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "id": "t1", "name": "Grep", "input": {"pattern": "refresh_token"}}
+            ]}}),
+        ];
+        index_transcript(&mut conn, &file, &Transcript::from_claude("s1", &history)).unwrap();
+
+        let hits = search(&conn, "oauth refresh", None, None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, "s1");
+        assert_eq!(hits[0].kind, "prompt");
+        assert_eq!(hits[0].project_path.as_deref(), Some("/work/app"));
+        assert!(hits[0].snippet.contains("<mark>"));
+        assert!(hits[0].snippet.contains("&lt;bug&gt;"));
+
+        let tool_hits = search(&conn, "refresh_token", Some(EngineKind::Claude), None, 10).unwrap();
+        assert_eq!(tool_hits[0].kind, "tool");
+        assert!(search(&conn, "refresh", Some(EngineKind::Codex), None, 10)
+            .unwrap()
+            .is_empty());
+
+        // Re-indexing the same file replaces its rows
+        let history = vec![
+            json!({"type": "user", "message": {"role": "user", "content": "Rename the crate"}}),
+        ];
+        index_transcript(&mut conn, &file, &Transcript::from_claude("s1", &history)).unwrap();
+        assert!(search(&conn, "oauth", None, None, 10).unwrap().is_empty());
+        assert_eq!(search(&conn, "crate", None, None, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn short_terms_are_matched_with_like() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_search_tables(&conn).unwrap();
+
+        let file = SessionFile {
+            path: PathBuf::from("/tmp/claude/s1.jsonl"),
+            engine: EngineKind::Claude,
+            modified_at: 1,
+            size: 10,
+        };
+        let history = vec![
+            json!({"type": "user", "timestamp": "2025-06-01T10:00:00Z",
+                   "message": {"role": "user", "content": "修复登录页面的 OAuth 跳转"}}),
+            json!({"type": "user", "timestamp": "2025-06-01T10:01:00Z",
+                   "message": {"role": "user", "content": "100% done"}}),
+        ];
+        index_transcript(&mut conn, &file, &Transcript::from_claude("s1", &history)).unwrap();
+
+        let hits = search(&conn, "登录", None, None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "修复<mark>登录</mark>页面的 OAuth 跳转");
+
+        // Mixed with a long term, and LIKE wildcards are literal
+        assert_eq!(
+            search(&conn, "登录 oauth", None, None, 10).unwrap().len(),
+            1
+        );
+        assert!(search(&conn, "登录 rename", None, None, 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(search(&conn, "0%", None, None, 10).unwrap().len(), 1);
+        assert!(search(&conn, "0_", None, None, 10).unwrap().is_empty());
+    }
+}
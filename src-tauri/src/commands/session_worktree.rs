@@ -0,0 +1,666 @@
+//! Session Worktrees (会话隔离模式)
+//!
+//! Optional execution mode where a new session runs in its own `git worktree`
+//! on its own branch instead of the user's checkout. Auto-commits from
+//! `ensure_git_repo` / `mark_prompt_completed` and every rewind then happen on
+//! the session branch; the user's working copy is only touched by an explicit
+//! `merge_session_worktree`. The project must already be a git repository
+//! with at least one commit.
+//!
+//! - Worktrees: ~/.anycode/worktrees/{project}-session-{id}
+//! - Branches:  anycode/session-{id}
+//! - Registry:  ~/.anycode/session-worktrees.json
+//!
+//! Session IDs are attached to a worktree as the engines announce them
+//! (`claude-session-state`, `{engine}-session-init`, `{engine}-cli-session-id`),
+//! so later calls that only know the session can be routed to its worktree via
+//! `resolve_project_path`.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Listener};
+
+use super::engine::{EngineKind, EngineRequest};
+use super::git_stats::{get_git_diff_stats, GitDiffStats};
+use super::simple_git;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// Serializes read-modify-write cycles on the registry file
+static REGISTRY_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionWorktree {
+    pub id: String,
+    pub engine: EngineKind,
+    /// The user's checkout the worktree was created from
+    pub project_path: String,
+    pub worktree_path: String,
+    pub branch: String,
+    /// Branch checked out in the user's checkout at creation time
+    pub source_branch: Option<String>,
+    pub base_commit: String,
+    /// Backend and CLI session IDs that ran in this worktree
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionWorktreeInfo {
+    #[serde(flatten)]
+    pub worktree: SessionWorktree,
+    /// False when the directory was deleted outside Any Code
+    pub exists: bool,
+    pub has_uncommitted_changes: bool,
+    /// Committed changes on the session branch since `base_commit`
+    pub diff_stats: Option<GitDiffStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionWorktreeRegistry {
+    #[serde(default)]
+    worktrees: Vec<SessionWorktree>,
+}
+
+fn get_registry_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("session-worktrees.json"))
+}
+
+fn load_registry() -> Result<SessionWorktreeRegistry, String> {
+    load_json_config(get_registry_path()?)
+}
+
+fn update_registry<T>(
+    f: impl FnOnce(&mut SessionWorktreeRegistry) -> Result<T, String>,
+) -> Result<T, String> {
+    let _guard = REGISTRY_LOCK.lock().unwrap();
+    let mut registry = load_registry()?;
+    let result = f(&mut registry)?;
+    save_json_config(&registry, get_registry_path()?)?;
+    Ok(result)
+}
+
+fn same_path(a: &str, b: &str) -> bool {
+    a.trim_end_matches(['/', '\\']) == b.trim_end_matches(['/', '\\'])
+}
+
+fn find_worktree(id: &str) -> Result<SessionWorktree, String> {
+    load_registry()?
+        .worktrees
+        .into_iter()
+        .find(|w| w.id == id)
+        .ok_or_else(|| format!("Session worktree not found: {}", id))
+}
+
+// ============================================================================
+// Routing
+// ============================================================================
+
+fn session_worktree_in(
+    worktrees: Vec<SessionWorktree>,
+    session_id: &str,
+) -> Option<SessionWorktree> {
+    if session_id.is_empty() {
+        return None;
+    }
+    worktrees
+        .into_iter()
+        .find(|w| w.session_ids.iter().any(|s| s == session_id))
+}
+
+/// Worktree a session runs in, if it was started in isolation mode
+pub fn worktree_for_session(session_id: &str) -> Option<SessionWorktree> {
+    session_worktree_in(load_registry().ok()?.worktrees, session_id)
+}
+
+/// Path git/rewind operations for a session must use
+///
+/// Returns the session's worktree when it has one, otherwise `project_path`
+/// unchanged.
+pub fn resolve_project_path(session_id: &str, project_path: String) -> String {
+    let worktrees = load_registry().map(|r| r.worktrees).unwrap_or_default();
+    resolve_in(worktrees, session_id, project_path)
+}
+
+fn resolve_in(worktrees: Vec<SessionWorktree>, session_id: &str, project_path: String) -> String {
+    match session_worktree_in(worktrees, session_id) {
+        Some(worktree) if Path::new(&worktree.worktree_path).exists() => {
+            if !same_path(&worktree.worktree_path, &project_path) {
+                log::debug!(
+                    "[SessionWorktree] Routing session {} to {}",
+                    session_id,
+                    worktree.worktree_path
+                );
+            }
+            worktree.worktree_path
+        }
+        _ => project_path,
+    }
+}
+
+/// The user's checkout a session or fan-out worktree was created from
+///
+/// Budgets and usage rows are keyed by project, so runs inside
+/// ~/.anycode/worktrees count towards the project they isolate. Other paths
+/// are returned unchanged.
+pub fn source_project_path(path: &str) -> String {
+    let in_worktrees_dir = dirs::home_dir()
+        .is_some_and(|home| Path::new(path).starts_with(home.join(".anycode").join("worktrees")));
+    if !in_worktrees_dir {
+        return path.to_string();
+    }
+    let worktrees = load_registry().map(|r| r.worktrees).unwrap_or_default();
+    source_in(worktrees, path)
+}
+
+fn source_in(worktrees: Vec<SessionWorktree>, path: &str) -> String {
+    if let Some(worktree) = worktrees
+        .into_iter()
+        .find(|w| same_path(&w.worktree_path, path))
+    {
+        return worktree.project_path;
+    }
+    // Fan-out worktrees aren't registered here; git knows their checkout
+    simple_git::git_main_worktree(path).unwrap_or_else(|_| path.to_string())
+}
+
+fn attach_session_id(worktree_path: Option<&str>, known_id: Option<&str>, session_id: &str) {
+    let matches = |w: &SessionWorktree| {
+        worktree_path.is_some_and(|p| same_path(&w.worktree_path, p))
+            || known_id.is_some_and(|id| w.session_ids.iter().any(|s| s == id))
+    };
+
+    // Most sessions don't run in a worktree; avoid rewriting the registry for them
+    let needs_update = load_registry()
+        .map(|r| {
+            r.worktrees
+                .iter()
+                .any(|w| matches(w) && !w.session_ids.iter().any(|s| s == session_id))
+        })
+        .unwrap_or(false);
+    if !needs_update {
+        return;
+    }
+
+    let result = update_registry(|registry| {
+        if let Some(worktree) = registry.worktrees.iter_mut().find(|w| matches(w)) {
+            if !worktree.session_ids.iter().any(|s| s == session_id) {
+                log::info!(
+                    "[SessionWorktree] Session {} runs in {}",
+                    session_id,
+                    worktree.id
+                );
+                worktree.session_ids.push(session_id.to_string());
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!(
+            "[SessionWorktree] Failed to attach session {}: {}",
+            session_id,
+            e
+        );
+    }
+}
+
+/// Route a forked session to the worktree of the session it was forked from
+pub fn attach_forked_session(source_session_id: &str, fork_session_id: &str) {
+    attach_session_id(None, Some(source_session_id), fork_session_id);
+}
+
+/// Listen for session IDs of runs started inside a session worktree
+pub fn start_session_tracker(app: &AppHandle) {
+    for event in [
+        "claude-session-state",
+        "codex-session-init",
+        "gemini-session-init",
+    ] {
+        app.listen_any(event, |event| {
+            let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+                return;
+            };
+            let (Some(session_id), Some(project_path)) = (
+                payload.get("session_id").and_then(|s| s.as_str()),
+                payload.get("project_path").and_then(|p| p.as_str()),
+            ) else {
+                return;
+            };
+            attach_session_id(Some(project_path), None, session_id);
+        });
+    }
+
+    // Codex/Gemini report the real CLI session ID after the backend one
+    for event in ["codex-cli-session-id", "gemini-cli-session-id"] {
+        app.listen_any(event, |event| {
+            let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+                return;
+            };
+            if let (Some(backend_id), Some(cli_id)) = (
+                payload.get("backend_session_id").and_then(|s| s.as_str()),
+                payload.get("cli_session_id").and_then(|s| s.as_str()),
+            ) {
+                attach_session_id(None, Some(backend_id), cli_id);
+            }
+        });
+    }
+}
+
+/// Create a session worktree and point the request at it when isolation is requested
+pub fn prepare_request(engine: EngineKind, request: &mut EngineRequest) -> Result<(), String> {
+    if !request.worktree_isolation {
+        return Ok(());
+    }
+    let worktree = create_worktree(&request.project_path, engine)?;
+    request.project_path = worktree.worktree_path;
+    request.worktree_isolation = false;
+    Ok(())
+}
+
+/// HEAD of the user's checkout; never runs `git init` or commits there
+fn base_commit(project_path: &str) -> Result<String, String> {
+    if !simple_git::is_git_repo(project_path) {
+        return Err(format!(
+            "Session isolation needs a git repository, {} is not one",
+            project_path
+        ));
+    }
+    simple_git::git_current_commit(project_path)
+        .map_err(|e| format!("Session isolation needs a commit to start from: {}", e))
+}
+
+fn create_worktree(project_path: &str, engine: EngineKind) -> Result<SessionWorktree, String> {
+    let base_commit = base_commit(project_path)?;
+
+    let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let project_name = Path::new(project_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    let worktrees_dir = home.join(".anycode").join("worktrees");
+    std::fs::create_dir_all(&worktrees_dir)
+        .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+
+    let worktree = SessionWorktree {
+        branch: format!("anycode/session-{}", id),
+        worktree_path: worktrees_dir
+            .join(format!("{}-session-{}", project_name, id))
+            .to_string_lossy()
+            .to_string(),
+        id,
+        engine,
+        project_path: project_path.to_string(),
+        source_branch: simple_git::git_current_branch(project_path).unwrap_or(None),
+        base_commit,
+        session_ids: Vec::new(),
+        created_at: Utc::now(),
+    };
+
+    simple_git::git_worktree_add(
+        project_path,
+        &worktree.worktree_path,
+        &worktree.branch,
+        &worktree.base_commit,
+    )?;
+    update_registry(|registry| {
+        registry.worktrees.push(worktree.clone());
+        Ok(())
+    })?;
+
+    log::info!(
+        "[SessionWorktree] Created {} for {} at {}",
+        worktree.id,
+        engine,
+        worktree.worktree_path
+    );
+    Ok(worktree)
+}
+
+fn remove_worktree(worktree: &SessionWorktree) -> Result<(), String> {
+    simple_git::git_worktree_remove(&worktree.project_path, &worktree.worktree_path)?;
+    if let Err(e) = simple_git::git_branch_delete(&worktree.project_path, &worktree.branch) {
+        log::warn!(
+            "[SessionWorktree] Failed to delete branch {}: {}",
+            worktree.branch,
+            e
+        );
+    }
+    update_registry(|registry| {
+        registry.worktrees.retain(|w| w.id != worktree.id);
+        Ok(())
+    })
+}
+
+/// Merge the session branch into the checkout it was created from
+///
+/// The user's checkout must be clean and still on `source_branch`: a
+/// conflicting merge is aborted, which could lose uncommitted work there.
+fn merge_worktree(worktree: &SessionWorktree) -> Result<String, String> {
+    simple_git::git_check_merge_target(&worktree.project_path, worktree.source_branch.as_deref())?;
+    commit_pending_changes(worktree)?;
+
+    let message = format!(
+        "[Any Code] Merge session worktree {} ({})",
+        worktree.id, worktree.engine
+    );
+    simple_git::git_merge_branch(&worktree.project_path, &worktree.branch, &message)
+}
+
+/// Commit whatever the last run left uncommitted so the branch is complete
+fn commit_pending_changes(worktree: &SessionWorktree) -> Result<(), String> {
+    if simple_git::git_has_uncommitted_changes(&worktree.worktree_path)? {
+        simple_git::git_commit_changes(
+            &worktree.worktree_path,
+            &format!(
+                "[Any Code] Session worktree {} pending changes",
+                worktree.id
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Create a worktree up front; use its `worktreePath` as the session's project path
+#[tauri::command]
+pub async fn create_session_worktree(
+    project_path: String,
+    engine: EngineKind,
+) -> Result<SessionWorktree, String> {
+    create_worktree(&project_path, engine)
+}
+
+/// List session worktrees, optionally only those of one project
+#[tauri::command]
+pub async fn list_session_worktrees(
+    project_path: Option<String>,
+) -> Result<Vec<SessionWorktreeInfo>, String> {
+    let worktrees = load_registry()?.worktrees;
+    let mut infos = Vec::new();
+
+    for worktree in worktrees {
+        if let Some(filter) = &project_path {
+            if !same_path(&worktree.project_path, filter) {
+                continue;
+            }
+        }
+        let exists = Path::new(&worktree.worktree_path).exists();
+        let (has_uncommitted_changes, diff_stats) = if exists {
+            (
+                simple_git::git_has_uncommitted_changes(&worktree.worktree_path).unwrap_or(false),
+                get_git_diff_stats(
+                    worktree.worktree_path.clone(),
+                    worktree.base_commit.clone(),
+                    None,
+                )
+                .await
+                .ok(),
+            )
+        } else {
+            (false, None)
+        };
+        infos.push(SessionWorktreeInfo {
+            worktree,
+            exists,
+            has_uncommitted_changes,
+            diff_stats,
+        });
+    }
+
+    Ok(infos)
+}
+
+/// Worktree a session runs in (None = runs in the user's checkout)
+#[tauri::command]
+pub async fn get_session_worktree(session_id: String) -> Result<Option<SessionWorktree>, String> {
+    Ok(worktree_for_session(&session_id))
+}
+
+/// Merge the session branch into the user's checkout
+///
+/// Refused while the checkout has uncommitted changes or is on another branch
+/// than the one the worktree was created from. Pending changes in the worktree
+/// are committed first. With `cleanup` (default false) the worktree and branch
+/// are removed afterwards.
+#[tauri::command]
+pub async fn merge_session_worktree(
+    worktree_id: String,
+    cleanup: Option<bool>,
+) -> Result<String, String> {
+    let worktree = find_worktree(&worktree_id)?;
+    let commit = merge_worktree(&worktree)?;
+    log::info!("[SessionWorktree] Merged {} as {}", worktree.id, commit);
+
+    if cleanup.unwrap_or(false) {
+        remove_worktree(&worktree)?;
+    }
+    Ok(commit)
+}
+
+/// Rebase the session branch onto the user's current HEAD
+#[tauri::command]
+pub async fn rebase_session_worktree(worktree_id: String) -> Result<String, String> {
+    let worktree = find_worktree(&worktree_id)?;
+    commit_pending_changes(&worktree)?;
+
+    let onto = simple_git::git_current_commit(&worktree.project_path)?;
+    let head = simple_git::git_rebase(&worktree.worktree_path, &onto)?;
+
+    update_registry(|registry| {
+        if let Some(w) = registry.worktrees.iter_mut().find(|w| w.id == worktree_id) {
+            w.base_commit = onto.clone();
+        }
+        Ok(())
+    })?;
+    log::info!("[SessionWorktree] Rebased {} onto {}", worktree_id, onto);
+    Ok(head)
+}
+
+/// Delete a session worktree and its branch without merging
+#[tauri::command]
+pub async fn discard_session_worktree(worktree_id: String) -> Result<(), String> {
+    let worktree = find_worktree(&worktree_id)?;
+    remove_worktree(&worktree)?;
+    log::info!("[SessionWorktree] Discarded {}", worktree_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &str, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn base_commit_never_initializes_the_project() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one").unwrap();
+
+        let err = base_commit(path).unwrap_err();
+        assert!(err.contains("git repository"));
+        assert!(!dir.path().join(".git").exists());
+    }
+
+    #[test]
+    fn base_commit_refuses_a_repo_without_commits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        git(path, &["init", "-q"]);
+        std::fs::write(dir.path().join("a.txt"), "one").unwrap();
+
+        assert!(base_commit(path).is_err());
+        assert!(git(path, &["status", "--porcelain"]).contains("a.txt"));
+    }
+
+    #[test]
+    fn base_commit_is_head_of_the_checkout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        git(path, &["init", "-q"]);
+        std::fs::write(dir.path().join("a.txt"), "one").unwrap();
+        git(path, &["add", "."]);
+        git(path, &["commit", "-qm", "one"]);
+
+        assert_eq!(
+            base_commit(path).unwrap(),
+            git(path, &["rev-parse", "HEAD"])
+        );
+    }
+
+    /// A checkout on `main` with one commit, and a session worktree whose
+    /// branch changes a.txt
+    fn session_repo(root: &Path) -> SessionWorktree {
+        let project = root.join("project");
+        std::fs::create_dir(&project).unwrap();
+        let path = project.to_str().unwrap();
+        git(path, &["init", "-q", "-b", "main"]);
+        // The merge commits through simple_git, without the `git()` overrides
+        git(path, &["config", "user.name", "test"]);
+        git(path, &["config", "user.email", "test@example.com"]);
+        std::fs::write(project.join("a.txt"), "one\n").unwrap();
+        git(path, &["add", "."]);
+        git(path, &["commit", "-qm", "one"]);
+
+        let worktree = SessionWorktree {
+            id: "s1".to_string(),
+            engine: EngineKind::Claude,
+            project_path: path.to_string(),
+            worktree_path: root.join("session").to_string_lossy().to_string(),
+            branch: "anycode/session-s1".to_string(),
+            source_branch: Some("main".to_string()),
+            base_commit: base_commit(path).unwrap(),
+            session_ids: vec!["claude-1".to_string()],
+            created_at: Utc::now(),
+        };
+        simple_git::git_worktree_add(
+            path,
+            &worktree.worktree_path,
+            &worktree.branch,
+            &worktree.base_commit,
+        )
+        .unwrap();
+        std::fs::write(Path::new(&worktree.worktree_path).join("a.txt"), "two\n").unwrap();
+        worktree
+    }
+
+    #[test]
+    fn merge_brings_the_session_branch_into_a_clean_checkout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let worktree = session_repo(dir.path());
+
+        let commit = merge_worktree(&worktree).unwrap();
+        assert_eq!(commit, git(&worktree.project_path, &["rev-parse", "HEAD"]));
+        assert_eq!(
+            std::fs::read_to_string(Path::new(&worktree.project_path).join("a.txt")).unwrap(),
+            "two\n"
+        );
+    }
+
+    #[test]
+    fn merge_refuses_a_dirty_checkout_and_leaves_it_alone() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let worktree = session_repo(dir.path());
+        let project = Path::new(&worktree.project_path);
+        let head = git(&worktree.project_path, &["rev-parse", "HEAD"]);
+        std::fs::write(project.join("a.txt"), "mine\n").unwrap();
+
+        let err = merge_worktree(&worktree).unwrap_err();
+        assert!(err.contains("uncommitted changes"), "{}", err);
+        assert_eq!(git(&worktree.project_path, &["rev-parse", "HEAD"]), head);
+        assert_eq!(
+            std::fs::read_to_string(project.join("a.txt")).unwrap(),
+            "mine\n"
+        );
+    }
+
+    #[test]
+    fn merge_refuses_a_checkout_on_another_branch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let worktree = session_repo(dir.path());
+        git(&worktree.project_path, &["checkout", "-qb", "feature"]);
+        let head = git(&worktree.project_path, &["rev-parse", "HEAD"]);
+
+        let err = merge_worktree(&worktree).unwrap_err();
+        assert!(err.contains("feature") && err.contains("main"), "{}", err);
+        assert_eq!(git(&worktree.project_path, &["rev-parse", "HEAD"]), head);
+    }
+
+    #[test]
+    fn worktree_runs_are_charged_to_their_checkout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let worktree = session_repo(dir.path());
+        let project = Path::new(&worktree.project_path).canonicalize().unwrap();
+
+        assert_eq!(
+            source_in(vec![worktree.clone()], &worktree.worktree_path),
+            worktree.project_path
+        );
+        // Not registered (fan-out): resolved through git
+        let resolved = source_in(Vec::new(), &worktree.worktree_path);
+        assert_eq!(Path::new(&resolved).canonicalize().unwrap(), project);
+        assert_eq!(
+            source_in(Vec::new(), &worktree.project_path),
+            project.to_string_lossy()
+        );
+
+        // Outside ~/.anycode/worktrees nothing is looked up
+        assert_eq!(
+            source_project_path(&worktree.worktree_path),
+            worktree.worktree_path
+        );
+    }
+
+    #[test]
+    fn sessions_are_routed_to_existing_worktrees_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let worktree = session_repo(dir.path());
+        let project_path = worktree.project_path.clone();
+        let worktrees = || vec![worktree.clone()];
+
+        assert_eq!(
+            resolve_in(worktrees(), "claude-1", project_path.clone()),
+            worktree.worktree_path
+        );
+        assert_eq!(
+            resolve_in(worktrees(), "other", project_path.clone()),
+            project_path
+        );
+        assert_eq!(
+            resolve_in(worktrees(), "", project_path.clone()),
+            project_path
+        );
+
+        // Deleted outside Any Code: fall back to the checkout
+        std::fs::remove_dir_all(&worktree.worktree_path).unwrap();
+        assert_eq!(
+            resolve_in(worktrees(), "claude-1", project_path.clone()),
+            project_path
+        );
+    }
+}
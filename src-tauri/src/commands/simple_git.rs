@@ -785,3 +785,150 @@ pub fn check_reset_safety(
     })
 }
 
+// ============================================================================
+// Worktrees (多引擎并行 / 会话隔离)
+// ============================================================================
+
+/// Run a git command and return trimmed stdout
+fn run_git(project_path: &str, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.args(args);
+    cmd.current_dir(project_path);
+
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute git {}: {}", args[0], e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Current branch name (None when HEAD is detached)
+pub fn git_current_branch(project_path: &str) -> Result<Option<String>, String> {
+    let branch = run_git(project_path, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    Ok((branch != "HEAD").then_some(branch))
+}
+
+/// Main checkout of the repository `path` belongs to (itself unless it is a linked worktree)
+pub fn git_main_worktree(path: &str) -> Result<String, String> {
+    let common_dir = run_git(
+        path,
+        &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+    )?;
+    Path::new(&common_dir)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Unexpected git directory: {}", common_dir))
+}
+
+/// Create a worktree on a new branch starting at `base`
+pub fn git_worktree_add(
+    project_path: &str,
+    worktree_path: &str,
+    branch: &str,
+    base: &str,
+) -> Result<(), String> {
+    log::info!(
+        "Creating worktree {} on branch {} from {}",
+        worktree_path,
+        branch,
+        base
+    );
+    run_git(
+        project_path,
+        &["worktree", "add", "-b", branch, worktree_path, base],
+    )?;
+    Ok(())
+}
+
+/// Remove a worktree (uncommitted changes in it are discarded)
+pub fn git_worktree_remove(project_path: &str, worktree_path: &str) -> Result<(), String> {
+    log::info!("Removing worktree {}", worktree_path);
+    if let Err(e) = run_git(project_path, &["worktree", "remove", "--force", worktree_path]) {
+        // Directory already gone: drop the stale administrative entry instead
+        log::warn!("Worktree remove failed ({}), pruning", e);
+        run_git(project_path, &["worktree", "prune"])?;
+    }
+    Ok(())
+}
+
+/// Delete a local branch, even if it was never merged
+pub fn git_branch_delete(project_path: &str, branch: &str) -> Result<(), String> {
+    run_git(project_path, &["branch", "-D", branch])?;
+    Ok(())
+}
+
+/// Merge a branch into the current branch with a merge commit
+///
+/// On conflicts the merge is aborted. `merge --abort` can't always restore
+/// uncommitted changes, so callers check `git_check_merge_target` first.
+pub fn git_merge_branch(project_path: &str, branch: &str, message: &str) -> Result<String, String> {
+    log::info!("Merging branch {} into {}", branch, project_path);
+    if let Err(e) = run_git(project_path, &["merge", "--no-ff", "-m", message, branch]) {
+        let _ = run_git(project_path, &["merge", "--abort"]);
+        return Err(e);
+    }
+    git_current_commit(project_path)
+}
+
+/// Whether the working tree has uncommitted (staged, unstaged or untracked) changes
+pub fn git_has_uncommitted_changes(project_path: &str) -> Result<bool, String> {
+    Ok(!run_git(project_path, &["status", "--porcelain"])?.is_empty())
+}
+
+/// Refuse to merge into a checkout the user is working in
+///
+/// The working tree must be clean and still on `expected_branch`, the branch
+/// the worktrees were created from (None = detached HEAD).
+pub fn git_check_merge_target(
+    project_path: &str,
+    expected_branch: Option<&str>,
+) -> Result<(), String> {
+    if git_has_uncommitted_changes(project_path)? {
+        return Err(format!(
+            "{} has uncommitted changes; commit or stash them before merging",
+            project_path
+        ));
+    }
+    let current = git_current_branch(project_path)?;
+    if current.as_deref() != expected_branch {
+        return Err(format!(
+            "{} is on {}, but the worktree was created from {}; switch back before merging",
+            project_path,
+            current.as_deref().unwrap_or("a detached HEAD"),
+            expected_branch.unwrap_or("a detached HEAD")
+        ));
+    }
+    Ok(())
+}
+
+/// Rebase the current branch onto `onto`, aborting on conflicts
+pub fn git_rebase(project_path: &str, onto: &str) -> Result<String, String> {
+    log::info!("Rebasing {} onto {}", project_path, onto);
+    if let Err(e) = run_git(project_path, &["rebase", onto]) {
+        let _ = run_git(project_path, &["rebase", "--abort"]);
+        return Err(e);
+    }
+    git_current_commit(project_path)
+}
+
+/// Create `branch` at `start` and switch the working tree to it
+pub fn git_checkout_new_branch(
+    project_path: &str,
+    branch: &str,
+    start: &str,
+) -> Result<(), String> {
+    log::info!("Checking out new branch {} at {}", branch, start);
+    run_git(project_path, &["checkout", "-b", branch, start])?;
+    Ok(())
+}
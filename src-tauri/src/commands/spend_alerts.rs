@@ -0,0 +1,516 @@
+//! Spend and quota alerts
+//!
+//! A background watcher that raises a desktop notification and a
+//! `spend-alert` event when:
+//!
+//! - today's or this month's spend (all engines, from the usage index)
+//!   reaches its threshold; once per day / month
+//! - the remaining balance of an active third-party provider key drops to
+//!   the low-balance threshold; once until the balance recovers
+//!
+//! Balances are read through `query_provider_usage`, so only New-API style
+//! providers report one; official endpoints are not polled.
+//!
+//! - Config: ~/.anycode/spend_alerts.json (disabled by default)
+//! - Raised alerts: ~/.anycode/spend_alerts_raised.json, so a restart doesn't
+//!   announce the same threshold again
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use super::engine::EngineKind;
+use super::storage::AgentDb;
+use super::usage_index;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// Don't check more often than this, whatever the config says
+const MIN_INTERVAL_SECS: u64 = 60;
+
+/// Alerts already raised (`daily:2025-06-03`, `balance:<url>`, ...)
+static RAISED: Lazy<Mutex<BTreeSet<String>>> = Lazy::new(|| Mutex::new(load_raised()));
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendAlertConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Alert when today's spend reaches this (USD)
+    #[serde(default)]
+    pub daily_threshold_usd: Option<f64>,
+    /// Alert when this month's spend reaches this (USD)
+    #[serde(default)]
+    pub monthly_threshold_usd: Option<f64>,
+    /// Alert when a provider key has this much left or less (USD)
+    #[serde(default)]
+    pub low_balance_usd: Option<f64>,
+    /// Show a desktop notification besides the event
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+    #[serde(default = "default_spend_interval_secs")]
+    pub spend_interval_secs: u64,
+    #[serde(default = "default_balance_interval_secs")]
+    pub balance_interval_secs: u64,
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+fn default_spend_interval_secs() -> u64 {
+    300
+}
+
+fn default_balance_interval_secs() -> u64 {
+    1800
+}
+
+impl Default for SpendAlertConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            daily_threshold_usd: None,
+            monthly_threshold_usd: None,
+            low_balance_usd: None,
+            notify: default_notify(),
+            spend_interval_secs: default_spend_interval_secs(),
+            balance_interval_secs: default_balance_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpendAlertKind {
+    Daily,
+    Monthly,
+    LowBalance,
+}
+
+/// Payload of `spend-alert`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendAlert {
+    pub kind: SpendAlertKind,
+    /// Provider the balance belongs to (low balance only)
+    pub engine: Option<EngineKind>,
+    pub base_url: Option<String>,
+    pub threshold_usd: f64,
+    /// Spend so far, or the remaining balance
+    pub value_usd: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderBalance {
+    pub engine: EngineKind,
+    pub base_url: String,
+    pub remaining_usd: Option<f64>,
+    pub is_unlimited: bool,
+    /// Set when the provider couldn't be queried
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendAlertStatus {
+    pub config: SpendAlertConfig,
+    pub today_usd: f64,
+    pub month_usd: f64,
+    pub balances: Vec<ProviderBalance>,
+    /// Alerts raised by this check
+    pub alerts: Vec<SpendAlert>,
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("spend_alerts.json"))
+}
+
+fn load_config() -> SpendAlertConfig {
+    get_config_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[SpendAlerts] Failed to load config, using defaults: {}", e);
+            SpendAlertConfig::default()
+        })
+}
+
+fn get_raised_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("spend_alerts_raised.json"))
+}
+
+fn load_raised() -> BTreeSet<String> {
+    get_raised_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[SpendAlerts] Failed to load raised alerts: {}", e);
+            BTreeSet::new()
+        })
+}
+
+fn save_raised(raised: &BTreeSet<String>) {
+    if let Err(e) = get_raised_path().and_then(|path| save_json_config(raised, path)) {
+        log::warn!("[SpendAlerts] Failed to save raised alerts: {}", e);
+    }
+}
+
+/// Forget spend alerts of past days and months (their keys can't come back)
+fn prune_raised(raised: &mut BTreeSet<String>, today: &str, month: &str) {
+    raised.retain(|key| match key.split_once(':') {
+        Some(("daily", day)) => day == today,
+        Some(("monthly", m)) => m == month,
+        _ => true,
+    });
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+/// Today's and this month's spend across all engines
+fn current_spend(app: &AppHandle) -> Result<(f64, f64), String> {
+    let today = Local::now().date_naive();
+    let start_of = |date: NaiveDate| {
+        date.and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|start| start.to_rfc3339())
+            .ok_or_else(|| format!("No local midnight on {}", date))
+    };
+    let day_start = start_of(today)?;
+    let month_start = start_of(today.with_day(1).unwrap_or(today))?;
+    usage_index::spend_since(&app.state::<AgentDb>().0, &day_start, &month_start)
+}
+
+/// Crossed spend thresholds, keyed per day / month
+fn spend_alerts(config: &SpendAlertConfig, day: f64, month: f64) -> Vec<(String, SpendAlert)> {
+    let now = Local::now();
+    let mut alerts = Vec::new();
+    let checks = [
+        (
+            SpendAlertKind::Daily,
+            config.daily_threshold_usd,
+            day,
+            format!("daily:{}", now.format("%Y-%m-%d")),
+            "Today's",
+        ),
+        (
+            SpendAlertKind::Monthly,
+            config.monthly_threshold_usd,
+            month,
+            format!("monthly:{}", now.format("%Y-%m")),
+            "This month's",
+        ),
+    ];
+    for (kind, threshold, spent, key, label) in checks {
+        let Some(threshold) = threshold.filter(|t| *t > 0.0) else {
+            continue;
+        };
+        if spent >= threshold {
+            alerts.push((
+                key,
+                SpendAlert {
+                    kind,
+                    engine: None,
+                    base_url: None,
+                    threshold_usd: threshold,
+                    value_usd: spent,
+                    message: format!(
+                        "{} AI spend is ${:.2}, over the ${:.2} threshold",
+                        label, spent, threshold
+                    ),
+                },
+            ));
+        }
+    }
+    alerts
+}
+
+/// Base URL and key of each engine's active third-party provider
+async fn active_provider_keys() -> Vec<(EngineKind, String, String)> {
+    let mut keys = Vec::new();
+    if let Ok(c) = super::provider::get_current_provider_config() {
+        let key = c.anthropic_auth_token.or(c.anthropic_api_key);
+        if let (Some(url), Some(key)) = (c.anthropic_base_url, key) {
+            keys.push((EngineKind::Claude, url, key));
+        }
+    }
+    if let Ok(c) = super::codex::config::get_current_codex_config().await {
+        if let (Some(url), Some(key)) = (c.base_url, c.api_key) {
+            keys.push((EngineKind::Codex, url, key));
+        }
+    }
+    if let Ok(c) = super::gemini::provider::get_current_gemini_provider_config().await {
+        if let (Some(url), Some(key)) = (c.base_url, c.api_key) {
+            keys.push((EngineKind::Gemini, url, key));
+        }
+    }
+    keys.retain(|(_, url, key)| !url.trim().is_empty() && !key.trim().is_empty());
+    keys
+}
+
+async fn poll_balances() -> Vec<ProviderBalance> {
+    let mut balances = Vec::new();
+    for (engine, base_url, api_key) in active_provider_keys().await {
+        let balance = match super::provider::query_provider_usage(base_url.clone(), api_key).await {
+            Ok(usage) => ProviderBalance {
+                engine,
+                base_url,
+                remaining_usd: Some(usage.remaining_balance),
+                is_unlimited: usage.is_unlimited,
+                error: None,
+            },
+            Err(e) => {
+                log::debug!("[SpendAlerts] No balance for {}: {}", base_url, e);
+                ProviderBalance {
+                    engine,
+                    base_url,
+                    remaining_usd: None,
+                    is_unlimited: false,
+                    error: Some(e),
+                }
+            }
+        };
+        balances.push(balance);
+    }
+    balances
+}
+
+/// Low-balance alerts; a key that recovered can alert again
+fn balance_alerts(
+    config: &SpendAlertConfig,
+    balances: &[ProviderBalance],
+    raised: &mut BTreeSet<String>,
+) -> Vec<(String, SpendAlert)> {
+    let Some(threshold) = config.low_balance_usd else {
+        return Vec::new();
+    };
+    let mut alerts = Vec::new();
+    for balance in balances {
+        let Some(remaining) = balance.remaining_usd.filter(|_| !balance.is_unlimited) else {
+            continue;
+        };
+        let key = format!("balance:{}", balance.base_url);
+        if remaining > threshold {
+            raised.remove(&key);
+            continue;
+        }
+        alerts.push((
+            key,
+            SpendAlert {
+                kind: SpendAlertKind::LowBalance,
+                engine: Some(balance.engine),
+                base_url: Some(balance.base_url.clone()),
+                threshold_usd: threshold,
+                value_usd: remaining,
+                message: format!(
+                    "{} key at {} has ${:.2} left",
+                    balance.engine, balance.base_url, remaining
+                ),
+            },
+        ));
+    }
+    alerts
+}
+
+fn raise(app: &AppHandle, config: &SpendAlertConfig, alert: &SpendAlert) {
+    log::warn!("[SpendAlerts] {}", alert.message);
+    if let Err(e) = app.emit("spend-alert", alert) {
+        log::warn!("[SpendAlerts] Failed to emit spend-alert: {}", e);
+    }
+    if config.notify {
+        let title = match alert.kind {
+            SpendAlertKind::Daily | SpendAlertKind::Monthly => "Spend threshold reached",
+            SpendAlertKind::LowBalance => "Provider balance low",
+        };
+        if let Err(e) = app
+            .notification()
+            .builder()
+            .title(title)
+            .body(&alert.message)
+            .show()
+        {
+            log::warn!("[SpendAlerts] Failed to show notification: {}", e);
+        }
+    }
+}
+
+/// Raise the alerts that weren't raised before; returns the new ones
+fn raise_new(
+    app: &AppHandle,
+    config: &SpendAlertConfig,
+    alerts: Vec<(String, SpendAlert)>,
+    raised: &mut BTreeSet<String>,
+) -> Vec<SpendAlert> {
+    alerts
+        .into_iter()
+        .filter(|(key, _)| raised.insert(key.clone()))
+        .map(|(_, alert)| {
+            raise(app, config, &alert);
+            alert
+        })
+        .collect()
+}
+
+/// One round of checks; balances are only polled when `with_balances` is set
+async fn check(
+    app: &AppHandle,
+    config: &SpendAlertConfig,
+    with_balances: bool,
+) -> Result<SpendAlertStatus, String> {
+    let handle = app.clone();
+    let (today_usd, month_usd) =
+        tauri::async_runtime::spawn_blocking(move || current_spend(&handle))
+            .await
+            .map_err(|e| format!("Failed to collect usage: {}", e))??;
+    let balances = if with_balances {
+        poll_balances().await
+    } else {
+        Vec::new()
+    };
+
+    let mut raised = RAISED.lock().map_err(|e| e.to_string())?;
+    let before = raised.clone();
+    let now = Local::now();
+    prune_raised(
+        &mut raised,
+        &now.format("%Y-%m-%d").to_string(),
+        &now.format("%Y-%m").to_string(),
+    );
+    let mut pending = spend_alerts(config, today_usd, month_usd);
+    pending.extend(balance_alerts(config, &balances, &mut raised));
+    let alerts = raise_new(app, config, pending, &mut raised);
+    if *raised != before {
+        save_raised(&raised);
+    }
+
+    Ok(SpendAlertStatus {
+        config: config.clone(),
+        today_usd,
+        month_usd,
+        balances,
+        alerts,
+    })
+}
+
+/// Start the watcher; the config is re-read every round
+pub fn start_spend_alert_watcher(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut last_balance_poll: Option<Instant> = None;
+        loop {
+            let config = load_config();
+            if config.enabled {
+                let balance_due = config.low_balance_usd.is_some()
+                    && last_balance_poll.is_none_or(|at| {
+                        at.elapsed() >= Duration::from_secs(config.balance_interval_secs)
+                    });
+                if balance_due {
+                    last_balance_poll = Some(Instant::now());
+                }
+                if let Err(e) = check(&app, &config, balance_due).await {
+                    log::warn!("[SpendAlerts] Check failed: {}", e);
+                }
+            }
+            let interval = config.spend_interval_secs.max(MIN_INTERVAL_SECS);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_spend_alert_config() -> Result<SpendAlertConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_spend_alert_config(
+    config: SpendAlertConfig,
+) -> Result<SpendAlertConfig, String> {
+    save_json_config(&config, get_config_path()?)?;
+    Ok(config)
+}
+
+/// Check spend and balances now (alerts are raised as by the watcher)
+#[tauri::command]
+pub async fn check_spend_alerts(app: AppHandle) -> Result<SpendAlertStatus, String> {
+    check(&app, &load_config(), true).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(remaining: f64) -> ProviderBalance {
+        ProviderBalance {
+            engine: EngineKind::Claude,
+            base_url: "https://relay.example".to_string(),
+            remaining_usd: Some(remaining),
+            is_unlimited: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn low_balance_alerts_again_after_recovering() {
+        let config = SpendAlertConfig {
+            low_balance_usd: Some(5.0),
+            ..Default::default()
+        };
+        let mut raised = BTreeSet::new();
+
+        let alerts = balance_alerts(&config, &[balance(3.0)], &mut raised);
+        assert_eq!(alerts.len(), 1);
+        assert!(raised.insert(alerts[0].0.clone()));
+
+        // Still low: same key, so it is not raised twice
+        let alerts = balance_alerts(&config, &[balance(2.0)], &mut raised);
+        assert!(!raised.insert(alerts[0].0.clone()));
+
+        // Topped up, then low again
+        assert!(balance_alerts(&config, &[balance(50.0)], &mut raised).is_empty());
+        let alerts = balance_alerts(&config, &[balance(4.0)], &mut raised);
+        assert!(raised.insert(alerts[0].0.clone()));
+    }
+
+    #[test]
+    fn only_current_spend_alerts_are_kept() {
+        let mut raised: BTreeSet<String> = [
+            "daily:2025-06-02",
+            "daily:2025-06-03",
+            "monthly:2025-05",
+            "monthly:2025-06",
+            "balance:https://relay.example",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        prune_raised(&mut raised, "2025-06-03", "2025-06");
+        assert_eq!(
+            raised.into_iter().collect::<Vec<_>>(),
+            vec![
+                "balance:https://relay.example",
+                "daily:2025-06-03",
+                "monthly:2025-06"
+            ]
+        );
+    }
+}
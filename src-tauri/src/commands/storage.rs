@@ -17,7 +17,11 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         .expect("Failed to get app data dir");
     std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
 
-    let db_path = app_dir.join("agents.db");
+    open_database(&app_dir.join("agents.db"))
+}
+
+/// Open `agents.db` at `db_path` and create any missing tables
+pub fn open_database(db_path: &std::path::Path) -> SqliteResult<Connection> {
     let conn = Connection::open(db_path)?;
 
     // ========== 🚀 性能优化：启用 WAL 模式和优化参数 ==========
@@ -44,10 +48,15 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
             total_tokens INTEGER DEFAULT 0,
             cost REAL DEFAULT 0.0,
             project_path TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            engine TEXT NOT NULL DEFAULT 'claude',
+            message_id TEXT,
+            file_id INTEGER
         )",
         [],
     )?;
+    // engine / message_id / file_id 列（旧数据库补列）+ (session_id, message_id) 唯一索引
+    super::usage_recorder::migrate_usage_table(&conn)?;
 
     // ========== 🚀 性能优化：添加数据库索引 ==========
 
@@ -93,7 +102,13 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
-    log::info!("✅ Database indexes created successfully (6 indexes)");
+    log::info!("✅ Database indexes created successfully (7 indexes)");
+
+    // 会话全文检索（FTS5）
+    super::session_search::create_search_tables(&conn)?;
+
+    // 用量增量索引（文件偏移 + 解析结果）
+    super::usage_index::create_usage_index_tables(&conn)?;
 
     Ok(conn)
 }
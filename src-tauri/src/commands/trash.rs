@@ -0,0 +1,513 @@
+//! Recycle Bin (会话 / 项目回收站)
+//!
+//! Deleting a session or a project moves its files here instead of removing
+//! them, so an accidental (batch) delete can be undone.
+//!
+//! - Entries:  ~/.anycode/trash/{id}/entry.json + the moved files next to it
+//! - Config:   ~/.anycode/trash.json (`retentionDays`, 0 = keep until purged)
+//!
+//! Expired entries are purged at startup, whenever something new is trashed
+//! and when the trash is listed.
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::engine::EngineKind;
+use crate::utils::config_utils::{load_json_config, save_json_config};
+
+/// Serializes moves in and out of the trash directory
+static TRASH_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+const ENTRY_FILE: &str = "entry.json";
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashConfig {
+    /// Entries older than this are purged automatically (0 = never)
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_retention_days() -> u32 {
+    30
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: default_retention_days(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashItemKind {
+    Session,
+    Project,
+}
+
+/// What is being deleted; the caller passes the files that belong to it
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    pub kind: TrashItemKind,
+    pub engine: EngineKind,
+    pub project_id: Option<String>,
+    pub project_path: Option<String>,
+    pub session_id: Option<String>,
+    /// Shown in the trash list (e.g. the first user message)
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedFile {
+    pub original_path: String,
+    /// Name inside the entry directory
+    pub stored_name: String,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: String,
+    pub kind: TrashItemKind,
+    pub engine: EngineKind,
+    pub project_id: Option<String>,
+    pub project_path: Option<String>,
+    pub session_id: Option<String>,
+    pub title: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+    /// Total size in bytes
+    pub size: u64,
+    pub files: Vec<TrashedFile>,
+}
+
+fn get_trash_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("trash"))
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".anycode").join("trash.json"))
+}
+
+fn load_config() -> TrashConfig {
+    get_config_path()
+        .and_then(|path| load_json_config(&path))
+        .unwrap_or_else(|e| {
+            log::warn!("[Trash] Failed to load config, using defaults: {}", e);
+            TrashConfig::default()
+        })
+}
+
+// ============================================================================
+// File moves
+// ============================================================================
+
+fn copy_recursively(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Rename, falling back to copy + remove across filesystems (e.g. WSL UNC paths)
+///
+/// A failed copy leaves nothing behind at `to`. A failed remove leaves a full
+/// copy at `to` and possibly part of the source.
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    if let Err(e) = copy_recursively(from, to) {
+        let _ = remove_path(to);
+        return Err(format!("Failed to copy {:?}: {}", from, e));
+    }
+    remove_path(from).map_err(|e| format!("Failed to remove {:?}: {}", from, e))
+}
+
+fn path_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+// ============================================================================
+// Trash operations
+// ============================================================================
+
+/// Entry IDs are directory names; don't let one point outside the trash
+fn entry_dir(root: &Path, entry_id: &str) -> Result<PathBuf, String> {
+    if entry_id.is_empty() || entry_id.contains(['/', '\\']) || entry_id.contains("..") {
+        return Err(format!("Invalid trash entry ID: {}", entry_id));
+    }
+    Ok(root.join(entry_id))
+}
+
+fn read_entry(entry_dir: &Path) -> Result<TrashEntry, String> {
+    let content = fs::read_to_string(entry_dir.join(ENTRY_FILE))
+        .map_err(|e| format!("Failed to read trash entry: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse trash entry: {}", e))
+}
+
+/// Move the files of a trash operation that failed halfway back
+///
+/// Returns the original error; files that can't be moved back stay listed in
+/// the entry so they can still be restored from the trash.
+fn abort_trash(entry_dir: &Path, mut entry: TrashEntry, error: String) -> String {
+    let mut stuck = Vec::new();
+    for file in entry.files.drain(..).rev() {
+        if let Err(e) = move_path(
+            &entry_dir.join(&file.stored_name),
+            Path::new(&file.original_path),
+        ) {
+            log::error!("[Trash] Failed to roll back {}: {}", file.original_path, e);
+            stuck.push(file);
+        }
+    }
+
+    if stuck.is_empty() {
+        let _ = fs::remove_dir_all(entry_dir);
+        return error;
+    }
+    entry.files = stuck;
+    if let Err(e) = save_json_config(&entry, entry_dir.join(ENTRY_FILE)) {
+        log::error!("[Trash] Failed to update trash entry {}: {}", entry.id, e);
+    }
+    format!(
+        "{} ({} file(s) could not be moved back and remain in trash entry {})",
+        error,
+        entry.files.len(),
+        entry.id
+    )
+}
+
+/// Move `paths` into a new entry, all or nothing
+///
+/// entry.json is written before the first move and updated after each one,
+/// so the entry always lists what has been moved so far.
+fn trash_into(root: &Path, item: TrashItem, paths: &[PathBuf]) -> Result<TrashEntry, String> {
+    let paths: Vec<&PathBuf> = paths.iter().filter(|p| p.exists()).collect();
+    if paths.is_empty() {
+        return Err("Nothing to delete: none of the files exist".to_string());
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let entry_dir = root.join(&id);
+    fs::create_dir_all(&entry_dir)
+        .map_err(|e| format!("Failed to create trash directory: {}", e))?;
+    let entry_file = entry_dir.join(ENTRY_FILE);
+
+    let mut entry = TrashEntry {
+        id,
+        kind: item.kind,
+        engine: item.engine,
+        project_id: item.project_id,
+        project_path: item.project_path,
+        session_id: item.session_id,
+        title: item.title,
+        deleted_at: Utc::now(),
+        size: 0,
+        files: Vec::new(),
+    };
+    if let Err(e) = save_json_config(&entry, &entry_file) {
+        let _ = fs::remove_dir_all(&entry_dir);
+        return Err(e);
+    }
+
+    for (index, path) in paths.into_iter().enumerate() {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stored_name = format!("{}-{}", index, file_name);
+        let stored = entry_dir.join(&stored_name);
+        let file = TrashedFile {
+            original_path: path.to_string_lossy().to_string(),
+            stored_name,
+            is_dir: path.is_dir(),
+        };
+        let size = path_size(path);
+
+        if let Err(e) = move_path(path, &stored) {
+            // Copied but the source could not be removed: move the copy back too
+            if stored.exists() {
+                entry.files.push(file);
+            }
+            return Err(abort_trash(&entry_dir, entry, e));
+        }
+
+        entry.size += size;
+        entry.files.push(file);
+        if let Err(e) = save_json_config(&entry, &entry_file) {
+            return Err(abort_trash(&entry_dir, entry, e));
+        }
+    }
+
+    Ok(entry)
+}
+
+fn list_in(root: &Path) -> Vec<TrashEntry> {
+    let Ok(dirs) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<TrashEntry> = dirs
+        .flatten()
+        .filter(|d| d.path().is_dir())
+        .filter_map(|d| match read_entry(&d.path()) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("[Trash] Skipping {:?}: {}", d.path(), e);
+                None
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    entries
+}
+
+fn restore_from(root: &Path, entry_id: &str) -> Result<TrashEntry, String> {
+    let entry_dir = entry_dir(root, entry_id)?;
+    let entry = read_entry(&entry_dir)?;
+
+    // Refuse before moving anything, so a restore is all-or-nothing
+    if let Some(existing) = entry
+        .files
+        .iter()
+        .find(|f| Path::new(&f.original_path).exists())
+    {
+        return Err(format!(
+            "无法恢复：{} 已存在，请先移走或重命名",
+            existing.original_path
+        ));
+    }
+
+    for file in &entry.files {
+        let original = Path::new(&file.original_path);
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        move_path(&entry_dir.join(&file.stored_name), original)?;
+    }
+
+    fs::remove_dir_all(&entry_dir).map_err(|e| format!("Failed to remove trash entry: {}", e))?;
+    Ok(entry)
+}
+
+fn purge_in(root: &Path, entry_id: &str) -> Result<(), String> {
+    fs::remove_dir_all(entry_dir(root, entry_id)?)
+        .map_err(|e| format!("Failed to purge trash entry {}: {}", entry_id, e))
+}
+
+fn purge_expired_in(root: &Path, retention_days: u32) -> usize {
+    if retention_days == 0 {
+        return 0;
+    }
+
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    list_in(root)
+        .into_iter()
+        .filter(|entry| entry.deleted_at < cutoff)
+        .filter(|entry| match purge_in(root, &entry.id) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("[Trash] {}", e);
+                false
+            }
+        })
+        .count()
+}
+
+/// Move the files of a deleted session / project into the trash
+///
+/// Paths that don't exist are skipped; it is an error if none exist.
+pub fn move_to_trash(item: TrashItem, paths: &[PathBuf]) -> Result<TrashEntry, String> {
+    let root = get_trash_dir()?;
+    let _guard = TRASH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let entry = trash_into(&root, item, paths)?;
+    log::info!(
+        "[Trash] Moved {} file(s) of {:?} {} to trash entry {}",
+        entry.files.len(),
+        entry.kind,
+        entry
+            .session_id
+            .as_deref()
+            .or(entry.project_id.as_deref())
+            .unwrap_or(""),
+        entry.id
+    );
+
+    purge_expired_in(&root, load_config().retention_days);
+    Ok(entry)
+}
+
+/// Purge expired entries (called once at startup)
+pub fn purge_expired_trash() {
+    let Ok(root) = get_trash_dir() else {
+        return;
+    };
+    let _guard = TRASH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let purged = purge_expired_in(&root, load_config().retention_days);
+    if purged > 0 {
+        log::info!("[Trash] Purged {} expired entries", purged);
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List trash entries, newest first
+#[tauri::command]
+pub async fn list_trash() -> Result<Vec<TrashEntry>, String> {
+    let root = get_trash_dir()?;
+    let _guard = TRASH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    purge_expired_in(&root, load_config().retention_days);
+    Ok(list_in(&root))
+}
+
+/// Move an entry's files back to where they were deleted from
+#[tauri::command]
+pub async fn restore_from_trash(entry_id: String) -> Result<TrashEntry, String> {
+    let root = get_trash_dir()?;
+    let _guard = TRASH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = restore_from(&root, &entry_id)?;
+    log::info!("[Trash] Restored trash entry {}", entry_id);
+    Ok(entry)
+}
+
+/// Permanently delete the given entries, or everything when `entry_ids` is None
+#[tauri::command]
+pub async fn purge_trash(entry_ids: Option<Vec<String>>) -> Result<usize, String> {
+    let root = get_trash_dir()?;
+    let _guard = TRASH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let entry_ids = entry_ids.unwrap_or_else(|| list_in(&root).into_iter().map(|e| e.id).collect());
+    for entry_id in &entry_ids {
+        purge_in(&root, entry_id)?;
+    }
+
+    log::info!("[Trash] Purged {} entries", entry_ids.len());
+    Ok(entry_ids.len())
+}
+
+#[tauri::command]
+pub async fn get_trash_config() -> Result<TrashConfig, String> {
+    Ok(load_config())
+}
+
+#[tauri::command]
+pub async fn update_trash_config(config: TrashConfig) -> Result<TrashConfig, String> {
+    save_json_config(&config, get_config_path()?)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_item() -> TrashItem {
+        TrashItem {
+            kind: TrashItemKind::Session,
+            engine: EngineKind::Claude,
+            project_id: Some("-tmp-project".to_string()),
+            project_path: None,
+            session_id: Some("abc".to_string()),
+            title: None,
+        }
+    }
+
+    #[test]
+    fn trash_and_restore_round_trip() {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path().join("trash");
+        let session = home.path().join("project").join("abc.jsonl");
+        let todos = home.path().join("todos");
+        fs::create_dir_all(session.parent().unwrap()).unwrap();
+        fs::create_dir_all(&todos).unwrap();
+        fs::write(&session, "{}\n").unwrap();
+        fs::write(todos.join("abc.json"), "[]").unwrap();
+
+        let entry = trash_into(
+            &root,
+            session_item(),
+            &[session.clone(), todos.clone(), home.path().join("missing")],
+        )
+        .unwrap();
+        assert_eq!(entry.files.len(), 2);
+        assert!(!session.exists() && !todos.exists());
+        assert_eq!(list_in(&root).len(), 1);
+
+        // A file recreated at the original path blocks the restore
+        fs::write(&session, "new").unwrap();
+        assert!(restore_from(&root, &entry.id).is_err());
+        fs::remove_file(&session).unwrap();
+
+        restore_from(&root, &entry.id).unwrap();
+        assert_eq!(fs::read_to_string(&session).unwrap(), "{}\n");
+        assert!(todos.join("abc.json").exists());
+        assert!(list_in(&root).is_empty());
+    }
+
+    #[test]
+    fn aborted_trash_moves_files_back() {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path().join("trash");
+        let session = home.path().join("project").join("abc.jsonl");
+        let todos = home.path().join("todos");
+        fs::create_dir_all(session.parent().unwrap()).unwrap();
+        fs::create_dir_all(&todos).unwrap();
+        fs::write(&session, "{}\n").unwrap();
+        fs::write(todos.join("abc.json"), "[]").unwrap();
+
+        let entry = trash_into(&root, session_item(), &[session.clone(), todos.clone()]).unwrap();
+        let entry_dir = root.join(&entry.id);
+        assert_eq!(read_entry(&entry_dir).unwrap().files.len(), 2);
+
+        let error = abort_trash(&entry_dir, entry, "move failed".to_string());
+        assert_eq!(error, "move failed");
+        assert_eq!(fs::read_to_string(&session).unwrap(), "{}\n");
+        assert!(todos.join("abc.json").exists());
+        assert!(!entry_dir.exists());
+    }
+
+    #[test]
+    fn purge_rejects_paths_outside_trash() {
+        let home = tempfile::tempdir().unwrap();
+        assert!(purge_in(home.path(), "../etc").is_err());
+        assert!(purge_in(home.path(), "").is_err());
+    }
+}
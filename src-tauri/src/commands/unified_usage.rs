@@ -0,0 +1,384 @@
+//! Cross-engine usage statistics
+//!
+//! Claude, Codex and Gemini usage is normalized into engine-tagged rows and
+//! aggregated in one pass, so a single query covers every engine:
+//!
+//! - Claude: one row per assistant message
+//! - Codex: one row per turn
+//! - Gemini: one row per model message
+//!
+//! so a session spanning midnight is split over both days. Codex counts cached
+//! input inside `input_tokens`; its rows keep it in `cache_read_tokens` only,
+//! so the token fields of all engines add up the same way. Gemini rows carry
+//! the project path when a Claude / Codex session of the same project is known.
+//! Dates are local dates, filtered like `get_usage_by_date_range`.
+
+use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use super::engine::EngineKind;
+use super::storage::AgentDb;
+use super::usage::parse_range_date;
+use super::usage_index;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Usage of one message (Claude / Gemini) or turn (Codex)
+#[derive(Debug, Clone)]
+pub struct UsageRow {
+    pub engine: EngineKind,
+    pub session_id: String,
+    /// RFC3339, empty when unknown
+    pub timestamp: String,
+    /// Local date; None when the timestamp could not be parsed
+    pub date: Option<NaiveDate>,
+    pub model: String,
+    pub project_path: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub session_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineUsage {
+    pub engine: EngineKind,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedModelUsage {
+    pub engine: EngineKind,
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedDailyUsage {
+    pub date: String,
+    pub engine: EngineKind,
+    pub models_used: Vec<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedProjectUsage {
+    pub engine: EngineKind,
+    pub project_path: String,
+    pub project_name: String,
+    pub last_used: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnifiedUsageStats {
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    pub by_engine: Vec<EngineUsage>,
+    pub by_model: Vec<UnifiedModelUsage>,
+    /// One entry per date and engine, oldest first
+    pub by_date: Vec<UnifiedDailyUsage>,
+    pub by_project: Vec<UnifiedProjectUsage>,
+}
+
+// ============================================================================
+// Collection
+// ============================================================================
+
+fn local_date(timestamp: &str) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.with_timezone(&Local).date_naive())
+}
+
+/// Usage rows of all engines, read from the usage index
+pub fn collect_usage_rows(db: &Mutex<Connection>) -> Result<Vec<UsageRow>, String> {
+    let engines = [EngineKind::Claude, EngineKind::Codex, EngineKind::Gemini];
+    Ok(usage_index::entries(db, &engines)?
+        .into_iter()
+        .flat_map(|(engine, entries)| {
+            entries.into_iter().map(move |e| UsageRow {
+                engine,
+                date: local_date(&e.timestamp),
+                session_id: e.session_id,
+                timestamp: e.timestamp,
+                model: e.model,
+                project_path: e.project_path,
+                input_tokens: e.input_tokens,
+                output_tokens: e.output_tokens,
+                cache_creation_tokens: e.cache_creation_tokens,
+                cache_read_tokens: e.cache_read_tokens,
+                cost: e.cost,
+            })
+        })
+        .collect())
+}
+
+// ============================================================================
+// Aggregation
+// ============================================================================
+
+#[derive(Default)]
+struct Bucket {
+    totals: UsageTotals,
+    sessions: HashSet<(EngineKind, String)>,
+    models: Vec<String>,
+    last_used: String,
+}
+
+impl Bucket {
+    fn add(&mut self, row: &UsageRow) {
+        let t = &mut self.totals;
+        t.total_cost += row.cost;
+        t.input_tokens += row.input_tokens;
+        t.output_tokens += row.output_tokens;
+        t.cache_creation_tokens += row.cache_creation_tokens;
+        t.cache_read_tokens += row.cache_read_tokens;
+        t.total_tokens =
+            t.input_tokens + t.output_tokens + t.cache_creation_tokens + t.cache_read_tokens;
+        self.sessions.insert((row.engine, row.session_id.clone()));
+        if !self.models.contains(&row.model) {
+            self.models.push(row.model.clone());
+        }
+        if row.timestamp > self.last_used {
+            self.last_used = row.timestamp.clone();
+        }
+    }
+
+    fn finish(mut self) -> UsageTotals {
+        self.totals.session_count = self.sessions.len() as u64;
+        self.totals
+    }
+}
+
+fn by_cost_desc(a: &UsageTotals, b: &UsageTotals) -> std::cmp::Ordering {
+    b.total_cost.total_cmp(&a.total_cost)
+}
+
+/// Aggregate rows into totals and engine-tagged breakdowns
+pub fn aggregate(rows: &[UsageRow]) -> UnifiedUsageStats {
+    let mut total = Bucket::default();
+    let mut engines: HashMap<EngineKind, Bucket> = HashMap::new();
+    let mut models: HashMap<(EngineKind, String), Bucket> = HashMap::new();
+    let mut dates: HashMap<(NaiveDate, EngineKind), Bucket> = HashMap::new();
+    let mut projects: HashMap<(EngineKind, String), Bucket> = HashMap::new();
+
+    for row in rows {
+        total.add(row);
+        engines.entry(row.engine).or_default().add(row);
+        models
+            .entry((row.engine, row.model.clone()))
+            .or_default()
+            .add(row);
+        if let Some(date) = row.date {
+            dates.entry((date, row.engine)).or_default().add(row);
+        }
+        projects
+            .entry((row.engine, row.project_path.clone()))
+            .or_default()
+            .add(row);
+    }
+
+    let mut by_engine: Vec<EngineUsage> = engines
+        .into_iter()
+        .map(|(engine, bucket)| EngineUsage {
+            engine,
+            totals: bucket.finish(),
+        })
+        .collect();
+    by_engine.sort_by(|a, b| by_cost_desc(&a.totals, &b.totals));
+
+    let mut by_model: Vec<UnifiedModelUsage> = models
+        .into_iter()
+        .map(|((engine, model), bucket)| UnifiedModelUsage {
+            engine,
+            model,
+            totals: bucket.finish(),
+        })
+        .collect();
+    by_model.sort_by(|a, b| by_cost_desc(&a.totals, &b.totals));
+
+    let mut dated: Vec<_> = dates.into_iter().collect();
+    dated.sort_by_key(|((date, engine), _)| (*date, engine.as_str()));
+    let by_date = dated
+        .into_iter()
+        .map(|((date, engine), bucket)| UnifiedDailyUsage {
+            date: date.format("%Y-%m-%d").to_string(),
+            engine,
+            models_used: bucket.models.clone(),
+            totals: bucket.finish(),
+        })
+        .collect();
+
+    let mut by_project: Vec<UnifiedProjectUsage> = projects
+        .into_iter()
+        .map(|((engine, project_path), bucket)| UnifiedProjectUsage {
+            engine,
+            project_name: project_path
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(&project_path)
+                .to_string(),
+            project_path,
+            last_used: bucket.last_used.clone(),
+            totals: bucket.finish(),
+        })
+        .collect();
+    by_project.sort_by(|a, b| by_cost_desc(&a.totals, &b.totals));
+
+    UnifiedUsageStats {
+        totals: total.finish(),
+        by_engine,
+        by_model,
+        by_date,
+        by_project,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Usage of all engines, optionally limited to an inclusive date range
+///
+/// Either bound may be omitted; rows without a usable date are only counted
+/// when no range is given.
+#[tauri::command]
+pub async fn get_unified_usage_stats(
+    app: AppHandle,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<UnifiedUsageStats, String> {
+    let rows =
+        tauri::async_runtime::spawn_blocking(move || collect_usage_rows(&app.state::<AgentDb>().0))
+            .await
+            .map_err(|e| format!("Failed to collect usage: {}", e))??;
+    let rows = filter_by_date(rows, start_date.as_deref(), end_date.as_deref())?;
+
+    Ok(aggregate(&rows))
+}
+
+/// Keep rows dated within the (inclusive) range; undated rows only survive
+/// when no bound is given
+pub(crate) fn filter_by_date(
+    rows: Vec<UsageRow>,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+) -> Result<Vec<UsageRow>, String> {
+    let start = start_date
+        .map(|d| parse_range_date(d, "start"))
+        .transpose()?;
+    let end = end_date.map(|d| parse_range_date(d, "end")).transpose()?;
+
+    if start.is_none() && end.is_none() {
+        return Ok(rows);
+    }
+    Ok(rows
+        .into_iter()
+        .filter(|row| {
+            row.date.is_some_and(|date| {
+                start.map_or(true, |s| date >= s) && end.map_or(true, |e| date <= e)
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(engine: EngineKind, session: &str, model: &str, date: &str, cost: f64) -> UsageRow {
+        UsageRow {
+            engine,
+            session_id: session.to_string(),
+            timestamp: format!("{}T12:00:00+00:00", date),
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").ok(),
+            model: model.to_string(),
+            project_path: "/tmp/app".to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 1,
+            cost,
+        }
+    }
+
+    #[test]
+    fn breakdowns_are_tagged_with_engine() {
+        let rows = vec![
+            row(
+                EngineKind::Claude,
+                "c1",
+                "claude-sonnet-4-5",
+                "2025-11-01",
+                1.0,
+            ),
+            row(
+                EngineKind::Claude,
+                "c1",
+                "claude-sonnet-4-5",
+                "2025-11-02",
+                2.0,
+            ),
+            row(EngineKind::Codex, "x1", "gpt-5-codex", "2025-11-01", 0.5),
+            row(
+                EngineKind::Gemini,
+                "g1",
+                "gemini-2.5-pro",
+                "2025-11-01",
+                0.25,
+            ),
+        ];
+        let stats = aggregate(&rows);
+
+        assert_eq!(stats.totals.total_cost, 3.75);
+        assert_eq!(stats.totals.total_tokens, 64);
+        assert_eq!(stats.totals.session_count, 3);
+
+        assert_eq!(stats.by_engine[0].engine, EngineKind::Claude);
+        assert_eq!(stats.by_engine[0].totals.session_count, 1);
+        assert_eq!(stats.by_model.len(), 3);
+
+        let dates: Vec<_> = stats
+            .by_date
+            .iter()
+            .map(|d| (d.date.as_str(), d.engine))
+            .collect();
+        assert_eq!(
+            dates,
+            vec![
+                ("2025-11-01", EngineKind::Claude),
+                ("2025-11-01", EngineKind::Codex),
+                ("2025-11-01", EngineKind::Gemini),
+                ("2025-11-02", EngineKind::Claude),
+            ]
+        );
+
+        // Same path, different engines: kept apart
+        assert_eq!(stats.by_project.len(), 3);
+        assert_eq!(stats.by_project[0].project_name, "app");
+        assert_eq!(stats.by_project[0].last_used, "2025-11-02T12:00:00+00:00");
+    }
+}
@@ -2,24 +2,31 @@
 // Source: https://github.com/meistrari/opcode
 
 use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::PathBuf;
-use tauri::command;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{command, State};
+
+use super::claude::ClaudeUsage;
+use super::engine::EngineKind;
+use super::pricing::{self, TokenCounts};
+use super::storage::AgentDb;
+use super::usage_index;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UsageEntry {
-    timestamp: String,
-    model: String,
-    input_tokens: u64,
-    output_tokens: u64,
-    cache_creation_tokens: u64,
-    cache_read_tokens: u64,
-    cost: f64,
-    session_id: String,
-    project_path: String,
+    pub(crate) timestamp: String,
+    pub(crate) model: String,
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cache_creation_tokens: u64,
+    pub(crate) cache_read_tokens: u64,
+    pub(crate) cost: f64,
+    pub(crate) session_id: String,
+    pub(crate) project_path: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -66,146 +73,6 @@ pub struct ProjectUsage {
     last_used: String,
 }
 
-// ============================================================================
-// Claude Model Pricing - Single Source of Truth
-// Source: https://platform.claude.com/docs/en/about-claude/pricing
-// Last Updated: February 2026
-// ============================================================================
-
-/// Model pricing structure (prices per million tokens)
-#[derive(Debug, Clone, Copy)]
-struct ModelPricing {
-    input: f64,
-    output: f64,
-    cache_write: f64,
-    cache_read: f64,
-}
-
-/// Model family enumeration for categorization
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum ModelFamily {
-    Opus46,   // Claude 4.6 Opus
-    Sonnet46, // Claude 4.6 Sonnet
-    Opus45,   // Claude 4.5 Opus
-    Opus41,   // Claude 4.1 Opus
-    Sonnet45, // Claude 4.5 Sonnet
-    Haiku45,  // Claude 4.5 Haiku
-    Unknown,  // Unknown model
-}
-
-impl ModelPricing {
-    /// Get pricing for a specific model family
-    const fn for_family(family: ModelFamily) -> Self {
-        match family {
-            // Claude 4.6 Series (Latest - February 2026)
-            ModelFamily::Opus46 => ModelPricing {
-                input: 5.0,
-                output: 25.0,
-                cache_write: 6.25,
-                cache_read: 0.50,
-            },
-            ModelFamily::Sonnet46 => ModelPricing {
-                input: 3.0,
-                output: 15.0,
-                cache_write: 3.75,
-                cache_read: 0.30,
-            },
-            // Claude 4.5 Series
-            ModelFamily::Opus45 => ModelPricing {
-                input: 5.0,
-                output: 25.0,
-                cache_write: 6.25,
-                cache_read: 0.50,
-            },
-            ModelFamily::Sonnet45 => ModelPricing {
-                input: 3.0,
-                output: 15.0,
-                cache_write: 3.75,
-                cache_read: 0.30,
-            },
-            ModelFamily::Haiku45 => ModelPricing {
-                input: 1.0,
-                output: 5.0,
-                cache_write: 1.25,
-                cache_read: 0.10,
-            },
-            // Claude 4.1 Series
-            ModelFamily::Opus41 => ModelPricing {
-                input: 15.0,
-                output: 75.0,
-                cache_write: 18.75,
-                cache_read: 1.50,
-            },
-            ModelFamily::Unknown => ModelPricing {
-                input: 0.0,
-                output: 0.0,
-                cache_write: 0.0,
-                cache_read: 0.0,
-            },
-        }
-    }
-}
-
-/// Parse model name and determine its family
-///
-/// This function handles various model name formats including:
-/// - Full names: claude-sonnet-4-5-20250929
-/// - Aliases: claude-sonnet-4-5
-/// - Short names: sonnet-4-5
-/// - Bedrock format: anthropic.claude-sonnet-4-5-20250929-v1:0
-/// - Vertex AI format: claude-sonnet-4-5@20250929
-fn parse_model_family(model: &str) -> ModelFamily {
-    // Normalize the model name (lowercase + remove common prefixes/suffixes)
-    let mut normalized = model.to_lowercase();
-    normalized = normalized.replace("anthropic.", "");
-    normalized = normalized.replace("-v1:0", "");
-
-    // Handle @ symbol for Vertex AI format
-    if let Some(pos) = normalized.find('@') {
-        normalized = normalized[..pos].to_string();
-    }
-
-    // Priority-based matching (order matters!)
-    // Check for specific model families in order from most to least specific
-
-    // Claude 4.6 Series (Latest)
-    if normalized.contains("opus") && (normalized.contains("4.6") || normalized.contains("4-6")) {
-        return ModelFamily::Opus46;
-    }
-    if normalized.contains("sonnet") && (normalized.contains("4.6") || normalized.contains("4-6")) {
-        return ModelFamily::Sonnet46;
-    }
-
-    // Claude 4.5 Series (Latest)
-    if normalized.contains("opus") && (normalized.contains("4.5") || normalized.contains("4-5")) {
-        return ModelFamily::Opus45;
-    }
-    if normalized.contains("haiku") && (normalized.contains("4.5") || normalized.contains("4-5")) {
-        return ModelFamily::Haiku45;
-    }
-    if normalized.contains("sonnet") && (normalized.contains("4.5") || normalized.contains("4-5")) {
-        return ModelFamily::Sonnet45;
-    }
-
-    // Claude 4.1 Series
-    if normalized.contains("opus") && (normalized.contains("4.1") || normalized.contains("4-1")) {
-        return ModelFamily::Opus41;
-    }
-
-    // Generic family detection (fallback)
-    if normalized.contains("haiku") {
-        return ModelFamily::Haiku45; // Default to latest Haiku
-    }
-    if normalized.contains("opus") {
-        return ModelFamily::Opus46; // Default to latest Opus
-    }
-    if normalized.contains("sonnet") {
-        return ModelFamily::Sonnet46; // Default to latest Sonnet
-    }
-
-    ModelFamily::Unknown
-}
-
 #[derive(Debug, Deserialize)]
 struct JsonlEntry {
     timestamp: String,
@@ -235,193 +102,143 @@ struct UsageData {
 
 /// Calculate cost for a model usage
 ///
-/// This is the single source of truth for cost calculations.
-/// All cost computations in the application should ultimately use this function.
-fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
-    let input_tokens = usage.input_tokens.unwrap_or(0) as f64;
-    let output_tokens = usage.output_tokens.unwrap_or(0) as f64;
-    let cache_creation_tokens = usage.cache_creation_input_tokens.unwrap_or(0) as f64;
-    let cache_read_tokens = usage.cache_read_input_tokens.unwrap_or(0) as f64;
-
-    // Parse model and get pricing
-    let family = parse_model_family(model);
-    let pricing = ModelPricing::for_family(family);
-
-    // Log unrecognized models for debugging
-    if family == ModelFamily::Unknown {
+/// Prices come from the shared pricing catalog (`pricing`), as in effect on `on`.
+fn calculate_cost(model: &str, usage: &UsageData, on: NaiveDate) -> f64 {
+    let tokens = TokenCounts {
+        input: usage.input_tokens.unwrap_or(0),
+        output: usage.output_tokens.unwrap_or(0),
+        cache_write: usage.cache_creation_input_tokens.unwrap_or(0),
+        cache_read: usage.cache_read_input_tokens.unwrap_or(0),
+    };
+
+    pricing::cost(EngineKind::Claude, model, &tokens, on).unwrap_or_else(|| {
+        // Log unrecognized models for debugging
         log::warn!(
             "Unknown model detected: '{}'. Cost calculation will return 0.",
             model
         );
-    }
-
-    // Calculate cost (prices are per million tokens)
-    let cost = (input_tokens * pricing.input / 1_000_000.0)
-        + (output_tokens * pricing.output / 1_000_000.0)
-        + (cache_creation_tokens * pricing.cache_write / 1_000_000.0)
-        + (cache_read_tokens * pricing.cache_read / 1_000_000.0);
+        0.0
+    })
+}
 
-    cost
+/// Price usage reported live on the stream-json output
+pub(crate) fn calculate_stream_cost(model: &str, usage: &ClaudeUsage) -> f64 {
+    calculate_cost(
+        model,
+        &UsageData {
+            input_tokens: Some(usage.input_tokens),
+            output_tokens: Some(usage.output_tokens),
+            cache_creation_input_tokens: Some(usage.cache_creation_input_tokens),
+            cache_read_input_tokens: Some(usage.cache_read_input_tokens),
+        },
+        Local::now().date_naive(),
+    )
 }
 
-fn parse_jsonl_file(
-    path: &PathBuf,
-    encoded_project_name: &str,
-    processed_hashes: &mut HashSet<String>,
-) -> Vec<UsageEntry> {
-    let mut entries = Vec::new();
-    let mut actual_project_path: Option<String> = None;
+/// Incremental reader of one Claude history file
+///
+/// Fed line by line; the state between calls is stored with the file's
+/// usage index entry so appended lines can be parsed on their own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ClaudeUsageScan {
+    /// Directory name under ~/.claude/projects, used until a `cwd` is seen
+    encoded_project_name: String,
+    fallback_session_id: String,
+    actual_project_path: Option<String>,
+}
 
-    if let Ok(content) = fs::read_to_string(path) {
+impl ClaudeUsageScan {
+    pub(crate) fn new(path: &Path, encoded_project_name: &str) -> Self {
         // Extract session ID from the file path
-        let session_id = path
+        let fallback_session_id = path
             .parent()
             .and_then(|p| p.file_name())
             .and_then(|n| n.to_str())
             .unwrap_or("unknown")
             .to_string();
-
-        for line in content.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                // Extract the actual project path from cwd if we haven't already
-                if actual_project_path.is_none() {
-                    if let Some(cwd) = json_value.get("cwd").and_then(|v| v.as_str()) {
-                        actual_project_path = Some(cwd.to_string());
-                    }
-                }
-
-                // Try to parse as JsonlEntry for usage data
-                if let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) {
-                    if let Some(message) = &entry.message {
-                        // Deduplication based on message ID and request ID
-                        if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
-                            let unique_hash = format!("{}:{}", msg_id, req_id);
-                            if processed_hashes.contains(&unique_hash) {
-                                continue; // Skip duplicate entry
-                            }
-                            processed_hashes.insert(unique_hash);
-                        }
-
-                        if let Some(usage) = &message.usage {
-                            // Skip entries without meaningful token usage
-                            if usage.input_tokens.unwrap_or(0) == 0
-                                && usage.output_tokens.unwrap_or(0) == 0
-                                && usage.cache_creation_input_tokens.unwrap_or(0) == 0
-                                && usage.cache_read_input_tokens.unwrap_or(0) == 0
-                            {
-                                continue;
-                            }
-
-                            let cost = entry.cost_usd.unwrap_or_else(|| {
-                                if let Some(model_str) = &message.model {
-                                    calculate_cost(model_str, usage)
-                                } else {
-                                    0.0
-                                }
-                            });
-
-                            // Use actual project path if found, otherwise use encoded name
-                            let project_path = actual_project_path
-                                .clone()
-                                .unwrap_or_else(|| encoded_project_name.to_string());
-
-                            entries.push(UsageEntry {
-                                timestamp: entry.timestamp,
-                                model: message
-                                    .model
-                                    .clone()
-                                    .unwrap_or_else(|| "unknown".to_string()),
-                                input_tokens: usage.input_tokens.unwrap_or(0),
-                                output_tokens: usage.output_tokens.unwrap_or(0),
-                                cache_creation_tokens: usage
-                                    .cache_creation_input_tokens
-                                    .unwrap_or(0),
-                                cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
-                                cost,
-                                session_id: entry.session_id.unwrap_or_else(|| session_id.clone()),
-                                project_path,
-                            });
-                        }
-                    }
-                }
-            }
+        Self {
+            encoded_project_name: encoded_project_name.to_string(),
+            fallback_session_id,
+            actual_project_path: None,
         }
     }
 
-    entries
-}
-
-fn get_earliest_timestamp(path: &PathBuf) -> Option<String> {
-    if let Ok(content) = fs::read_to_string(path) {
-        let mut earliest_timestamp: Option<String> = None;
-        for line in content.lines() {
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                if let Some(timestamp_str) = json_value.get("timestamp").and_then(|v| v.as_str()) {
-                    if let Some(current_earliest) = &earliest_timestamp {
-                        if timestamp_str < current_earliest.as_str() {
-                            earliest_timestamp = Some(timestamp_str.to_string());
-                        }
-                    } else {
-                        earliest_timestamp = Some(timestamp_str.to_string());
-                    }
-                }
-            }
+    /// Parse one line; returns its usage and the `message:request` deduplication key
+    pub(crate) fn feed(&mut self, line: &str) -> Option<(UsageEntry, Option<String>)> {
+        if line.trim().is_empty() {
+            return None;
         }
-        return earliest_timestamp;
-    }
-    None
-}
+        let json_value = serde_json::from_str::<serde_json::Value>(line).ok()?;
 
-fn get_all_usage_entries(claude_path: &PathBuf) -> Vec<UsageEntry> {
-    let mut all_entries = Vec::new();
-    let mut processed_hashes = HashSet::new();
-    let projects_dir = claude_path.join("projects");
-
-    let mut files_to_process: Vec<(PathBuf, String)> = Vec::new();
-
-    if let Ok(projects) = fs::read_dir(&projects_dir) {
-        for project in projects.flatten() {
-            if project.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                let project_name = project.file_name().to_string_lossy().to_string();
-                let project_path = project.path();
-
-                walkdir::WalkDir::new(&project_path)
-                    .into_iter()
-                    .filter_map(Result::ok)
-                    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("jsonl"))
-                    .for_each(|entry| {
-                        files_to_process.push((entry.path().to_path_buf(), project_name.clone()));
-                    });
+        // Extract the actual project path from cwd if we haven't already
+        if self.actual_project_path.is_none() {
+            if let Some(cwd) = json_value.get("cwd").and_then(|v| v.as_str()) {
+                self.actual_project_path = Some(cwd.to_string());
             }
         }
-    }
 
-    // Sort files by their earliest timestamp to ensure chronological processing
-    // and deterministic deduplication
-    files_to_process.sort_by_cached_key(|(path, _)| get_earliest_timestamp(path));
+        let entry = serde_json::from_value::<JsonlEntry>(json_value).ok()?;
+        let message = entry.message.as_ref()?;
+        let usage = message.usage.as_ref()?;
+
+        // Skip entries without meaningful token usage
+        if usage.input_tokens.unwrap_or(0) == 0
+            && usage.output_tokens.unwrap_or(0) == 0
+            && usage.cache_creation_input_tokens.unwrap_or(0) == 0
+            && usage.cache_read_input_tokens.unwrap_or(0) == 0
+        {
+            return None;
+        }
 
-    for (path, project_name) in files_to_process {
-        let entries = parse_jsonl_file(&path, &project_name, &mut processed_hashes);
-        all_entries.extend(entries);
-    }
+        // Deduplication based on message ID and request ID
+        let dedup_key = match (&message.id, &entry.request_id) {
+            (Some(msg_id), Some(req_id)) => Some(format!("{}:{}", msg_id, req_id)),
+            _ => None,
+        };
 
-    // Sort by timestamp
-    all_entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        let cost = entry.cost_usd.unwrap_or_else(|| {
+            if let Some(model_str) = &message.model {
+                calculate_cost(model_str, usage, pricing::usage_date(&entry.timestamp))
+            } else {
+                0.0
+            }
+        });
 
-    all_entries
+        // Use actual project path if found, otherwise use encoded name
+        let project_path = self
+            .actual_project_path
+            .clone()
+            .unwrap_or_else(|| self.encoded_project_name.clone());
+
+        let usage_entry = UsageEntry {
+            model: message
+                .model
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            input_tokens: usage.input_tokens.unwrap_or(0),
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_creation_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+            cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+            cost,
+            session_id: entry
+                .session_id
+                .clone()
+                .unwrap_or_else(|| self.fallback_session_id.clone()),
+            project_path,
+            timestamp: entry.timestamp,
+        };
+        Some((usage_entry, dedup_key))
+    }
 }
 
 #[command]
-pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+pub fn get_usage_stats(db: State<'_, AgentDb>, days: Option<u32>) -> Result<UsageStats, String> {
+    usage_stats(&db.0, days)
+}
 
-    let all_entries = get_all_usage_entries(&claude_path);
+/// Claude usage statistics, optionally limited to the last `days` days
+pub fn usage_stats(db: &Mutex<Connection>, days: Option<u32>) -> Result<UsageStats, String> {
+    let all_entries = usage_index::claude_entries(db)?;
 
     if all_entries.is_empty() {
         return Ok(UsageStats {
@@ -584,25 +401,26 @@ pub fn get_usage_stats(days: Option<u32>) -> Result<UsageStats, String> {
     })
 }
 
-#[command]
-pub fn get_usage_by_date_range(start_date: String, end_date: String) -> Result<UsageStats, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
+/// Parse a date-range bound: `YYYY-MM-DD` or RFC3339
+pub(crate) fn parse_range_date(value: &str, bound: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").or_else(|_| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|dt| dt.naive_local().date())
+            .map_err(|e| format!("Invalid {} date: {}", bound, e))
+    })
+}
 
-    let all_entries = get_all_usage_entries(&claude_path);
+#[command]
+pub fn get_usage_by_date_range(
+    db: State<'_, AgentDb>,
+    start_date: String,
+    end_date: String,
+) -> Result<UsageStats, String> {
+    let all_entries = usage_index::claude_entries(&db.0)?;
 
     // Parse dates
-    let start = NaiveDate::parse_from_str(&start_date, "%Y-%m-%d").or_else(|_| {
-        DateTime::parse_from_rfc3339(&start_date)
-            .map(|dt| dt.naive_local().date())
-            .map_err(|e| format!("Invalid start date: {}", e))
-    })?;
-    let end = NaiveDate::parse_from_str(&end_date, "%Y-%m-%d").or_else(|_| {
-        DateTime::parse_from_rfc3339(&end_date)
-            .map(|dt| dt.naive_local().date())
-            .map_err(|e| format!("Invalid end date: {}", e))
-    })?;
+    let start = parse_range_date(&start_date, "start")?;
+    let end = parse_range_date(&end_date, "end")?;
 
     // Filter entries by date range
     // 🚀 修复时区问题：转换为本地时区后进行日期比较
@@ -761,15 +579,12 @@ pub fn get_usage_by_date_range(start_date: String, end_date: String) -> Result<U
 
 #[command]
 pub fn get_session_stats(
+    db: State<'_, AgentDb>,
     since: Option<String>,
     until: Option<String>,
     order: Option<String>,
 ) -> Result<Vec<ProjectUsage>, String> {
-    let claude_path = dirs::home_dir()
-        .ok_or("Failed to get home directory")?
-        .join(".claude");
-
-    let all_entries = get_all_usage_entries(&claude_path);
+    let all_entries = usage_index::claude_entries(&db.0)?;
 
     // Filter by date range if provided
     // 🚀 修复时区问题：转换为本地时区后进行日期比较
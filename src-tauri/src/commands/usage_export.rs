@@ -0,0 +1,579 @@
+//! Usage export for chargeback
+//!
+//! Writes the cross-engine usage rows as CSV or JSON, summed per day, ISO
+//! week or month and split by any of project, model, engine, provider and
+//! cost center.
+//!
+//! - Provider: the base URL that was active when the usage happened, taken
+//!   from the provider switch history (the current one when nothing was logged)
+//! - Cost center: looked up in an optional JSON file mapping project paths to
+//!   cost centers; the longest matching path prefix wins, unmatched projects
+//!   are reported as `unassigned`. Gemini rows whose project hash could not be
+//!   resolved from other sessions are matched against the hashes of the mapped
+//!   paths first.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use super::engine::EngineKind;
+use super::gemini::config::hash_project_path;
+use super::provider_history::{self, ProviderHistory};
+use super::storage::AgentDb;
+use super::unified_usage::{collect_usage_rows, filter_by_date, UsageRow};
+
+const UNASSIGNED_COST_CENTER: &str = "unassigned";
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageExportFormat {
+    Csv,
+    Json,
+}
+
+impl UsageExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            UsageExportFormat::Csv => "csv",
+            UsageExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPeriod {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl ExportPeriod {
+    /// `2025-06-03`, `2025-W23` or `2025-06`
+    fn label(self, date: NaiveDate) -> String {
+        match self {
+            ExportPeriod::Day => date.format("%Y-%m-%d").to_string(),
+            ExportPeriod::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            ExportPeriod::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportDimension {
+    Project,
+    Model,
+    Engine,
+    Provider,
+    CostCenter,
+}
+
+const ALL_DIMENSIONS: [ExportDimension; 5] = [
+    ExportDimension::Project,
+    ExportDimension::Model,
+    ExportDimension::Engine,
+    ExportDimension::Provider,
+    ExportDimension::CostCenter,
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UsageExportOptions {
+    pub period: ExportPeriod,
+    /// Columns to split by besides the period; empty means all of them
+    pub group_by: Vec<ExportDimension>,
+    /// YYYY-MM-DD or RFC3339, inclusive
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    /// JSON object of project path (or path prefix) -> cost center
+    pub cost_center_file: Option<String>,
+}
+
+/// One line of the export; dimensions that aren't grouped by are left out
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageExportRow {
+    pub period: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_center: Option<String>,
+    pub session_count: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub total_tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageExport {
+    pub format: UsageExportFormat,
+    pub content: String,
+    /// Set when the export was also written to disk
+    pub output_path: Option<String>,
+    pub row_count: usize,
+    pub total_cost_usd: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonExport<'a> {
+    generated_at: DateTime<Utc>,
+    period: ExportPeriod,
+    group_by: &'a [ExportDimension],
+    start_date: Option<&'a str>,
+    end_date: Option<&'a str>,
+    total_cost_usd: f64,
+    rows: &'a [UsageExportRow],
+}
+
+// ============================================================================
+// Attribution
+// ============================================================================
+
+/// Project path -> cost center, matched by longest path prefix
+#[derive(Debug, Clone, Default)]
+pub struct CostCenters {
+    prefixes: Vec<(String, String)>,
+    /// Gemini project hash -> mapped path
+    hashed_paths: HashMap<String, String>,
+}
+
+fn normalize_project_path(path: &str) -> String {
+    path.replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+impl CostCenters {
+    pub fn new(mapping: HashMap<String, String>) -> Self {
+        let mut hashed_paths = HashMap::new();
+        for path in mapping.keys() {
+            // Gemini hashes the path as the CLI saw it, so try both spellings
+            let normalized = normalize_project_path(path);
+            hashed_paths.insert(hash_project_path(path), normalized.clone());
+            hashed_paths.insert(hash_project_path(&normalized), normalized);
+        }
+        let mut prefixes: Vec<(String, String)> = mapping
+            .into_iter()
+            .map(|(path, center)| (normalize_project_path(&path), center))
+            .collect();
+        prefixes.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        Self {
+            prefixes,
+            hashed_paths,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cost center file: {}", e))?;
+        let mapping: HashMap<String, String> = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid cost center file: {}", e))?;
+        Ok(Self::new(mapping))
+    }
+
+    /// Project path of a row; unresolved Gemini `project:{hash}` paths are
+    /// looked up among the mapped paths
+    pub fn resolve<'a>(&'a self, project_path: &'a str) -> &'a str {
+        project_path
+            .strip_prefix("project:")
+            .and_then(|hash| self.hashed_paths.get(hash))
+            .map_or(project_path, String::as_str)
+    }
+
+    pub fn lookup(&self, project_path: &str) -> &str {
+        let project = normalize_project_path(project_path);
+        self.prefixes
+            .iter()
+            .find(|(prefix, _)| {
+                project == *prefix
+                    || project
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .map(|(_, center)| center.as_str())
+            .unwrap_or(UNASSIGNED_COST_CENTER)
+    }
+}
+
+/// Base URL that served `row`
+fn provider_for(
+    row: &UsageRow,
+    history: &ProviderHistory,
+    current: &HashMap<EngineKind, String>,
+) -> String {
+    DateTime::parse_from_rfc3339(&row.timestamp)
+        .ok()
+        .and_then(|ts| history.base_url_at(row.engine, ts.with_timezone(&Utc)))
+        .map(str::to_string)
+        .or_else(|| current.get(&row.engine).cloned())
+        .unwrap_or_else(|| provider_history::official_base_url(row.engine).to_string())
+}
+
+// ============================================================================
+// Grouping
+// ============================================================================
+
+#[derive(Default)]
+struct Group {
+    row: UsageExportRow,
+    sessions: HashSet<(EngineKind, String)>,
+}
+
+/// Sum rows per period and the selected dimensions, sorted by period then key
+fn group_rows(
+    rows: &[UsageRow],
+    period: ExportPeriod,
+    dimensions: &[ExportDimension],
+    provider: impl Fn(&UsageRow) -> String,
+    cost_centers: &CostCenters,
+) -> Vec<UsageExportRow> {
+    let has = |dimension| dimensions.contains(&dimension);
+    let mut groups: BTreeMap<Vec<String>, Group> = BTreeMap::new();
+
+    for row in rows {
+        let project_path = cost_centers.resolve(&row.project_path);
+        let mut key = UsageExportRow {
+            period: row
+                .date
+                .map(|date| period.label(date))
+                .unwrap_or_else(|| "unknown".to_string()),
+            ..Default::default()
+        };
+        if has(ExportDimension::Project) {
+            key.project = Some(project_path.to_string());
+        }
+        if has(ExportDimension::Model) {
+            key.model = Some(row.model.clone());
+        }
+        if has(ExportDimension::Engine) {
+            key.engine = Some(row.engine.as_str().to_string());
+        }
+        if has(ExportDimension::Provider) {
+            key.provider = Some(provider(row));
+        }
+        if has(ExportDimension::CostCenter) {
+            key.cost_center = Some(cost_centers.lookup(project_path).to_string());
+        }
+
+        let sort_key = [
+            Some(&key.period),
+            key.project.as_ref(),
+            key.model.as_ref(),
+            key.engine.as_ref(),
+            key.provider.as_ref(),
+            key.cost_center.as_ref(),
+        ]
+        .into_iter()
+        .map(|value| value.cloned().unwrap_or_default())
+        .collect();
+
+        let group = groups.entry(sort_key).or_insert_with(|| Group {
+            row: key,
+            sessions: HashSet::new(),
+        });
+        let total = &mut group.row;
+        total.input_tokens += row.input_tokens;
+        total.output_tokens += row.output_tokens;
+        total.cache_creation_tokens += row.cache_creation_tokens;
+        total.cache_read_tokens += row.cache_read_tokens;
+        total.total_tokens += row.input_tokens
+            + row.output_tokens
+            + row.cache_creation_tokens
+            + row.cache_read_tokens;
+        total.cost_usd += row.cost;
+        group.sessions.insert((row.engine, row.session_id.clone()));
+    }
+
+    groups
+        .into_values()
+        .map(|mut group| {
+            group.row.session_count = group.sessions.len() as u64;
+            group.row
+        })
+        .collect()
+}
+
+// ============================================================================
+// Rendering
+// ============================================================================
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_csv(rows: &[UsageExportRow], dimensions: &[ExportDimension]) -> String {
+    let mut header = vec!["period"];
+    for dimension in ALL_DIMENSIONS {
+        if dimensions.contains(&dimension) {
+            header.push(match dimension {
+                ExportDimension::Project => "project",
+                ExportDimension::Model => "model",
+                ExportDimension::Engine => "engine",
+                ExportDimension::Provider => "provider",
+                ExportDimension::CostCenter => "cost_center",
+            });
+        }
+    }
+    header.extend([
+        "sessions",
+        "input_tokens",
+        "output_tokens",
+        "cache_creation_tokens",
+        "cache_read_tokens",
+        "total_tokens",
+        "cost_usd",
+    ]);
+
+    let mut out = header.join(",");
+    out.push('\n');
+    for row in rows {
+        let mut fields = vec![csv_field(&row.period)];
+        for value in [
+            &row.project,
+            &row.model,
+            &row.engine,
+            &row.provider,
+            &row.cost_center,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            fields.push(csv_field(value));
+        }
+        fields.extend([
+            row.session_count.to_string(),
+            row.input_tokens.to_string(),
+            row.output_tokens.to_string(),
+            row.cache_creation_tokens.to_string(),
+            row.cache_read_tokens.to_string(),
+            row.total_tokens.to_string(),
+            format!("{:.6}", row.cost_usd),
+        ]);
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// `usage-{period}-{today}.{ext}`, used when `output_path` is a directory
+fn default_file_name(period: ExportPeriod, format: UsageExportFormat) -> String {
+    let period = match period {
+        ExportPeriod::Day => "daily",
+        ExportPeriod::Week => "weekly",
+        ExportPeriod::Month => "monthly",
+    };
+    format!(
+        "usage-{}-{}.{}",
+        period,
+        chrono::Local::now().format("%Y%m%d"),
+        format.extension()
+    )
+}
+
+// ============================================================================
+// Command
+// ============================================================================
+
+/// Export usage of all engines; the content is always returned and also
+/// written to `output_path` (a file, or a directory to put a default-named file in)
+#[tauri::command]
+pub async fn export_usage(
+    app: AppHandle,
+    format: UsageExportFormat,
+    options: Option<UsageExportOptions>,
+    output_path: Option<String>,
+) -> Result<UsageExport, String> {
+    let options = options.unwrap_or_default();
+    let dimensions: Vec<ExportDimension> = if options.group_by.is_empty() {
+        ALL_DIMENSIONS.to_vec()
+    } else {
+        ALL_DIMENSIONS
+            .into_iter()
+            .filter(|d| options.group_by.contains(d))
+            .collect()
+    };
+    log::info!(
+        "Exporting usage as {:?} per {:?}, grouped by {:?}",
+        format,
+        options.period,
+        dimensions
+    );
+
+    let cost_centers = match options.cost_center_file.as_deref() {
+        Some(path) => CostCenters::load(path)?,
+        None => CostCenters::default(),
+    };
+
+    let rows =
+        tauri::async_runtime::spawn_blocking(move || collect_usage_rows(&app.state::<AgentDb>().0))
+            .await
+            .map_err(|e| format!("Failed to collect usage: {}", e))??;
+    let rows = filter_by_date(
+        rows,
+        options.start_date.as_deref(),
+        options.end_date.as_deref(),
+    )?;
+
+    let history = provider_history::load_history();
+    let mut current = HashMap::new();
+    for engine in [EngineKind::Claude, EngineKind::Codex, EngineKind::Gemini] {
+        current.insert(engine, provider_history::current_base_url(engine).await);
+    }
+
+    let grouped = group_rows(
+        &rows,
+        options.period,
+        &dimensions,
+        |row| provider_for(row, &history, &current),
+        &cost_centers,
+    );
+    let total_cost_usd: f64 = grouped.iter().map(|r| r.cost_usd).sum();
+
+    let content = match format {
+        UsageExportFormat::Csv => render_csv(&grouped, &dimensions),
+        UsageExportFormat::Json => serde_json::to_string_pretty(&JsonExport {
+            generated_at: Utc::now(),
+            period: options.period,
+            group_by: &dimensions,
+            start_date: options.start_date.as_deref(),
+            end_date: options.end_date.as_deref(),
+            total_cost_usd,
+            rows: &grouped,
+        })
+        .map_err(|e| format!("Failed to serialize usage export: {}", e))?,
+    };
+
+    let output_path = match output_path {
+        Some(path) => {
+            let mut path = PathBuf::from(path);
+            if path.is_dir() {
+                path.push(default_file_name(options.period, format));
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create export directory: {}", e))?;
+            }
+            std::fs::write(&path, &content)
+                .map_err(|e| format!("Failed to write export: {}", e))?;
+            log::info!("Usage export written to {}", path.display());
+            Some(path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    Ok(UsageExport {
+        format,
+        content,
+        output_path,
+        row_count: grouped.len(),
+        total_cost_usd,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(engine: EngineKind, session: &str, project: &str, date: &str, cost: f64) -> UsageRow {
+        UsageRow {
+            engine,
+            session_id: session.to_string(),
+            timestamp: format!("{}T12:00:00Z", date),
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").ok(),
+            model: "m".to_string(),
+            project_path: project.to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost,
+        }
+    }
+
+    #[test]
+    fn monthly_rows_are_split_by_cost_center() {
+        let rows = vec![
+            row(EngineKind::Claude, "a", "/work/app", "2025-06-02", 1.0),
+            row(EngineKind::Claude, "a", "/work/app/web", "2025-06-20", 2.0),
+            row(EngineKind::Codex, "b", "/work/apps", "2025-06-21", 4.0),
+            row(EngineKind::Codex, "c", "/work/app", "2025-07-01", 8.0),
+            // No Claude / Codex session resolved this Gemini project
+            row(
+                EngineKind::Gemini,
+                "d",
+                &format!("project:{}", hash_project_path("/work/app/")),
+                "2025-07-02",
+                16.0,
+            ),
+        ];
+        let centers = CostCenters::new(HashMap::from([
+            ("/work".to_string(), "Platform".to_string()),
+            ("/work/app/".to_string(), "Team A".to_string()),
+        ]));
+
+        let grouped = group_rows(
+            &rows,
+            ExportPeriod::Month,
+            &[ExportDimension::CostCenter],
+            |_| String::new(),
+            &centers,
+        );
+
+        let summary: Vec<_> = grouped
+            .iter()
+            .map(|r| {
+                (
+                    r.period.as_str(),
+                    r.cost_center.as_deref().unwrap(),
+                    r.session_count,
+                    r.cost_usd,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2025-06", "Platform", 1, 4.0),
+                ("2025-06", "Team A", 1, 3.0),
+                ("2025-07", "Team A", 2, 24.0),
+            ]
+        );
+
+        let by_project = group_rows(
+            &rows[4..],
+            ExportPeriod::Month,
+            &[ExportDimension::Project],
+            |_| String::new(),
+            &centers,
+        );
+        assert_eq!(by_project[0].project.as_deref(), Some("/work/app"));
+
+        let csv = render_csv(&grouped, &[ExportDimension::CostCenter]);
+        assert!(csv.starts_with("period,cost_center,sessions,"));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
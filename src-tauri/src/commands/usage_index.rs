@@ -0,0 +1,1003 @@
+//! Incremental Usage Index
+//!
+//! The usage statistics of all three engines are served from `agents.db`
+//! instead of re-reading every history file on each request. The index
+//! remembers the size, mtime and read offset of every history file, and the
+//! refresh that runs before each usage query only parses what changed:
+//!
+//! - Claude / Codex JSONL: only the bytes appended after the stored offset.
+//!   The parser state (Claude project path, Codex running totals) is stored
+//!   with the file, so appended lines are parsed without the file's head.
+//! - Gemini session JSON and archived (`.zst`) files: parsed whole.
+//! - A file that shrank is parsed again from the start; rows of files that
+//!   disappeared are dropped.
+//!
+//! Tables:
+//! - `usage_index_files`:    one row per history file (offset, parser state)
+//! - `usage_entries`:        one row per Claude message, Codex turn and Gemini
+//!   message, tagged with its file. The table is shared with `usage_recorder`;
+//!   rows recorded live for a session are replaced by the parsed ones once its
+//!   file is indexed.
+//! - `usage_index_sessions`: Codex / Gemini session summaries, one row per file
+//!
+//! Gemini history only records a hash of the project path. Rows are stored as
+//! `project:{hash}` and resolved when read, against the project paths of the
+//! Claude / Codex sessions known at that time.
+
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use super::codex::usage::{CodexSessionUsage, CodexUsageScan};
+use super::engine::EngineKind;
+use super::gemini::config::hash_project_path;
+use super::gemini::usage::{parse_session_for_usage, GeminiSessionUsage};
+use super::session_archive::{is_archived, is_session_file, open_session_file};
+use super::session_search::{file_meta, project_paths_by_hash};
+use super::session_worktree;
+use super::storage::AgentDb;
+use super::usage::{ClaudeUsageScan, UsageEntry};
+use super::usage_recorder::{insert_indexed_usage, UsageRecord};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageIndexStats {
+    pub scanned_files: usize,
+    /// Files parsed from the start (new, shrunk, Gemini, archived)
+    pub parsed_files: usize,
+    /// Files of which only appended lines were parsed
+    pub appended_files: usize,
+    pub removed_files: usize,
+    pub failed_files: usize,
+}
+
+/// A history file found on disk
+struct UsageFile {
+    path: PathBuf,
+    engine: EngineKind,
+    /// Claude: project directory name; Gemini: project hash
+    project_key: String,
+    modified_at: i64,
+    size: i64,
+}
+
+/// What the index remembers about a file
+struct IndexedFile {
+    id: i64,
+    modified_at: i64,
+    size: i64,
+    byte_offset: i64,
+    state: Option<String>,
+}
+
+/// Usage parsed from a file (or from its appended part)
+#[derive(Default)]
+struct ParsedUsage {
+    /// Usage rows with their deduplication key (Claude `message:request`,
+    /// Gemini message ID)
+    entries: Vec<(UsageEntry, Option<String>)>,
+    /// Codex / Gemini: (session ID, session usage as JSON)
+    session: Option<(String, String)>,
+    /// Parser state to resume from
+    state: Option<String>,
+    byte_offset: i64,
+}
+
+// ============================================================================
+// Schema
+// ============================================================================
+
+/// Create the index tables (called from `storage::open_database`)
+pub fn create_usage_index_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS usage_index_files (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            path TEXT NOT NULL UNIQUE,
+            engine TEXT NOT NULL,
+            modified_at INTEGER NOT NULL,
+            size INTEGER NOT NULL,
+            byte_offset INTEGER NOT NULL,
+            state TEXT,
+            indexed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS usage_index_sessions (
+            file_id INTEGER PRIMARY KEY,
+            engine TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            usage TEXT NOT NULL
+        );",
+    )
+}
+
+// ============================================================================
+// Discovery
+// ============================================================================
+
+fn push_file(files: &mut Vec<UsageFile>, path: PathBuf, engine: EngineKind, project_key: &str) {
+    if let Some((modified_at, size)) = file_meta(&path) {
+        files.push(UsageFile {
+            path,
+            engine,
+            project_key: project_key.to_string(),
+            modified_at,
+            size,
+        });
+    }
+}
+
+/// All history files that carry usage
+fn discover_usage_files() -> Vec<UsageFile> {
+    let mut files = Vec::new();
+
+    // Claude: ~/.claude/projects/{project_id}/**/*.jsonl (subagent files included)
+    if let Ok(claude_dir) = super::claude::get_claude_dir() {
+        if let Ok(projects) = std::fs::read_dir(claude_dir.join("projects")) {
+            for project in projects.flatten() {
+                if !project.file_type().is_ok_and(|t| t.is_dir()) {
+                    continue;
+                }
+                let project_name = project.file_name().to_string_lossy().to_string();
+                for entry in walkdir::WalkDir::new(project.path()).into_iter().flatten() {
+                    if is_session_file(entry.path(), "jsonl") {
+                        push_file(
+                            &mut files,
+                            entry.into_path(),
+                            EngineKind::Claude,
+                            &project_name,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Codex: {sessions_dir}/YYYY/MM/DD/rollout-*.jsonl
+    if let Ok(sessions_dir) = super::codex::get_codex_sessions_dir() {
+        for entry in walkdir::WalkDir::new(&sessions_dir).into_iter().flatten() {
+            if is_session_file(entry.path(), "jsonl") {
+                push_file(&mut files, entry.into_path(), EngineKind::Codex, "");
+            }
+        }
+    }
+
+    // Gemini: ~/.gemini/tmp/{project_hash}/chats/*.json
+    if let Ok(gemini_dir) = super::gemini::config::get_gemini_dir() {
+        let pattern = gemini_dir.join("tmp").join("*").join("chats").join("*");
+        for path in glob::glob(&pattern.to_string_lossy())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|p| is_session_file(p, "json"))
+        {
+            let project_hash = path
+                .parent()
+                .and_then(Path::parent)
+                .and_then(Path::file_name)
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            push_file(&mut files, path, EngineKind::Gemini, &project_hash);
+        }
+    }
+
+    files
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// Complete lines after `offset`, and the offset just past the last of them
+///
+/// A trailing line without newline may still be in the middle of being
+/// written; it is left for the next refresh. Archived files are read whole.
+fn read_lines_from(path: &Path, offset: u64) -> io::Result<(Vec<String>, u64)> {
+    if is_archived(path) {
+        let lines = open_session_file(path)?
+            .lines()
+            .map_while(Result::ok)
+            .collect();
+        return Ok((lines, std::fs::metadata(path)?.len()));
+    }
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    let complete = buf.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    let lines = String::from_utf8_lossy(&buf[..complete])
+        .lines()
+        .map(str::to_string)
+        .collect();
+    Ok((lines, offset + complete as u64))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| e.to_string())
+}
+
+/// Parse `file`, resuming after `resume` when given
+fn parse_file(file: &UsageFile, resume: Option<&IndexedFile>) -> Result<ParsedUsage, String> {
+    let offset = resume.map_or(0, |r| r.byte_offset as u64);
+    let state = resume.and_then(|r| r.state.as_deref());
+    let read = |offset| {
+        read_lines_from(&file.path, offset)
+            .map_err(|e| format!("Failed to read {:?}: {}", file.path, e))
+    };
+
+    match file.engine {
+        EngineKind::Claude => {
+            let mut scan = match state {
+                Some(state) => serde_json::from_str(state).map_err(|e| e.to_string())?,
+                None => ClaudeUsageScan::new(&file.path, &file.project_key),
+            };
+            let (lines, end) = read(offset)?;
+            Ok(ParsedUsage {
+                entries: lines.iter().filter_map(|line| scan.feed(line)).collect(),
+                session: None,
+                state: Some(to_json(&scan)?),
+                byte_offset: end as i64,
+            })
+        }
+        EngineKind::Codex => {
+            // None until the session_meta line has been read (or when it is missing)
+            let mut scan: Option<CodexUsageScan> = match state {
+                Some(state) => serde_json::from_str(state).map_err(|e| e.to_string())?,
+                None => None,
+            };
+            let (lines, end) = read(offset)?;
+            let mut lines = lines.iter();
+            if offset == 0 {
+                scan = lines
+                    .next()
+                    .and_then(|first| CodexUsageScan::from_meta(first));
+            }
+            let entries = match scan.as_mut() {
+                Some(scan) => lines
+                    .filter_map(|line| scan.feed(line))
+                    .map(|entry| (entry, None))
+                    .collect(),
+                None => Vec::new(),
+            };
+
+            let session = match &scan {
+                Some(scan) => {
+                    let usage = scan.finish();
+                    Some((usage.session_id.clone(), to_json(&usage)?))
+                }
+                None => None,
+            };
+            Ok(ParsedUsage {
+                entries,
+                session,
+                state: Some(to_json(&scan)?),
+                byte_offset: end as i64,
+            })
+        }
+        EngineKind::Gemini => {
+            let Some((mut usage, mut entries)) =
+                parse_session_for_usage(&file.path, &file.project_key)
+            else {
+                return Ok(ParsedUsage {
+                    byte_offset: file.size,
+                    ..Default::default()
+                });
+            };
+            // Sessions don't record their project path; the hash identifies it
+            let project = format!("project:{}", file.project_key);
+            for (entry, _) in &mut entries {
+                entry.project_path = project.clone();
+            }
+            usage.project_path = project;
+            Ok(ParsedUsage {
+                entries,
+                session: Some((usage.session_id.clone(), to_json(&usage)?)),
+                byte_offset: file.size,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+// ============================================================================
+// Indexing
+// ============================================================================
+
+fn clear_usage_rows(conn: &Connection, file_id: i64) -> SqliteResult<()> {
+    conn.execute(
+        "DELETE FROM usage_entries WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    conn.execute(
+        "DELETE FROM usage_index_sessions WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    Ok(())
+}
+
+fn remove_file_rows(conn: &Connection, file_id: i64) -> SqliteResult<()> {
+    clear_usage_rows(conn, file_id)?;
+    conn.execute(
+        "DELETE FROM usage_index_files WHERE id = ?1",
+        params![file_id],
+    )?;
+    Ok(())
+}
+
+/// Write what was parsed from `file`
+///
+/// `appended` adds to the file's rows; otherwise they are replaced.
+fn store_usage(
+    conn: &mut Connection,
+    file: &UsageFile,
+    existing: Option<i64>,
+    appended: bool,
+    parsed: &ParsedUsage,
+) -> SqliteResult<()> {
+    let tx = conn.transaction()?;
+
+    let file_id = match existing {
+        Some(file_id) => {
+            if !appended {
+                clear_usage_rows(&tx, file_id)?;
+            }
+            tx.execute(
+                "UPDATE usage_index_files
+                 SET modified_at = ?2, size = ?3, byte_offset = ?4, state = ?5,
+                     indexed_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1",
+                params![
+                    file_id,
+                    file.modified_at,
+                    file.size,
+                    parsed.byte_offset,
+                    parsed.state
+                ],
+            )?;
+            file_id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO usage_index_files (path, engine, modified_at, size, byte_offset, state)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    file.path.to_string_lossy(),
+                    file.engine.as_str(),
+                    file.modified_at,
+                    file.size,
+                    parsed.byte_offset,
+                    parsed.state
+                ],
+            )?;
+            tx.last_insert_rowid()
+        }
+    };
+
+    // The file now covers these sessions; drop what was recorded while they ran
+    let sessions: HashSet<&str> = parsed
+        .entries
+        .iter()
+        .map(|(entry, _)| entry.session_id.as_str())
+        .collect();
+    for session_id in sessions {
+        tx.execute(
+            "DELETE FROM usage_entries
+             WHERE file_id IS NULL AND engine = ?1 AND session_id = ?2",
+            params![file.engine.as_str(), session_id],
+        )?;
+    }
+    // Sessions that ran in a session / fan-out worktree count towards its project
+    let mut source_paths: HashMap<&str, String> = HashMap::new();
+    for (entry, dedup_key) in &parsed.entries {
+        let project_path = source_paths
+            .entry(entry.project_path.as_str())
+            .or_insert_with_key(|path| session_worktree::source_project_path(path))
+            .clone();
+        let record = UsageRecord {
+            engine: file.engine,
+            session_id: entry.session_id.clone(),
+            message_id: dedup_key.clone(),
+            model: entry.model.clone(),
+            input_tokens: entry.input_tokens,
+            output_tokens: entry.output_tokens,
+            cache_creation_tokens: entry.cache_creation_tokens,
+            cache_read_tokens: entry.cache_read_tokens,
+            cost: entry.cost,
+            project_path,
+        };
+        insert_indexed_usage(&tx, &record, &entry.timestamp, file_id)?;
+    }
+
+    if let Some((session_id, usage)) = &parsed.session {
+        tx.execute(
+            "INSERT INTO usage_index_sessions (file_id, engine, session_id, usage)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(file_id) DO UPDATE SET
+                session_id = excluded.session_id,
+                usage = excluded.usage",
+            params![file_id, file.engine.as_str(), session_id, usage],
+        )?;
+    }
+
+    tx.commit()
+}
+
+fn load_indexed_files(conn: &Connection) -> SqliteResult<HashMap<String, IndexedFile>> {
+    let mut stmt = conn
+        .prepare("SELECT id, path, modified_at, size, byte_offset, state FROM usage_index_files")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(1)?,
+            IndexedFile {
+                id: row.get(0)?,
+                modified_at: row.get(2)?,
+                size: row.get(3)?,
+                byte_offset: row.get(4)?,
+                state: row.get(5)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Parse one changed file and store the result; returns whether only appended lines were read
+fn update_file(
+    db: &Mutex<Connection>,
+    file: &UsageFile,
+    indexed: Option<&IndexedFile>,
+) -> Result<bool, String> {
+    // Appended lines can be parsed on their own only in plain JSONL files that grew
+    let resume = indexed.filter(|i| {
+        file.engine != EngineKind::Gemini && !is_archived(&file.path) && file.size >= i.byte_offset
+    });
+    let parsed = parse_file(file, resume)?;
+
+    let mut conn = db.lock().map_err(|e| e.to_string())?;
+    store_usage(
+        &mut conn,
+        file,
+        indexed.map(|i| i.id),
+        resume.is_some(),
+        &parsed,
+    )
+    .map_err(|e| format!("Failed to index {:?}: {}", file.path, e))?;
+    Ok(resume.is_some())
+}
+
+static REFRESH_LOCK: Mutex<()> = Mutex::new(());
+
+fn refresh_locked(db: &Mutex<Connection>) -> Result<UsageIndexStats, String> {
+    let mut files = discover_usage_files();
+    let mut stats = UsageIndexStats {
+        scanned_files: files.len(),
+        ..Default::default()
+    };
+
+    let indexed = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        load_indexed_files(&conn).map_err(|e| e.to_string())?
+    };
+
+    // Drop vanished files first: Claude messages are deduplicated in favour of
+    // the earliest indexed row, and an archived file replaces its plain one
+    let on_disk: HashSet<String> = files
+        .iter()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .collect();
+    {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        for (path, file) in &indexed {
+            if !on_disk.contains(path) {
+                remove_file_rows(&conn, file.id).map_err(|e| e.to_string())?;
+                stats.removed_files += 1;
+            }
+        }
+    }
+
+    // Oldest first, so duplicated Claude messages count for their original session
+    files.sort_by_key(|f| f.modified_at);
+    for file in &files {
+        let known = indexed.get(file.path.to_string_lossy().as_ref());
+        if known.is_some_and(|k| k.modified_at == file.modified_at && k.size == file.size) {
+            continue;
+        }
+        match update_file(db, file, known) {
+            Ok(true) => stats.appended_files += 1,
+            Ok(false) => stats.parsed_files += 1,
+            Err(e) => {
+                log::debug!("[UsageIndex] Skipping {:?}: {}", file.path, e);
+                stats.failed_files += 1;
+            }
+        }
+    }
+
+    if stats.parsed_files > 0 || stats.appended_files > 0 || stats.removed_files > 0 {
+        log::info!("[UsageIndex] Index refreshed: {:?}", stats);
+    }
+    Ok(stats)
+}
+
+/// Bring the index up to date with the history files on disk
+///
+/// Parsing happens without holding the database lock; each changed file is
+/// written in its own short transaction.
+pub fn refresh_index(db: &Mutex<Connection>) -> Result<UsageIndexStats, String> {
+    let _guard = REFRESH_LOCK.lock().map_err(|e| e.to_string())?;
+    refresh_locked(db)
+}
+
+// ============================================================================
+// Queries
+// ============================================================================
+
+/// Gemini project hashes mapped to the project paths of known sessions
+fn gemini_project_paths(conn: &Connection) -> SqliteResult<HashMap<String, String>> {
+    let mut projects = project_paths_by_hash(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT project_path FROM usage_entries
+         WHERE engine != 'gemini' AND project_path IS NOT NULL",
+    )?;
+    for path in stmt.query_map([], |row| row.get::<_, String>(0))? {
+        let path = path?;
+        projects.entry(hash_project_path(&path)).or_insert(path);
+    }
+    Ok(projects)
+}
+
+/// Replace a `project:{hash}` placeholder with the project path, when known
+fn resolve_project_path(project_path: &mut String, projects: &HashMap<String, String>) {
+    if let Some(path) = project_path
+        .strip_prefix("project:")
+        .and_then(|hash| projects.get(hash))
+    {
+        *project_path = path.clone();
+    }
+}
+
+fn query_entries(conn: &Connection, engine: EngineKind) -> SqliteResult<Vec<UsageEntry>> {
+    // Indexed rows carry the deduplication key; live rows are not in a file yet
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, model, input_tokens, output_tokens,
+                cache_creation_tokens, cache_read_tokens, cost, session_id, project_path
+         FROM usage_entries
+         WHERE engine = ?1
+           AND (file_id IS NULL
+                OR message_id IS NULL
+                OR id IN (SELECT MIN(id) FROM usage_entries
+                          WHERE engine = ?1 AND file_id IS NOT NULL
+                            AND message_id IS NOT NULL
+                          GROUP BY message_id))
+         ORDER BY timestamp, id",
+    )?;
+    let rows = stmt.query_map(params![engine.as_str()], |row| {
+        Ok(UsageEntry {
+            timestamp: row.get(0)?,
+            model: row.get(1)?,
+            input_tokens: row.get::<_, i64>(2)? as u64,
+            output_tokens: row.get::<_, i64>(3)? as u64,
+            cache_creation_tokens: row.get::<_, i64>(4)? as u64,
+            cache_read_tokens: row.get::<_, i64>(5)? as u64,
+            cost: row.get(6)?,
+            session_id: row.get(7)?,
+            project_path: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
+        })
+    })?;
+    let mut entries = rows.collect::<SqliteResult<Vec<_>>>()?;
+
+    if engine == EngineKind::Gemini {
+        let projects = gemini_project_paths(conn)?;
+        for entry in &mut entries {
+            resolve_project_path(&mut entry.project_path, &projects);
+        }
+    }
+    Ok(entries)
+}
+
+/// Usage rows of each of `engines`, oldest first, after one refresh
+///
+/// A message copied into several files (resumed sessions) counts once.
+pub fn entries(
+    db: &Mutex<Connection>,
+    engines: &[EngineKind],
+) -> Result<Vec<(EngineKind, Vec<UsageEntry>)>, String> {
+    refresh_index(db)?;
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    engines
+        .iter()
+        .map(|&engine| {
+            query_entries(&conn, engine)
+                .map(|entries| (engine, entries))
+                .map_err(|e| format!("Failed to read usage index: {}", e))
+        })
+        .collect()
+}
+
+/// Claude usage entries, oldest first
+pub fn claude_entries(db: &Mutex<Connection>) -> Result<Vec<UsageEntry>, String> {
+    Ok(entries(db, &[EngineKind::Claude])?
+        .pop()
+        .map(|(_, entries)| entries)
+        .unwrap_or_default())
+}
+
+fn indexed_sessions<T: DeserializeOwned>(
+    db: &Mutex<Connection>,
+    engine: EngineKind,
+) -> Result<Vec<T>, String> {
+    refresh_index(db)?;
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT usage FROM usage_index_sessions WHERE engine = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![engine.as_str()], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|usage| serde_json::from_str(&usage).ok())
+        .collect())
+}
+
+/// Codex sessions, newest first
+pub fn codex_sessions(db: &Mutex<Connection>) -> Result<Vec<CodexSessionUsage>, String> {
+    let mut sessions: Vec<CodexSessionUsage> = indexed_sessions(db, EngineKind::Codex)?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(sessions)
+}
+
+/// Gemini sessions, newest first
+pub fn gemini_sessions(db: &Mutex<Connection>) -> Result<Vec<GeminiSessionUsage>, String> {
+    let mut sessions: Vec<GeminiSessionUsage> = indexed_sessions(db, EngineKind::Gemini)?;
+    let projects = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        gemini_project_paths(&conn).map_err(|e| e.to_string())?
+    };
+    for session in &mut sessions {
+        resolve_project_path(&mut session.project_path, &projects);
+    }
+    sessions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+    Ok(sessions)
+}
+
+/// Cost since the start of today and of this month (RFC 3339 instants), all
+/// engines, after one refresh
+///
+/// Summed in SQL so the periodic spend check doesn't load the whole history;
+/// `julianday` compares timestamps whatever their UTC offset.
+pub fn spend_since(
+    db: &Mutex<Connection>,
+    day_start: &str,
+    month_start: &str,
+) -> Result<(f64, f64), String> {
+    refresh_index(db)?;
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    query_spend_since(&conn, day_start, month_start)
+        .map_err(|e| format!("Failed to read usage index: {}", e))
+}
+
+fn query_spend_since(
+    conn: &Connection,
+    day_start: &str,
+    month_start: &str,
+) -> SqliteResult<(f64, f64)> {
+    // Same deduplication as query_entries, per engine
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN julianday(timestamp) >= julianday(?1) THEN cost END), 0),
+                COALESCE(SUM(cost), 0)
+         FROM usage_entries
+         WHERE julianday(timestamp) >= julianday(?2)
+           AND (file_id IS NULL
+                OR message_id IS NULL
+                OR id IN (SELECT MIN(id) FROM usage_entries
+                          WHERE file_id IS NOT NULL AND message_id IS NOT NULL
+                          GROUP BY engine, message_id))",
+        params![day_start, month_start],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Drop the index and parse every history file again
+#[tauri::command]
+pub async fn rebuild_usage_index(app: AppHandle) -> Result<UsageIndexStats, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let db = app.state::<AgentDb>();
+        let _guard = REFRESH_LOCK.lock().map_err(|e| e.to_string())?;
+        db.0.lock()
+            .map_err(|e| e.to_string())?
+            .execute_batch(
+                "DELETE FROM usage_entries WHERE file_id IS NOT NULL;
+                 DELETE FROM usage_index_sessions;
+                 DELETE FROM usage_index_files;",
+            )
+            .map_err(|e| format!("Failed to clear usage index: {}", e))?;
+        refresh_locked(&db.0)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::storage::open_database;
+    use crate::commands::usage_recorder::insert_usage;
+    use std::io::Write;
+
+    fn claude_line(message_id: &str, output_tokens: u64) -> String {
+        format!(
+            r#"{{"timestamp":"2025-06-01T10:00:00Z","sessionId":"s1","requestId":"r1","cwd":"/work/app","costUSD":0.5,"message":{{"id":"{}","model":"claude-sonnet-4-5","usage":{{"input_tokens":10,"output_tokens":{}}}}}}}"#,
+            message_id, output_tokens
+        )
+    }
+
+    fn claude_file(path: &Path) -> UsageFile {
+        let size = std::fs::metadata(path).unwrap().len() as i64;
+        UsageFile {
+            path: path.to_path_buf(),
+            engine: EngineKind::Claude,
+            project_key: "-work-app".to_string(),
+            modified_at: size,
+            size,
+        }
+    }
+
+    fn open_db() -> Mutex<Connection> {
+        Mutex::new(open_database(Path::new(":memory:")).unwrap())
+    }
+
+    #[test]
+    fn appended_lines_are_parsed_from_the_stored_offset() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("s1.jsonl");
+        let mut out = File::create(&path).unwrap();
+        // The last line is still being written
+        write!(
+            out,
+            "{}\n{}",
+            claude_line("m1", 5),
+            &claude_line("m2", 7)[..20]
+        )
+        .unwrap();
+        out.flush().unwrap();
+
+        let db = open_db();
+        assert!(!update_file(&db, &claude_file(&path), None).unwrap());
+
+        // Finish the partial line, then add one more and a duplicate of m1
+        write!(
+            out,
+            "{}\n{}\n{}\n",
+            &claude_line("m2", 7)[20..],
+            claude_line("m3", 9),
+            claude_line("m1", 5)
+        )
+        .unwrap();
+        out.flush().unwrap();
+
+        let indexed = load_indexed_files(&db.lock().unwrap()).unwrap();
+        let known = &indexed[path.to_string_lossy().as_ref()];
+        assert_eq!(known.byte_offset, claude_line("m1", 5).len() as i64 + 1);
+        let second = claude_file(&path);
+        assert!(update_file(&db, &second, Some(known)).unwrap());
+
+        let indexed = load_indexed_files(&db.lock().unwrap()).unwrap();
+        assert_eq!(
+            indexed[path.to_string_lossy().as_ref()].byte_offset,
+            second.size
+        );
+
+        let entries = query_entries(&db.lock().unwrap(), EngineKind::Claude).unwrap();
+        let outputs: Vec<(u64, &str)> = entries
+            .iter()
+            .map(|e| (e.output_tokens, e.project_path.as_str()))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![(5, "/work/app"), (7, "/work/app"), (9, "/work/app")]
+        );
+    }
+
+    #[test]
+    fn indexing_a_file_replaces_the_rows_recorded_live() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("s1.jsonl");
+        std::fs::write(&path, format!("{}\n", claude_line("m1", 5))).unwrap();
+
+        let db = open_db();
+        let live = UsageRecord {
+            engine: EngineKind::Claude,
+            session_id: "s1".to_string(),
+            message_id: Some("m1".to_string()),
+            model: "claude-sonnet-4-5".to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost: 0.5,
+            project_path: "/work/app".to_string(),
+        };
+        insert_usage(&db.lock().unwrap(), &live, "2025-06-01T10:00:00Z").unwrap();
+        assert_eq!(
+            query_entries(&db.lock().unwrap(), EngineKind::Claude)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        update_file(&db, &claude_file(&path), None).unwrap();
+
+        let conn = db.lock().unwrap();
+        assert_eq!(query_entries(&conn, EngineKind::Claude).unwrap().len(), 1);
+        let live_rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM usage_entries WHERE file_id IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(live_rows, 0);
+    }
+
+    #[test]
+    fn spend_is_summed_over_deduplicated_rows_since_each_start() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("s1.jsonl");
+        let lines = [
+            claude_line("m1", 5),
+            claude_line("m2", 7),
+            claude_line("m1", 5),
+        ];
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let db = open_db();
+        update_file(&db, &claude_file(&path), None).unwrap();
+        let live = |cost: f64| UsageRecord {
+            engine: EngineKind::Codex,
+            session_id: "c1".to_string(),
+            message_id: None,
+            model: "gpt-5-codex".to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost,
+            project_path: "/work/app".to_string(),
+        };
+        let conn = db.lock().unwrap();
+        // Midnight UTC written with an offset, and a row of the previous month
+        insert_usage(&conn, &live(2.0), "2025-06-02T08:00:00+08:00").unwrap();
+        insert_usage(&conn, &live(4.0), "2025-05-31T23:00:00Z").unwrap();
+
+        let (day, month) =
+            query_spend_since(&conn, "2025-06-02T00:00:00Z", "2025-06-01T00:00:00Z").unwrap();
+        assert_eq!(day, 2.0);
+        assert_eq!(month, 3.0);
+    }
+
+    #[test]
+    fn codex_and_gemini_files_give_one_row_per_turn() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let codex_path = dir.path().join("rollout-c1.jsonl");
+        let token_count = |time: &str, input: u64, cached: u64, output: u64| {
+            format!(
+                r#"{{"type":"event_msg","timestamp":"{}","payload":{{"type":"token_count","info":{{"total_token_usage":{{"input_tokens":{},"cached_input_tokens":{},"output_tokens":{}}}}}}}}}"#,
+                time, input, cached, output
+            )
+        };
+        let rollout = [
+            r#"{"type":"session_meta","payload":{"id":"c1","timestamp":"2025-06-01T23:50:00Z","cwd":"/work/app"}}"#.to_string(),
+            r#"{"type":"turn_context","payload":{"model":"gpt-5-codex"}}"#.to_string(),
+            token_count("2025-06-01T23:55:00Z", 100, 40, 10),
+            token_count("2025-06-02T00:05:00Z", 300, 140, 30),
+        ];
+        std::fs::write(&codex_path, rollout.join("\n") + "\n").unwrap();
+
+        let gemini_path = dir.path().join("session-g1.json");
+        let gemini_session = serde_json::json!({
+            "sessionId": "g1",
+            "projectHash": hash_project_path("/work/app"),
+            "startTime": "2025-06-01T09:00:00Z",
+            "lastUpdated": "2025-06-02T09:00:00Z",
+            "messages": [
+                {"id": "m1", "timestamp": "2025-06-01T09:00:00Z", "type": "user", "content": "hi"},
+                {"id": "m2", "timestamp": "2025-06-01T09:00:05Z", "type": "gemini",
+                 "model": "gemini-2.5-pro", "tokens": {"input": 100, "output": 20}},
+                {"id": "m3", "timestamp": "2025-06-02T09:00:05Z", "type": "gemini",
+                 "model": "gemini-2.5-pro", "tokens": {"input": 200, "output": 40}}
+            ]
+        });
+        std::fs::write(&gemini_path, gemini_session.to_string()).unwrap();
+
+        let db = open_db();
+        for (path, engine, project_key) in [
+            (&codex_path, EngineKind::Codex, String::new()),
+            (
+                &gemini_path,
+                EngineKind::Gemini,
+                hash_project_path("/work/app"),
+            ),
+        ] {
+            let size = std::fs::metadata(path).unwrap().len() as i64;
+            let file = UsageFile {
+                path: path.clone(),
+                engine,
+                project_key,
+                modified_at: size,
+                size,
+            };
+            update_file(&db, &file, None).unwrap();
+        }
+
+        let conn = db.lock().unwrap();
+        let codex = query_entries(&conn, EngineKind::Codex).unwrap();
+        let turns: Vec<(&str, u64, u64, u64)> = codex
+            .iter()
+            .map(|e| {
+                (
+                    e.timestamp.as_str(),
+                    e.input_tokens,
+                    e.cache_read_tokens,
+                    e.output_tokens,
+                )
+            })
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("2025-06-01T23:55:00Z", 60, 40, 10),
+                ("2025-06-02T00:05:00Z", 100, 100, 20),
+            ]
+        );
+
+        // The Codex session of the same directory resolves the Gemini project hash
+        let gemini = query_entries(&conn, EngineKind::Gemini).unwrap();
+        let messages: Vec<(&str, u64, &str)> = gemini
+            .iter()
+            .map(|e| {
+                (
+                    e.timestamp.as_str(),
+                    e.input_tokens,
+                    e.project_path.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("2025-06-01T09:00:05Z", 100, "/work/app"),
+                ("2025-06-02T09:00:05Z", 200, "/work/app"),
+            ]
+        );
+
+        // Session totals are the sum of the turn costs, not the totals priced at once
+        let session_cost = |engine: EngineKind| -> f64 {
+            let usage: String = conn
+                .query_row(
+                    "SELECT usage FROM usage_index_sessions WHERE engine = ?1",
+                    params![engine.as_str()],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            serde_json::from_str::<serde_json::Value>(&usage).unwrap()["total_cost"]
+                .as_f64()
+                .unwrap()
+        };
+        let codex_cost: f64 = codex.iter().map(|e| e.cost).sum();
+        let gemini_cost: f64 = gemini.iter().map(|e| e.cost).sum();
+        assert!((session_cost(EngineKind::Codex) - codex_cost).abs() < 1e-12);
+        assert!((session_cost(EngineKind::Gemini) - gemini_cost).abs() < 1e-12);
+    }
+}
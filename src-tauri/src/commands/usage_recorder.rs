@@ -0,0 +1,450 @@
+//! Live usage recording
+//!
+//! The stdout readers of all three engines write their usage into the
+//! `usage_entries` table of `agents.db` while the stream runs:
+//!
+//! - Claude: one row per assistant message. The stream repeats a message's
+//!   usage on each of its content blocks, so rows are keyed by message ID and
+//!   later reports replace earlier ones.
+//! - Codex: one row per `turn.completed`
+//! - Gemini: one row per run (usage only arrives on the result line)
+//!
+//! Token and cost totals of a session can then be read from SQLite instead of
+//! re-parsing its history file.
+//!
+//! The usage index (`usage_index`) writes the rows it parses from history
+//! files into the same table, tagged with the file they came from. Once a
+//! session's file has been indexed, its live rows are replaced by the parsed
+//! ones, so every usage query reads this one table.
+
+use chrono::Utc;
+use rusqlite::{params, Connection, Result as SqliteResult};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use super::claude::ClaudeStreamEvent;
+use super::engine::EngineKind;
+use super::gemini::types::GeminiStreamEvent;
+use super::storage::AgentDb;
+use super::{codex, gemini, session_worktree, usage};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Usage of one billed unit (message / turn / run)
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub engine: EngineKind,
+    pub session_id: String,
+    /// Set when the engine may report the same unit more than once
+    pub message_id: Option<String>,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cost: f64,
+    pub project_path: String,
+}
+
+impl UsageRecord {
+    fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+    }
+
+    /// Usage of a Claude assistant message; `model` is used when the message names none
+    pub fn from_claude_event(
+        event: &ClaudeStreamEvent,
+        model: &str,
+        session_id: &str,
+        project_path: &str,
+    ) -> Option<Self> {
+        let ClaudeStreamEvent::Assistant {
+            message_id: Some(message_id),
+            model: message_model,
+            usage: Some(usage),
+            ..
+        } = event
+        else {
+            return None;
+        };
+        let model = message_model.as_deref().unwrap_or(model);
+        Some(Self {
+            engine: EngineKind::Claude,
+            session_id: session_id.to_string(),
+            message_id: Some(message_id.clone()),
+            model: model.to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cache_creation_tokens: usage.cache_creation_input_tokens,
+            cache_read_tokens: usage.cache_read_input_tokens,
+            cost: usage::calculate_stream_cost(model, usage),
+            project_path: project_path.to_string(),
+        })
+    }
+
+    /// Usage of a Codex `turn.completed` line
+    pub fn from_codex_line(
+        line: &str,
+        model: &str,
+        session_id: &str,
+        project_path: &str,
+    ) -> Option<Self> {
+        let usage = serde_json::from_str::<serde_json::Value>(line)
+            .ok()
+            .filter(|v| v["type"] == "turn.completed")
+            .map(|v| v["usage"].clone())?;
+        let tokens = |key: &str| usage[key].as_u64().unwrap_or(0);
+        let (input, output, cached) = (
+            tokens("input_tokens"),
+            tokens("output_tokens"),
+            tokens("cached_input_tokens"),
+        );
+        Some(Self {
+            engine: EngineKind::Codex,
+            session_id: session_id.to_string(),
+            message_id: None,
+            model: if model.is_empty() {
+                "unknown".to_string()
+            } else {
+                model.to_string()
+            },
+            // input_tokens includes the cached part
+            input_tokens: input.saturating_sub(cached),
+            output_tokens: output,
+            cache_creation_tokens: 0,
+            cache_read_tokens: cached,
+            cost: codex::usage::calculate_cost(
+                model,
+                input,
+                output,
+                cached,
+                chrono::Local::now().date_naive(),
+            ),
+            project_path: project_path.to_string(),
+        })
+    }
+
+    /// Usage of a Gemini run, from its result event
+    pub fn from_gemini_event(
+        event: &GeminiStreamEvent,
+        model: &str,
+        session_id: &str,
+        project_path: &str,
+    ) -> Option<Self> {
+        let GeminiStreamEvent::Result {
+            stats,
+            usage_metadata,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        let (input, output) = stats
+            .as_ref()
+            .map(|s| (s.input_tokens, s.output_tokens))
+            .or_else(|| {
+                usage_metadata
+                    .as_ref()
+                    .map(|u| (u.prompt_token_count, u.candidates_token_count))
+            })?;
+        let (input, output) = (input.unwrap_or(0), output.unwrap_or(0));
+        Some(Self {
+            engine: EngineKind::Gemini,
+            session_id: session_id.to_string(),
+            message_id: None,
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: output,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            cost: gemini::usage::calculate_cost(
+                model,
+                input,
+                output,
+                chrono::Local::now().date_naive(),
+            ),
+            project_path: project_path.to_string(),
+        })
+    }
+}
+
+/// Totals of the rows recorded for a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedSessionUsage {
+    pub session_id: String,
+    pub entries: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub total_tokens: u64,
+    pub total_cost: f64,
+}
+
+// ============================================================================
+// Schema
+// ============================================================================
+
+/// Columns added to `usage_entries` after its first release
+const ADDED_COLUMNS: [(&str, &str); 3] = [
+    ("engine", "TEXT NOT NULL DEFAULT 'claude'"),
+    ("message_id", "TEXT"),
+    // usage_index_files.id of indexed rows; NULL for live rows
+    ("file_id", "INTEGER"),
+];
+
+/// Bring an existing `usage_entries` table up to date
+pub(crate) fn migrate_usage_table(conn: &Connection) -> SqliteResult<()> {
+    let existing: Vec<String> = conn
+        .prepare("PRAGMA table_info(usage_entries)")?
+        .query_map([], |row| row.get(1))?
+        .collect::<SqliteResult<_>>()?;
+
+    for (column, definition) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == column) {
+            conn.execute(
+                &format!(
+                    "ALTER TABLE usage_entries ADD COLUMN {} {}",
+                    column, definition
+                ),
+                [],
+            )?;
+        }
+    }
+
+    // NULL message IDs never conflict, so per-turn / per-run rows simply append
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_usage_session_message
+         ON usage_entries(session_id, message_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_file_id ON usage_entries(file_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Recording
+// ============================================================================
+
+pub(crate) fn insert_usage(
+    conn: &Connection,
+    record: &UsageRecord,
+    timestamp: &str,
+) -> SqliteResult<()> {
+    upsert_usage(conn, record, timestamp, None)
+}
+
+/// Write a row parsed from the history file `file_id` (see `usage_index`)
+pub(crate) fn insert_indexed_usage(
+    conn: &Connection,
+    record: &UsageRecord,
+    timestamp: &str,
+    file_id: i64,
+) -> SqliteResult<()> {
+    upsert_usage(conn, record, timestamp, Some(file_id))
+}
+
+fn upsert_usage(
+    conn: &Connection,
+    record: &UsageRecord,
+    timestamp: &str,
+    file_id: Option<i64>,
+) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT INTO usage_entries (
+            engine, session_id, message_id, timestamp, model,
+            input_tokens, output_tokens, cache_creation_tokens, cache_read_tokens,
+            total_tokens, cost, project_path, file_id
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+         ON CONFLICT(session_id, message_id) DO UPDATE SET
+            model = excluded.model,
+            input_tokens = excluded.input_tokens,
+            output_tokens = excluded.output_tokens,
+            cache_creation_tokens = excluded.cache_creation_tokens,
+            cache_read_tokens = excluded.cache_read_tokens,
+            total_tokens = excluded.total_tokens,
+            cost = excluded.cost,
+            file_id = excluded.file_id",
+        params![
+            record.engine.as_str(),
+            record.session_id,
+            record.message_id,
+            timestamp,
+            record.model,
+            record.input_tokens as i64,
+            record.output_tokens as i64,
+            record.cache_creation_tokens as i64,
+            record.cache_read_tokens as i64,
+            record.total_tokens() as i64,
+            record.cost,
+            record.project_path,
+            file_id,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Write one usage row; failures are logged, never surfaced to the stream
+pub fn record_usage<R: Runtime>(app: &AppHandle<R>, mut record: UsageRecord) {
+    if record.total_tokens() == 0 {
+        return;
+    }
+    // Worktree runs are reported under the project they isolate
+    record.project_path = session_worktree::source_project_path(&record.project_path);
+    let Some(db) = app.try_state::<AgentDb>() else {
+        return;
+    };
+    let result = match db.0.lock() {
+        Ok(conn) => insert_usage(&conn, &record, &Utc::now().to_rfc3339()),
+        Err(e) => {
+            log::warn!("[UsageRecorder] Database lock poisoned: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = result {
+        log::warn!(
+            "[UsageRecorder] Failed to record {} usage for {}: {}",
+            record.engine,
+            record.session_id,
+            e
+        );
+    }
+}
+
+/// Token and cost totals of the rows recorded for a session
+pub(crate) fn recorded_session_usage(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<RecordedSessionUsage, String> {
+    conn.query_row(
+        "SELECT COUNT(*),
+                COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0),
+                COALESCE(SUM(cache_creation_tokens), 0), COALESCE(SUM(cache_read_tokens), 0),
+                COALESCE(SUM(total_tokens), 0), COALESCE(SUM(cost), 0.0)
+         FROM usage_entries WHERE session_id = ?1",
+        params![session_id],
+        |row| {
+            Ok(RecordedSessionUsage {
+                session_id: session_id.to_string(),
+                entries: row.get::<_, i64>(0)? as u64,
+                input_tokens: row.get::<_, i64>(1)? as u64,
+                output_tokens: row.get::<_, i64>(2)? as u64,
+                cache_creation_tokens: row.get::<_, i64>(3)? as u64,
+                cache_read_tokens: row.get::<_, i64>(4)? as u64,
+                total_tokens: row.get::<_, i64>(5)? as u64,
+                total_cost: row.get(6)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Failed to read recorded usage: {}", e))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Token and cost totals recorded so far for a session
+#[tauri::command]
+pub async fn get_recorded_session_usage(
+    db: State<'_, AgentDb>,
+    session_id: String,
+) -> Result<RecordedSessionUsage, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    recorded_session_usage(&conn, &session_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_message_reports_replace_the_row() {
+        let conn = Connection::open_in_memory().unwrap();
+        // First-release schema
+        conn.execute(
+            "CREATE TABLE usage_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER DEFAULT 0,
+                output_tokens INTEGER DEFAULT 0,
+                cache_creation_tokens INTEGER DEFAULT 0,
+                cache_read_tokens INTEGER DEFAULT 0,
+                total_tokens INTEGER DEFAULT 0,
+                cost REAL DEFAULT 0.0,
+                project_path TEXT
+            )",
+            [],
+        )
+        .unwrap();
+        migrate_usage_table(&conn).unwrap();
+        migrate_usage_table(&conn).unwrap();
+
+        let mut record = UsageRecord {
+            engine: EngineKind::Claude,
+            session_id: "s1".to_string(),
+            message_id: Some("msg_1".to_string()),
+            model: "claude-sonnet-4-5".to_string(),
+            input_tokens: 10,
+            output_tokens: 1,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 100,
+            cost: 0.01,
+            project_path: "/tmp/app".to_string(),
+        };
+        insert_usage(&conn, &record, "t1").unwrap();
+        record.output_tokens = 50;
+        insert_usage(&conn, &record, "t2").unwrap();
+
+        record.engine = EngineKind::Codex;
+        record.message_id = None;
+        insert_usage(&conn, &record, "t3").unwrap();
+        insert_usage(&conn, &record, "t4").unwrap();
+
+        let rows: Vec<(String, i64)> = conn
+            .prepare("SELECT engine, output_tokens FROM usage_entries ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<SqliteResult<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("claude".to_string(), 50),
+                ("codex".to_string(), 50),
+                ("codex".to_string(), 50),
+            ]
+        );
+    }
+
+    #[test]
+    fn codex_turn_cost_prices_the_recorded_tokens() {
+        let line = r#"{"type":"turn.completed","usage":{"input_tokens":24763,"cached_input_tokens":24448,"output_tokens":122}}"#;
+        let record = UsageRecord::from_codex_line(line, "gpt-5-codex", "t1", "/tmp/app").unwrap();
+        assert_eq!(record.input_tokens, 315);
+        assert_eq!(record.cache_read_tokens, 24448);
+
+        let tokens = crate::commands::pricing::TokenCounts {
+            input: record.input_tokens,
+            output: record.output_tokens,
+            cache_write: 0,
+            cache_read: record.cache_read_tokens,
+        };
+        let expected = crate::commands::pricing::cost(
+            EngineKind::Codex,
+            "gpt-5-codex",
+            &tokens,
+            chrono::Local::now().date_naive(),
+        )
+        .unwrap();
+        assert!((record.cost - expected).abs() < 1e-12);
+    }
+}
@@ -72,6 +72,14 @@ use commands::translator::{
     update_translation_config,
 };
 use commands::usage::{get_session_stats, get_usage_by_date_range, get_usage_stats};
+use commands::usage_index::rebuild_usage_index;
+use commands::unified_usage::get_unified_usage_stats;
+use commands::usage_export::export_usage;
+use commands::spend_alerts::{
+    check_spend_alerts, get_spend_alert_config, update_spend_alert_config,
+};
+use commands::usage_recorder::get_recorded_session_usage;
+use commands::pricing::{get_pricing_catalog, resolve_model_pricing, update_pricing_overrides};
 use commands::window::{
     broadcast_to_session_windows, close_session_window, create_session_window, emit_to_window,
     focus_session_window, list_session_windows, set_titlebar_theme,
@@ -166,6 +174,41 @@ use commands::gemini::{
     update_gemini_provider_config,
     GeminiProcessState,
 };
+use commands::engine::timeout::{get_run_timeout_config, update_run_timeout_config};
+use commands::budget::{get_budget_config, get_budget_status, update_budget_config};
+use commands::engine::{engine_cancel, engine_execute, engine_resume};
+use commands::prompt_queue::{
+    enqueue_prompt, get_prompt_queue, get_prompt_queue_config, remove_queued_prompt,
+    reorder_prompt_queue, set_queued_prompt_priority, update_prompt_queue_config,
+    PromptQueueState,
+};
+use commands::fan_out::{
+    discard_fan_out, fan_out_prompt, get_fan_out_result, merge_fan_out_run,
+};
+use commands::orphan_processes::{
+    attach_orphan_session, kill_orphan_process, list_orphan_processes,
+};
+use commands::resource_monitor::{
+    get_resource_monitor_config, list_process_resources, update_resource_monitor_config,
+};
+use commands::session_archive::{
+    archive_sessions_now, get_session_archive_config, rehydrate_session,
+    update_session_archive_config,
+};
+use commands::session_export::export_session;
+use commands::session_fork::fork_session_at_prompt;
+use commands::session_search::{refresh_session_search_index, search_sessions};
+use commands::trash::{
+    get_trash_config, list_trash, purge_trash, restore_from_trash, update_trash_config,
+};
+use commands::session_worktree::{
+    create_session_worktree, discard_session_worktree, get_session_worktree,
+    list_session_worktrees, merge_session_worktree, rebase_session_worktree,
+};
+use commands::control_api::{
+    get_control_api_status, regenerate_control_api_token, update_control_api_config,
+    ControlApiState,
+};
 use commands::git_stats::{get_git_diff_stats, get_session_code_changes};
 use process::ProcessRegistryState;
 use tauri::{Manager, WindowEvent};
@@ -184,6 +227,7 @@ fn main() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(
             WindowStatePlugin::default()
                 .with_state_flags(tauri_plugin_window_state::StateFlags::all())
@@ -198,8 +242,37 @@ fn main() {
             let conn = init_database(&app.handle()).expect("Failed to initialize database");
             app.manage(AgentDb(Mutex::new(conn)));
 
-            // Initialize process registry
-            app.manage(ProcessRegistryState::default());
+            // Keep the session full-text index in sync with history files
+            commands::session_search::start_search_indexer(app.handle());
+
+            // Drop recycle bin entries past their retention period
+            std::thread::spawn(commands::trash::purge_expired_trash);
+
+            // Compress sessions past the archive threshold (when enabled)
+            commands::session_archive::start_session_archiver();
+
+            // Initialize process registry, persisted in agents.db so processes
+            // left running by a crash are found again
+            let process_registry = ProcessRegistryState::default();
+            match app.path().app_data_dir() {
+                Ok(app_dir) => {
+                    match process_registry.0.attach_store(&app_dir.join("agents.db")) {
+                        Ok(0) => {}
+                        Ok(count) => {
+                            log::warn!("Found {} orphaned processes from a previous run", count)
+                        }
+                        Err(e) => log::warn!("Process registry will not be persisted: {}", e),
+                    }
+                }
+                Err(e) => log::warn!("Process registry will not be persisted: {}", e),
+            }
+            app.manage(process_registry);
+
+            // Sample CPU / memory / MCP servers of registered runs
+            commands::resource_monitor::start_resource_monitor(app.handle());
+
+            // Daily / monthly spend and provider balance alerts (when enabled)
+            commands::spend_alerts::start_spend_alert_watcher(app.handle());
 
             // Initialize Claude process state
             app.manage(ClaudeProcessState::default());
@@ -210,6 +283,19 @@ fn main() {
             // Initialize Gemini process state
             app.manage(GeminiProcessState::default());
 
+            // Initialize per-project prompt queue
+            app.manage(PromptQueueState::default());
+
+            // Map new sessions to their isolation worktrees
+            commands::session_worktree::start_session_tracker(app.handle());
+
+            // Local control API (opt-in, loopback only)
+            app.manage(ControlApiState::default());
+            let app_handle_for_api = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                commands::control_api::start_control_api(app_handle_for_api).await;
+            });
+
             // Initialize auto-compact manager for context management
             let auto_compact_manager =
                 Arc::new(commands::context_manager::AutoCompactManager::new());
@@ -352,6 +438,17 @@ fn main() {
             get_usage_stats,
             get_usage_by_date_range,
             get_session_stats,
+            get_recorded_session_usage,
+            get_unified_usage_stats,
+            rebuild_usage_index,
+            export_usage,
+            get_spend_alert_config,
+            update_spend_alert_config,
+            check_spend_alerts,
+            // Pricing Catalog
+            get_pricing_catalog,
+            update_pricing_overrides,
+            resolve_model_pricing,
             // MCP (Model Context Protocol)
             mcp_add,
             mcp_list,
@@ -549,7 +646,76 @@ fn main() {
             set_gemini_wsl_mode_config,
             // Gemini Usage Statistics
             get_gemini_usage_stats,
+            // Unified Engine API (Claude / Codex / Gemini)
+            engine_execute,
+            engine_resume,
+            engine_cancel,
+            // Run Timeouts (wall-clock / inactivity)
+            get_run_timeout_config,
+            update_run_timeout_config,
+            // Cost Budgets
+            get_budget_config,
+            update_budget_config,
+            get_budget_status,
+            // Local Control API (HTTP/WebSocket)
+            get_control_api_status,
+            update_control_api_config,
+            regenerate_control_api_token,
+            // Prompt Queue
+            enqueue_prompt,
+            get_prompt_queue,
+            remove_queued_prompt,
+            set_queued_prompt_priority,
+            reorder_prompt_queue,
+            get_prompt_queue_config,
+            update_prompt_queue_config,
+            // Multi-Engine Fan-out (git worktrees)
+            fan_out_prompt,
+            get_fan_out_result,
+            merge_fan_out_run,
+            discard_fan_out,
+            // Session Worktrees (isolation mode)
+            create_session_worktree,
+            list_session_worktrees,
+            get_session_worktree,
+            merge_session_worktree,
+            rebase_session_worktree,
+            discard_session_worktree,
+            // Session Export
+            export_session,
+            // Session Fork
+            fork_session_at_prompt,
+            // Session Search (FTS5)
+            search_sessions,
+            refresh_session_search_index,
+            // Recycle Bin
+            list_trash,
+            restore_from_trash,
+            purge_trash,
+            get_trash_config,
+            update_trash_config,
+            // Session Archive (zstd)
+            get_session_archive_config,
+            update_session_archive_config,
+            archive_sessions_now,
+            rehydrate_session,
+            // Orphaned Processes
+            list_orphan_processes,
+            kill_orphan_process,
+            attach_orphan_session,
+            // Resource Monitor
+            list_process_resources,
+            get_resource_monitor_config,
+            update_resource_monitor_config,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Take engine CLIs and their MCP servers down with the app
+            if let tauri::RunEvent::Exit = event {
+                tauri::async_runtime::block_on(commands::engine::terminate_all_processes(
+                    app_handle,
+                ));
+            }
+        });
 }
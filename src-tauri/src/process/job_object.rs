@@ -138,25 +138,151 @@ pub mod windows_job {
 
 #[cfg(not(windows))]
 pub mod windows_job {
-    /// Dummy JobObject for non-Windows platforms
-    pub struct JobObject;
+    //! Unix counterpart of the Job Object, built on process groups
+    //!
+    //! Engine CLIs are spawned with `process_group(0)`, so the CLI leads its own
+    //! group and every descendant that does not call `setsid` itself (including
+    //! `npx`-launched MCP servers) shares that PGID. Terminating the job signals
+    //! the whole group: SIGTERM first, SIGKILL for whatever is still alive after
+    //! the grace period. As on Windows, dropping the job kills the group.
+
+    use log::{debug, info, warn};
+    use std::process::Command;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How long a group gets to exit after SIGTERM before SIGKILL
+    const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(3);
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub struct JobObject {
+        pgid: AtomicI32, // 0 = no process group assigned yet
+    }
+
+    /// Send `signal` (e.g. "-TERM") to every process in group `pgid`
+    fn signal_group(pgid: i32, signal: &str) -> bool {
+        Command::new("kill")
+            .args([signal, &format!("-{}", pgid)]) // Negative PID targets the process group
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    /// Whether group `pgid` still has a member that is not a zombie
+    ///
+    /// `kill -0` succeeds for unreaped zombies, which the CLI leader is until its
+    /// `Child` is waited on, so this goes through `ps` instead.
+    fn group_alive(pgid: i32) -> bool {
+        let Ok(output) = Command::new("ps")
+            .args(["-A", "-o", "pgid=", "-o", "stat="])
+            .output()
+        else {
+            return false;
+        };
+        String::from_utf8_lossy(&output.stdout).lines().any(|line| {
+            let mut fields = line.split_whitespace();
+            fields.next().and_then(|p| p.parse::<i32>().ok()) == Some(pgid)
+                && fields.next().is_some_and(|stat| !stat.starts_with('Z'))
+        })
+    }
+
+    fn process_group_of(pid: u32) -> Option<i32> {
+        let output = Command::new("ps")
+            .args(["-o", "pgid=", "-p", &pid.to_string()])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    /// SIGTERM the group, then SIGKILL it if it outlives the grace period
+    fn terminate_group(pgid: i32) -> Result<(), String> {
+        if !group_alive(pgid) {
+            return Ok(());
+        }
+        if !signal_group(pgid, "-TERM") {
+            return Err(format!("Failed to send SIGTERM to process group {}", pgid));
+        }
+        info!("Sent SIGTERM to process group {}", pgid);
+
+        let deadline = Instant::now() + TERMINATE_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+            if !group_alive(pgid) {
+                info!("Process group {} exited after SIGTERM", pgid);
+                return Ok(());
+            }
+        }
+
+        warn!(
+            "Process group {} still running after {:?}, sending SIGKILL",
+            pgid, TERMINATE_GRACE_PERIOD
+        );
+        if signal_group(pgid, "-KILL") || !group_alive(pgid) {
+            Ok(())
+        } else {
+            Err(format!("Failed to send SIGKILL to process group {}", pgid))
+        }
+    }
 
     impl JobObject {
         pub fn create() -> Result<Self, String> {
-            // No-op on non-Windows platforms
-            Ok(JobObject)
+            Ok(JobObject {
+                pgid: AtomicI32::new(0),
+            })
         }
 
-        pub fn assign_process_by_pid(&self, _pid: u32) -> Result<(), String> {
-            // No-op on non-Windows platforms
-            Ok(())
+        /// Track the process group led by `pid`
+        ///
+        /// The process must have been spawned as a group leader; adopting the
+        /// group of an arbitrary process could take the app down with it.
+        pub fn assign_process_by_pid(&self, pid: u32) -> Result<(), String> {
+            match process_group_of(pid) {
+                Some(pgid) if pgid == pid as i32 => {
+                    self.pgid.store(pgid, Ordering::SeqCst);
+                    debug!("Tracking process group {}", pgid);
+                    Ok(())
+                }
+                Some(pgid) => Err(format!(
+                    "Process {} is not a process group leader (group {})",
+                    pid, pgid
+                )),
+                None => Err(format!("Failed to read process group of {}", pid)),
+            }
         }
 
+        /// Terminate all processes in the group (SIGTERM, then SIGKILL)
         pub fn terminate_all(&self, _exit_code: u32) -> Result<(), String> {
-            // No-op on non-Windows platforms
-            Ok(())
+            match self.pgid.swap(0, Ordering::SeqCst) {
+                0 => Err("No process group assigned".to_string()),
+                pgid => terminate_group(pgid),
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            let pgid = *self.pgid.get_mut();
+            if pgid != 0 && group_alive(pgid) {
+                debug!("Dropping JobObject, killing process group {}", pgid);
+                signal_group(pgid, "-KILL");
+            }
         }
     }
 }
 
 pub use windows_job::JobObject;
+
+impl JobObject {
+    /// `terminate_all` for callers on the async runtime
+    ///
+    /// On Unix the SIGTERM grace period polls the group for up to 3 seconds,
+    /// so the termination runs on the blocking pool instead of a worker thread.
+    pub async fn terminate_all_async(
+        self: std::sync::Arc<Self>,
+        exit_code: u32,
+    ) -> Result<(), String> {
+        tauri::async_runtime::spawn_blocking(move || self.terminate_all(exit_code))
+            .await
+            .map_err(|e| format!("Terminate task failed: {}", e))?
+    }
+}
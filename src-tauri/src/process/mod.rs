@@ -1,5 +1,7 @@
 pub mod job_object;
 pub mod registry;
+pub mod resources;
+pub mod store;
 
 pub use job_object::JobObject;
 pub use registry::*;
@@ -1,15 +1,40 @@
+use super::resources::ProcessResources;
+use super::store::{self, StoredProcess};
 use super::JobObject;
+use crate::commands::engine::EngineKind;
 use chrono::{DateTime, Utc};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use tokio::process::Child;
 
 /// Type of process being tracked
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessType {
-    AgentRun { agent_id: i64, agent_name: String },
-    ClaudeSession { session_id: String },
+    AgentRun {
+        agent_id: i64,
+        agent_name: String,
+    },
+    ClaudeSession {
+        session_id: String,
+    },
+    /// Codex / Gemini run; `session_id` is the backend channel ID until the CLI reports its own
+    EngineSession {
+        engine: EngineKind,
+        session_id: String,
+    },
+}
+
+impl ProcessType {
+    /// Engine whose CLI backs this process (agents run on Claude Code)
+    pub fn engine(&self) -> EngineKind {
+        match self {
+            ProcessType::AgentRun { .. } | ProcessType::ClaudeSession { .. } => EngineKind::Claude,
+            ProcessType::EngineSession { engine, .. } => *engine,
+        }
+    }
 }
 
 /// Information about a running agent process
@@ -22,6 +47,12 @@ pub struct ProcessInfo {
     pub project_path: String,
     pub task: String,
     pub model: String,
+    /// Latest sample of the resource monitor (not persisted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ProcessResources>,
+    /// Why the app is stopping this run (e.g. "inactivity_timeout")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
 }
 
 /// Information about a running process with handle
@@ -30,14 +61,15 @@ pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
     pub live_output: Arc<Mutex<String>>,
-    #[cfg(windows)]
-    pub job_object: Option<Arc<JobObject>>, // Job object for automatic cleanup on Windows
+    pub job_object: Option<Arc<JobObject>>, // Job Object (Windows) / process group (Unix) for cleanup
 }
 
 /// Registry for tracking active agent processes
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
     next_id: Arc<Mutex<i64>>, // Auto-incrementing ID for non-agent processes
+    store: Arc<Mutex<Option<Connection>>>, // agents.db mirror, see attach_store
+    orphans: Arc<Mutex<HashMap<i64, StoredProcess>>>, // Left running by a previous app instance
 }
 
 impl ProcessRegistry {
@@ -45,6 +77,71 @@ impl ProcessRegistry {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1000000)), // Start at high number to avoid conflicts
+            store: Arc::new(Mutex::new(None)),
+            orphans: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Mirror the registry into agents.db and adopt rows left by a previous run
+    ///
+    /// Rows whose process is still alive become orphans; the rest are dropped.
+    /// Returns the number of orphans found.
+    pub fn attach_store(&self, db_path: &Path) -> Result<usize, String> {
+        let conn = Connection::open(db_path)
+            .map_err(|e| format!("Failed to open process store: {}", e))?;
+        // The search indexer and usage index write to the same file through
+        // AgentDb; wait for their transactions instead of failing with SQLITE_BUSY
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|e| format!("Failed to configure process store: {}", e))?;
+        store::create_process_tables(&conn).map_err(|e| e.to_string())?;
+
+        let mut orphans = self.orphans.lock().map_err(|e| e.to_string())?;
+        let mut next_id = self.next_id.lock().map_err(|e| e.to_string())?;
+        for process in store::load_all(&conn)? {
+            let run_id = process.info.run_id;
+            // Never hand out a run_id that still has a row
+            *next_id = (*next_id).max(run_id + 1);
+
+            if process.is_alive() {
+                log::warn!(
+                    "Found orphaned {} process from a previous run: run_id={}, PID={}",
+                    process.info.process_type.engine(),
+                    run_id,
+                    process.info.pid
+                );
+                orphans.insert(run_id, process);
+            } else {
+                store::delete(&conn, run_id)?;
+            }
+        }
+
+        *self.store.lock().map_err(|e| e.to_string())? = Some(conn);
+        Ok(orphans.len())
+    }
+
+    fn persist(&self, info: &ProcessInfo) {
+        let Ok(guard) = self.store.lock() else {
+            return;
+        };
+        if let Some(conn) = guard.as_ref() {
+            let process = StoredProcess {
+                info: info.clone(),
+                pid_started: store::process_start_marker(info.pid),
+            };
+            if let Err(e) = store::save(conn, &process) {
+                log::warn!("{}", e);
+            }
+        }
+    }
+
+    fn forget(&self, run_id: i64) {
+        let Ok(guard) = self.store.lock() else {
+            return;
+        };
+        if let Some(conn) = guard.as_ref() {
+            if let Err(e) = store::delete(conn, run_id) {
+                log::warn!("{}", e);
+            }
         }
     }
 
@@ -80,6 +177,8 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            resources: None,
+            stop_reason: None,
         };
 
         self.register_process_internal(run_id, process_info, child)
@@ -97,14 +196,7 @@ impl ProcessRegistry {
         model: String,
     ) -> Result<i64, String> {
         // Call the new function with no pre-created job object (will create one here)
-        #[cfg(windows)]
-        {
-            self.register_claude_session_with_job(session_id, pid, project_path, task, model, None)
-        }
-        #[cfg(not(windows))]
-        {
-            self.register_claude_session_with_job(session_id, pid, project_path, task, model, None)
-        }
+        self.register_claude_session_with_job(session_id, pid, project_path, task, model, None)
     }
 
     /// Register a new Claude session with an optional pre-created Job Object
@@ -115,7 +207,6 @@ impl ProcessRegistry {
     ///
     /// If no Job Object is provided, one will be created here (legacy behavior, but may miss
     /// child processes that were already started).
-    #[cfg(windows)]
     pub fn register_claude_session_with_job(
         &self,
         session_id: String,
@@ -124,17 +215,42 @@ impl ProcessRegistry {
         task: String,
         model: String,
         pre_created_job: Option<Arc<JobObject>>,
+    ) -> Result<i64, String> {
+        self.register_session(
+            ProcessType::ClaudeSession { session_id },
+            pid,
+            project_path,
+            task,
+            model,
+            pre_created_job,
+        )
+    }
+
+    /// Register a session run of any engine
+    ///
+    /// Codex / Gemini runners keep owning their child; sharing the Job Object lets
+    /// kill_process, the resource monitor and orphan recovery cover them too.
+    pub fn register_session(
+        &self,
+        process_type: ProcessType,
+        pid: u32,
+        project_path: String,
+        task: String,
+        model: String,
+        pre_created_job: Option<Arc<JobObject>>,
     ) -> Result<i64, String> {
         let run_id = self.generate_id()?;
 
         let process_info = ProcessInfo {
             run_id,
-            process_type: ProcessType::ClaudeSession { session_id },
+            process_type,
             pid,
             started_at: Utc::now(),
             project_path,
             task,
             model,
+            resources: None,
+            stop_reason: None,
         };
 
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
@@ -182,41 +298,7 @@ impl ProcessRegistry {
             job_object,
         };
 
-        processes.insert(run_id, process_handle);
-        Ok(run_id)
-    }
-
-    /// Register a new Claude session with an optional pre-created Job Object (non-Windows version)
-    #[cfg(not(windows))]
-    pub fn register_claude_session_with_job(
-        &self,
-        session_id: String,
-        pid: u32,
-        project_path: String,
-        task: String,
-        model: String,
-        _pre_created_job: Option<()>,
-    ) -> Result<i64, String> {
-        let run_id = self.generate_id()?;
-
-        let process_info = ProcessInfo {
-            run_id,
-            process_type: ProcessType::ClaudeSession { session_id },
-            pid,
-            started_at: Utc::now(),
-            project_path,
-            task,
-            model,
-        };
-
-        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
-
-        let process_handle = ProcessHandle {
-            info: process_info,
-            child: Arc::new(Mutex::new(None)),
-            live_output: Arc::new(Mutex::new(String::new())),
-        };
-
+        self.persist(&process_handle.info);
         processes.insert(run_id, process_handle);
         Ok(run_id)
     }
@@ -231,8 +313,7 @@ impl ProcessRegistry {
     ) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
 
-        // Create Job Object on Windows for automatic process cleanup
-        #[cfg(windows)]
+        // Create Job Object (process group on Unix) for automatic process cleanup
         let job_object = {
             let pid = process_info.pid;
             match JobObject::create() {
@@ -263,10 +344,10 @@ impl ProcessRegistry {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
             live_output: Arc::new(Mutex::new(String::new())),
-            #[cfg(windows)]
             job_object,
         };
 
+        self.persist(&process_handle.info);
         processes.insert(run_id, process_handle);
         Ok(())
     }
@@ -298,16 +379,34 @@ impl ProcessRegistry {
             .map(|handle| handle.info.clone()))
     }
 
+    /// Switch a session run over to the ID the CLI reported (persisted for orphan recovery)
+    pub fn set_session_id(&self, run_id: i64, session_id: &str) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            match &mut handle.info.process_type {
+                ProcessType::ClaudeSession { session_id: sid }
+                | ProcessType::EngineSession {
+                    session_id: sid, ..
+                } => {
+                    *sid = session_id.to_string();
+                }
+                ProcessType::AgentRun { .. } => return Ok(()),
+            }
+            self.persist(&handle.info);
+        }
+        Ok(())
+    }
+
     /// Unregister a process (called when it completes)
     #[allow(dead_code)]
     pub fn unregister_process(&self, run_id: i64) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
         processes.remove(&run_id);
+        self.forget(run_id);
         Ok(())
     }
 
     /// Get all running processes
-    #[allow(dead_code)]
     pub fn get_running_processes(&self) -> Result<Vec<ProcessInfo>, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         Ok(processes
@@ -336,15 +435,71 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
+    /// Processes left running by a previous app instance that are still alive
+    pub fn get_orphan_processes(&self) -> Result<Vec<ProcessInfo>, String> {
+        let mut orphans = self.orphans.lock().map_err(|e| e.to_string())?;
+        let exited: Vec<i64> = orphans
+            .iter()
+            .filter(|(_, process)| !process.is_alive())
+            .map(|(run_id, _)| *run_id)
+            .collect();
+        for run_id in exited {
+            orphans.remove(&run_id);
+            self.forget(run_id);
+        }
+
+        let mut infos: Vec<ProcessInfo> = orphans.values().map(|p| p.info.clone()).collect();
+        infos.sort_by_key(|info| info.started_at);
+        Ok(infos)
+    }
+
+    /// Look up a live orphan; exited ones are forgotten on the way
+    pub fn get_orphan_process(&self, run_id: i64) -> Result<Option<ProcessInfo>, String> {
+        let mut orphans = self.orphans.lock().map_err(|e| e.to_string())?;
+        match orphans.get(&run_id) {
+            Some(process) if process.is_alive() => Ok(Some(process.info.clone())),
+            Some(_) => {
+                orphans.remove(&run_id);
+                self.forget(run_id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Kill an orphan and its children (MCP servers etc.)
+    ///
+    /// Orphans have no Child handle or Job Object, so this goes by PID.
+    pub fn kill_orphan_process(&self, run_id: i64) -> Result<bool, String> {
+        let Some(info) = self.get_orphan_process(run_id)? else {
+            return Ok(false);
+        };
+
+        log::info!("Killing orphaned process {} (PID: {})", run_id, info.pid);
+        let _ = self.kill_child_processes(info.pid);
+        crate::commands::claude::kill_process_tree(info.pid)?;
+
+        self.orphans
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(&run_id);
+        self.forget(run_id);
+        Ok(true)
+    }
+
     /// Kill a running process with proper cleanup
     pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
         use log::{error, info, warn};
 
         // First check if the process exists and get its PID
-        let (pid, child_arc) = {
+        let (pid, child_arc, job_object) = {
             let processes = self.processes.lock().map_err(|e| e.to_string())?;
             if let Some(handle) = processes.get(&run_id) {
-                (handle.info.pid, handle.child.clone())
+                (
+                    handle.info.pid,
+                    handle.child.clone(),
+                    handle.job_object.clone(),
+                )
             } else {
                 warn!("Process {} not found in registry", run_id);
                 return Ok(false); // Process not found
@@ -356,15 +511,35 @@ impl ProcessRegistry {
             run_id, pid
         );
 
+        // Terminate the whole tree through the Job Object / process group
+        // (SIGTERM, then SIGKILL after a grace period on Unix)
+        let tree_result = match job_object {
+            Some(job) => Some(job.terminate_all_async(1).await),
+            None => None,
+        };
+        let tree_terminated = match tree_result {
+            Some(Ok(())) => {
+                info!("Terminated process tree of {} (PID: {})", run_id, pid);
+                true
+            }
+            Some(Err(e)) => {
+                warn!("Failed to terminate process tree of {}: {}", run_id, e);
+                false
+            }
+            None => false,
+        };
+
         // IMPORTANT: First kill all child processes to prevent orphans
-        info!(
-            "Killing child processes of PID {} before killing parent",
-            pid
-        );
-        let _ = self.kill_child_processes(pid);
+        if !tree_terminated {
+            info!(
+                "Killing child processes of PID {} before killing parent",
+                pid
+            );
+            let _ = self.kill_child_processes(pid);
+        }
 
         // Send kill signal to the process
-        let kill_sent = {
+        let kill_sent = tree_terminated || {
             let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
             if let Some(child) = child_guard.as_mut() {
                 match child.start_kill() {
@@ -682,6 +857,31 @@ impl ProcessRegistry {
         Ok(())
     }
 
+    /// Store the latest resource sample of a process
+    pub fn update_resources(
+        &self,
+        run_id: i64,
+        resources: Option<ProcessResources>,
+    ) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.info.resources = resources;
+        }
+        Ok(())
+    }
+
+    /// Record why the run with this PID is being stopped, before it is killed
+    pub fn set_stop_reason_by_pid(&self, pid: u32, reason: &str) -> Result<bool, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        match processes.values_mut().find(|handle| handle.info.pid == pid) {
+            Some(handle) => {
+                handle.info.stop_reason = Some(reason.to_string());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     /// Get live output for a process
     pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
@@ -786,9 +986,15 @@ impl ProcessRegistry {
         // Small delay to let child processes terminate
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-        // Second pass: Kill main processes
-        for (run_id, _pid) in process_info {
-            match self.kill_process(run_id).await {
+        // Second pass: Kill main processes, in parallel so the grace periods overlap
+        let results = futures::future::join_all(
+            process_info
+                .iter()
+                .map(|(run_id, _pid)| async move { (*run_id, self.kill_process(*run_id).await) }),
+        )
+        .await;
+        for (run_id, result) in results {
+            match result {
                 Ok(true) => {
                     info!("Successfully killed process {}", run_id);
                     killed_count += 1;
@@ -0,0 +1,256 @@
+//! Resource usage of a registered process and its descendants
+//!
+//! Sampled from `/proc` on Linux: CPU (utime + stime deltas between two
+//! samples), RSS, descendant count and MCP server processes. Other platforms
+//! produce no samples.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// An MCP server started by the CLI (top-most process of its subtree)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerProcess {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// Latest resource sample of a process tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessResources {
+    /// CPU of the whole tree since the previous sample (100 = one core)
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// Descendants of the CLI process, MCP servers included
+    pub child_processes: usize,
+    pub mcp_servers: Vec<McpServerProcess>,
+    pub sampled_at: DateTime<Utc>,
+}
+
+/// One row of the process table
+#[derive(Debug, Clone)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct ProcEntry {
+    ppid: u32,
+    cpu_ticks: u64,
+    rss_bytes: u64,
+}
+
+/// Snapshot of all processes, read once per sampling round
+pub struct ProcTable {
+    entries: HashMap<u32, ProcEntry>,
+    children: HashMap<u32, Vec<u32>>,
+}
+
+impl ProcTable {
+    #[cfg(target_os = "linux")]
+    pub fn read() -> Self {
+        let mut entries = HashMap::new();
+        for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            // Processes may exit between read_dir and read
+            if let Some(proc_entry) = std::fs::read_to_string(entry.path().join("stat"))
+                .ok()
+                .and_then(|stat| linux::parse_stat(&stat))
+            {
+                entries.insert(pid, proc_entry);
+            }
+        }
+        Self::from_entries(entries)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn read() -> Self {
+        Self::from_entries(HashMap::new())
+    }
+
+    fn from_entries(entries: HashMap<u32, ProcEntry>) -> Self {
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        for (pid, entry) in &entries {
+            children.entry(entry.ppid).or_default().push(*pid);
+        }
+        Self { entries, children }
+    }
+
+    /// `root` and all of its descendants, root first
+    fn tree(&self, root: u32) -> Vec<u32> {
+        let mut tree = vec![root];
+        let mut i = 0;
+        while i < tree.len() {
+            if let Some(children) = self.children.get(&tree[i]) {
+                tree.extend(children);
+            }
+            i += 1;
+        }
+        tree
+    }
+}
+
+/// Keeps the previous CPU reading of each root so percentages can be derived
+#[derive(Default)]
+pub struct ResourceSampler {
+    last_cpu: HashMap<u32, (u64, Instant)>,
+}
+
+impl ResourceSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample `root_pid` and its descendants; None when it is not in `table`
+    pub fn sample(&mut self, table: &ProcTable, root_pid: u32) -> Option<ProcessResources> {
+        if !table.entries.contains_key(&root_pid) {
+            self.last_cpu.remove(&root_pid);
+            return None;
+        }
+
+        let tree = table.tree(root_pid);
+        let (cpu_ticks, rss_bytes) = tree
+            .iter()
+            .filter_map(|pid| table.entries.get(pid))
+            .fold((0, 0), |(ticks, rss), e| {
+                (ticks + e.cpu_ticks, rss + e.rss_bytes)
+            });
+
+        // The first sample of a tree has nothing to compare against
+        let now = Instant::now();
+        let cpu_percent = match self.last_cpu.insert(root_pid, (cpu_ticks, now)) {
+            Some((last_ticks, last_at)) => {
+                let elapsed = now.duration_since(last_at).as_secs_f64();
+                if elapsed > 0.0 {
+                    let ticks = cpu_ticks.saturating_sub(last_ticks) as f64;
+                    ticks / clock_ticks_per_second() / elapsed * 100.0
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+
+        Some(ProcessResources {
+            cpu_percent,
+            rss_bytes,
+            child_processes: tree.len() - 1,
+            mcp_servers: mcp_servers(table, &tree[1..]),
+            sampled_at: Utc::now(),
+        })
+    }
+
+    /// Drop readings of roots that are no longer sampled
+    pub fn retain(&mut self, root_pids: &[u32]) {
+        self.last_cpu.retain(|pid, _| root_pids.contains(pid));
+    }
+}
+
+/// Descendants that look like MCP servers, without their own subprocesses
+/// (e.g. the `node` started by an `npx @modelcontextprotocol/...` wrapper)
+fn mcp_servers(table: &ProcTable, descendants: &[u32]) -> Vec<McpServerProcess> {
+    let commands: HashMap<u32, String> = descendants
+        .iter()
+        .filter_map(|pid| command_line(*pid).map(|cmd| (*pid, cmd)))
+        .collect();
+    let is_mcp = |pid: &u32| {
+        commands
+            .get(pid)
+            .is_some_and(|cmd| cmd.to_lowercase().contains("mcp"))
+    };
+
+    let mut servers: Vec<McpServerProcess> = descendants
+        .iter()
+        .filter(|pid| is_mcp(pid))
+        .filter(|pid| !table.entries.get(pid).is_some_and(|e| is_mcp(&e.ppid)))
+        .map(|pid| McpServerProcess {
+            pid: *pid,
+            command: commands[pid].clone(),
+        })
+        .collect();
+    servers.sort_by_key(|s| s.pid);
+    servers
+}
+
+#[cfg(target_os = "linux")]
+fn command_line(pid: u32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let args: Vec<String> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn command_line(_pid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn clock_ticks_per_second() -> f64 {
+    *linux::CLOCK_TICKS
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clock_ticks_per_second() -> f64 {
+    100.0
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::ProcEntry;
+    use once_cell::sync::Lazy;
+
+    pub(super) static CLOCK_TICKS: Lazy<f64> =
+        Lazy::new(|| getconf("CLK_TCK").unwrap_or(100) as f64);
+    pub(super) static PAGE_SIZE: Lazy<u64> = Lazy::new(|| getconf("PAGESIZE").unwrap_or(4096));
+
+    fn getconf(name: &str) -> Option<u64> {
+        let output = std::process::Command::new("getconf")
+            .arg(name)
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    /// Parse `/proc/<pid>/stat`
+    ///
+    /// The command name is in parentheses and may itself contain spaces or
+    /// parentheses, so fields are counted from the last `)`.
+    pub(super) fn parse_stat(stat: &str) -> Option<ProcEntry> {
+        let rest = &stat[stat.rfind(')')? + 1..];
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        // fields[0] is field 3 (state) of proc(5)
+        let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+        Some(ProcEntry {
+            ppid: field(4)? as u32,
+            cpu_ticks: field(14)? + field(15)?,
+            rss_bytes: field(24)? * *PAGE_SIZE,
+        })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stat_with_parentheses_in_name() {
+        let stat = "4242 (node (mcp) x) S 4200 4242 4242 0 -1 4194560 1 0 0 0 \
+                    150 50 0 0 20 0 11 0 100 1000 256 18446744073709551615";
+        let entry = linux::parse_stat(stat).unwrap();
+        assert_eq!(entry.ppid, 4200);
+        assert_eq!(entry.cpu_ticks, 200);
+        assert_eq!(entry.rss_bytes, 256 * *linux::PAGE_SIZE);
+    }
+
+    #[test]
+    fn samples_current_process() {
+        let table = ProcTable::read();
+        let mut sampler = ResourceSampler::new();
+        let resources = sampler.sample(&table, std::process::id()).unwrap();
+        assert!(resources.rss_bytes > 0);
+        assert_eq!(resources.cpu_percent, 0.0);
+    }
+}
@@ -0,0 +1,244 @@
+//! Persisted process registry (agents.db → `process_registry`)
+//!
+//! Every registered process is mirrored into SQLite together with the OS start
+//! time of its PID. If the app crashes, the rows outlive it; on the next launch
+//! they are reconciled against the live process table. Rows whose PID is gone,
+//! or now belongs to a different process, are dropped; the rest are orphans.
+
+use rusqlite::{params, Connection, Result as SqliteResult};
+
+use super::registry::{ProcessInfo, ProcessType};
+
+pub fn create_process_tables(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS process_registry (
+            run_id INTEGER PRIMARY KEY,
+            pid INTEGER NOT NULL,
+            pid_started TEXT,
+            engine TEXT NOT NULL,
+            session_id TEXT,
+            process_type TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            task TEXT NOT NULL,
+            model TEXT NOT NULL,
+            started_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A persisted row: the process info plus the OS start time of its PID
+pub struct StoredProcess {
+    pub info: ProcessInfo,
+    pub pid_started: Option<String>,
+}
+
+impl StoredProcess {
+    /// Whether the PID is still running *and* still the process we spawned
+    pub fn is_alive(&self) -> bool {
+        match (process_start_marker(self.info.pid), &self.pid_started) {
+            (Some(current), Some(recorded)) => &current == recorded,
+            // Without a recorded start time the PID can't be told apart from a recycled one
+            (Some(_), None) | (None, _) => false,
+        }
+    }
+}
+
+pub fn save(conn: &Connection, process: &StoredProcess) -> Result<(), String> {
+    let info = &process.info;
+    let session_id = match &info.process_type {
+        ProcessType::ClaudeSession { session_id }
+        | ProcessType::EngineSession { session_id, .. } => Some(session_id.as_str()),
+        ProcessType::AgentRun { .. } => None,
+    };
+    let process_type = serde_json::to_string(&info.process_type).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO process_registry
+            (run_id, pid, pid_started, engine, session_id, process_type,
+             project_path, task, model, started_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            info.run_id,
+            info.pid,
+            process.pid_started,
+            info.process_type.engine().as_str(),
+            session_id,
+            process_type,
+            info.project_path,
+            info.task,
+            info.model,
+            info.started_at.to_rfc3339(),
+        ],
+    )
+    .map_err(|e| format!("Failed to persist process {}: {}", info.run_id, e))?;
+    Ok(())
+}
+
+pub fn delete(conn: &Connection, run_id: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM process_registry WHERE run_id = ?1",
+        params![run_id],
+    )
+    .map_err(|e| format!("Failed to forget process {}: {}", run_id, e))?;
+    Ok(())
+}
+
+pub fn load_all(conn: &Connection) -> Result<Vec<StoredProcess>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT run_id, pid, pid_started, process_type, project_path, task, model, started_at
+             FROM process_registry ORDER BY run_id",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let process_type: String = row.get(3)?;
+            let started_at: String = row.get(7)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                process_type,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, String>(6)?,
+                started_at,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut processes = Vec::new();
+    for row in rows.flatten() {
+        let (run_id, pid, pid_started, process_type, project_path, task, model, started_at) = row;
+        let Ok(process_type) = serde_json::from_str::<ProcessType>(&process_type) else {
+            log::warn!("[ProcessStore] Skipping row {} with unknown type", run_id);
+            continue;
+        };
+        let started_at = chrono::DateTime::parse_from_rfc3339(&started_at)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|_| chrono::Utc::now());
+
+        processes.push(StoredProcess {
+            info: ProcessInfo {
+                run_id,
+                process_type,
+                pid,
+                started_at,
+                project_path,
+                task,
+                model,
+                resources: None,
+                stop_reason: None,
+            },
+            pid_started,
+        });
+    }
+    Ok(processes)
+}
+
+/// OS-reported start time of `pid`, or None when no such process is running
+///
+/// Stored next to the PID so a recycled PID is not mistaken for an orphan.
+#[cfg(unix)]
+pub fn process_start_marker(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    let marker = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!marker.is_empty()).then_some(marker)
+}
+
+/// OS-reported start time of `pid`, or None when no such process is running
+///
+/// Creation FILETIME from GetProcessTimes (`wmic` is gone from Windows 11 24H2).
+#[cfg(windows)]
+pub fn process_start_marker(pid: u32) -> Option<String> {
+    use windows::Win32::Foundation::{CloseHandle, FILETIME, STILL_ACTIVE};
+    use windows::Win32::System::Threading::{
+        GetExitCodeProcess, GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+        // An exited process keeps its times while someone holds a handle to it
+        let mut exit_code = 0u32;
+        let running = GetExitCodeProcess(handle, &mut exit_code).is_ok()
+            && exit_code == STILL_ACTIVE.0 as u32;
+
+        let (mut created, mut exited, mut kernel, mut user) = (
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+        );
+        let times = GetProcessTimes(handle, &mut created, &mut exited, &mut kernel, &mut user);
+        let _ = CloseHandle(handle);
+
+        if !running || times.is_err() {
+            return None;
+        }
+        let ticks = ((created.dwHighDateTime as u64) << 32) | created.dwLowDateTime as u64;
+        Some(ticks.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_liveness() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_process_tables(&conn).unwrap();
+
+        let pid = std::process::id();
+        let process = StoredProcess {
+            info: ProcessInfo {
+                run_id: 1000001,
+                process_type: ProcessType::ClaudeSession {
+                    session_id: "abc".to_string(),
+                },
+                pid,
+                started_at: chrono::Utc::now(),
+                project_path: "/work/app".to_string(),
+                task: "fix tests".to_string(),
+                model: "sonnet".to_string(),
+                resources: None,
+                stop_reason: None,
+            },
+            pid_started: process_start_marker(pid),
+        };
+        save(&conn, &process).unwrap();
+
+        let loaded = load_all(&conn).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].info.pid, pid);
+        assert!(loaded[0].is_alive());
+
+        // Same PID, different start time: the PID was recycled
+        let recycled = StoredProcess {
+            pid_started: Some("Thu Jan  1 00:00:00 1970".to_string()),
+            ..loaded.into_iter().next().unwrap()
+        };
+        assert!(!recycled.is_alive());
+
+        // No recorded start time: can't prove the PID is still ours
+        let unmarked = StoredProcess {
+            pid_started: None,
+            ..recycled
+        };
+        assert!(!unmarked.is_alive());
+
+        delete(&conn, 1000001).unwrap();
+        assert!(load_all(&conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,658 @@
+//! End-to-end engine tests against `fake-agent-cli`
+//!
+//! Every run goes through the real engine runners (`spawn_watched`,
+//! `EngineRunner::cancel`) on a `tauri::test` mock app that manages the same
+//! state as the desktop app: binary discovery (`CLAUDE_PATH`, `CODEX_PATH`,
+//! `GEMINI_CLI_PATH`), command builders, stdin prompt hand-off, line parsers,
+//! the emitted events, `EngineProcessState` / Job Objects, `ProcessRegistry`
+//! and usage recording into `agents.db`; only the CLI on the other end is
+//! scripted. See `tests/support/fake_agent_cli.rs` for the prompt markers it
+//! understands.
+//!
+//! Every test works in its own `TestEnv`: a mock app with its own app data
+//! dir, and a temp dir holding the project and `agents.db`. The only
+//! process-wide state is HOME and the discovery variables, set once before
+//! the first spawn since the runners build their commands themselves.
+//!
+//! Run with `cargo test --features fake-cli --test engine_harness`.
+
+#![cfg(all(unix, feature = "fake-cli"))]
+// The harness compiles the full command layer but only drives a subset of it
+#![allow(dead_code)]
+
+#[path = "../src/claude_binary.rs"]
+mod claude_binary;
+#[path = "../src/commands/mod.rs"]
+mod commands;
+#[path = "../src/process/mod.rs"]
+mod process;
+#[path = "../src/utils/mod.rs"]
+mod utils;
+
+#[path = "../src/claude_mcp.rs"]
+mod claude_mcp;
+#[path = "../src/codex_mcp.rs"]
+mod codex_mcp;
+#[path = "../src/gemini_mcp.rs"]
+mod gemini_mcp;
+#[path = "../src/mcp/mod.rs"]
+mod mcp;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use rusqlite::Connection;
+use serde_json::Value;
+use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+use tauri::{App, AppHandle, Listener, Manager};
+
+use commands::claude::{parse_claude_line, ClaudeProcessState};
+use commands::codex::CodexProcessState;
+use commands::engine::{
+    runner_for, spawn_watched, unified_message, EngineKind, EngineProcessState, EngineRequest,
+};
+use commands::gemini::GeminiProcessState;
+use commands::pricing::{self, TokenCounts};
+use commands::storage::{open_database, AgentDb};
+use commands::usage_recorder::recorded_session_usage;
+use process::{ProcessRegistry, ProcessRegistryState, ProcessType};
+
+const RUN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Session IDs baked into the fixtures; the fake CLI must replace them
+const CLAUDE_FIXTURE_SESSION: &str = "7f1c2a9e-3b4d-4e8a-9c1f-0a2b3c4d5e6f";
+const CODEX_FIXTURE_THREAD: &str = "0199a213-81c0-7800-8aa1-bbab2a035a53";
+
+/// Events the runners emit under a fixed name (per-session ones are collected by `spawn_watched`)
+const RECORDED_EVENTS: &[&str] = &[
+    "claude-session-state",
+    "claude-error",
+    "claude-cancelled",
+    "codex-session-init",
+    "codex-cli-session-id",
+    "gemini-session-init",
+    "gemini-cli-session-id",
+    "gemini-error",
+    "gemini-cancelled",
+];
+
+/// Symlink the fake CLI as claude/codex/gemini, point discovery at it and
+/// keep ~/.claude, ~/.codex, ~/.gemini and ~/.anycode out of the developer's home
+fn install_fake_cli() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let bin = tmp.join("engine-harness-bin");
+        let home = tmp.join("engine-harness-home");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::env::set_var("HOME", &home);
+
+        let fake = env!("CARGO_BIN_EXE_fake-agent-cli");
+        for (name, env_var) in [
+            ("claude", "CLAUDE_PATH"),
+            ("codex", "CODEX_PATH"),
+            ("gemini", "GEMINI_CLI_PATH"),
+        ] {
+            let link = bin.join(name);
+            let _ = std::fs::remove_file(&link);
+            std::os::unix::fs::symlink(fake, &link).unwrap();
+            std::env::set_var(env_var, &link);
+        }
+    });
+}
+
+/// One test's app: the project, `agents.db` and the state the runners expect
+struct TestEnv {
+    dir: tempfile::TempDir,
+    app: App<MockRuntime>,
+    events: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl TestEnv {
+    fn new() -> Self {
+        install_fake_cli();
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("project")).unwrap();
+
+        // Binary discovery caches the CLI path in the app data dir; keep it per test
+        let mut context = mock_context(noop_assets());
+        context.config_mut().identifier =
+            format!("any-code.engine-harness.{}", uuid::Uuid::new_v4().simple());
+        let app = mock_builder().build(context).unwrap();
+
+        let db_path = dir.path().join("agents.db");
+        app.manage(AgentDb(Mutex::new(open_database(&db_path).unwrap())));
+        let registry = ProcessRegistryState::default();
+        registry.0.attach_store(&db_path).unwrap();
+        app.manage(registry);
+        app.manage(ClaudeProcessState::default());
+        app.manage(CodexProcessState::default());
+        app.manage(GeminiProcessState::default());
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        for name in RECORDED_EVENTS {
+            let events = events.clone();
+            app.listen_any(*name, move |event| {
+                let payload = serde_json::from_str(event.payload()).unwrap_or(Value::Null);
+                events.lock().unwrap().push((name.to_string(), payload));
+            });
+        }
+
+        Self { dir, app, events }
+    }
+
+    fn handle(&self) -> &AppHandle<MockRuntime> {
+        self.app.handle()
+    }
+
+    fn project(&self) -> String {
+        self.dir
+            .path()
+            .join("project")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn open_db(&self) -> Connection {
+        open_database(&self.dir.path().join("agents.db")).unwrap()
+    }
+
+    fn request(&self, prompt: &str) -> EngineRequest {
+        EngineRequest {
+            project_path: self.project(),
+            prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Start (or resume) a run through the engine's runner and wait for it to end
+    async fn run(&self, kind: EngineKind, request: EngineRequest, resume: Option<&str>) -> Run {
+        let mut watch = spawn_watched(self.handle(), kind, request, resume, true)
+            .await
+            .unwrap();
+        let success = tokio::time::timeout(RUN_TIMEOUT, watch.wait())
+            .await
+            .expect("engine run timed out");
+        Run {
+            kind,
+            success,
+            session_id: watch.session_id(),
+            lines: watch.take_output(),
+        }
+    }
+
+    /// What the fake CLI received (args + prompt) on its latest invocation
+    fn invocation(&self) -> Value {
+        let log = std::fs::read_to_string(self.dir.path().join("project/.fake-agent-cli.jsonl"))
+            .expect("fake CLI did not report its invocation");
+        serde_json::from_str(log.lines().last().unwrap()).unwrap()
+    }
+
+    fn args(&self) -> Vec<String> {
+        serde_json::from_value(self.invocation()["args"].clone()).unwrap()
+    }
+
+    /// Payloads of one recorded event, in emit order
+    fn events(&self, name: &str) -> Vec<Value> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(event, _)| event == name)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+
+    /// Thread / session ID the CLI reported for a Codex or Gemini backend session
+    fn cli_session_id(&self, kind: EngineKind, backend_session_id: &str) -> Option<String> {
+        self.events(&format!("{}-cli-session-id", kind))
+            .into_iter()
+            .find(|e| e["backend_session_id"] == backend_session_id)
+            .and_then(|e| e["cli_session_id"].as_str().map(String::from))
+    }
+
+    fn registry(&self) -> Arc<ProcessRegistry> {
+        self.app.state::<ProcessRegistryState>().0.clone()
+    }
+
+    fn process_state(&self, kind: EngineKind) -> &EngineProcessState {
+        match kind {
+            EngineKind::Claude => &self.app.state::<ClaudeProcessState>().inner().0,
+            EngineKind::Codex => &self.app.state::<CodexProcessState>().inner().0,
+            EngineKind::Gemini => &self.app.state::<GeminiProcessState>().inner().0,
+        }
+    }
+
+    /// Runs tracked in the engine's `EngineProcessState`: (key, pid, has a Job Object)
+    async fn tracked(&self, kind: EngineKind) -> Vec<(String, u32, bool)> {
+        self.process_state(kind)
+            .processes
+            .lock()
+            .await
+            .iter()
+            .map(|(key, handle)| (key.clone(), handle.pid, handle.job_object.is_some()))
+            .collect()
+    }
+}
+
+struct Run {
+    kind: EngineKind,
+    success: bool,
+    /// CLI session ID (Claude) or backend session ID (Codex / Gemini)
+    session_id: Option<String>,
+    lines: Vec<String>,
+}
+
+impl Run {
+    /// Output as unified messages, as the app and any-code-cli see them
+    fn messages(&self) -> Vec<Value> {
+        self.lines
+            .iter()
+            .filter_map(|l| unified_message(self.kind, l))
+            .collect()
+    }
+
+    /// Usage of the last result that carries one (Gemini closes with a bare status result)
+    fn result_usage(&self) -> Value {
+        self.messages()
+            .into_iter()
+            .rev()
+            .find(|m| m["type"] == "result" && m.get("usage").is_some())
+            .map(|m| m["usage"].clone())
+            .expect("no result message with usage")
+    }
+}
+
+/// Whether `pid` is gone (or only a zombie waiting to be reaped)
+fn process_exited(pid: u32) -> bool {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "stat=", "-p", &pid.to_string()])
+        .output()
+        .unwrap();
+    let stat = String::from_utf8_lossy(&output.stdout);
+    stat.trim().is_empty() || stat.trim_start().starts_with('Z')
+}
+
+/// Poll `check` until it holds; the runners clean up in background tasks
+async fn eventually<F, Fut>(what: &str, mut check: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    tokio::time::timeout(RUN_TIMEOUT, async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting until {}", what));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn claude_new_session_streams_session_id_and_usage() {
+    let env = TestEnv::new();
+    let run = env
+        .run(
+            EngineKind::Claude,
+            env.request("Summarize the README"),
+            None,
+        )
+        .await;
+
+    assert!(run.success);
+    assert_eq!(env.invocation()["prompt"], "Summarize the README");
+
+    let session_id = run.session_id.clone().unwrap();
+    assert_ne!(session_id, CLAUDE_FIXTURE_SESSION);
+    assert!(run
+        .messages()
+        .iter()
+        .filter_map(|m| m["session_id"].as_str())
+        .all(|sid| sid == session_id));
+
+    let event = parse_claude_line(run.lines.last().unwrap()).unwrap();
+    let usage = event.usage().copied().unwrap();
+    assert_eq!(usage.input_tokens, 9);
+    assert_eq!(usage.output_tokens, 240);
+    assert_eq!(usage.cache_read_input_tokens, 25500);
+
+    // Registered on the init message, reported stopped on exit
+    let states = env.events("claude-session-state");
+    assert_eq!(states.len(), 2);
+    assert_eq!(states[0]["status"], "started");
+    assert_eq!(states[0]["session_id"], session_id.as_str());
+    assert!(states[0]["pid"].as_u64().is_some());
+    assert_eq!(states[1]["status"], "stopped");
+    assert_eq!(states[1]["success"], true);
+
+    // The exited run is dropped from the state and the registry
+    assert!(env.tracked(EngineKind::Claude).await.is_empty());
+    let registry = env.registry();
+    eventually("the run is unregistered", || async {
+        registry.get_running_processes().unwrap().is_empty()
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn claude_resume_keeps_the_session_id() {
+    let env = TestEnv::new();
+    let run = env
+        .run(
+            EngineKind::Claude,
+            env.request("Keep going"),
+            Some("resume-me-123"),
+        )
+        .await;
+
+    assert!(run.success);
+    let args = env.args();
+    let pos = args.iter().position(|a| a == "--resume").unwrap();
+    assert_eq!(args[pos + 1], "resume-me-123");
+    assert_eq!(run.session_id.as_deref(), Some("resume-me-123"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn codex_run_maps_thread_id_and_usage() {
+    let env = TestEnv::new();
+    let started = env
+        .run(EngineKind::Codex, env.request("Summarize the README"), None)
+        .await;
+
+    assert!(started.success);
+    let args = env.args();
+    assert_eq!(args.first().map(String::as_str), Some("exec"));
+    assert!(args.iter().any(|a| a == "--json"));
+    assert_eq!(args.last().map(String::as_str), Some("-"));
+    assert_eq!(env.invocation()["prompt"], "Summarize the README");
+
+    // The backend channel is announced first, the thread ID once the CLI reports it
+    let backend_id = started.session_id.clone().unwrap();
+    assert!(backend_id.starts_with("codex-"));
+    assert_eq!(
+        env.events("codex-session-init")[0]["session_id"],
+        backend_id.as_str()
+    );
+    let thread_id = env.cli_session_id(EngineKind::Codex, &backend_id).unwrap();
+    assert_ne!(thread_id, CODEX_FIXTURE_THREAD);
+
+    let usage = started.result_usage();
+    assert_eq!(usage["input_tokens"], 24763);
+    assert_eq!(usage["cached_input_tokens"], 24448);
+    assert_eq!(usage["output_tokens"], 122);
+
+    let resumed = env
+        .run(
+            EngineKind::Codex,
+            env.request("Next step"),
+            Some(&thread_id),
+        )
+        .await;
+    assert!(resumed.success);
+    let args = env.args();
+    let pos = args.iter().position(|a| a == "resume").unwrap();
+    assert_eq!(args[pos + 1], thread_id);
+    let resumed_backend_id = resumed.session_id.unwrap();
+    assert_ne!(resumed_backend_id, backend_id);
+    assert_eq!(
+        env.cli_session_id(EngineKind::Codex, &resumed_backend_id),
+        Some(thread_id)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gemini_run_streams_unified_messages() {
+    let env = TestEnv::new();
+    let run = env
+        .run(
+            EngineKind::Gemini,
+            env.request("Summarize the README"),
+            None,
+        )
+        .await;
+
+    assert!(run.success);
+    let args = env.args();
+    let pos = args.iter().position(|a| a == "--output-format").unwrap();
+    assert_eq!(args[pos + 1], "stream-json");
+
+    let backend_id = run.session_id.clone().unwrap();
+    assert!(backend_id.starts_with("gemini-"));
+    let cli_session_id = env.cli_session_id(EngineKind::Gemini, &backend_id).unwrap();
+    assert_ne!(cli_session_id, backend_id);
+
+    let messages = run.messages();
+    assert_eq!(messages[0]["session_id"], backend_id.as_str());
+    assert!(messages.iter().any(|m| m["type"] == "assistant"));
+    let usage = run.result_usage();
+    assert_eq!(usage["input_tokens"], 7990);
+    assert_eq!(usage["output_tokens"], 133);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crashed_cli_exits_non_zero_after_init() {
+    let env = TestEnv::new();
+    let run = env
+        .run(EngineKind::Claude, env.request("[fake:crash] boom"), None)
+        .await;
+
+    assert!(!run.success);
+    assert_eq!(run.lines.len(), 1);
+    assert!(run.session_id.is_some());
+    assert!(env.events("claude-error").iter().any(|e| e["payload"]
+        .as_str()
+        .unwrap_or_default()
+        .contains("simulated crash")));
+
+    let stopped = env.events("claude-session-state").pop().unwrap();
+    assert_eq!(stopped["status"], "stopped");
+    assert_eq!(stopped["success"], false);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hung_session_is_registered_and_cancelled_through_the_registry() {
+    let env = TestEnv::new();
+    let mut watch = spawn_watched(
+        env.handle(),
+        EngineKind::Claude,
+        env.request("[fake:hang] wait"),
+        None,
+        false,
+    )
+    .await
+    .unwrap();
+
+    let registry = env.registry();
+    eventually("the session is registered", || async {
+        !registry.get_running_claude_sessions().unwrap().is_empty()
+    })
+    .await;
+    let session_id = watch.session_id().unwrap();
+    let info = registry
+        .get_claude_session_by_id(&session_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(registry.get_running_claude_sessions().unwrap().len(), 1);
+    assert!(registry
+        .get_live_output(info.run_id)
+        .unwrap()
+        .contains(&session_id));
+    assert_eq!(env.events("claude-session-state")[0]["run_id"], info.run_id);
+
+    let tracked = env.tracked(EngineKind::Claude).await;
+    assert_eq!(tracked.len(), 1);
+    assert_eq!(tracked[0].1, info.pid);
+    assert!(!process_exited(info.pid));
+
+    runner_for::<MockRuntime>(EngineKind::Claude)
+        .cancel(env.handle(), Some(session_id.clone()))
+        .await
+        .unwrap();
+    let success = tokio::time::timeout(RUN_TIMEOUT, watch.wait())
+        .await
+        .expect("cancelled run did not complete");
+    assert!(!success);
+    assert!(!env.events("claude-cancelled").is_empty());
+
+    eventually("the cancelled CLI exits", || async {
+        process_exited(info.pid)
+    })
+    .await;
+    eventually("the session is unregistered", || async {
+        registry
+            .get_claude_session_by_id(&session_id)
+            .unwrap()
+            .is_none()
+    })
+    .await;
+    eventually("the run is dropped from the state", || async {
+        env.tracked(EngineKind::Claude).await.is_empty()
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tracked_codex_run_exits_and_is_dropped_from_the_state() {
+    let env = TestEnv::new();
+    let run = env
+        .run(EngineKind::Codex, env.request("Summarize the README"), None)
+        .await;
+    assert!(run.success);
+    assert!(!run.lines.is_empty());
+
+    // The complete event goes out before the exit is reaped
+    eventually("the run is dropped from the state", || async {
+        env.tracked(EngineKind::Codex).await.is_empty()
+    })
+    .await;
+    assert!(env.registry().get_running_processes().unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancel_terminates_the_tracked_process_group() {
+    let env = TestEnv::new();
+    let mut watch = spawn_watched(
+        env.handle(),
+        EngineKind::Gemini,
+        env.request("[fake:hang] wait"),
+        None,
+        false,
+    )
+    .await
+    .unwrap();
+    let backend_id = watch.session_id().unwrap();
+
+    eventually("the CLI reports its session ID", || async {
+        env.cli_session_id(EngineKind::Gemini, &backend_id)
+            .is_some()
+    })
+    .await;
+    let cli_session_id = env.cli_session_id(EngineKind::Gemini, &backend_id).unwrap();
+
+    let tracked = env.tracked(EngineKind::Gemini).await;
+    assert_eq!(tracked.len(), 1);
+    let (key, pid, has_job_object) = tracked[0].clone();
+    assert_eq!(key, backend_id);
+    assert!(has_job_object);
+    assert!(!process_exited(pid));
+
+    // The registry entry follows the CLI's session ID
+    let registry = env.registry();
+    let running = registry.get_running_processes().unwrap();
+    assert_eq!(running.len(), 1);
+    assert_eq!(running[0].pid, pid);
+    assert!(matches!(
+        &running[0].process_type,
+        ProcessType::EngineSession { engine: EngineKind::Gemini, session_id }
+            if *session_id == cli_session_id
+    ));
+
+    runner_for::<MockRuntime>(EngineKind::Gemini)
+        .cancel(env.handle(), Some(backend_id.clone()))
+        .await
+        .unwrap();
+    assert_eq!(env.events("gemini-cancelled").len(), 1);
+    assert!(env.tracked(EngineKind::Gemini).await.is_empty());
+    assert!(registry.get_running_processes().unwrap().is_empty());
+
+    let success = tokio::time::timeout(RUN_TIMEOUT, watch.wait())
+        .await
+        .expect("cancelled run did not complete");
+    assert!(!success);
+    eventually("the cancelled CLI exits", || async { process_exited(pid) }).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn claude_usage_is_recorded_once_per_message() {
+    let env = TestEnv::new();
+    let run = env
+        .run(
+            EngineKind::Claude,
+            env.request("Summarize the README"),
+            None,
+        )
+        .await;
+    let session_id = run.session_id.unwrap();
+
+    // The stream may repeat a message's usage; later reports replace earlier ones
+    let recorded = recorded_session_usage(&env.open_db(), &session_id).unwrap();
+    assert_eq!(recorded.entries, 2);
+    assert_eq!(recorded.input_tokens, 9);
+    assert_eq!(recorded.output_tokens, 240);
+    assert_eq!(recorded.cache_creation_tokens, 1620);
+    assert_eq!(recorded.cache_read_tokens, 25500);
+    assert!(recorded.total_cost > 0.0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn codex_usage_is_recorded_per_turn() {
+    let env = TestEnv::new();
+    let mut request = env.request("Summarize the README");
+    request.model = Some("gpt-5.3-codex".to_string());
+    let run = env.run(EngineKind::Codex, request, None).await;
+    let thread_id = env
+        .cli_session_id(EngineKind::Codex, &run.session_id.unwrap())
+        .unwrap();
+
+    let recorded = recorded_session_usage(&env.open_db(), &thread_id).unwrap();
+    assert_eq!(recorded.entries, 1);
+    assert_eq!(recorded.input_tokens, 24763 - 24448);
+    assert_eq!(recorded.cache_read_tokens, 24448);
+    assert_eq!(recorded.output_tokens, 122);
+
+    // The cost prices exactly the recorded tokens: cached input is not billed twice
+    let tokens = TokenCounts {
+        input: recorded.input_tokens,
+        output: recorded.output_tokens,
+        cache_write: 0,
+        cache_read: recorded.cache_read_tokens,
+    };
+    let expected = pricing::cost(
+        EngineKind::Codex,
+        "gpt-5.3-codex",
+        &tokens,
+        chrono::Local::now().date_naive(),
+    )
+    .unwrap();
+    assert!((recorded.total_cost - expected).abs() < 1e-12);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gemini_usage_is_recorded_from_the_result() {
+    let env = TestEnv::new();
+    let mut request = env.request("Summarize the README");
+    request.model = Some("gemini-2.5-pro".to_string());
+    let run = env.run(EngineKind::Gemini, request, None).await;
+    let session_id = env
+        .cli_session_id(EngineKind::Gemini, &run.session_id.unwrap())
+        .unwrap();
+
+    let recorded = recorded_session_usage(&env.open_db(), &session_id).unwrap();
+    assert_eq!(recorded.entries, 1);
+    assert_eq!(recorded.input_tokens, 7990);
+    assert_eq!(recorded.output_tokens, 133);
+    assert!(recorded.total_cost > 0.0);
+}
@@ -0,0 +1,155 @@
+//! Stand-in for the `claude`, `codex` and `gemini` CLIs (engine tests only)
+//!
+//! The persona comes from the executable name — the harness symlinks this
+//! binary as `claude` / `codex` / `gemini` — or from `FAKE_AGENT_PERSONA`.
+//! A run replays `tests/fixtures/<persona>/simple_session.jsonl` with the
+//! fixture's session ID swapped for a fresh one (or the ID passed to
+//! `--resume` / `exec resume`). The prompt is read from `-p` or stdin, like
+//! the real CLIs, and these markers in it change the run:
+//!
+//! - `[fake:hang]`            print the first line, then block until killed
+//! - `[fake:crash]`           print the first line, then exit with code 3
+//! - `[fake:fixture=<name>]`  replay `<name>.jsonl` instead
+//!
+//! Each invocation is echoed to stderr as `fake-agent-cli: {json}` and
+//! appended to `.fake-agent-cli.jsonl` in the working directory (the project
+//! the engine runs in), so tests can check what was passed even when the
+//! engine keeps stderr to itself.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const INVOCATION_LOG: &str = ".fake-agent-cli.jsonl";
+
+fn persona() -> String {
+    if let Ok(persona) = std::env::var("FAKE_AGENT_PERSONA") {
+        return persona;
+    }
+    std::env::args()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "claude".to_string())
+}
+
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+fn resume_session_id(persona: &str, args: &[String]) -> Option<String> {
+    match persona {
+        // codex exec --json resume <SESSION_ID> -
+        "codex" => flag_value(args, "resume"),
+        // Gemini only resumes "latest"; there's no ID to keep
+        "gemini" => None,
+        _ => flag_value(args, "--resume"),
+    }
+}
+
+fn marker<'a>(prompt: &'a str, name: &str) -> Option<&'a str> {
+    let start = prompt.find(&format!("[fake:{}", name))? + name.len() + 6;
+    let end = start + prompt[start..].find(']')?;
+    Some(prompt[start..end].trim_start_matches('='))
+}
+
+/// Session ID announced on the fixture's first line
+fn fixture_session_id(first_line: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(first_line).ok()?;
+    value
+        .get("session_id")
+        .or_else(|| value.get("thread_id"))
+        .and_then(|s| s.as_str())
+        .map(String::from)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let persona = persona();
+
+    if args.iter().any(|a| a == "--version") {
+        match persona.as_str() {
+            "claude" => println!("2.0.0 (Claude Code)"),
+            "codex" => println!("codex-cli 0.50.0"),
+            _ => println!("0.10.0"),
+        }
+        return;
+    }
+
+    // `-p` is a flag followed by the prompt (Claude slash commands); otherwise stdin
+    let prompt = match flag_value(&args, "-p").filter(|p| !p.starts_with("--")) {
+        Some(prompt) => prompt,
+        None => {
+            let mut buf = String::new();
+            let _ = std::io::stdin().read_to_string(&mut buf);
+            buf
+        }
+    };
+
+    let invocation = serde_json::json!({ "persona": persona, "args": args, "prompt": prompt });
+    eprintln!("fake-agent-cli: {}", invocation);
+    if let Ok(mut log) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(INVOCATION_LOG)
+    {
+        let _ = writeln!(log, "{}", invocation);
+    }
+
+    let fixture_name = marker(&prompt, "fixture").unwrap_or("simple_session");
+    let fixture_path = Path::new(FIXTURE_DIR)
+        .join(&persona)
+        .join(format!("{}.jsonl", fixture_name));
+    let fixture = match std::fs::read_to_string(&fixture_path) {
+        Ok(fixture) => fixture,
+        Err(e) => {
+            eprintln!(
+                "fake-agent-cli: cannot read {}: {}",
+                fixture_path.display(),
+                e
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let session_id =
+        resume_session_id(&persona, &args).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let fixture = match fixture.lines().next().and_then(fixture_session_id) {
+        Some(original) => fixture.replace(&original, &session_id),
+        None => fixture,
+    };
+
+    let delay = std::env::var("FAKE_AGENT_DELAY_MS")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .unwrap_or(10);
+    let hang = marker(&prompt, "hang").is_some();
+    let crash = marker(&prompt, "crash").is_some();
+
+    let stdout = std::io::stdout();
+    for (index, line) in fixture.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        {
+            let mut out = stdout.lock();
+            let _ = writeln!(out, "{}", line);
+            let _ = out.flush();
+        }
+
+        if index == 0 && hang {
+            loop {
+                std::thread::sleep(Duration::from_secs(3600));
+            }
+        }
+        if index == 0 && crash {
+            eprintln!("fake-agent-cli: simulated crash");
+            std::process::exit(3);
+        }
+        std::thread::sleep(Duration::from_millis(delay));
+    }
+}
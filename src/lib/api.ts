@@ -63,7 +63,7 @@ export interface Session {
  * Session conversion source information
  */
 export interface ConversionSource {
-  /** Source engine type: "claude" | "codex" */
+  /** Source engine type: "claude" | "codex" | "gemini" */
   engine: string;
   /** Source session ID */
   sessionId: string;
@@ -4006,20 +4006,20 @@ export const api = {
   },
 
   // ============================================================================
-  // Session Conversion (Claude ↔ Codex)
+  // Session Conversion (Claude ↔ Codex ↔ Gemini)
   // ============================================================================
 
   /**
-   * Convert a session between Claude and Codex formats
+   * Convert a session between Claude, Codex and Gemini formats
    * @param sessionId - The source session ID
-   * @param targetEngine - The target engine ('claude' | 'codex')
+   * @param targetEngine - The target engine ('claude' | 'codex' | 'gemini')
    * @param projectId - The project ID (directory name)
    * @param projectPath - The project path
    * @returns Promise resolving to conversion result
    */
   async convertSession(
     sessionId: string,
-    targetEngine: 'claude' | 'codex',
+    targetEngine: 'claude' | 'codex' | 'gemini',
     projectId: string,
     projectPath: string
   ): Promise<ConversionResult> {